@@ -0,0 +1,9 @@
+-- Remember which device_auth stanza worked for each device. The read
+-- community and version stay in device.snmp_comm/snmp_ver.
+
+CREATE TABLE IF NOT EXISTS community (
+    ip                  inet PRIMARY KEY,
+    snmp_comm_rw        text,
+    snmp_auth_tag_read  text,
+    snmp_auth_tag_write text
+);
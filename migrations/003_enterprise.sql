@@ -0,0 +1,46 @@
+-- SNMP private enterprise numbers, used to name a device's vendor from
+-- the enterprise arc of its sysObjectID (1.3.6.1.4.1.<id>). Names follow
+-- SNMP::Info's vendor strings; add rows for anything not listed here.
+
+CREATE TABLE IF NOT EXISTS enterprise (
+    id    integer PRIMARY KEY,
+    name  text
+);
+
+INSERT INTO enterprise (id, name) VALUES
+    (9,     'cisco'),
+    (11,    'hp'),
+    (43,    '3com'),
+    (171,   'dlink'),
+    (207,   'allied'),
+    (674,   'dell'),
+    (1588,  'brocade'),
+    (1916,  'extreme'),
+    (1991,  'foundry'),
+    (2011,  'huawei'),
+    (2272,  'avaya'),
+    (2636,  'juniper'),
+    (3076,  'altiga'),
+    (3375,  'f5'),
+    (4526,  'netgear'),
+    (5624,  'enterasys'),
+    (6027,  'force10'),
+    (6486,  'alcatel-lucent'),
+    (6527,  'nokia'),
+    (8072,  'netsnmp'),
+    (9303,  'packetfront'),
+    (11863, 'tplink'),
+    (12356, 'fortinet'),
+    (14179, 'cisco'),
+    (14525, 'trapeze'),
+    (14823, 'aruba'),
+    (14988, 'mikrotik'),
+    (25053, 'ruckus'),
+    (25461, 'paloaltonetworks'),
+    (25506, 'h3c'),
+    (26543, 'ibm'),
+    (29671, 'meraki'),
+    (30065, 'arista'),
+    (41112, 'ubiquiti'),
+    (47196, 'aruba')
+ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,4 @@
+-- Power drawn from each PSE group, in watts (pethMainPseConsumptionPower),
+-- alongside its budget in device_power.power.
+
+ALTER TABLE device_power ADD COLUMN IF NOT EXISTS consumption integer;
@@ -0,0 +1,26 @@
+-- Spanning tree instances per device, and port state per instance.
+-- Instance 0 is the single tree or MSTP CIST (vlans empty); MST instances
+-- and Cisco PVST VLANs list the VLANs they cover.
+
+CREATE TABLE IF NOT EXISTS device_stp (
+    ip            inet,
+    instance      integer,
+    protocol      text,
+    vlans         integer[] NOT NULL DEFAULT '{}',
+    bridge        text,
+    root          text,
+    root_cost     integer,
+    root_port     text,
+    last_discover TIMESTAMP,
+    PRIMARY KEY(ip, instance)
+);
+
+CREATE TABLE IF NOT EXISTS device_port_stp (
+    ip          inet,
+    port        text,
+    instance    integer,
+    state       text,
+    PRIMARY KEY(ip, port, instance)
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_port_stp_state ON device_port_stp(state);
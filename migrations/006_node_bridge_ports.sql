@@ -0,0 +1,6 @@
+-- Nodes used to be stored against "bridge-port-N" rather than the port
+-- name. Those rows match no device_port and the bridge port cannot be
+-- mapped back after the fact, so drop them; the next macsuck stores the
+-- same MACs under their real ports.
+
+DELETE FROM node WHERE port LIKE 'bridge-port-%';
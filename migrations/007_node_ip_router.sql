@@ -0,0 +1,11 @@
+-- node_ip rows had no record of which router's ARP/NDP table they came
+-- from, so arpnip could only deactivate them globally. No run can
+-- deactivate a row without a router, so the existing ones are archived
+-- here; arpnip reactivates whatever it still sees, and expire removes the
+-- rest.
+
+ALTER TABLE node_ip ADD COLUMN IF NOT EXISTS router inet;
+
+CREATE INDEX IF NOT EXISTS idx_node_ip_router ON node_ip(router);
+
+UPDATE node_ip SET active = false WHERE router IS NULL AND active = true;
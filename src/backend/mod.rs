@@ -4,12 +4,13 @@
 
 pub mod scheduler;
 pub mod manager;
+pub mod trapd;
 
 use anyhow::Result;
 use sqlx::PgPool;
 use std::sync::Arc;
 use tokio::signal;
-use tracing::info;
+use tracing::{error, info};
 
 use crate::config::NetdiscoConfig;
 
@@ -27,6 +28,18 @@ pub async fn start_backend(config: Arc<NetdiscoConfig>, pool: PgPool) -> Result<
         scheduler::run_scheduler(scheduler_config, scheduler_pool).await
     });
 
+    // Start the trap receiver
+    let mut trapd_handle = if config.trap_receiver.enabled {
+        let socket = trapd::bind_trap_receiver(&config).await?;
+        let trapd_config = config.clone();
+        let trapd_pool = pool.clone();
+        Some(tokio::spawn(async move {
+            trapd::run_trap_receiver(trapd_config, trapd_pool, socket).await
+        }))
+    } else {
+        None
+    };
+
     // Start worker tasks for job processing
     let mut worker_handles = Vec::new();
     for worker_id in 0..num_workers {
@@ -40,12 +53,30 @@ pub async fn start_backend(config: Arc<NetdiscoConfig>, pool: PgPool) -> Result<
 
     info!("Backend daemon running. Press Ctrl+C to stop.");
 
-    // Wait for shutdown signal
-    signal::ctrl_c().await?;
+    // Wait for shutdown signal; the trap receiver only returns if it fails
+    loop {
+        tokio::select! {
+            result = signal::ctrl_c() => {
+                result?;
+                break;
+            }
+            result = async { trapd_handle.as_mut().unwrap().await }, if trapd_handle.is_some() => {
+                trapd_handle = None;
+                match result {
+                    Ok(Ok(())) => error!("Trap receiver stopped; traps are no longer received"),
+                    Ok(Err(e)) => error!("Trap receiver failed; traps are no longer received: {:#}", e),
+                    Err(e) => error!("Trap receiver task failed; traps are no longer received: {}", e),
+                }
+            }
+        }
+    }
     info!("Shutdown signal received, stopping workers...");
 
     // Cancel all tasks
     scheduler_handle.abort();
+    if let Some(handle) = trapd_handle {
+        handle.abort();
+    }
     for handle in worker_handles {
         handle.abort();
     }
@@ -56,7 +87,7 @@ pub async fn start_backend(config: Arc<NetdiscoConfig>, pool: PgPool) -> Result<
 
 /// Calculate number of worker tasks from config string (e.g., "AUTO * 2").
 /// Bounded between 1 and 256 to prevent resource exhaustion.
-fn calculate_workers(tasks_str: &str) -> usize {
+pub fn calculate_workers(tasks_str: &str) -> usize {
     const MIN_WORKERS: usize = 1;
     const MAX_WORKERS: usize = 256;
 
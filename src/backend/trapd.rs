@@ -0,0 +1,136 @@
+//! SNMP trap and inform listener.
+//!
+//! Receives notifications on `trap_receiver.listen` and reacts to them:
+//! linkUp/linkDown update device_port.up, MAC notifications queue a
+//! macsuck and coldStart queues a discover. Each reaction, and logging
+//! every notification to the log table, can be turned off in config.
+
+use anyhow::{Context, Result};
+use ipnetwork::IpNetwork;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+use crate::config::NetdiscoConfig;
+use crate::db;
+use crate::snmp::client::usm_user_from_credentials;
+use crate::snmp::trap::{Notification, TrapEvent, TrapReceiver};
+use crate::snmp::SnmpCredentials;
+
+/// Bind `trap_receiver.listen`, before the receiver is spawned, so a port
+/// in use or a missing CAP_NET_BIND_SERVICE fails backend startup.
+pub async fn bind_trap_receiver(config: &NetdiscoConfig) -> Result<UdpSocket> {
+    let listen = &config.trap_receiver.listen;
+    let socket = UdpSocket::bind(listen).await
+        .with_context(|| format!("Failed to bind trap receiver on {}", listen))?;
+    info!("Trap receiver listening on {}", listen);
+    Ok(socket)
+}
+
+/// Listen for notifications on `socket` until the task is aborted.
+pub async fn run_trap_receiver(config: Arc<NetdiscoConfig>, pool: PgPool, socket: UdpSocket) -> Result<()> {
+    let settings = &config.trap_receiver;
+    let communities = if settings.community.is_empty() {
+        config.community.clone()
+    } else {
+        settings.community.clone()
+    };
+    let mut users = Vec::new();
+    for auth in config.device_auth.iter().filter(|a| a.user.is_some()) {
+        match usm_user_from_credentials(&SnmpCredentials::from_device_auth(auth)) {
+            Ok(user) => users.push(user),
+            Err(e) => warn!("Trap receiver: skipping device_auth user: {:#}", e),
+        }
+    }
+    let receiver = TrapReceiver::new(communities, users);
+
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                debug!("Trap receiver: receive error: {}", e);
+                continue;
+            }
+        };
+        // A dual-stack listener sees IPv4 agents as ::ffff:a.b.c.d
+        let received = match receiver.receive(&buf[..len], peer.ip().to_canonical()) {
+            Ok(received) => received,
+            Err(e) => {
+                debug!("Trap receiver: ignoring datagram from {}: {:#}", peer, e);
+                continue;
+            }
+        };
+        if let Some(reply) = received.reply {
+            if let Err(e) = socket.send_to(&reply, peer).await {
+                warn!("Trap receiver: failed to reply to {}: {}", peer, e);
+            }
+        }
+        if let Some(notification) = received.notification {
+            if let Err(e) = handle_notification(&config, &pool, &notification).await {
+                warn!("Trap receiver: failed to handle {}: {:#}", notification, e);
+            }
+        }
+    }
+}
+
+async fn handle_notification(config: &NetdiscoConfig, pool: &PgPool, notification: &Notification) -> Result<()> {
+    let settings = &config.trap_receiver;
+    info!("Received {}", notification);
+
+    if settings.log {
+        sqlx::query("INSERT INTO log (class, entry) VALUES ('snmptrap', $1)")
+            .bind(notification.to_string())
+            .execute(pool)
+            .await?;
+    }
+
+    let Some(device) = find_device(pool, &IpNetwork::from(notification.agent)).await? else {
+        debug!("Trap receiver: {} is not a known device", notification.agent);
+        return Ok(());
+    };
+
+    match notification.event() {
+        TrapEvent::LinkChange { ifindex, up } if settings.link_updown => {
+            sqlx::query("UPDATE device_port SET up = $3 WHERE ip = $1 AND ifindex = $2")
+                .bind(device)
+                .bind(ifindex as i32)
+                .bind(if up { "up" } else { "down" })
+                .execute(pool)
+                .await?;
+        }
+        TrapEvent::MacNotification if settings.mac_notification => {
+            enqueue_once(pool, "macsuck", &device).await?;
+        }
+        TrapEvent::ColdStart if settings.coldstart => {
+            enqueue_once(pool, "discover", &device).await?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The device a notification came from, by its address or one of its
+/// interface aliases.
+async fn find_device(pool: &PgPool, agent: &IpNetwork) -> Result<Option<IpNetwork>> {
+    let device = sqlx::query_scalar::<_, IpNetwork>(
+        r#"SELECT ip FROM device WHERE ip = $1
+           UNION SELECT ip FROM device_ip WHERE alias = $1
+           LIMIT 1"#
+    )
+        .bind(agent)
+        .fetch_optional(pool)
+        .await?;
+    Ok(device)
+}
+
+/// Queue a job unless the same one is already waiting, so a burst of
+/// traps makes one job.
+async fn enqueue_once(pool: &PgPool, action: &str, device: &IpNetwork) -> Result<()> {
+    if !db::job_is_queued(pool, action, device).await? {
+        db::enqueue_job(pool, action, Some(device), None, Some("snmptrapd")).await?;
+        info!("Trap receiver: queued {} for {}", action, device);
+    }
+    Ok(())
+}
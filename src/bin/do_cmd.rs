@@ -16,6 +16,11 @@ struct Cli {
     #[arg(short = 'D', long)]
     debug: bool,
 
+    /// Answer SNMP for the device from a recorded walk (snmpwalk -On
+    /// output or snmprec) instead of polling it
+    #[arg(long, value_name = "FILE")]
+    replay: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -48,6 +53,12 @@ enum Commands {
         #[arg(short, long)]
         device: String,
     },
+    /// Show where a MAC address has been seen, and its moves
+    MacHistory {
+        /// MAC address, in any common format
+        #[arg(short, long)]
+        mac: String,
+    },
     /// Run scheduled tasks
     DiscoverAll,
     Macwalk,
@@ -69,13 +80,27 @@ async fn main() -> Result<()> {
         .with_env_filter(EnvFilter::from_default_env().add_directive(filter.parse()?))
         .init();
 
-    let config = Arc::new(
-        netdisco::config::load_config(cli.config.as_deref().map(std::path::Path::new))?
-    );
+    let mut config = netdisco::config::load_config(cli.config.as_deref().map(std::path::Path::new))?;
 
     let db = netdisco::db::DbPool::new(&config.database).await?;
     db.ping().await?;
 
+    // Kept alive until the command finishes
+    let _simulator = match &cli.replay {
+        Some(file) => {
+            let device = command_device(&cli.command)
+                .ok_or_else(|| anyhow::anyhow!("--replay needs a command with --device"))?;
+            let ip = resolve_device(device).await?.ip();
+            let recording = netdisco::snmp::simulator::Recording::load(file)?;
+            println!("Replaying {} ({} objects) as {}", file.display(), recording.len(), ip);
+            let simulator = netdisco::snmp::simulator::Simulator::start_for(ip, recording)?;
+            config.snmp_replay.insert(ip, simulator.local_addr());
+            Some(simulator)
+        }
+        None => None,
+    };
+    let config = Arc::new(config);
+
     match cli.command {
         Commands::Discover { device } => {
             let ip = resolve_device(&device).await?;
@@ -118,6 +143,26 @@ async fn main() -> Result<()> {
                 None => println!("Device {} not found", ip),
             }
         }
+        Commands::MacHistory { mac } => {
+            let mac = netdisco::util::parse_mac(&mac)
+                .ok_or_else(|| anyhow::anyhow!("Not a MAC address: {}", mac))?;
+            let history = netdisco::db::node_history(&db.pool, &mac).await?;
+            if history.is_empty() {
+                println!("{} has not been seen", mac);
+            }
+            let time = |t: Option<chrono::NaiveDateTime>| t.map(|t| t.to_string()).unwrap_or_default();
+            for node in &history {
+                println!(
+                    "{} {} vlan {}: {} to {}{}",
+                    node.switch.ip(), node.port, node.vlan.as_deref().unwrap_or("0"),
+                    time(node.time_first), time(node.time_last),
+                    if node.active == Some(true) { " (active)" } else { "" },
+                );
+            }
+            for entry in netdisco::db::node_moves(&db.pool, &mac).await? {
+                println!("{} {}", time(entry.creation), entry.entry.unwrap_or_default());
+            }
+        }
         Commands::DiscoverAll => {
             let result = netdisco::worker::discover::discover_all(&config, &db.pool).await?;
             println!("{}", result);
@@ -155,6 +200,18 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// The device a command acts on, if it takes one.
+fn command_device(command: &Commands) -> Option<&str> {
+    match command {
+        Commands::Discover { device }
+        | Commands::Macsuck { device }
+        | Commands::Arpnip { device }
+        | Commands::Delete { device }
+        | Commands::Show { device } => Some(device),
+        _ => None,
+    }
+}
+
 async fn resolve_device(device: &str) -> Result<ipnetwork::IpNetwork> {
     // Try parsing as IP first
     if let Ok(ip) = device.parse::<std::net::IpAddr>() {
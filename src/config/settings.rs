@@ -4,7 +4,9 @@
 //! matching the keys in config.yml / deployment.yml.
 
 use serde::{Deserialize, Serialize};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
 
 /// Top-level Netdisco configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +40,15 @@ pub struct NetdiscoConfig {
     pub snmpretries: u32,
     pub bulkwalk_off: bool,
     pub bulkwalk_repeaters: u32,
+    pub snmp_max_inflight: usize,
+    /// Local addresses to poll from, at most one per address family
+    pub snmp_source_address: Vec<String>,
+    pub device_auth: Vec<DeviceAuth>,
+    pub trap_receiver: TrapReceiverConfig,
+    /// Devices answered by a replay simulator instead of polled; set by
+    /// `netdisco-do --replay`, never read from config files
+    #[serde(skip)]
+    pub snmp_replay: BTreeMap<IpAddr, SocketAddr>,
 
     // Discovery control
     pub devices_no: Vec<String>,
@@ -49,6 +60,9 @@ pub struct NetdiscoConfig {
     pub discover_waps: bool,
     pub discover_phones: bool,
     pub discover_min_age: u64,
+    /// Methods for picking the address a device is stored under, tried in
+    /// order: "loopback", "sysname", "lowest"
+    pub device_identity: Vec<String>,
 
     // MAC/ARP collection
     pub macsuck_no: Vec<String>,
@@ -199,6 +213,99 @@ pub struct DnsConfig {
     pub no: Vec<String>,
 }
 
+/// An SNMP credential stanza from the `device_auth` list.
+///
+/// Stanzas apply to devices matched by `only`/`no` and are tried in
+/// order. A stanza with a `user` is SNMPv3; otherwise it carries a
+/// `community` for v1/v2c.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceAuth {
+    pub tag: Option<String>,
+    /// Devices this stanza applies to (empty means all)
+    pub only: Vec<String>,
+    pub no: Vec<String>,
+    /// Use for polling
+    pub read: bool,
+    /// Use for SNMP SET
+    pub write: bool,
+    /// SNMP version for a community stanza; defaults to `snmpver`
+    pub snmpver: Option<u8>,
+    pub community: Option<String>,
+    /// SNMPv3 user name
+    pub user: Option<String>,
+    pub auth: Option<DeviceAuthSecret>,
+    #[serde(rename = "priv")]
+    pub privacy: Option<DeviceAuthSecret>,
+    /// UDP port; defaults to 161
+    pub port: Option<u16>,
+    /// Timeout in microseconds; defaults to `snmptimeout`
+    pub timeout: Option<u64>,
+    /// Defaults to `snmpretries`
+    pub retries: Option<u32>,
+}
+
+impl Default for DeviceAuth {
+    fn default() -> Self {
+        Self {
+            tag: None,
+            only: vec![],
+            no: vec![],
+            read: true,
+            write: false,
+            snmpver: None,
+            community: None,
+            user: None,
+            auth: None,
+            privacy: None,
+            port: None,
+            timeout: None,
+            retries: None,
+        }
+    }
+}
+
+/// SNMPv3 authentication or privacy settings (password and protocol).
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DeviceAuthSecret {
+    pub pass: String,
+    /// Protocol name, e.g. "SHA" or "AES"; defaults to MD5 / DES
+    pub proto: Option<String>,
+}
+
+/// SNMP trap and inform listener run by the backend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrapReceiverConfig {
+    pub enabled: bool,
+    pub listen: String,
+    /// Accepted v1/v2c communities; defaults to `community`
+    pub community: Vec<String>,
+    /// Record every notification in the log table
+    pub log: bool,
+    /// Update device_port.up on linkUp/linkDown
+    pub link_updown: bool,
+    /// Queue a macsuck on MAC notification traps
+    pub mac_notification: bool,
+    /// Queue a discover on coldStart
+    pub coldstart: bool,
+}
+
+impl Default for TrapReceiverConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: "0.0.0.0:162".into(),
+            community: vec![],
+            log: true,
+            link_updown: true,
+            mac_notification: true,
+            coldstart: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct LdapConfig {
@@ -251,6 +358,11 @@ impl Default for NetdiscoConfig {
             snmpretries: 2,
             bulkwalk_off: false,
             bulkwalk_repeaters: 20,
+            snmp_max_inflight: 4,
+            snmp_source_address: vec![],
+            snmp_replay: BTreeMap::new(),
+            device_auth: vec![],
+            trap_receiver: TrapReceiverConfig::default(),
             devices_no: vec![],
             devices_only: vec![],
             discover_no: vec![],
@@ -260,6 +372,7 @@ impl Default for NetdiscoConfig {
             discover_waps: true,
             discover_phones: false,
             discover_min_age: 0,
+            device_identity: vec![],
             macsuck_no: vec![],
             macsuck_only: vec![],
             macsuck_all_vlans: false,
@@ -331,6 +444,10 @@ impl NetdiscoConfig {
                 self.domain_suffix = suffixes;
             }
         }
+        if let Some(v) = overrides.get("device_auth") {
+            self.device_auth = serde_yaml::from_value(v.clone())
+                .context("Invalid device_auth in deployment overrides")?;
+        }
         if let Some(v) = overrides.get("no_auth").and_then(|v| v.as_bool()) {
             self.no_auth = v;
         }
@@ -359,4 +476,16 @@ impl NetdiscoConfig {
             self.domain_suffix = vec![v];
         }
     }
+
+    /// The configured source address for polling `target`, if any.
+    pub fn snmp_source_for(&self, target: &IpAddr) -> Result<Option<IpAddr>> {
+        for entry in &self.snmp_source_address {
+            let source: IpAddr = entry.parse()
+                .with_context(|| format!("Invalid snmp_source_address: {}", entry))?;
+            if source.is_ipv4() == target.is_ipv4() {
+                return Ok(Some(source));
+            }
+        }
+        Ok(None)
+    }
 }
@@ -47,3 +47,21 @@ impl DbPool {
         Ok(())
     }
 }
+
+/// How many times a worker run's transaction is tried before a deadlock
+/// or serialization failure is given up on.
+pub const RUN_ATTEMPTS: u32 = 3;
+
+/// Whether a SQLSTATE is a serialization failure (40001) or deadlock
+/// (40P01), after which the rolled back transaction can be run again.
+pub fn is_retryable_sqlstate(code: &str) -> bool {
+    matches!(code, "40001" | "40P01")
+}
+
+/// Whether `err` was caused by a serialization failure or deadlock.
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| match cause.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(e)) => e.code().is_some_and(|code| is_retryable_sqlstate(&code)),
+        _ => false,
+    })
+}
@@ -3,9 +3,12 @@
 //! Provides CRUD operations matching the Perl DBIx::Class layer.
 
 use anyhow::Result;
+use chrono::NaiveDateTime;
 use ipnetwork::IpNetwork;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
 use crate::models::*;
+use crate::snmp::auth::{Access, RememberedAuth};
+use crate::snmp::NeighborInfo;
 
 // ==================== Device Queries ====================
 
@@ -49,13 +52,25 @@ pub async fn search_devices(pool: &PgPool, query: &str) -> Result<Vec<Device>> {
     Ok(devices)
 }
 
+/// Whether an address belongs to a known device, as its IP or an alias.
+pub async fn device_known(pool: &PgPool, ip: &IpNetwork) -> Result<bool> {
+    let known = sqlx::query_scalar::<_, bool>(
+        r#"SELECT EXISTS(SELECT 1 FROM device WHERE ip = $1)
+               OR EXISTS(SELECT 1 FROM device_ip WHERE alias = $1)"#
+    )
+        .bind(ip)
+        .fetch_one(pool)
+        .await?;
+    Ok(known)
+}
+
 /// Insert or update a device record.
 pub async fn upsert_device(pool: &PgPool, device: &Device) -> Result<()> {
     sqlx::query(
         r#"INSERT INTO device (ip, dns, description, uptime, contact, name, location,
             layers, ports, mac, serial, model, vendor, os, os_ver, snmp_ver,
             snmp_comm, snmp_class, vtp_domain, last_discover)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14,
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10::macaddr, $11, $12, $13, $14,
                    $15, $16, $17, $18, $19, $20)
            ON CONFLICT (ip) DO UPDATE SET
             dns = EXCLUDED.dns,
@@ -67,8 +82,8 @@ pub async fn upsert_device(pool: &PgPool, device: &Device) -> Result<()> {
             layers = EXCLUDED.layers,
             ports = EXCLUDED.ports,
             mac = EXCLUDED.mac,
-            serial = EXCLUDED.serial,
-            model = EXCLUDED.model,
+            serial = COALESCE(EXCLUDED.serial, device.serial),
+            model = COALESCE(EXCLUDED.model, device.model),
             vendor = EXCLUDED.vendor,
             os = EXCLUDED.os,
             os_ver = EXCLUDED.os_ver,
@@ -108,8 +123,11 @@ pub async fn delete_device(pool: &PgPool, ip: &IpNetwork) -> Result<()> {
     let mut tx = pool.begin().await?;
 
     sqlx::query("DELETE FROM node WHERE switch = $1").bind(ip).execute(&mut *tx).await?;
+    // Nothing will refresh or deactivate these again, so archive them
+    sqlx::query("UPDATE node_ip SET active = false, router = NULL WHERE router = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM device_port_vlan WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM device_port_power WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM device_port_stp WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM device_port_ssid WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM device_port_wireless WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM device_port_log WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
@@ -118,14 +136,72 @@ pub async fn delete_device(pool: &PgPool, ip: &IpNetwork) -> Result<()> {
     sqlx::query("DELETE FROM device_ip WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM device_vlan WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM device_power WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM device_stp WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM device_skip WHERE device = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM admin WHERE device = $1").bind(ip).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM community WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
     sqlx::query("DELETE FROM device WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
 
     tx.commit().await?;
     Ok(())
 }
 
+/// The SNMP credential that last worked for a device.
+pub async fn get_snmp_auth(pool: &PgPool, ip: &IpNetwork, access: Access) -> Result<RememberedAuth> {
+    let row = match access {
+        Access::Read => sqlx::query_as::<_, (Option<i32>, Option<String>, Option<String>)>(
+            r#"SELECT d.snmp_ver, d.snmp_comm, c.snmp_auth_tag_read
+               FROM device d LEFT JOIN community c ON c.ip = d.ip
+               WHERE d.ip = $1"#
+        ),
+        Access::Write => sqlx::query_as::<_, (Option<i32>, Option<String>, Option<String>)>(
+            "SELECT NULL::integer, snmp_comm_rw, snmp_auth_tag_write FROM community WHERE ip = $1"
+        ),
+    }
+        .bind(ip)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|(snmp_ver, snmp_comm, tag)| RememberedAuth { snmp_ver, snmp_comm, tag })
+        .unwrap_or_default())
+}
+
+/// Remember the SNMP credential that worked for a device.
+pub async fn set_snmp_auth(pool: &PgPool, ip: &IpNetwork, access: Access, auth: &RememberedAuth) -> Result<()> {
+    match access {
+        Access::Read => {
+            sqlx::query("UPDATE device SET snmp_ver = $2, snmp_comm = $3 WHERE ip = $1")
+                .bind(ip)
+                .bind(auth.snmp_ver)
+                .bind(&auth.snmp_comm)
+                .execute(pool)
+                .await?;
+            sqlx::query(
+                r#"INSERT INTO community (ip, snmp_auth_tag_read) VALUES ($1, $2)
+                   ON CONFLICT (ip) DO UPDATE SET snmp_auth_tag_read = EXCLUDED.snmp_auth_tag_read"#
+            )
+                .bind(ip)
+                .bind(&auth.tag)
+                .execute(pool)
+                .await?;
+        }
+        Access::Write => {
+            sqlx::query(
+                r#"INSERT INTO community (ip, snmp_comm_rw, snmp_auth_tag_write) VALUES ($1, $2, $3)
+                   ON CONFLICT (ip) DO UPDATE SET
+                       snmp_comm_rw = EXCLUDED.snmp_comm_rw,
+                       snmp_auth_tag_write = EXCLUDED.snmp_auth_tag_write"#
+            )
+                .bind(ip)
+                .bind(&auth.snmp_comm)
+                .bind(&auth.tag)
+                .execute(pool)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
 // ==================== Node Queries ====================
 
 /// Find nodes (MAC addresses) on a specific switch port.
@@ -170,14 +246,49 @@ pub async fn find_node_by_ip(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<NodeIp
     Ok(nodes)
 }
 
-/// Upsert a node (MAC on switch port).
-pub async fn upsert_node(pool: &PgPool, node: &Node) -> Result<()> {
+/// Upsert a node (MAC on switch port), archiving the MAC's active rows on
+/// any other switch port and logging the moves.
+///
+/// Archived rows keep the `time_last` of their last sighting. A row only
+/// counts as a move if it was last seen before this switch port last saw
+/// the MAC, so a MAC that several switches report at once (bleed, or an
+/// unmanaged switch in between) is archived but not logged on every run.
+/// Returns the switch ports the MAC moved from. Runs on the caller's
+/// transaction; callers store MACs in sorted order so that concurrent runs
+/// lock rows in the same order, and retry the transaction if it deadlocks
+/// anyway (see [`is_retryable`]).
+pub async fn upsert_node(conn: &mut PgConnection, node: &Node) -> Result<Vec<(IpNetwork, String)>> {
+    // Lock the MAC's rows in a fixed order, so that two runs storing it on
+    // different switches don't each hold one of them
+    let rows = sqlx::query_as::<_, (IpNetwork, String, Option<NaiveDateTime>)>(
+        r#"SELECT switch, port, time_last FROM node WHERE mac = $1::macaddr
+           ORDER BY switch, port, vlan FOR UPDATE"#
+    )
+        .bind(&node.mac)
+        .fetch_all(&mut *conn)
+        .await?;
+    let previous = rows.into_iter()
+        .filter(|(switch, port, _)| *switch == node.switch && *port == node.port)
+        .filter_map(|(_, _, time_last)| time_last)
+        .max();
+
+    let archived = sqlx::query_as::<_, (IpNetwork, String, Option<NaiveDateTime>)>(
+        r#"UPDATE node SET active = false
+           WHERE mac = $1::macaddr AND active = true AND NOT (switch = $2 AND port = $3)
+           RETURNING switch, port, time_last"#
+    )
+        .bind(&node.mac)
+        .bind(node.switch)
+        .bind(&node.port)
+        .fetch_all(&mut *conn)
+        .await?;
+
     sqlx::query(
         r#"INSERT INTO node (mac, switch, port, vlan, active, oui, time_first, time_recent, time_last)
            VALUES ($1::macaddr, $2, $3, $4, $5, $6, NOW(), NOW(), NOW())
            ON CONFLICT (mac, switch, port, vlan) DO UPDATE SET
             active = EXCLUDED.active,
-            time_recent = NOW(),
+            time_recent = CASE WHEN node.active THEN node.time_recent ELSE NOW() END,
             time_last = NOW()"#
     )
         .bind(&node.mac)
@@ -186,27 +297,102 @@ pub async fn upsert_node(pool: &PgPool, node: &Node) -> Result<()> {
         .bind(&node.vlan)
         .bind(node.active)
         .bind(&node.oui)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
-    Ok(())
+
+    // One MAC can be archived in several VLANs on the same port
+    let mut moved: Vec<(IpNetwork, String)> = Vec::new();
+    for (switch, port, time_last) in archived {
+        let from = (switch, port);
+        if Node::is_move(previous, time_last) && !moved.contains(&from) {
+            moved.push(from);
+        }
+    }
+    for (switch, port) in &moved {
+        sqlx::query("INSERT INTO log (class, entry) VALUES ('nodemove', $1)")
+            .bind(node.move_entry(switch, port))
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(moved)
+}
+
+/// Deactivate a switch's nodes that the current macsuck run did not store
+/// and that have not been seen for `freshness` minutes.
+///
+/// NOW() is the transaction's start time, so on the run's transaction
+/// every row it stored has `time_last = NOW()` and nothing else does.
+pub async fn deactivate_stale_nodes(conn: &mut PgConnection, switch: &IpNetwork, freshness: u32) -> Result<u64> {
+    let result = sqlx::query(
+        r#"UPDATE node SET active = false
+           WHERE switch = $1 AND active = true
+             AND time_last < NOW() - make_interval(mins => $2)"#
+    )
+        .bind(switch)
+        .bind(freshness as i32)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected())
 }
 
-/// Upsert a node_ip (MAC to IP mapping from ARP/NDP).
-pub async fn upsert_node_ip(pool: &PgPool, mac: &str, ip: &IpNetwork) -> Result<()> {
+/// Every row a MAC has had in the node table, oldest first.
+pub async fn node_history(pool: &PgPool, mac: &str) -> Result<Vec<Node>> {
+    let nodes = sqlx::query_as::<_, Node>(
+        r#"SELECT mac::text AS mac, switch, port, vlan, active, oui, time_first, time_recent, time_last
+           FROM node WHERE mac = $1::macaddr ORDER BY time_first, time_last"#
+    )
+        .bind(mac)
+        .fetch_all(pool)
+        .await?;
+    Ok(nodes)
+}
+
+/// The moves logged for a MAC, oldest first.
+pub async fn node_moves(pool: &PgPool, mac: &str) -> Result<Vec<log::Log>> {
+    let moves = sqlx::query_as::<_, log::Log>(
+        "SELECT * FROM log WHERE class = 'nodemove' AND entry LIKE $1::macaddr::text || ' %' ORDER BY creation, id"
+    )
+        .bind(mac)
+        .fetch_all(pool)
+        .await?;
+    Ok(moves)
+}
+
+/// Upsert a node_ip (MAC to IP mapping from ARP/NDP) seen in `router`'s
+/// table. Runs on the caller's transaction.
+pub async fn upsert_node_ip(conn: &mut PgConnection, mac: &str, ip: &IpNetwork, router: &IpNetwork) -> Result<()> {
     sqlx::query(
-        r#"INSERT INTO node_ip (mac, ip, active, time_first, time_last)
-           VALUES ($1::macaddr, $2, true, NOW(), NOW())
+        r#"INSERT INTO node_ip (mac, ip, active, router, time_first, time_last)
+           VALUES ($1::macaddr, $2, true, $3, NOW(), NOW())
            ON CONFLICT (mac, ip) DO UPDATE SET
             active = true,
+            router = EXCLUDED.router,
             time_last = NOW()"#
     )
         .bind(mac)
         .bind(ip)
-        .execute(pool)
+        .bind(router)
+        .execute(conn)
         .await?;
     Ok(())
 }
 
+/// Deactivate the node_ips last seen in `router`'s table that the current
+/// arpnip run did not store and that have not been seen for `freshness`
+/// minutes. See [`deactivate_stale_nodes`].
+pub async fn deactivate_stale_node_ips(conn: &mut PgConnection, router: &IpNetwork, freshness: u32) -> Result<u64> {
+    let result = sqlx::query(
+        r#"UPDATE node_ip SET active = false
+           WHERE router = $1 AND active = true
+             AND time_last < NOW() - make_interval(mins => $2)"#
+    )
+        .bind(router)
+        .bind(freshness as i32)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected())
+}
+
 // ==================== Admin/Job Queue Queries ====================
 
 /// Add a job to the admin queue.
@@ -231,6 +417,18 @@ pub async fn enqueue_job(
     Ok(row)
 }
 
+/// Whether a job for this action and device is already waiting.
+pub async fn job_is_queued(pool: &PgPool, action: &str, device: &IpNetwork) -> Result<bool> {
+    let queued = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM admin WHERE action = $1 AND device = $2 AND status = 'queued')"
+    )
+        .bind(action)
+        .bind(device)
+        .fetch_one(pool)
+        .await?;
+    Ok(queued)
+}
+
 /// Fetch the next queued job for processing.
 pub async fn dequeue_job(pool: &PgPool) -> Result<Option<Admin>> {
     let job = sqlx::query_as::<_, Admin>(
@@ -311,7 +509,119 @@ pub async fn create_user(
 /// Get all ports for a device.
 pub async fn get_device_ports(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<DevicePort>> {
     let ports = sqlx::query_as::<_, DevicePort>(
-        "SELECT * FROM device_port WHERE ip = $1 ORDER BY port"
+        r#"SELECT ip, port, creation, descr, up, up_admin, type, duplex, duplex_admin, speed, name,
+            mac::text AS mac, mtu, stp, remote_ip, remote_port, remote_type, remote_id, vlan, pvid,
+            lastchange, ifindex, is_uplink, speed_admin, is_master, slave_of, custom_fields, tags
+           FROM device_port WHERE ip = $1 ORDER BY port"#
+    )
+        .bind(ip)
+        .fetch_all(pool)
+        .await?;
+    Ok(ports)
+}
+
+/// Insert or update a port as discovered. Neighbor and uplink columns
+/// are kept; they are set separately.
+pub async fn upsert_device_port(pool: &PgPool, port: &DevicePort) -> Result<()> {
+    sqlx::query(
+        r#"INSERT INTO device_port (ip, port, descr, up, up_admin, type, speed, name, mac, mtu,
+            lastchange, ifindex, vlan, pvid, is_master, slave_of, stp)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::macaddr, $10, $11, $12, $13, $14, $15, $16, $17)
+           ON CONFLICT (port, ip) DO UPDATE SET
+            descr = EXCLUDED.descr,
+            up = EXCLUDED.up,
+            up_admin = EXCLUDED.up_admin,
+            type = EXCLUDED.type,
+            speed = EXCLUDED.speed,
+            name = EXCLUDED.name,
+            mac = EXCLUDED.mac,
+            mtu = EXCLUDED.mtu,
+            lastchange = EXCLUDED.lastchange,
+            ifindex = EXCLUDED.ifindex,
+            vlan = EXCLUDED.vlan,
+            pvid = EXCLUDED.pvid,
+            is_master = EXCLUDED.is_master,
+            slave_of = EXCLUDED.slave_of,
+            stp = EXCLUDED.stp"#
+    )
+        .bind(port.ip)
+        .bind(&port.port)
+        .bind(&port.descr)
+        .bind(&port.up)
+        .bind(&port.up_admin)
+        .bind(&port.port_type)
+        .bind(&port.speed)
+        .bind(&port.name)
+        .bind(&port.mac)
+        .bind(port.mtu)
+        .bind(port.lastchange)
+        .bind(port.ifindex)
+        .bind(&port.vlan)
+        .bind(port.pvid)
+        .bind(port.is_master)
+        .bind(&port.slave_of)
+        .bind(&port.stp)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Remove a device's ports other than those named.
+pub async fn delete_stale_ports(pool: &PgPool, ip: &IpNetwork, ports: &[String]) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM device_port WHERE ip = $1 AND NOT (port = ANY($2))")
+        .bind(ip)
+        .bind(ports)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Replace the CDP/LLDP neighbor recorded on each of a device's ports.
+pub async fn set_port_neighbors(pool: &PgPool, ip: &IpNetwork, neighbors: &[(String, NeighborInfo)]) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        r#"UPDATE device_port SET remote_ip = NULL, remote_port = NULL, remote_type = NULL, remote_id = NULL
+           WHERE ip = $1"#
+    )
+        .bind(ip)
+        .execute(&mut *tx)
+        .await?;
+    for (port, neighbor) in neighbors {
+        sqlx::query(
+            r#"UPDATE device_port SET remote_ip = $3, remote_port = $4, remote_type = $5, remote_id = $6
+               WHERE ip = $1 AND port = $2"#
+        )
+            .bind(ip)
+            .bind(port)
+            .bind(neighbor.remote_ip.map(IpNetwork::from))
+            .bind(&neighbor.remote_port)
+            .bind(&neighbor.remote_type)
+            .bind(&neighbor.remote_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Mark exactly the named ports of a device as uplinks.
+pub async fn set_port_uplinks(pool: &PgPool, ip: &IpNetwork, ports: &[String]) -> Result<()> {
+    sqlx::query("UPDATE device_port SET is_uplink = (port = ANY($2)) WHERE ip = $1")
+        .bind(ip)
+        .bind(ports)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// A device's ports whose neighbor is itself a known device.
+pub async fn get_device_neighbor_ports(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<String>> {
+    let ports = sqlx::query_scalar::<_, String>(
+        r#"SELECT port FROM device_port
+           WHERE ip = $1 AND remote_ip IS NOT NULL
+             AND (remote_ip IN (SELECT ip FROM device)
+                  OR remote_ip IN (SELECT alias FROM device_ip))
+           ORDER BY port"#
     )
         .bind(ip)
         .fetch_all(pool)
@@ -330,6 +640,271 @@ pub async fn get_device_vlans(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<Devic
     Ok(vlans)
 }
 
+/// Replace a device's VLANs and port VLAN membership, keeping the
+/// creation time of rows it still has.
+pub async fn replace_device_vlans(
+    pool: &PgPool,
+    ip: &IpNetwork,
+    vlans: &[DeviceVlan],
+    port_vlans: &[DevicePortVlan],
+) -> Result<()> {
+    let ids: Vec<i32> = vlans.iter().map(|v| v.vlan).collect();
+    let ports: Vec<String> = port_vlans.iter().map(|pv| pv.port.clone()).collect();
+    let port_ids: Vec<i32> = port_vlans.iter().map(|pv| pv.vlan).collect();
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM device_vlan WHERE ip = $1 AND NOT (vlan = ANY($2))")
+        .bind(ip)
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await?;
+    for vlan in vlans {
+        sqlx::query(
+            r#"INSERT INTO device_vlan (ip, vlan, description, last_discover)
+               VALUES ($1, $2, $3, $4)
+               ON CONFLICT (ip, vlan) DO UPDATE SET
+                description = EXCLUDED.description,
+                last_discover = EXCLUDED.last_discover"#
+        )
+            .bind(ip)
+            .bind(vlan.vlan)
+            .bind(&vlan.description)
+            .bind(vlan.last_discover)
+            .execute(&mut *tx)
+            .await?;
+    }
+    sqlx::query(
+        r#"DELETE FROM device_port_vlan WHERE ip = $1
+           AND (port, vlan) NOT IN (SELECT * FROM UNNEST($2::text[], $3::integer[]))"#
+    )
+        .bind(ip)
+        .bind(&ports)
+        .bind(&port_ids)
+        .execute(&mut *tx)
+        .await?;
+    for port_vlan in port_vlans {
+        sqlx::query(
+            r#"INSERT INTO device_port_vlan (ip, port, vlan, native, last_discover, vlantype)
+               VALUES ($1, $2, $3, $4, $5, $6)
+               ON CONFLICT (ip, port, vlan) DO UPDATE SET
+                native = EXCLUDED.native,
+                last_discover = EXCLUDED.last_discover,
+                vlantype = EXCLUDED.vlantype"#
+        )
+            .bind(ip)
+            .bind(&port_vlan.port)
+            .bind(port_vlan.vlan)
+            .bind(port_vlan.native)
+            .bind(port_vlan.last_discover)
+            .bind(&port_vlan.vlantype)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Replace a device's PoE modules and port power state.
+pub async fn replace_device_power(
+    pool: &PgPool,
+    ip: &IpNetwork,
+    modules: &[DevicePower],
+    ports: &[DevicePortPower],
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM device_power WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM device_port_power WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
+    for module in modules {
+        sqlx::query(
+            r#"INSERT INTO device_power (ip, module, power, status, consumption)
+               VALUES ($1, $2, $3, $4, $5)"#
+        )
+            .bind(ip)
+            .bind(module.module)
+            .bind(module.power)
+            .bind(&module.status)
+            .bind(module.consumption)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for port in ports {
+        sqlx::query(
+            r#"INSERT INTO device_port_power (ip, port, module, admin, status, class, power)
+               VALUES ($1, $2, $3, $4, $5, $6, $7)"#
+        )
+            .bind(ip)
+            .bind(&port.port)
+            .bind(port.module)
+            .bind(&port.admin)
+            .bind(&port.status)
+            .bind(&port.class)
+            .bind(port.power)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// PoE budget, draw and headroom for every PSE module, by device.
+///
+/// Draw is the module's reported consumption, or the sum of its ports'
+/// draw where the device does not report one.
+pub async fn poe_headroom(pool: &PgPool) -> Result<Vec<PoeHeadroom>> {
+    let rows = sqlx::query_as::<_, PoeHeadroom>(
+        r#"SELECT ip, dns, name, module, status, power, consumption,
+                  power - consumption AS headroom, ports, ports_powered
+           FROM (
+               SELECT d.ip, d.dns, d.name, p.module, p.status, p.power,
+                      COALESCE(p.consumption, (pp.drawn / 1000)::integer) AS consumption,
+                      COALESCE(pp.ports, 0) AS ports,
+                      COALESCE(pp.ports_powered, 0) AS ports_powered
+               FROM device_power p
+               JOIN device d ON d.ip = p.ip
+               LEFT JOIN (
+                   SELECT ip, module, SUM(power) AS drawn, COUNT(*) AS ports,
+                          COUNT(*) FILTER (WHERE status = 'deliveringPower') AS ports_powered
+                   FROM device_port_power
+                   GROUP BY ip, module
+               ) pp ON pp.ip = p.ip AND pp.module = p.module
+           ) modules
+           ORDER BY ip, module"#
+    )
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Replace a device's spanning tree instances and port states.
+pub async fn replace_device_stp(
+    pool: &PgPool,
+    ip: &IpNetwork,
+    instances: &[DeviceStp],
+    ports: &[DevicePortStp],
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM device_stp WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM device_port_stp WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
+    for instance in instances {
+        sqlx::query(
+            r#"INSERT INTO device_stp (ip, instance, protocol, vlans, bridge, root, root_cost, root_port, last_discover)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#
+        )
+            .bind(ip)
+            .bind(instance.instance)
+            .bind(&instance.protocol)
+            .bind(&instance.vlans)
+            .bind(&instance.bridge)
+            .bind(&instance.root)
+            .bind(instance.root_cost)
+            .bind(&instance.root_port)
+            .bind(instance.last_discover)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for port in ports {
+        sqlx::query("INSERT INTO device_port_stp (ip, port, instance, state) VALUES ($1, $2, $3, $4)")
+            .bind(ip)
+            .bind(&port.port)
+            .bind(port.instance)
+            .bind(&port.state)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Get spanning tree instances for a device.
+pub async fn get_device_stp(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<DeviceStp>> {
+    let instances = sqlx::query_as::<_, DeviceStp>(
+        "SELECT * FROM device_stp WHERE ip = $1 ORDER BY instance"
+    )
+        .bind(ip)
+        .fetch_all(pool)
+        .await?;
+    Ok(instances)
+}
+
+/// Get per-instance spanning tree port states for a device.
+pub async fn get_device_port_stp(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<DevicePortStp>> {
+    let ports = sqlx::query_as::<_, DevicePortStp>(
+        "SELECT * FROM device_port_stp WHERE ip = $1 ORDER BY port, instance"
+    )
+        .bind(ip)
+        .fetch_all(pool)
+        .await?;
+    Ok(ports)
+}
+
+/// The root bridge of each VLAN, as reported by the devices carrying it.
+///
+/// A device reports a VLAN's root from the MST or PVST instance that maps
+/// the VLAN, else from its instance 0. More than one row for a VLAN means
+/// the devices disagree on the root.
+pub async fn stp_roots(pool: &PgPool) -> Result<Vec<StpRoot>> {
+    let rows = sqlx::query_as::<_, StpRoot>(
+        r#"SELECT r.vlan, r.root, rd.ip AS root_ip, rd.dns AS root_dns, rd.name AS root_name,
+                  COUNT(*) AS devices
+           FROM (
+               SELECT v.vlan, s.root
+               FROM device_vlan v
+               JOIN LATERAL (
+                   SELECT root FROM device_stp s
+                   WHERE s.ip = v.ip AND (v.vlan = ANY(s.vlans) OR s.instance = 0)
+                   ORDER BY s.instance = 0, s.instance
+                   LIMIT 1
+               ) s ON true
+           ) r
+           LEFT JOIN LATERAL (
+               SELECT d.ip, d.dns, d.name
+               FROM device_stp b
+               JOIN device d ON d.ip = b.ip
+               WHERE split_part(b.bridge, '.', 2) = split_part(r.root, '.', 2)
+               LIMIT 1
+           ) rd ON true
+           GROUP BY r.vlan, r.root, rd.ip, rd.dns, rd.name
+           ORDER BY r.vlan, devices DESC"#
+    )
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Every port blocking in a spanning tree instance.
+pub async fn stp_blocking(pool: &PgPool) -> Result<Vec<StpBlocking>> {
+    let rows = sqlx::query_as::<_, StpBlocking>(
+        r#"SELECT p.ip, d.dns, d.name, p.port, p.instance, s.vlans
+           FROM device_port_stp p
+           JOIN device d ON d.ip = p.ip
+           LEFT JOIN device_stp s ON s.ip = p.ip AND s.instance = p.instance
+           WHERE p.state = 'blocking'
+           ORDER BY p.ip, p.port, p.instance"#
+    )
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Search VLANs by number, or by name (case-insensitive substring).
+pub async fn search_vlans(pool: &PgPool, query: &str) -> Result<Vec<DeviceVlan>> {
+    let vlans = match query.trim().parse::<i32>() {
+        Ok(vlan) => sqlx::query_as::<_, DeviceVlan>(
+            "SELECT * FROM device_vlan WHERE vlan = $1 ORDER BY ip LIMIT 100"
+        )
+            .bind(vlan)
+            .fetch_all(pool)
+            .await?,
+        Err(_) => sqlx::query_as::<_, DeviceVlan>(
+            "SELECT * FROM device_vlan WHERE description ILIKE $1 ORDER BY vlan, ip LIMIT 100"
+        )
+            .bind(format!("%{}%", query.trim()))
+            .fetch_all(pool)
+            .await?,
+    };
+    Ok(vlans)
+}
+
 /// Get modules for a device.
 pub async fn get_device_modules(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<DeviceModule>> {
     let modules = sqlx::query_as::<_, DeviceModule>(
@@ -341,6 +916,38 @@ pub async fn get_device_modules(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<Dev
     Ok(modules)
 }
 
+/// Replace a device's module inventory.
+pub async fn replace_device_modules(pool: &PgPool, ip: &IpNetwork, modules: &[DeviceModule]) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM device_module WHERE ip = $1").bind(ip).execute(&mut *tx).await?;
+    for module in modules {
+        sqlx::query(
+            r#"INSERT INTO device_module (ip, index, description, type, parent, name, class, pos,
+                hw_ver, fw_ver, sw_ver, serial, model, fru, last_discover)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"#
+        )
+            .bind(ip)
+            .bind(module.index)
+            .bind(&module.description)
+            .bind(&module.module_type)
+            .bind(module.parent)
+            .bind(&module.name)
+            .bind(&module.class)
+            .bind(module.pos)
+            .bind(&module.hw_ver)
+            .bind(&module.fw_ver)
+            .bind(&module.sw_ver)
+            .bind(&module.serial)
+            .bind(&module.model)
+            .bind(module.fru)
+            .bind(module.last_discover)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
 /// Get IP aliases for a device.
 pub async fn get_device_ips(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<device_ip::DeviceIp>> {
     let ips = sqlx::query_as::<_, device_ip::DeviceIp>(
@@ -352,6 +959,143 @@ pub async fn get_device_ips(pool: &PgPool, ip: &IpNetwork) -> Result<Vec<device_
     Ok(ips)
 }
 
+/// Devices stored under any of `addresses`, or owning one of them as an
+/// alias, oldest first.
+pub async fn find_devices_by_addresses(pool: &PgPool, addresses: &[IpNetwork]) -> Result<Vec<DeviceIdentity>> {
+    let devices = sqlx::query_as::<_, DeviceIdentity>(
+        r#"SELECT ip, name, serial FROM device
+           WHERE ip = ANY($1)
+              OR ip IN (SELECT ip FROM device_ip WHERE alias = ANY($1))
+           ORDER BY creation, ip"#
+    )
+        .bind(addresses)
+        .fetch_all(pool)
+        .await?;
+    Ok(devices)
+}
+
+/// Fold device `from` into device `into`, which may not exist yet.
+///
+/// Rows keyed on the device move across unless `into` already has the same
+/// row (same port, VLAN, module, ...), in which case `into` keeps its own.
+pub async fn merge_device(pool: &PgPool, from: &IpNetwork, into: &IpNetwork) -> Result<()> {
+    // Table, device column, other primary key columns
+    const KEYED: &[(&str, &str, &[&str])] = &[
+        ("device_ip", "ip", &["alias"]),
+        ("device_module", "ip", &["index"]),
+        ("device_port", "ip", &["port"]),
+        ("device_port_power", "ip", &["port"]),
+        ("device_port_stp", "ip", &["port", "instance"]),
+        ("device_port_vlan", "ip", &["port", "vlan"]),
+        ("device_power", "ip", &["module"]),
+        ("device_stp", "ip", &["instance"]),
+        ("device_vlan", "ip", &["vlan"]),
+        ("community", "ip", &[]),
+        ("node", "switch", &["mac", "port", "vlan"]),
+    ];
+    const UNKEYED: &[(&str, &str)] = &[
+        ("device_port_log", "ip"),
+        ("device_port_ssid", "ip"),
+        ("device_port_wireless", "ip"),
+        ("topology", "dev1"),
+        ("topology", "dev2"),
+        ("admin", "device"),
+        ("node_ip", "router"),
+    ];
+
+    let mut tx = pool.begin().await?;
+    let into_exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM device WHERE ip = $1)")
+        .bind(into)
+        .fetch_one(&mut *tx)
+        .await?;
+    let device_sql = if into_exists {
+        "DELETE FROM device WHERE ip = $1"
+    } else {
+        "UPDATE device SET ip = $2 WHERE ip = $1"
+    };
+    sqlx::query(device_sql).bind(from).bind(into).execute(&mut *tx).await?;
+
+    for (table, column, key) in KEYED {
+        let same_row: String = key.iter().map(|k| format!(" AND o.{k} IS NOT DISTINCT FROM t.{k}")).collect();
+        sqlx::query(&format!(
+            "UPDATE {table} t SET {column} = $2 WHERE t.{column} = $1
+               AND NOT EXISTS (SELECT 1 FROM {table} o WHERE o.{column} = $2{same_row})"
+        ))
+            .bind(from)
+            .bind(into)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(&format!("DELETE FROM {table} WHERE {column} = $1"))
+            .bind(from)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for (table, column) in UNKEYED {
+        sqlx::query(&format!("UPDATE {table} SET {column} = $2 WHERE {column} = $1"))
+            .bind(from)
+            .bind(into)
+            .execute(&mut *tx)
+            .await?;
+    }
+    sqlx::query("DELETE FROM device_skip WHERE device = $1").bind(from).execute(&mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Append lines to a device's `log`.
+pub async fn append_device_log(pool: &PgPool, ip: &IpNetwork, entries: &[String]) -> Result<()> {
+    sqlx::query("UPDATE device SET log = concat_ws(E'\\n', log, $2) WHERE ip = $1")
+        .bind(ip)
+        .bind(entries.join("\n"))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Replace a device's IP aliases, keeping the creation time and DNS name
+/// of aliases it still has.
+pub async fn replace_device_ips(pool: &PgPool, ip: &IpNetwork, aliases: &[device_ip::DeviceIp]) -> Result<()> {
+    let addresses: Vec<IpNetwork> = aliases.iter().map(|a| a.alias).collect();
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM device_ip WHERE ip = $1 AND NOT (alias = ANY($2))")
+        .bind(ip)
+        .bind(&addresses)
+        .execute(&mut *tx)
+        .await?;
+    for alias in aliases {
+        sqlx::query(
+            r#"INSERT INTO device_ip (ip, alias, subnet, port)
+               VALUES ($1, $2, $3, $4)
+               ON CONFLICT (ip, alias) DO UPDATE SET
+                subnet = EXCLUDED.subnet,
+                port = EXCLUDED.port"#
+        )
+            .bind(ip)
+            .bind(alias.alias)
+            .bind(alias.subnet)
+            .bind(&alias.port)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Record subnets seen on devices, refreshing their last_discover.
+pub async fn upsert_subnets(pool: &PgPool, subnets: &[IpNetwork]) -> Result<()> {
+    for net in subnets {
+        sqlx::query(
+            r#"INSERT INTO subnets (net, last_discover) VALUES ($1, LOCALTIMESTAMP)
+               ON CONFLICT (net) DO UPDATE SET last_discover = EXCLUDED.last_discover"#
+        )
+            .bind(net)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
 // ==================== Statistics Queries ====================
 
 /// Get device count.
@@ -395,3 +1139,16 @@ pub async fn find_oui(pool: &PgPool, oui: &str) -> Result<Option<oui::Oui>> {
         .await?;
     Ok(result)
 }
+
+// ==================== Enterprise Queries ====================
+
+/// Look up a vendor by SNMP private enterprise number.
+pub async fn find_enterprise(pool: &PgPool, id: u32) -> Result<Option<enterprise::Enterprise>> {
+    let result = sqlx::query_as::<_, enterprise::Enterprise>(
+        "SELECT * FROM enterprise WHERE id = $1"
+    )
+        .bind(id as i32)
+        .fetch_optional(pool)
+        .await?;
+    Ok(result)
+}
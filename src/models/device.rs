@@ -114,6 +114,33 @@ impl Device {
     }
 }
 
+/// What identifies the box behind a device row, for telling whether two
+/// rows that share an address are the same device.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DeviceIdentity {
+    pub ip: IpNetwork,
+    /// sysName
+    pub name: Option<String>,
+    /// Chassis serial number
+    pub serial: Option<String>,
+}
+
+impl DeviceIdentity {
+    /// Whether this row is the box now answering with `name` and `serial`:
+    /// the chassis serials match or, with a serial missing on either side,
+    /// the sysNames do. Addresses alone never are enough, since VRRP/HSRP,
+    /// anycast and factory-default addresses are shared between boxes.
+    pub fn corroborated_by(&self, name: Option<&str>, serial: Option<&str>) -> bool {
+        let same = |a: Option<&str>, b: Option<&str>| {
+            matches!((a, b), (Some(a), Some(b)) if !a.is_empty() && a.eq_ignore_ascii_case(b))
+        };
+        match (self.serial.as_deref(), serial) {
+            (Some(_), Some(_)) => same(self.serial.as_deref(), serial),
+            _ => same(self.name.as_deref(), name),
+        }
+    }
+}
+
 /// For creating a new device record.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewDevice {
@@ -1,4 +1,4 @@
-//! DevicePower model - power supply modules on a device.
+//! DevicePower model - PoE power sourcing modules on a device.
 
 use ipnetwork::IpNetwork;
 use serde::{Deserialize, Serialize};
@@ -8,6 +8,27 @@ use sqlx::FromRow;
 pub struct DevicePower {
     pub ip: IpNetwork,
     pub module: i32,
+    /// PoE budget in watts
     pub power: Option<i32>,
     pub status: Option<String>,
+    /// Power drawn in watts, when the device reports it
+    pub consumption: Option<i32>,
+}
+
+/// A row of the PoE headroom report: one PSE module on a device.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PoeHeadroom {
+    pub ip: IpNetwork,
+    pub dns: Option<String>,
+    pub name: Option<String>,
+    pub module: i32,
+    pub status: Option<String>,
+    /// Budget in watts
+    pub power: Option<i32>,
+    /// Drawn in watts, reported or summed from the ports
+    pub consumption: Option<i32>,
+    /// Budget left in watts
+    pub headroom: Option<i32>,
+    pub ports: i64,
+    pub ports_powered: i64,
 }
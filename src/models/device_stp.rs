@@ -0,0 +1,61 @@
+//! DeviceStp model - spanning tree instances and port state on a device.
+
+use chrono::NaiveDateTime;
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// One spanning tree instance on a device.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DeviceStp {
+    pub ip: IpNetwork,
+    /// 0 for the single tree or CIST, else the MSTID or PVST VLAN
+    pub instance: i32,
+    /// "stp", "rstp", "mstp", "pvst", "rapid-pvst" or "mistp"
+    pub protocol: Option<String>,
+    /// VLANs the instance covers; empty for instance 0
+    pub vlans: Vec<i32>,
+    /// This device's bridge ID, as "priority.mac"
+    pub bridge: Option<String>,
+    /// The root bridge ID, as "priority.mac"
+    pub root: Option<String>,
+    pub root_cost: Option<i32>,
+    /// Port towards the root; None on the root bridge
+    pub root_port: Option<String>,
+    pub last_discover: Option<NaiveDateTime>,
+}
+
+/// A port's state in one spanning tree instance.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DevicePortStp {
+    pub ip: IpNetwork,
+    pub port: String,
+    pub instance: i32,
+    /// "forwarding", "blocking", "learning", "listening", "disabled" or "broken"
+    pub state: Option<String>,
+}
+
+/// A row of the spanning tree report: the root bridge a group of devices
+/// agree on for a VLAN.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct StpRoot {
+    pub vlan: i32,
+    pub root: Option<String>,
+    /// The known device with the root's bridge address
+    pub root_ip: Option<IpNetwork>,
+    pub root_dns: Option<String>,
+    pub root_name: Option<String>,
+    /// Devices carrying the VLAN that report this root
+    pub devices: i64,
+}
+
+/// A row of the spanning tree report: a port blocking in an instance.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct StpBlocking {
+    pub ip: IpNetwork,
+    pub dns: Option<String>,
+    pub name: Option<String>,
+    pub port: String,
+    pub instance: i32,
+    pub vlans: Option<Vec<i32>>,
+}
@@ -15,6 +15,7 @@ pub mod device_port_vlan;
 pub mod device_port_wireless;
 pub mod device_power;
 pub mod device_skip;
+pub mod device_stp;
 pub mod device_vlan;
 pub mod node;
 pub mod node_ip;
@@ -36,7 +37,7 @@ pub mod user;
 pub mod user_log;
 
 // Re-export commonly used models
-pub use device::Device;
+pub use device::{Device, DeviceIdentity};
 pub use device_ip::DeviceIp;
 pub use device_module::DeviceModule;
 pub use device_port::DevicePort;
@@ -45,8 +46,9 @@ pub use device_port_power::DevicePortPower;
 pub use device_port_ssid::DevicePortSsid;
 pub use device_port_vlan::DevicePortVlan;
 pub use device_port_wireless::DevicePortWireless;
-pub use device_power::DevicePower;
+pub use device_power::{DevicePower, PoeHeadroom};
 pub use device_skip::DeviceSkip;
+pub use device_stp::{DevicePortStp, DeviceStp, StpBlocking, StpRoot};
 pub use device_vlan::DeviceVlan;
 pub use node::Node;
 pub use node_ip::NodeIp;
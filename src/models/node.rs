@@ -37,4 +37,23 @@ impl Node {
             .collect::<String>()
             .to_uppercase()
     }
+
+    /// Whether a MAC last seen elsewhere at `other_time_last` has moved to
+    /// a switch port that last saw it at `previous` (never, if None).
+    /// Otherwise both are still reporting it and it has not moved.
+    pub fn is_move(previous: Option<NaiveDateTime>, other_time_last: Option<NaiveDateTime>) -> bool {
+        match (previous, other_time_last) {
+            (Some(previous), Some(other)) => other < previous,
+            _ => true,
+        }
+    }
+
+    /// The `log` entry for this MAC moving here from another switch port.
+    /// It starts with the MAC so a node's moves can be found by prefix.
+    pub fn move_entry(&self, from_switch: &IpNetwork, from_port: &str) -> String {
+        format!(
+            "{} moved from {} {} to {} {}",
+            self.mac, from_switch.ip(), from_port, self.switch.ip(), self.port,
+        )
+    }
 }
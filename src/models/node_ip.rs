@@ -19,4 +19,6 @@ pub struct NodeIp {
     pub time_last: Option<NaiveDateTime>,
     /// DNS name
     pub dns: Option<String>,
+    /// Router whose ARP/NDP table this was last seen in
+    pub router: Option<IpNetwork>,
 }
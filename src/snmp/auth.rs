@@ -0,0 +1,154 @@
+//! Per-device SNMP credential selection.
+//!
+//! Credentials come from the `device_auth` stanzas, followed by the
+//! legacy `community` (read) and `community_rw` (write) lists. A device
+//! gets the stanzas whose `only`/`no` ACLs match it, in config order.
+//! Discovery tries them in turn and remembers the one that answered, so
+//! later jobs try that one first.
+
+use anyhow::Result;
+use ipnetwork::IpNetwork;
+use std::net::IpAddr;
+use tracing::debug;
+
+use super::client::SnmpClient;
+use super::oids;
+use crate::config::{DeviceAuth, NetdiscoConfig};
+use crate::util::permission;
+
+/// Whether credentials are wanted for polling or for SNMP SET.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// The credential last known to work for a device, as stored in
+/// device.snmp_ver/snmp_comm and the community table's auth tags.
+/// For SNMPv3, `snmp_comm` holds the user name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RememberedAuth {
+    pub snmp_ver: Option<i32>,
+    pub snmp_comm: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl RememberedAuth {
+    pub fn from_stanza(auth: &DeviceAuth) -> Self {
+        Self {
+            snmp_ver: Some(stanza_version(auth) as i32),
+            snmp_comm: auth.user.clone().or_else(|| auth.community.clone()),
+            tag: auth.tag.clone(),
+        }
+    }
+
+    fn matches(&self, auth: &DeviceAuth) -> bool {
+        if let (Some(remembered), Some(tag)) = (&self.tag, &auth.tag) {
+            return remembered == tag;
+        }
+        if self.tag.is_some() || auth.tag.is_some() {
+            return false;
+        }
+        let name = auth.user.as_ref().or(auth.community.as_ref());
+        name.is_some()
+            && self.snmp_comm.as_ref() == name
+            && self.snmp_ver.is_none_or(|v| v == stanza_version(auth) as i32)
+    }
+}
+
+/// The credential stanzas that apply to `host`, in the order to try them.
+///
+/// Community stanzas without `snmpver` get the global `snmpver` (v2c when
+/// that is 3). With nothing configured for reading, "public" is used.
+pub fn stanzas_for(config: &NetdiscoConfig, host: &str, access: Access) -> Vec<DeviceAuth> {
+    let ip = host.parse::<IpAddr>().ok().map(IpNetwork::from);
+    let mut stanzas: Vec<DeviceAuth> = config.device_auth.iter()
+        .filter(|a| match access {
+            Access::Read => a.read,
+            Access::Write => a.write,
+        })
+        .filter(|a| a.user.is_some() || a.community.is_some())
+        .filter(|a| match &ip {
+            Some(ip) => permission::is_permitted(ip, &a.only, &a.no),
+            None => a.only.is_empty() && a.no.is_empty(),
+        })
+        .cloned()
+        .collect();
+
+    let legacy = match access {
+        Access::Read => &config.community,
+        Access::Write => &config.community_rw,
+    };
+    stanzas.extend(legacy.iter().map(|community| DeviceAuth {
+        community: Some(community.clone()),
+        read: access == Access::Read,
+        write: access == Access::Write,
+        ..Default::default()
+    }));
+    if stanzas.is_empty() && access == Access::Read {
+        stanzas.push(DeviceAuth { community: Some("public".to_string()), ..Default::default() });
+    }
+
+    let default_version = if config.snmpver == 1 { 1 } else { 2 };
+    for stanza in stanzas.iter_mut().filter(|a| a.user.is_none() && a.snmpver.is_none()) {
+        stanza.snmpver = Some(default_version);
+    }
+    stanzas
+}
+
+/// Move the remembered stanza, if still configured, to the front.
+pub fn prefer_remembered(stanzas: &mut [DeviceAuth], remembered: &RememberedAuth) {
+    if let Some(i) = stanzas.iter().position(|a| remembered.matches(a)) {
+        stanzas[..=i].rotate_right(1);
+    }
+}
+
+/// Try each stanza in turn until the device answers a GET of
+/// sysObjectID.0, and return that client and stanza.
+pub async fn connect(config: &NetdiscoConfig, host: &str, stanzas: &[DeviceAuth]) -> Result<(SnmpClient, DeviceAuth)> {
+    let probe: Vec<u32> = oids::SYS_OBJECT_ID.iter().copied().chain([0]).collect();
+    let mut last_error = None;
+
+    for auth in stanzas {
+        let attempt = async {
+            let client = SnmpClient::from_device_auth(config, host, auth)?;
+            let value = client.get(&probe).await?;
+            if value.is_exception() {
+                anyhow::bail!("sysObjectID.0 is {}", value);
+            }
+            Ok(client)
+        };
+        match attempt.await {
+            Ok(client) => {
+                debug!("SNMP credential {} works for {}", describe(auth), host);
+                return Ok((client, auth.clone()));
+            }
+            Err(e) => {
+                debug!("SNMP credential {} failed for {}: {:#}", describe(auth), host, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    match last_error {
+        Some(e) => Err(e.context(format!("No SNMP credentials worked for {} ({} tried)", host, stanzas.len()))),
+        None => Err(anyhow::anyhow!("No SNMP credentials configured for {}", host)),
+    }
+}
+
+/// Name a stanza for logs without revealing secrets.
+pub fn describe(auth: &DeviceAuth) -> String {
+    match (&auth.tag, &auth.user) {
+        (Some(tag), _) => format!("'{}'", tag),
+        (None, Some(user)) => format!("v3 user '{}'", user),
+        (None, None) => format!("v{} community", stanza_version(auth)),
+    }
+}
+
+fn stanza_version(auth: &DeviceAuth) -> u8 {
+    match (&auth.user, auth.snmpver) {
+        (Some(_), _) => 3,
+        (None, Some(1)) => 1,
+        _ => 2,
+    }
+}
@@ -1,11 +1,20 @@
 //! SNMP client implementation.
 
 use anyhow::{Context, Result};
-use std::net::{SocketAddr, UdpSocket};
-use std::time::Duration;
+use ipnetwork::IpNetwork;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 use tracing::{debug, info, warn};
 
-use crate::config::NetdiscoConfig;
+use super::auth::Access;
+use super::transport::SnmpTransport;
+use super::usm::{self, UsmKeys, UsmUser};
+use super::value::SnmpValue;
+use crate::config::{DeviceAuth, NetdiscoConfig};
 
 /// SNMP protocol version.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -17,7 +26,7 @@ pub enum SnmpVersion {
 
 impl SnmpVersion {
     /// BER-encoded version number for the SNMP message header.
-    fn version_byte(&self) -> u8 {
+    pub(crate) fn version_byte(&self) -> u8 {
         match self {
             SnmpVersion::V1 => 0,
             SnmpVersion::V2c => 1,
@@ -36,6 +45,16 @@ impl From<u8> for SnmpVersion {
     }
 }
 
+impl From<SnmpVersion> for u8 {
+    fn from(v: SnmpVersion) -> Self {
+        match v {
+            SnmpVersion::V1 => 1,
+            SnmpVersion::V2c => 2,
+            SnmpVersion::V3 => 3,
+        }
+    }
+}
+
 /// SNMP authentication credentials.
 #[derive(Debug, Clone)]
 pub struct SnmpCredentials {
@@ -49,6 +68,67 @@ pub struct SnmpCredentials {
     pub priv_password: Option<String>,
 }
 
+impl SnmpCredentials {
+    /// Credentials from a `device_auth` stanza: SNMPv3 when it names a
+    /// user, otherwise its community with `snmpver` (v2c by default).
+    pub fn from_device_auth(auth: &DeviceAuth) -> Self {
+        if auth.user.is_none() {
+            let version = match auth.snmpver {
+                Some(1) => SnmpVersion::V1,
+                _ => SnmpVersion::V2c,
+            };
+            return SnmpCredentials {
+                version,
+                community: auth.community.clone().unwrap_or_default(),
+                username: None,
+                auth_protocol: None,
+                auth_password: None,
+                priv_protocol: None,
+                priv_password: None,
+            };
+        }
+        SnmpCredentials {
+            version: SnmpVersion::V3,
+            community: String::new(),
+            username: auth.user.clone(),
+            auth_protocol: auth.auth.as_ref()
+                .map(|a| a.proto.clone().unwrap_or_else(|| "MD5".to_string())),
+            auth_password: auth.auth.as_ref().map(|a| a.pass.clone()),
+            priv_protocol: auth.privacy.as_ref()
+                .map(|p| p.proto.clone().unwrap_or_else(|| "DES".to_string())),
+            priv_password: auth.privacy.as_ref().map(|p| p.pass.clone()),
+        }
+    }
+}
+
+/// Authoritative engine state learned during SNMPv3 discovery.
+#[derive(Debug, Clone)]
+struct EngineState {
+    engine_id: Vec<u8>,
+    boots: u32,
+    time: u32,
+    /// Local clock reading when `time` was last synchronised.
+    synced_at: Instant,
+    keys: UsmKeys,
+}
+
+impl EngineState {
+    /// Our estimate of the agent's current snmpEngineTime.
+    fn current_time(&self) -> u32 {
+        self.time.saturating_add(self.synced_at.elapsed().as_secs() as u32)
+    }
+
+    /// Adopt newer boots/time values from an authentic message
+    /// (RFC 3414 section 3.2 step 7b).
+    fn synchronise(&mut self, boots: u32, time: u32) {
+        if boots > self.boots || (boots == self.boots && time > self.current_time()) {
+            self.boots = boots;
+            self.time = time;
+            self.synced_at = Instant::now();
+        }
+    }
+}
+
 /// A high-level SNMP client for Netdisco operations.
 #[derive(Debug)]
 pub struct SnmpClient {
@@ -56,6 +136,25 @@ pub struct SnmpClient {
     credentials: SnmpCredentials,
     timeout: Duration,
     retries: u32,
+    /// Parsed SNMPv3 user (v3 only).
+    usm_user: Option<UsmUser>,
+    /// Discovered authoritative engine (v3 only).
+    engine: Mutex<Option<EngineState>>,
+    /// Privacy salt counter; must never repeat for a given key.
+    salt: AtomicU64,
+    /// Walk with GETBULK (ignored for v1).
+    bulkwalk: bool,
+    /// GETBULK max-repetitions; shrinks when the agent answers tooBig.
+    max_repetitions: AtomicU32,
+    transport: Arc<SnmpTransport>,
+    /// Limits concurrent requests to this device, shared through the
+    /// transport with every other client talking to it; fetched on the
+    /// first request.
+    inflight: OnceLock<Arc<Semaphore>>,
+    /// The limit to create `inflight` with if no other client has.
+    max_inflight: usize,
+    /// SNMPv3 contextName; empty for the default context.
+    context: Vec<u8>,
 }
 
 impl SnmpClient {
@@ -67,89 +166,245 @@ impl SnmpClient {
         timeout_us: u64,
         retries: u32,
     ) -> Result<Self> {
-        if credentials.version == SnmpVersion::V3 {
-            warn!("SNMPv3 is not yet implemented, falling back to v2c for {}", host);
-        }
+        let target = target_addr(host, port)?;
 
-        let target: SocketAddr = format!("{}:{}", host, port)
-            .parse()
-            .with_context(|| format!("Invalid SNMP target: {}:{}", host, port))?;
+        let usm_user = if credentials.version == SnmpVersion::V3 {
+            Some(usm_user_from_credentials(&credentials)
+                .with_context(|| format!("Invalid SNMPv3 credentials for {}", host))?)
+        } else {
+            None
+        };
 
         Ok(Self {
             target,
             credentials,
             timeout: Duration::from_micros(timeout_us),
             retries,
+            usm_user,
+            engine: Mutex::new(None),
+            salt: AtomicU64::new(rand::random()),
+            bulkwalk: true,
+            max_repetitions: AtomicU32::new(DEFAULT_MAX_REPETITIONS),
+            transport: SnmpTransport::shared_for(&target, None)?,
+            inflight: OnceLock::new(),
+            max_inflight: DEFAULT_MAX_INFLIGHT,
+            context: Vec::new(),
         })
     }
 
-    /// Create a client from Netdisco config for a specific device.
+    /// Send from `source` rather than the wildcard address. It must be
+    /// of the same address family as the target.
+    pub fn set_source_address(&mut self, source: IpAddr) -> Result<()> {
+        self.transport = SnmpTransport::shared_for(&self.target, Some(source))?;
+        self.inflight = OnceLock::new();
+        Ok(())
+    }
+
+    /// Talk to the replay simulator at `agent` instead of the device.
+    pub fn replay_from(&mut self, agent: SocketAddr) -> Result<()> {
+        self.target = agent;
+        self.transport = SnmpTransport::shared_for(&agent, None)?;
+        self.inflight = OnceLock::new();
+        Ok(())
+    }
+
+    /// The agent address this client talks to.
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+
+    /// The local address requests are sent from.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.transport.local_addr()
+    }
+
+    /// Allow up to `max` requests to this device at once, across every
+    /// client talking to it (see [`SnmpTransport::inflight_for`]).
+    pub fn set_max_inflight(&mut self, max: usize) {
+        self.max_inflight = max.max(1);
+        self.inflight = OnceLock::new();
+    }
+
+    /// How many requests to this device may be in flight at once.
+    pub fn max_inflight(&self) -> usize {
+        self.max_inflight
+    }
+
+    /// Configure GETBULK walking: `enabled` is false for `bulkwalk_off`,
+    /// and `repeaters` is the max-repetitions to request.
+    pub fn set_bulkwalk(&mut self, enabled: bool, repeaters: u32) {
+        self.bulkwalk = enabled;
+        self.max_repetitions = AtomicU32::new(repeaters.max(1));
+    }
+
+    /// Whether `walk` will use GETBULK.
+    pub fn uses_bulkwalk(&self) -> bool {
+        self.bulkwalk && self.credentials.version != SnmpVersion::V1
+    }
+
+    /// Create a client for polling from config, using the first
+    /// credential stanza that applies to `host` (see `snmp::auth`).
     pub fn from_config(config: &NetdiscoConfig, host: &str) -> Result<Self> {
-        let community = config.community.first()
-            .cloned()
-            .unwrap_or_else(|| "public".to_string());
+        let stanzas = super::auth::stanzas_for(config, host, Access::Read);
+        let auth = stanzas.first()
+            .ok_or_else(|| anyhow::anyhow!("No read credentials configured for {}", host))?;
+        Self::from_device_auth(config, host, auth)
+    }
 
-        Self::new(
-            host,
-            161,
-            SnmpCredentials {
-                version: SnmpVersion::from(config.snmpver),
-                community,
-                username: None,
-                auth_protocol: None,
-                auth_password: None,
-                priv_protocol: None,
-                priv_password: None,
-            },
-            config.snmptimeout,
-            config.snmpretries,
-        )
+    /// Create a client for SET requests, using the first write stanza
+    /// (or `community_rw` string) that applies to `host`.
+    pub fn from_config_rw(config: &NetdiscoConfig, host: &str) -> Result<Self> {
+        let stanzas = super::auth::stanzas_for(config, host, Access::Write);
+        let auth = stanzas.first()
+            .ok_or_else(|| anyhow::anyhow!("No write credentials (device_auth or community_rw) configured for {}", host))?;
+        Self::from_device_auth(config, host, auth)
     }
 
-    /// Effective SNMP version (v3 falls back to v2c until implemented).
-    fn effective_version(&self) -> &SnmpVersion {
-        if self.credentials.version == SnmpVersion::V3 {
-            &SnmpVersion::V2c
-        } else {
-            &self.credentials.version
+    /// Create a client for one credential stanza. Port, timeout and
+    /// retries fall back to the global settings.
+    pub fn from_device_auth(config: &NetdiscoConfig, host: &str, auth: &DeviceAuth) -> Result<Self> {
+        let mut client = Self::new(
+            host,
+            auth.port.unwrap_or(161),
+            SnmpCredentials::from_device_auth(auth),
+            auth.timeout.unwrap_or(config.snmptimeout),
+            auth.retries.unwrap_or(config.snmpretries),
+        )?;
+        client.set_bulkwalk(!config.bulkwalk_off, config.bulkwalk_repeaters);
+        client.set_max_inflight(config.snmp_max_inflight);
+        if let Some(agent) = config.snmp_replay.get(&client.target.ip()) {
+            client.replay_from(*agent)?;
+        } else if let Some(source) = config.snmp_source_for(&client.target.ip())? {
+            client.set_source_address(source)?;
         }
+        Ok(client)
+    }
+
+    /// SNMP version this client speaks.
+    pub fn version(&self) -> SnmpVersion {
+        self.credentials.version
+    }
+
+    /// Credentials this client was built with.
+    pub fn credentials(&self) -> &SnmpCredentials {
+        &self.credentials
     }
 
     /// SNMP GET request for a single OID.
-    pub fn get(&self, oid: &[u32]) -> Result<Vec<u8>> {
-        debug!("SNMP GET {} from {}", oid_to_string(oid), self.target);
-        let pdu = build_get_pdu(self.effective_version(), &self.credentials.community, oid);
-        let response = self.send_receive(&pdu)?;
-        parse_get_response(&response)
+    ///
+    /// A missing object comes back as `SnmpValue::NoSuchObject` or
+    /// `NoSuchInstance` (v2c/v3) rather than an error.
+    pub async fn get(&self, oid: &[u32]) -> Result<SnmpValue> {
+        let varbinds = self.get_many(&[oid]).await?;
+        varbinds.into_iter()
+            .next()
+            .map(|(_, value)| value)
+            .ok_or_else(|| anyhow::anyhow!("SNMP GET: no varbind in response"))
     }
 
     /// SNMP GETNEXT request (walk single step).
-    pub fn get_next(&self, oid: &[u32]) -> Result<(Vec<u32>, Vec<u8>)> {
-        debug!("SNMP GETNEXT {} from {}", oid_to_string(oid), self.target);
-        let pdu = build_getnext_pdu(self.effective_version(), &self.credentials.community, oid);
-        let response = self.send_receive(&pdu)?;
-        parse_getnext_response(&response)
+    pub async fn get_next(&self, oid: &[u32]) -> Result<(Vec<u32>, SnmpValue)> {
+        let varbinds = self.get_next_many(&[oid]).await?;
+        varbinds.into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("SNMP GETNEXT: no varbind in response"))
     }
 
     /// SNMP GETBULK request (efficient table walking).
-    pub fn get_bulk(&self, oid: &[u32], max_repetitions: u32) -> Result<Vec<(Vec<u32>, Vec<u8>)>> {
-        debug!("SNMP GETBULK {} (max_rep={}) from {}", oid_to_string(oid), max_repetitions, self.target);
-        let pdu = build_getbulk_pdu(self.effective_version(), &self.credentials.community, oid, max_repetitions);
-        let response = self.send_receive(&pdu)?;
-        parse_getbulk_response(&response)
+    pub async fn get_bulk(&self, oid: &[u32], max_repetitions: u32) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+        self.get_bulk_many(0, max_repetitions, &[oid]).await
+    }
+
+    /// SNMP GET request for several OIDs in one PDU. Varbinds come back
+    /// in request order.
+    pub async fn get_many(&self, oids: &[&[u32]]) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+        debug!("SNMP GET {} from {}", oids_to_string(oids), self.target);
+        let response = self.request(|request_id| build_pdu(PDU_GET_REQUEST, request_id, oids, None)).await?;
+        parse_snmp_response(&response)
+    }
+
+    /// SNMP GETNEXT request for several OIDs in one PDU.
+    pub async fn get_next_many(&self, oids: &[&[u32]]) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+        debug!("SNMP GETNEXT {} from {}", oids_to_string(oids), self.target);
+        let response = self.request(|request_id| build_pdu(PDU_GET_NEXT_REQUEST, request_id, oids, None)).await?;
+        parse_snmp_response(&response)
+    }
+
+    /// SNMP GETBULK request for several OIDs. The first `non_repeaters`
+    /// OIDs get a single successor each; the rest are repeated up to
+    /// `max_repetitions` times, interleaved row by row (RFC 3416 4.2.3).
+    pub async fn get_bulk_many(
+        &self,
+        non_repeaters: u32,
+        max_repetitions: u32,
+        oids: &[&[u32]],
+    ) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+        debug!("SNMP GETBULK {} (non_rep={}, max_rep={}) from {}",
+            oids_to_string(oids), non_repeaters, max_repetitions, self.target);
+        let bulk = Some((non_repeaters, max_repetitions));
+        let response = self.request(|request_id| build_pdu(PDU_GET_BULK_REQUEST, request_id, oids, bulk)).await?;
+        parse_snmp_response(&response)
+    }
+
+    /// SNMP SET request. Returns the varbinds the agent echoes back.
+    pub async fn set(&self, varbinds: &[(&[u32], SnmpValue)]) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+        let oids: Vec<&[u32]> = varbinds.iter().map(|(oid, _)| *oid).collect();
+        debug!("SNMP SET {} on {}", oids_to_string(&oids), self.target);
+        let encoded = encode_varbinds(varbinds);
+        let response = self.request(|request_id| encode_pdu(PDU_SET_REQUEST, request_id, None, &encoded)).await?;
+        parse_snmp_response(&response)
+    }
+
+    /// SET a single object, then GET it back and fail unless the agent
+    /// now reports the value written.
+    pub async fn set_verified(&self, oid: &[u32], value: SnmpValue) -> Result<()> {
+        self.set(&[(oid, value.clone())]).await
+            .with_context(|| format!("SNMP SET {} on {} failed", oid_to_string(oid), self.target))?;
+        let current = self.get(oid).await
+            .with_context(|| format!("SNMP read-back of {} on {} failed", oid_to_string(oid), self.target))?;
+        if current != value {
+            anyhow::bail!("SNMP SET {} on {} did not take effect: wrote {}, read back {}",
+                oid_to_string(oid), self.target, value, current);
+        }
+        Ok(())
+    }
+
+    /// GETBULK with the client's max-repetitions, halving it whenever the
+    /// agent answers tooBig.
+    async fn get_bulk_shrinking(&self, oids: &[&[u32]]) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+        loop {
+            let max_repetitions = self.max_repetitions.load(Ordering::Relaxed);
+            match self.get_bulk_many(0, max_repetitions, oids).await {
+                Err(e) if is_too_big(&e) && max_repetitions > 1 => {
+                    let smaller = max_repetitions / 2;
+                    debug!("SNMP tooBig from {}, max-repetitions {} -> {}", self.target, max_repetitions, smaller);
+                    self.max_repetitions.store(smaller, Ordering::Relaxed);
+                }
+                result => return result,
+            }
+        }
     }
 
     /// Walk an entire OID subtree.
-    pub fn walk(&self, base_oid: &[u32]) -> Result<Vec<(Vec<u32>, Vec<u8>)>> {
+    ///
+    /// Uses GETBULK on v2c/v3 unless `bulkwalk_off` is set, and GETNEXT
+    /// otherwise. A walk that gets no answer at all (a timeout, or a
+    /// report such as unknownContext) is an error; one cut short after
+    /// that returns what it has.
+    pub async fn walk(&self, base_oid: &[u32]) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+        if self.uses_bulkwalk() {
+            return self.bulk_walk(base_oid).await;
+        }
+
         info!("SNMP WALK {} on {}", oid_to_string(base_oid), self.target);
         let mut results = Vec::new();
         let mut current_oid = base_oid.to_vec();
 
         loop {
-            match self.get_next(&current_oid) {
+            match self.get_next(&current_oid).await {
                 Ok((next_oid, value)) => {
                     // Check if we've gone past the subtree
-                    if next_oid.is_empty() || !next_oid.starts_with(base_oid) {
+                    if value.is_exception() || next_oid.is_empty() || !next_oid.starts_with(base_oid) {
                         break;
                     }
                     // Guard against infinite loops (OID didn't advance)
@@ -160,6 +415,7 @@ impl SnmpClient {
                     current_oid = next_oid.clone();
                     results.push((next_oid, value));
                 }
+                Err(e) if results.is_empty() && e.downcast_ref::<ErrorStatus>().is_none() => return Err(e),
                 Err(e) => {
                     warn!("SNMP walk ended: {}", e);
                     break;
@@ -171,159 +427,1572 @@ impl SnmpClient {
         Ok(results)
     }
 
+    /// Walk a subtree with GETBULK, halving max-repetitions whenever the
+    /// agent answers tooBig.
+    async fn bulk_walk(&self, base_oid: &[u32]) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+        info!("SNMP BULKWALK {} on {}", oid_to_string(base_oid), self.target);
+        let mut results = Vec::new();
+        let mut current_oid = base_oid.to_vec();
+
+        'walk: loop {
+            let varbinds = match self.get_bulk_shrinking(&[&current_oid]).await {
+                Ok(varbinds) => varbinds,
+                Err(e) if results.is_empty() && e.downcast_ref::<ErrorStatus>().is_none() => return Err(e),
+                Err(e) => {
+                    warn!("SNMP bulkwalk ended: {}", e);
+                    break;
+                }
+            };
+
+            if varbinds.is_empty() {
+                break;
+            }
+
+            for (next_oid, value) in varbinds {
+                // Check if we've gone past the subtree
+                if value.is_exception() || !next_oid.starts_with(base_oid) {
+                    break 'walk;
+                }
+                // Guard against infinite loops (OID didn't advance)
+                if next_oid <= current_oid {
+                    warn!("SNMP bulkwalk: OID did not advance, stopping");
+                    break 'walk;
+                }
+                current_oid = next_oid.clone();
+                results.push((next_oid, value));
+            }
+        }
+
+        info!("SNMP BULKWALK complete: {} results", results.len());
+        Ok(results)
+    }
+
+    /// Walk several table columns in step, one GETNEXT/GETBULK varbind
+    /// per column, and return rows keyed by instance index.
+    ///
+    /// Each row holds one value per column, in `columns` order, with
+    /// `None` where the agent has no instance of that column. As with
+    /// [`walk`](Self::walk), no answer at all is an error.
+    pub async fn get_table(&self, columns: &[&[u32]]) -> Result<BTreeMap<Vec<u32>, Vec<Option<SnmpValue>>>> {
+        info!("SNMP TABLE {} on {}", oids_to_string(columns), self.target);
+        let mut rows: BTreeMap<Vec<u32>, Vec<Option<SnmpValue>>> = BTreeMap::new();
+        // Last OID seen per column; None once the column is exhausted
+        let mut cursors: Vec<Option<Vec<u32>>> = columns.iter().map(|c| Some(c.to_vec())).collect();
+
+        loop {
+            let active: Vec<usize> = (0..columns.len()).filter(|&c| cursors[c].is_some()).collect();
+            if active.is_empty() {
+                break;
+            }
+            let request: Vec<&[u32]> = active.iter()
+                .filter_map(|&c| cursors[c].as_deref())
+                .collect();
+
+            let result = if self.uses_bulkwalk() {
+                self.get_bulk_shrinking(&request).await
+            } else {
+                self.get_next_many(&request).await
+            };
+            let varbinds = match result {
+                Ok(varbinds) => varbinds,
+                Err(e) => match e.downcast_ref::<ErrorStatus>() {
+                    // v1 agents report the end of a column as noSuchName
+                    // on that varbind
+                    Some(status) if status.status == ErrorStatus::NO_SUCH_NAME
+                        && status.index >= 1
+                        && (status.index as usize) <= active.len() =>
+                    {
+                        cursors[active[status.index as usize - 1]] = None;
+                        continue;
+                    }
+                    None if rows.is_empty() => return Err(e),
+                    _ => {
+                        warn!("SNMP table walk ended: {}", e);
+                        break;
+                    }
+                },
+            };
+            if varbinds.is_empty() {
+                break;
+            }
+
+            // Responses are row-major: repetition r of active column i is
+            // varbind r * active.len() + i
+            for (n, (oid, value)) in varbinds.into_iter().enumerate() {
+                let column = active[n % active.len()];
+                let Some(cursor) = &cursors[column] else { continue };
+                let base = columns[column];
+                if value.is_exception() || !oid.starts_with(base) || oid <= *cursor {
+                    cursors[column] = None;
+                    continue;
+                }
+                let index = oid[base.len()..].to_vec();
+                rows.entry(index).or_insert_with(|| vec![None; columns.len()])[column] = Some(value);
+                cursors[column] = Some(oid);
+            }
+        }
+
+        info!("SNMP TABLE complete: {} rows", rows.len());
+        Ok(rows)
+    }
+
     /// Get sysDescr, sysObjectID, sysUpTime, sysContact, sysName, sysLocation, sysServices.
-    pub fn get_system_info(&self) -> Result<SystemInfo> {
+    pub async fn get_system_info(&self) -> Result<SystemInfo> {
         info!("Getting system info from {}", self.target);
 
-        let sys_descr = self.get(&super::oids::SYS_DESCR).ok();
-        let sys_object_id = self.get(&super::oids::SYS_OBJECT_ID).ok();
-        let sys_uptime = self.get(&super::oids::SYS_UPTIME).ok();
-        let sys_contact = self.get(&super::oids::SYS_CONTACT).ok();
-        let sys_name = self.get(&super::oids::SYS_NAME).ok();
-        let sys_location = self.get(&super::oids::SYS_LOCATION).ok();
-        let sys_services = self.get(&super::oids::SYS_SERVICES).ok();
+        let oids: Vec<Vec<u32>> = [
+            &super::oids::SYS_DESCR,
+            &super::oids::SYS_OBJECT_ID,
+            &super::oids::SYS_UPTIME,
+            &super::oids::SYS_CONTACT,
+            &super::oids::SYS_NAME,
+            &super::oids::SYS_LOCATION,
+            &super::oids::SYS_SERVICES,
+        ].iter().map(|column| column.iter().copied().chain([0]).collect()).collect();
+        let oid_refs: Vec<&[u32]> = oids.iter().map(|o| o.as_slice()).collect();
+
+        // One GET for the whole group; v1 agents fail the request if any
+        // object is missing, so fall back to asking one at a time.
+        let mut values: Vec<Option<SnmpValue>> = match self.get_many(&oid_refs).await {
+            Ok(varbinds) if varbinds.len() == oids.len() => {
+                varbinds.into_iter().map(|(_, v)| Some(v)).collect()
+            }
+            Ok(_) | Err(_) => {
+                let mut values = Vec::with_capacity(oids.len());
+                for oid in &oid_refs {
+                    values.push(self.get(oid).await.ok());
+                }
+                values
+            }
+        };
+        values.resize(oids.len(), None);
+        let mut values = values.into_iter();
+        let mut next = || values.next().flatten();
+        let (sys_descr, sys_object_id, sys_uptime, sys_contact, sys_name, sys_location, sys_services) =
+            (next(), next(), next(), next(), next(), next(), next());
 
         Ok(SystemInfo {
-            description: sys_descr.map(|v| String::from_utf8_lossy(&v).to_string()),
-            object_id: sys_object_id.map(|v| String::from_utf8_lossy(&v).to_string()),
-            uptime: sys_uptime.and_then(|v| parse_timeticks(&v)),
-            contact: sys_contact.map(|v| String::from_utf8_lossy(&v).to_string()),
-            name: sys_name.map(|v| String::from_utf8_lossy(&v).to_string()),
-            location: sys_location.map(|v| String::from_utf8_lossy(&v).to_string()),
-            services: sys_services.and_then(|v| parse_integer(&v)),
+            description: sys_descr.and_then(|v| v.as_string()),
+            object_id: sys_object_id.and_then(|v| v.as_oid().map(oid_to_string)),
+            uptime: sys_uptime.and_then(|v| v.as_i64()),
+            contact: sys_contact.and_then(|v| v.as_string()),
+            name: sys_name.and_then(|v| v.as_string()),
+            location: sys_location.and_then(|v| v.as_string()),
+            services: sys_services.and_then(|v| v.as_i64()),
         })
     }
 
     /// Walk the ifTable to get interface information.
-    pub fn get_interfaces(&self) -> Result<Vec<InterfaceInfo>> {
+    pub async fn get_interfaces(&self) -> Result<Vec<InterfaceInfo>> {
+        use super::oids::*;
         info!("Getting interfaces from {}", self.target);
-        let if_descr = self.walk(&super::oids::IF_DESCR)?;
-        let if_type = self.walk(&super::oids::IF_TYPE)?;
-        let if_speed = self.walk(&super::oids::IF_SPEED)?;
-        let if_admin_status = self.walk(&super::oids::IF_ADMIN_STATUS)?;
-        let if_oper_status = self.walk(&super::oids::IF_OPER_STATUS)?;
+        let table = self.get_table(&[
+            &IF_DESCR,
+            &IF_TYPE,
+            &IF_MTU,
+            &IF_SPEED,
+            &IF_PHYS_ADDRESS,
+            &IF_ADMIN_STATUS,
+            &IF_OPER_STATUS,
+            &IF_LAST_CHANGE,
+        ]).await?;
+        // ifXTable is missing on SNMPv1-only agents
+        let xtable = self.get_table(&[&IF_NAME, &IF_HIGH_SPEED, &IF_ALIAS]).await.unwrap_or_else(|e| {
+            warn!("Failed to walk ifXTable on {}: {}", self.target, e);
+            BTreeMap::new()
+        });
 
         let mut interfaces = Vec::new();
-        for (oid, descr_bytes) in &if_descr {
-            let ifindex = *oid.last().unwrap_or(&0) as i32;
+        for (index, row) in table {
+            let ifindex = index.first().copied().unwrap_or(0) as i32;
+            let [descr, if_type, mtu, speed, phys_address, admin_status, oper_status, last_change]: [Option<SnmpValue>; 8] = row.try_into()
+                .map_err(|_| anyhow::anyhow!("SNMP: ifTable row has wrong column count"))?;
+            let [name, high_speed, alias] = match xtable.get(&index) {
+                Some(row) => [row[0].clone(), row[1].clone(), row[2].clone()],
+                None => [None, None, None],
+            };
             interfaces.push(InterfaceInfo {
                 ifindex,
-                descr: String::from_utf8_lossy(descr_bytes).to_string(),
-                if_type: find_value_for_index(&if_type, ifindex)
-                    .map(|v| String::from_utf8_lossy(&v).to_string()),
-                speed: find_value_for_index(&if_speed, ifindex)
-                    .and_then(|v| parse_integer(&v)),
-                admin_status: find_value_for_index(&if_admin_status, ifindex)
-                    .and_then(|v| parse_integer(&v)),
-                oper_status: find_value_for_index(&if_oper_status, ifindex)
-                    .and_then(|v| parse_integer(&v)),
+                descr: descr.and_then(|v| v.as_string()).unwrap_or_default(),
+                name: entity_string(name),
+                alias: entity_string(alias),
+                if_type: if_type.and_then(|v| v.as_i64()).map(if_type_name),
+                mtu: mtu.and_then(|v| v.as_i64()),
+                speed: speed.and_then(|v| v.as_i64()),
+                high_speed: high_speed.and_then(|v| v.as_i64()),
+                mac: phys_address.as_ref()
+                    .and_then(|v| v.as_bytes())
+                    .filter(|octets| octets.len() == 6)
+                    .map(format_mac_octets),
+                admin_status: admin_status.and_then(|v| v.as_i64()),
+                oper_status: oper_status.and_then(|v| v.as_i64()),
+                last_change: last_change.and_then(|v| v.as_i64()),
+            });
+        }
+
+        Ok(interfaces)
+    }
+
+    /// Walk the MAC address table for macsuck.
+    ///
+    /// Q-BRIDGE-MIB's dot1qTpFdbTable is preferred, with each entry's VLAN
+    /// found from its FID (see [`fdb_vlans`]). Agents without it fall back
+    /// to the VLAN-unaware dot1dTpFdbTable, where the MAC is
+    /// dot1dTpFdbAddress, or the table index where an agent leaves that
+    /// column out. Either way the switch's own addresses (status `self`)
+    /// and entries on no bridge port are skipped.
+    pub async fn get_mac_table(&self) -> Result<Vec<MacEntry>> {
+        use super::oids::*;
+        info!("Getting MAC address table from {}", self.target);
+        let qbridge = self.get_table(&[&DOT1Q_TP_FDB_PORT, &DOT1Q_TP_FDB_STATUS]).await.unwrap_or_default();
+        if !qbridge.is_empty() {
+            let fids = self.get_table(&[&DOT1Q_VLAN_FDB_ID]).await.unwrap_or_default().into_iter()
+                .filter_map(|(index, row)| Some((*index.last()? as i32, row[0].as_ref()?.as_i64()? as i32)));
+            let vlans = fdb_vlans(fids);
+            let mut entries = Vec::new();
+            for (index, row) in &qbridge {
+                let [fid, ref mac @ ..] = index[..] else { continue };
+                let Some(mac) = mac_from_index(mac) else { continue };
+                let Some(bridge_port) = fdb_port(&row[0], &row[1]) else { continue };
+                entries.push(MacEntry {
+                    mac,
+                    bridge_port,
+                    vlan: vlans.get(&(fid as i32)).copied().unwrap_or(Some(fid as i32)),
+                });
+            }
+            return Ok(entries);
+        }
+
+        let fdb = self.get_table(&[&DOT1D_TP_FDB_ADDRESS, &DOT1D_TP_FDB_PORT, &DOT1D_TP_FDB_STATUS]).await?;
+        let mut entries = Vec::new();
+        for (index, row) in &fdb {
+            let Some(bridge_port) = fdb_port(&row[1], &row[2]) else { continue };
+            let mac = match row[0].as_ref().and_then(|v| v.as_bytes()) {
+                Some(octets) if octets.len() == 6 => format_mac_octets(octets),
+                _ => match mac_from_index(index) {
+                    Some(mac) => mac,
+                    None => continue,
+                },
+            };
+            entries.push(MacEntry {
+                mac,
+                bridge_port,
+                vlan: None,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Walk the entPhysicalTable (ENTITY-MIB) for hardware inventory.
+    pub async fn get_entities(&self) -> Result<Vec<EntityInfo>> {
+        use super::oids::*;
+        info!("Getting entity inventory from {}", self.target);
+        let table = self.get_table(&[
+            &ENT_PHYSICAL_DESCR,
+            &ENT_PHYSICAL_VENDOR_TYPE,
+            &ENT_PHYSICAL_CONTAINED_IN,
+            &ENT_PHYSICAL_CLASS,
+            &ENT_PHYSICAL_PARENT_REL_POS,
+            &ENT_PHYSICAL_NAME,
+            &ENT_PHYSICAL_HW_REV,
+            &ENT_PHYSICAL_FW_REV,
+            &ENT_PHYSICAL_SW_REV,
+            &ENT_PHYSICAL_SERIAL,
+            &ENT_PHYSICAL_MODEL,
+            &ENT_PHYSICAL_IS_FRU,
+        ]).await?;
+
+        let mut entities = Vec::new();
+        for (index, row) in table {
+            let Some(&index) = index.first() else { continue };
+            let [descr, vendor_type, contained_in, class, pos, name, hw_ver, fw_ver, sw_ver, serial, model, fru]: [Option<SnmpValue>; 12] = row.try_into()
+                .map_err(|_| anyhow::anyhow!("SNMP: entPhysicalTable row has wrong column count"))?;
+            entities.push(EntityInfo {
+                index: index as i32,
+                descr: entity_string(descr),
+                // zeroDotZero means the vendor type is unknown
+                vendor_type: vendor_type.as_ref()
+                    .and_then(|v| v.as_oid())
+                    .filter(|oid| !oid.iter().all(|&c| c == 0))
+                    .map(oid_to_string),
+                contained_in: contained_in.and_then(|v| v.as_i64()).map(|v| v as i32),
+                class: class.and_then(|v| v.as_i64()).map(entity_class_name),
+                pos: pos.and_then(|v| v.as_i64()).map(|v| v as i32),
+                name: entity_string(name),
+                hw_ver: entity_string(hw_ver),
+                fw_ver: entity_string(fw_ver),
+                sw_ver: entity_string(sw_ver),
+                serial: entity_string(serial),
+                model: entity_string(model),
+                // TruthValue: true(1), false(2)
+                fru: fru.and_then(|v| v.as_i64()).map(|v| v == 1),
             });
         }
 
-        Ok(interfaces)
+        Ok(entities)
+    }
+
+    /// Walk the ipNetToMediaTable (ARP table) for arpnip.
+    pub async fn get_arp_table(&self) -> Result<Vec<ArpEntry>> {
+        info!("Getting ARP table from {}", self.target);
+        let arp_phys = self.walk(&super::oids::IP_NET_TO_MEDIA_PHYS).await?;
+
+        let mut entries = Vec::new();
+        for (oid, phys) in &arp_phys {
+            // IP is encoded in the last 4 octets of the OID
+            if oid.len() >= 4 {
+                let ip = format!("{}.{}.{}.{}", oid[oid.len()-4], oid[oid.len()-3], oid[oid.len()-2], oid[oid.len()-1]);
+                let mac = phys.as_bytes().unwrap_or_default().iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<Vec<_>>()
+                    .join(":");
+                entries.push(ArpEntry { ip, mac });
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Walk ipAddrTable and ipAddressTable for the device's own addresses.
+    ///
+    /// ipAddrTable is IPv4 only but near universal; ipAddressTable adds
+    /// IPv6, and IPv4 on agents that have dropped the older table.
+    pub async fn get_ip_addresses(&self) -> Result<Vec<IpAddressInfo>> {
+        use super::oids::*;
+        info!("Getting IP addresses from {}", self.target);
+        let v4 = self.get_table(&[&IP_AD_ENT_IF_INDEX, &IP_AD_ENT_NET_MASK]).await;
+        let any = self.get_table(&[&IP_ADDRESS_IF_INDEX, &IP_ADDRESS_TYPE, &IP_ADDRESS_PREFIX]).await;
+        if let (Err(v4), Err(any)) = (&v4, &any) {
+            return Err(anyhow::anyhow!("ipAddrTable: {}; ipAddressTable: {}", v4, any));
+        }
+
+        let mut addresses: Vec<IpAddressInfo> = Vec::new();
+        for (index, row) in v4.unwrap_or_default() {
+            let Some(ip) = ip_from_index(&index) else { continue };
+            let mask = row[1].as_ref().and_then(|v| v.as_ip());
+            addresses.push(IpAddressInfo {
+                ip,
+                ifindex: row[0].as_ref().and_then(|v| v.as_i64()).map(|i| i as i32),
+                prefix_len: mask.map(|m| u32::from(m).leading_ones() as u8),
+            });
+        }
+        for (index, row) in any.unwrap_or_default() {
+            // ipv4(1) and ipv6(2); the zoned types are link-local
+            let (Some(&(1 | 2)), Some(ip)) = (index.first(), index.get(2..).and_then(ip_from_index)) else { continue };
+            // unicast(1) and anycast(2), not broadcast(3)
+            if row[1].as_ref().and_then(|v| v.as_i64()).is_some_and(|t| t == 3) {
+                continue;
+            }
+            if addresses.iter().any(|a| a.ip == ip) {
+                continue;
+            }
+            // A RowPointer into ipAddressPrefixTable, whose last index arc
+            // is the prefix length; agents without one return zeroDotZero
+            let prefix_len = row[2].as_ref()
+                .and_then(|v| v.as_oid())
+                .filter(|oid| oid.starts_with(&IP_ADDRESS_PREFIX_TABLE))
+                .and_then(|oid| oid.last())
+                .and_then(|&len| u8::try_from(len).ok());
+            addresses.push(IpAddressInfo {
+                ip,
+                ifindex: row[0].as_ref().and_then(|v| v.as_i64()).map(|i| i as i32),
+                prefix_len,
+            });
+        }
+
+        Ok(addresses)
+    }
+
+    /// Walk dot1dBasePortIfIndex: bridge port number to ifIndex.
+    pub async fn get_bridge_ports(&self) -> Result<BTreeMap<i32, i32>> {
+        let base = &super::oids::DOT1D_BASE_PORT_IF_INDEX;
+        let ports = self.walk(base).await?;
+        Ok(ports.iter()
+            .filter_map(|(oid, value)| Some((*oid.get(base.len())? as i32, value.as_i64()? as i32)))
+            .collect())
+    }
+
+    /// Link aggregation membership, as member ifIndex to aggregate ifIndex.
+    ///
+    /// IEEE8023-LAG-MIB first, then Cisco PAgP, then ifStackTable entries
+    /// whose upper layer is an ieee8023adLag interface, as Juniper and
+    /// Linux bonds report them.
+    pub async fn get_lag_members(&self, interfaces: &[InterfaceInfo]) -> Result<BTreeMap<i32, i32>> {
+        use super::oids::*;
+        let mut members = BTreeMap::new();
+        for column in [&DOT3AD_AGG_PORT_ATTACHED_AGG_ID[..], &PAGP_GROUP_IF_INDEX[..]] {
+            for (oid, value) in self.walk(column).await.unwrap_or_default() {
+                let (Some(&port), Some(agg)) = (oid.get(column.len()), value.as_i64()) else { continue };
+                // Unaggregated ports report 0, or themselves
+                if agg != 0 && agg != port as i64 {
+                    members.entry(port as i32).or_insert(agg as i32);
+                }
+            }
+        }
+        let is_lag = |ifindex: u32| interfaces.iter()
+            .any(|i| i.ifindex == ifindex as i32 && i.if_type.as_deref() == Some("ieee8023adLag"));
+        for (oid, _) in self.walk(&IF_STACK_STATUS).await.unwrap_or_default() {
+            if let [higher, lower] = oid[IF_STACK_STATUS.len()..] {
+                if higher != 0 && lower != 0 && is_lag(higher) && !is_lag(lower) {
+                    members.entry(lower as i32).or_insert(higher as i32);
+                }
+            }
+        }
+        Ok(members)
+    }
+
+    /// Walk lldpRemTable for LLDP neighbors, each mapped from its
+    /// lldpLocPortNum back to the local ifIndex.
+    pub async fn get_lldp_neighbors(&self, interfaces: &[InterfaceInfo]) -> Result<Vec<NeighborInfo>> {
+        use super::oids::*;
+        info!("Getting LLDP neighbors from {}", self.target);
+        let remotes = self.get_table(&[
+            &LLDP_REM_CHASSIS_ID_SUBTYPE,
+            &LLDP_REM_CHASSIS_ID,
+            &LLDP_REM_PORT_ID_SUBTYPE,
+            &LLDP_REM_PORT_ID,
+            &LLDP_REM_PORT_DESC,
+            &LLDP_REM_SYS_NAME,
+            &LLDP_REM_SYS_DESC,
+            &LLDP_REM_SYS_CAP_ENABLED,
+        ]).await?;
+        if remotes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let local_ports = self.get_table(&[&LLDP_LOC_PORT_ID_SUBTYPE, &LLDP_LOC_PORT_ID, &LLDP_LOC_PORT_DESC])
+            .await
+            .unwrap_or_default();
+        let bridge_ports = self.get_bridge_ports().await.unwrap_or_default();
+
+        // Management addresses by lldpRemTable index
+        let mut addresses: BTreeMap<Vec<u32>, Vec<IpAddr>> = BTreeMap::new();
+        for (oid, _) in self.walk(&LLDP_REM_MAN_ADDR_IF_SUBTYPE).await.unwrap_or_default() {
+            let index = &oid[LLDP_REM_MAN_ADDR_IF_SUBTYPE.len()..];
+            if let (Some(rem_index), Some(addr)) = (index.get(..3), index.get(3..).and_then(decode_lldp_man_addr)) {
+                addresses.entry(rem_index.to_vec()).or_default().push(addr);
+            }
+        }
+
+        let mut neighbors = Vec::new();
+        for (index, row) in remotes {
+            let [chassis_subtype, chassis_id, port_subtype, port_id, port_desc, sys_name, sys_desc, caps]: [Option<SnmpValue>; 8] = row.try_into()
+                .map_err(|_| anyhow::anyhow!("SNMP: lldpRemTable row has wrong column count"))?;
+            let Some(&port_num) = index.get(1) else { continue };
+
+            let local = local_ports.get(&vec![port_num]).map(|row| LldpLocalPort {
+                id_subtype: row[0].as_ref().and_then(|v| v.as_i64()),
+                id: row[1].as_ref().and_then(|v| v.as_string()),
+                desc: row[2].as_ref().and_then(|v| v.as_string()),
+            });
+            let Some(ifindex) = lldp_local_ifindex(port_num as i32, local.as_ref(), interfaces, &bridge_ports) else {
+                debug!("LLDP local port {} on {} matches no interface", port_num, self.target);
+                continue;
+            };
+
+            // Chassis ID subtypes: macAddress(4), networkAddress(5)
+            let chassis_subtype = chassis_subtype.and_then(|v| v.as_i64());
+            let chassis_id = lldp_id(chassis_id, chassis_subtype == Some(4), chassis_subtype == Some(5));
+            // Port ID subtypes: macAddress(3), networkAddress(4),
+            // interfaceName(5); the others read better as the description
+            let port_subtype = port_subtype.and_then(|v| v.as_i64());
+            let port_id = lldp_id(port_id, port_subtype == Some(3), port_subtype == Some(4));
+            let port_desc = entity_string(port_desc);
+            let remote_port = match port_subtype {
+                Some(5) => port_id.or(port_desc),
+                _ => port_desc.or(port_id),
+            };
+
+            let addrs = addresses.get(&index);
+            neighbors.push(NeighborInfo {
+                protocol: "lldp",
+                ifindex,
+                remote_ip: addrs.and_then(|a| a.iter().find(|ip| ip.is_ipv4()).or(a.first())).copied(),
+                remote_port,
+                remote_type: entity_string(sys_desc),
+                remote_id: entity_string(sys_name).or(chassis_id),
+                capabilities: caps.as_ref()
+                    .and_then(|v| v.as_bytes())
+                    .map(lldp_capabilities)
+                    .unwrap_or_default(),
+            });
+        }
+
+        Ok(neighbors)
+    }
+
+    /// Walk cdpCacheTable for CDP neighbors; the table is indexed by the
+    /// local ifIndex.
+    pub async fn get_cdp_neighbors(&self) -> Result<Vec<NeighborInfo>> {
+        use super::oids::*;
+        info!("Getting CDP neighbors from {}", self.target);
+        let table = self.get_table(&[
+            &CDP_CACHE_ADDRESS,
+            &CDP_CACHE_DEVICE_ID,
+            &CDP_CACHE_DEVICE_PORT,
+            &CDP_CACHE_PLATFORM,
+            &CDP_CACHE_CAPABILITIES,
+        ]).await?;
+
+        let mut neighbors = Vec::new();
+        for (index, row) in table {
+            let [address, device_id, device_port, platform, caps]: [Option<SnmpValue>; 5] = row.try_into()
+                .map_err(|_| anyhow::anyhow!("SNMP: cdpCacheTable row has wrong column count"))?;
+            let Some(&ifindex) = index.first() else { continue };
+            neighbors.push(NeighborInfo {
+                protocol: "cdp",
+                ifindex: ifindex as i32,
+                remote_ip: address.and_then(|v| match v.as_ip() {
+                    Some(ip) => Some(IpAddr::V4(ip)),
+                    None => v.as_bytes().and_then(ip_from_octets),
+                }),
+                remote_port: entity_string(device_port),
+                remote_type: entity_string(platform),
+                remote_id: entity_string(device_id),
+                capabilities: caps.as_ref()
+                    .and_then(|v| v.as_bytes())
+                    .map(cdp_capabilities)
+                    .unwrap_or_default(),
+            });
+        }
+
+        Ok(neighbors)
+    }
+
+    /// Walk the device's VLANs, VTP domain and per-port VLAN membership.
+    ///
+    /// VLANs and membership come from Q-BRIDGE-MIB, whose port lists are
+    /// bitmaps of bridge ports; the current table is preferred over the
+    /// static one. Agents without Q-BRIDGE-MIB fall back to CISCO-VTP-MIB
+    /// for the VLAN list, and to vmVlan and the VTP trunk tables for
+    /// membership, as SNMP::Info does.
+    pub async fn get_vlan_table(&self) -> Result<VlanTable> {
+        use super::oids::*;
+        info!("Getting VLANs from {}", self.target);
+        let bridge_ports = self.get_bridge_ports().await.unwrap_or_default();
+        let mut table = VlanTable::default();
+
+        // Q-BRIDGE-MIB: static rows are indexed by VLAN, current rows by
+        // time mark and VLAN
+        let statics = self.get_table(&[&DOT1Q_VLAN_STATIC_NAME, &DOT1Q_VLAN_STATIC_EGRESS_PORTS, &DOT1Q_VLAN_STATIC_UNTAGGED_PORTS])
+            .await
+            .unwrap_or_default();
+        let current = self.get_table(&[&DOT1Q_VLAN_CURRENT_EGRESS_PORTS, &DOT1Q_VLAN_CURRENT_UNTAGGED_PORTS])
+            .await
+            .unwrap_or_default();
+        let mut names: BTreeMap<i32, Option<String>> = BTreeMap::new();
+        for (index, row) in &statics {
+            let Some(&vlan) = index.last() else { continue };
+            names.insert(vlan as i32, entity_string(row[0].clone()));
+        }
+        for index in current.keys() {
+            let Some(&vlan) = index.last() else { continue };
+            names.entry(vlan as i32).or_default();
+        }
+        let port_lists: Vec<(i32, Option<&SnmpValue>, Option<&SnmpValue>)> = if current.is_empty() {
+            statics.iter().filter_map(|(index, row)| Some((*index.last()? as i32, row[1].as_ref(), row[2].as_ref()))).collect()
+        } else {
+            current.iter().filter_map(|(index, row)| Some((*index.last()? as i32, row[0].as_ref(), row[1].as_ref()))).collect()
+        };
+        let mut members: BTreeMap<(i32, i32), bool> = BTreeMap::new();
+        for (vlan, egress, untagged) in port_lists {
+            let untagged = untagged.and_then(|v| v.as_bytes()).map(port_list).unwrap_or_default();
+            let egress = egress.and_then(|v| v.as_bytes()).map(port_list).unwrap_or_default();
+            for port in egress.iter().chain(&untagged) {
+                let Some(&ifindex) = bridge_ports.get(&(*port as i32)) else { continue };
+                members.insert((ifindex, vlan), untagged.contains(port));
+            }
+        }
+        for (oid, pvid) in self.walk(&DOT1Q_PVID).await.unwrap_or_default() {
+            let port = oid.get(DOT1Q_PVID.len()).map(|&p| p as i32);
+            if let (Some(&ifindex), Some(pvid)) = (port.and_then(|p| bridge_ports.get(&p)), pvid.as_i64()) {
+                table.pvids.insert(ifindex, pvid as i32);
+            }
+        }
+
+        // CISCO-VTP-MIB: the domain, and VLANs where there is no Q-BRIDGE
+        table.vtp_domain = self.walk(&MANAGEMENT_DOMAIN_NAME).await.unwrap_or_default()
+            .into_iter()
+            .find_map(|(_, name)| entity_string(Some(name)));
+        if names.is_empty() {
+            let vtp = self.get_table(&[&VTP_VLAN_STATE, &VTP_VLAN_TYPE, &VTP_VLAN_NAME]).await.unwrap_or_default();
+            for (index, row) in vtp {
+                // operational(1) ethernet(1) VLANs; not FDDI/Token Ring
+                let state = row[0].as_ref().and_then(|v| v.as_i64());
+                let vlan_type = row[1].as_ref().and_then(|v| v.as_i64());
+                if state != Some(1) || vlan_type.is_some_and(|t| t != 1) {
+                    continue;
+                }
+                let Some(&vlan) = index.last() else { continue };
+                names.insert(vlan as i32, entity_string(row[2].clone()));
+            }
+        }
+
+        // Cisco access and trunk ports, when Q-BRIDGE has no membership
+        if members.is_empty() {
+            for (index, row) in self.get_table(&[&VM_VLAN]).await.unwrap_or_default() {
+                let (Some(&ifindex), Some(vlan)) = (index.first(), row[0].as_ref().and_then(|v| v.as_i64())) else { continue };
+                members.insert((ifindex as i32, vlan as i32), true);
+                table.pvids.insert(ifindex as i32, vlan as i32);
+            }
+            let trunks = self.get_table(&[
+                &VLAN_TRUNK_PORT_DYNAMIC_STATUS,
+                &VLAN_TRUNK_PORT_NATIVE_VLAN,
+                &VLAN_TRUNK_PORT_VLANS_ENABLED,
+                &VLAN_TRUNK_PORT_VLANS_ENABLED_2K,
+                &VLAN_TRUNK_PORT_VLANS_ENABLED_3K,
+                &VLAN_TRUNK_PORT_VLANS_ENABLED_4K,
+            ]).await.unwrap_or_default();
+            for (index, row) in trunks {
+                // trunking(1)
+                let Some(&ifindex) = index.first() else { continue };
+                if row[0].as_ref().and_then(|v| v.as_i64()) != Some(1) {
+                    continue;
+                }
+                let native = row[1].as_ref().and_then(|v| v.as_i64()).map(|v| v as i32);
+                if let Some(native) = native {
+                    table.pvids.insert(ifindex as i32, native);
+                }
+                // Each bitmap covers 1024 VLANs, from VLAN 0
+                for (block, bitmap) in row[2..].iter().enumerate() {
+                    let Some(bitmap) = bitmap.as_ref().and_then(|v| v.as_bytes()) else { continue };
+                    for bit in port_list(bitmap) {
+                        let vlan = (block * 1024) as i32 + bit as i32 - 1;
+                        if names.contains_key(&vlan) {
+                            members.insert((ifindex as i32, vlan), Some(vlan) == native);
+                        }
+                    }
+                }
+            }
+        }
+
+        table.vlans = names.into_iter().map(|(vlan, name)| VlanInfo { vlan, name }).collect();
+        table.members = members.into_iter()
+            .map(|((ifindex, vlan), untagged)| VlanMember { ifindex, vlan, untagged })
+            .collect();
+        Ok(table)
+    }
+
+    /// Walk POWER-ETHERNET-MIB for PoE budgets and port state, with each
+    /// port's draw from CISCO-POWER-ETHERNET-EXT-MIB where the agent has it.
+    /// PSE ports are matched to `interfaces` by [`pse_port_ifindex`].
+    pub async fn get_poe(&self, interfaces: &[InterfaceInfo]) -> Result<PoeInfo> {
+        use super::oids::*;
+        info!("Getting PoE status from {}", self.target);
+        let mains = self.get_table(&[&PETH_MAIN_PSE_POWER, &PETH_MAIN_PSE_OPER_STATUS, &PETH_MAIN_PSE_CONSUMPTION]).await?;
+        let ports = self.get_table(&[&PSE_PORT_ADMIN, &PSE_PORT_STATUS, &PSE_PORT_CLASS]).await?;
+        let cisco = if ports.is_empty() {
+            BTreeMap::new()
+        } else {
+            self.get_table(&[&CPE_EXT_PSE_PORT_PWR_CONSUMPTION, &CPE_EXT_PSE_PORT_ENT_PHY_INDEX]).await.unwrap_or_default()
+        };
+        let aliases = if cisco.values().any(|row| row[1].is_some()) {
+            self.get_entity_ifindexes().await.unwrap_or_default()
+        } else {
+            BTreeMap::new()
+        };
+
+        let int = |value: &Option<SnmpValue>| value.as_ref().and_then(|v| v.as_i64());
+        let mut poe = PoeInfo::default();
+        for (index, row) in &mains {
+            let Some(&group) = index.first() else { continue };
+            poe.modules.push(PseModule {
+                group: group as i32,
+                power: int(&row[0]).map(|w| w as i32),
+                status: int(&row[1]).map(|s| enum_name(&["on", "off", "faulty"], s)),
+                consumption: int(&row[2]).map(|w| w as i32),
+            });
+        }
+        for (index, row) in &ports {
+            let [group, port] = index[..] else { continue };
+            let cisco = cisco.get(index);
+            let aliased = cisco
+                .and_then(|row| int(&row[1]))
+                .and_then(|entity| aliases.get(&(entity as u32)).copied());
+            poe.ports.push(PsePort {
+                group: group as i32,
+                port: port as i32,
+                ifindex: pse_port_ifindex(group, port, aliased, interfaces),
+                admin: int(&row[0]).map(|a| if a == 1 { "true" } else { "false" }.to_string()),
+                status: int(&row[1]).map(|s| enum_name(
+                    &["disabled", "searching", "deliveringPower", "fault", "test", "otherFault"], s)),
+                class: int(&row[2]).map(|c| format!("class{}", c - 1)),
+                power: cisco.and_then(|row| int(&row[0])).map(|mw| mw as i32),
+            });
+        }
+        Ok(poe)
+    }
+
+    /// The ifIndex of each physical entity that is an interface, from
+    /// entAliasMappingTable, keyed by entPhysicalIndex.
+    pub async fn get_entity_ifindexes(&self) -> Result<BTreeMap<u32, i32>> {
+        use super::oids::*;
+        let mut ifindexes = BTreeMap::new();
+        for (oid, value) in self.walk(&ENT_ALIAS_MAPPING_IDENTIFIER).await? {
+            let Some(&entity) = oid.get(ENT_ALIAS_MAPPING_IDENTIFIER.len()) else { continue };
+            let Some(ifindex) = value.as_oid().and_then(|id| id.strip_prefix(&IF_INDEX[..])) else { continue };
+            if let [ifindex] = ifindex {
+                ifindexes.insert(entity, *ifindex as i32);
+            }
+        }
+        Ok(ifindexes)
+    }
+
+    /// Walk the spanning tree: the protocol, and per instance the root
+    /// bridge and the state of each port.
+    ///
+    /// Plain STP and RSTP are one instance, 0, from BRIDGE-MIB. MSTP adds
+    /// the MST instances from IEEE8021-MSTP-MIB. Cisco PVST+ and Rapid
+    /// PVST+ run an instance per VLAN, read from each of `vlans` through
+    /// [`SnmpClient::for_vlan`].
+    pub async fn get_stp(&self, vlans: &[i32]) -> Result<StpInfo> {
+        use super::oids::*;
+        info!("Getting spanning tree from {}", self.target);
+        let scalar = |oid: &[u32]| oid.iter().copied().chain([0]).collect::<Vec<u32>>();
+        let cisco_type = self.get(&scalar(&STPX_SPANNING_TREE_TYPE)).await.ok().and_then(|v| v.as_i64());
+        let version = self.get(&scalar(&DOT1D_STP_VERSION)).await.ok().and_then(|v| v.as_i64());
+        let mut stp = StpInfo {
+            protocol: match (cisco_type, version) {
+                (Some(1), _) => Some("pvst"),
+                (Some(2 | 3), _) => Some("mistp"),
+                (Some(4), _) | (_, Some(3)) => Some("mstp"),
+                (Some(5), _) => Some("rapid-pvst"),
+                (_, Some(2)) => Some("rstp"),
+                (_, Some(0)) => Some("stp"),
+                _ => None,
+            }.map(str::to_string),
+            ..Default::default()
+        };
+
+        if matches!(stp.protocol.as_deref(), Some("pvst" | "rapid-pvst")) {
+            for &vlan in vlans {
+                match self.for_vlan(vlan).get_stp_instance(vlan, vec![vlan]).await {
+                    Ok(Some(instance)) => stp.instances.push(instance),
+                    Ok(None) => {}
+                    // Most likely per-VLAN indexing is not allowed;
+                    // don't time out on every VLAN
+                    Err(e) => {
+                        debug!("  No spanning tree for VLAN {}: {}", vlan, e);
+                        break;
+                    }
+                }
+            }
+            if !stp.instances.is_empty() {
+                return Ok(stp);
+            }
+        }
+
+        stp.instances.extend(self.get_stp_instance(0, Vec::new()).await?);
+        if stp.protocol.as_deref() == Some("mstp") {
+            stp.instances.extend(self.get_mst_instances().await.unwrap_or_default());
+        }
+        if stp.instances.is_empty() {
+            stp.protocol = None;
+        }
+        Ok(stp)
+    }
+
+    /// The BRIDGE-MIB spanning tree, as `instance` covering `vlans`, or
+    /// None if the agent has none.
+    async fn get_stp_instance(&self, instance: i32, vlans: Vec<i32>) -> Result<Option<StpInstance>> {
+        use super::oids::*;
+        let bridge_ports = self.get_bridge_ports().await.unwrap_or_default();
+        let mut values = Vec::new();
+        for (n, oid) in [&DOT1D_BASE_BRIDGE_ADDRESS[..], &DOT1D_STP_PRIORITY, &DOT1D_STP_DESIGNATED_ROOT,
+            &DOT1D_STP_ROOT_COST, &DOT1D_STP_ROOT_PORT].into_iter().enumerate()
+        {
+            let oid: Vec<u32> = oid.iter().copied().chain([0]).collect();
+            values.push(match self.get(&oid).await {
+                Ok(value) => Some(value).filter(|v| !v.is_exception()),
+                // No answer at all: don't wait out the rest
+                Err(e) if n == 0 && e.downcast_ref::<ErrorStatus>().is_none() => return Err(e),
+                Err(_) => None,
+            });
+        }
+        let [address, priority, root, cost, root_port] = &values[..] else { unreachable!() };
+
+        let mut ports = BTreeMap::new();
+        for (oid, state) in self.walk(&DOT1D_STP_PORT_STATE).await? {
+            let port = oid.get(DOT1D_STP_PORT_STATE.len()).map(|&p| p as i32);
+            if let (Some(&ifindex), Some(state)) = (port.and_then(|p| bridge_ports.get(&p)), state.as_i64()) {
+                ports.insert(ifindex, enum_name(
+                    &["disabled", "blocking", "listening", "learning", "forwarding", "broken"], state));
+            }
+        }
+        let root = root.as_ref().and_then(|v| v.as_bytes()).and_then(bridge_id);
+        if root.is_none() && ports.is_empty() {
+            return Ok(None);
+        }
+        let address = address.as_ref().and_then(|v| v.as_bytes()).filter(|a| a.len() == 6);
+        let priority = priority.as_ref().and_then(|v| v.as_i64());
+        Ok(Some(StpInstance {
+            instance,
+            vlans,
+            bridge: address.zip(priority).map(|(mac, priority)| format!("{}.{}", priority, format_mac_octets(mac))),
+            root,
+            root_cost: cost.as_ref().and_then(|v| v.as_i64()).map(|c| c as i32),
+            root_port: root_port.as_ref().and_then(|v| v.as_i64()).and_then(|p| bridge_ports.get(&(p as i32)).copied()),
+            ports,
+        }))
+    }
+
+    /// The MST instances other than the CIST, from IEEE8021-MSTP-MIB.
+    async fn get_mst_instances(&self) -> Result<Vec<StpInstance>> {
+        use super::oids::*;
+        let bridge_ports = self.get_bridge_ports().await.unwrap_or_default();
+        let msts = self.get_table(&[
+            &IEEE8021_MSTP_BRIDGE_ID, &IEEE8021_MSTP_DESIGNATED_ROOT, &IEEE8021_MSTP_ROOT_PATH_COST,
+            &IEEE8021_MSTP_ROOT_PORT, &IEEE8021_MSTP_VIDS0, &IEEE8021_MSTP_VIDS1, &IEEE8021_MSTP_VIDS2,
+            &IEEE8021_MSTP_VIDS3,
+        ]).await?;
+        let states = self.get_table(&[&IEEE8021_MSTP_PORT_STATE]).await?;
+
+        let mut instances = Vec::new();
+        for (index, row) in &msts {
+            let [_component, mstid] = index[..] else { continue };
+            if mstid == 0 {
+                continue;
+            }
+            let id = |value: &Option<SnmpValue>| value.as_ref().and_then(|v| v.as_bytes()).and_then(bridge_id);
+            let int = |value: &Option<SnmpValue>| value.as_ref().and_then(|v| v.as_i64());
+            let vlans = row[4..].iter().enumerate()
+                .flat_map(|(block, vids)| {
+                    let bits = vids.as_ref().and_then(|v| v.as_bytes()).map(port_list).unwrap_or_default();
+                    bits.into_iter().map(move |bit| (block * 1024) as i32 + bit as i32 - 1)
+                })
+                .collect();
+            let ports = states.iter()
+                .filter(|(port_index, _)| port_index.starts_with(index))
+                .filter_map(|(port_index, state)| {
+                    let ifindex = *bridge_ports.get(&(*port_index.get(2)? as i32))?;
+                    let state = int(&state[0])?;
+                    Some((ifindex, enum_name(&["disabled", "listening", "learning", "forwarding", "blocking"], state)))
+                })
+                .collect();
+            instances.push(StpInstance {
+                instance: mstid as i32,
+                vlans,
+                bridge: id(&row[0]),
+                root: id(&row[1]),
+                root_cost: int(&row[2]).map(|c| c as i32),
+                root_port: int(&row[3]).and_then(|p| bridge_ports.get(&(p as i32)).copied()),
+                ports,
+            });
+        }
+        Ok(instances)
+    }
+
+    /// A client for `vlan`'s view of per-VLAN MIBs such as BRIDGE-MIB on
+    /// Cisco: community indexing (`community@vlan`) for v1 and v2c, and
+    /// the `vlan-N` context for SNMPv3.
+    pub fn for_vlan(&self, vlan: i32) -> SnmpClient {
+        let mut credentials = self.credentials.clone();
+        let mut context = Vec::new();
+        if credentials.version == SnmpVersion::V3 {
+            context = format!("vlan-{}", vlan).into_bytes();
+        } else {
+            credentials.community = format!("{}@{}", credentials.community, vlan);
+        }
+        SnmpClient {
+            target: self.target,
+            credentials,
+            timeout: self.timeout,
+            retries: self.retries,
+            usm_user: self.usm_user.clone(),
+            engine: Mutex::new(self.engine.lock().unwrap_or_else(|e| e.into_inner()).clone()),
+            salt: AtomicU64::new(rand::random()),
+            bulkwalk: self.bulkwalk,
+            max_repetitions: AtomicU32::new(self.max_repetitions.load(Ordering::Relaxed)),
+            transport: self.transport.clone(),
+            inflight: self.inflight.clone(),
+            max_inflight: self.max_inflight,
+            context,
+        }
+    }
+
+    /// Build a PDU with a fresh request-id, wrap it in a message for
+    /// this client's SNMP version, send it, and return the response PDU.
+    async fn request(&self, build: impl Fn(i32) -> Vec<u8>) -> Result<Vec<u8>> {
+        let inflight = self.inflight.get_or_init(|| self.transport.inflight_for(self.target, self.max_inflight));
+        let _permit = inflight.acquire().await
+            .context("SNMP client is shutting down")?;
+
+        match self.credentials.version {
+            SnmpVersion::V3 => self.request_v3(&build).await,
+            version => {
+                let request_id = self.transport.next_request_id();
+                let pdu = build(request_id);
+                let message = encode_community_message(&version, &self.credentials.community, &pdu);
+                let response = self.send_receive(request_id, &message).await?;
+                let pdu = decode_community_message(&response)?;
+                check_request_id(&pdu, request_id)?;
+                Ok(pdu)
+            }
+        }
+    }
+
+    /// SNMPv3 request: discover the engine if needed, then send with the
+    /// user's security level, resynchronising once on a USM report. The
+    /// clock is only taken from an authenticated notInTimeWindow Report
+    /// from the engine we know, and unknownEngineID only triggers
+    /// rediscovery when the Report names a different engine.
+    async fn request_v3(&self, build: &impl Fn(i32) -> Vec<u8>) -> Result<Vec<u8>> {
+        let user = self.usm_user.as_ref()
+            .context("SNMPv3 client has no USM user")?;
+
+        for _attempt in 0..2 {
+            let engine = self.engine_state(user).await?;
+            let msg_id = self.transport.next_request_id();
+            let pdu = build(msg_id);
+            let security = UsmSecurityParameters {
+                engine_id: engine.engine_id.clone(),
+                engine_boots: engine.boots,
+                engine_time: engine.current_time(),
+                user_name: user.name.clone(),
+            };
+            let salt = self.salt.fetch_add(1, Ordering::Relaxed);
+            let message = encode_v3_message_in_context(
+                msg_id, true, &security, &engine.keys, salt, &engine.engine_id, &self.context, &pdu,
+            )?;
+
+            let response = self.send_receive(msg_id, &message).await?;
+            let decoded = decode_v3_message(&response, Some(&engine.keys))?;
+            if decoded.msg_id != msg_id {
+                anyhow::bail!("SNMPv3: response msgID {} does not match request {}", decoded.msg_id, msg_id);
+            }
+
+            if decoded.pdu.first() == Some(&PDU_REPORT) {
+                let report = usm_report_oid(&decoded.pdu)?;
+                let same_engine = decoded.security.engine_id == engine.engine_id;
+                if report.starts_with(&super::oids::USM_STATS_NOT_IN_TIME_WINDOWS) {
+                    // Anyone can send an unauthenticated Report; only the
+                    // engine itself may move our copy of its clock
+                    if decoded.flags & usm::FLAG_AUTH == 0 || !same_engine {
+                        anyhow::bail!("SNMPv3: {} sent an unauthenticated notInTimeWindow report", self.target);
+                    }
+                    debug!("SNMPv3: {} reports notInTimeWindow, resynchronising", self.target);
+                    self.update_engine(|state| {
+                        state.boots = decoded.security.engine_boots;
+                        state.time = decoded.security.engine_time;
+                        state.synced_at = Instant::now();
+                    });
+                    continue;
+                }
+                if report.starts_with(&super::oids::USM_STATS_UNKNOWN_ENGINE_IDS) {
+                    // Rediscovery would only find the same engine again
+                    if same_engine {
+                        anyhow::bail!("SNMPv3: {} rejected request: unknownEngineID", self.target);
+                    }
+                    debug!("SNMPv3: {} reports unknownEngineID, rediscovering", self.target);
+                    *self.engine.lock().unwrap_or_else(|e| e.into_inner()) = None;
+                    continue;
+                }
+                if report.starts_with(&super::oids::SNMP_UNKNOWN_CONTEXTS) {
+                    let context = String::from_utf8_lossy(&self.context).into_owned();
+                    return Err(UnknownContext { context }.into());
+                }
+                anyhow::bail!("SNMPv3: {} rejected request: {}", self.target, usm_report_name(&report));
+            }
+
+            let required = engine.keys.flags();
+            if decoded.flags & required != required {
+                anyhow::bail!("SNMPv3: response security level is lower than the request's");
+            }
+            check_request_id(&decoded.pdu, msg_id)?;
+            if decoded.flags & usm::FLAG_AUTH != 0 {
+                self.update_engine(|state| {
+                    state.synchronise(decoded.security.engine_boots, decoded.security.engine_time)
+                });
+            }
+            return Ok(decoded.pdu);
+        }
+
+        anyhow::bail!("SNMPv3: time synchronisation with {} failed", self.target)
+    }
+
+    /// Current engine state, running engine-ID discovery on first use.
+    async fn engine_state(&self, user: &UsmUser) -> Result<EngineState> {
+        if let Some(state) = self.engine.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            return Ok(state.clone());
+        }
+
+        let state = self.discover_engine(user).await?;
+        *self.engine.lock().unwrap_or_else(|e| e.into_inner()) = Some(state.clone());
+        Ok(state)
+    }
+
+    fn update_engine(&self, f: impl FnOnce(&mut EngineState)) {
+        if let Some(state) = self.engine.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            f(state);
+        }
+    }
+
+    /// Engine-ID discovery (RFC 3414 section 4): send an unauthenticated,
+    /// reportable request with an empty engine ID and user; the agent's
+    /// Report carries its snmpEngineID, snmpEngineBoots and snmpEngineTime.
+    async fn discover_engine(&self, user: &UsmUser) -> Result<EngineState> {
+        debug!("SNMPv3 engine discovery for {}", self.target);
+        let msg_id = self.transport.next_request_id();
+        let pdu = encode_pdu(PDU_GET_REQUEST, msg_id, None, &[]);
+        let message = encode_v3_message(
+            msg_id, true, &UsmSecurityParameters::default(), &UsmKeys::default(), 0, &[], &pdu,
+        )?;
+
+        let response = self.send_receive(msg_id, &message).await?;
+        let decoded = decode_v3_message(&response, None)?;
+        if decoded.msg_id != msg_id {
+            anyhow::bail!("SNMPv3: discovery response msgID {} does not match request {}", decoded.msg_id, msg_id);
+        }
+        let engine_id = decoded.security.engine_id;
+        if engine_id.is_empty() {
+            anyhow::bail!("SNMPv3: {} did not report an engine ID", self.target);
+        }
+
+        debug!("SNMPv3 engine {} boots={} time={}",
+            hex_string(&engine_id), decoded.security.engine_boots, decoded.security.engine_time);
+
+        let keys = user.localize(&engine_id)?;
+        Ok(EngineState {
+            engine_id,
+            boots: decoded.security.engine_boots,
+            time: decoded.security.engine_time,
+            synced_at: Instant::now(),
+            keys,
+        })
+    }
+
+    /// Send a message and receive the response with retry logic.
+    async fn send_receive(&self, request_id: i32, message: &[u8]) -> Result<Vec<u8>> {
+        self.transport
+            .request(self.target, request_id, message, self.timeout, self.retries)
+            .await
+    }
+}
+
+/// System information from SNMP system group.
+#[derive(Debug, Clone)]
+pub struct SystemInfo {
+    pub description: Option<String>,
+    pub object_id: Option<String>,
+    pub uptime: Option<i64>,
+    pub contact: Option<String>,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub services: Option<i64>,
+}
+
+/// Interface information from ifTable and ifXTable.
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceInfo {
+    pub ifindex: i32,
+    pub descr: String,
+    /// ifName
+    pub name: Option<String>,
+    /// ifAlias, the configured description
+    pub alias: Option<String>,
+    /// IANAifType name, e.g. "ethernetCsmacd"
+    pub if_type: Option<String>,
+    pub mtu: Option<i64>,
+    /// ifSpeed in bits per second; saturates at 4294967295
+    pub speed: Option<i64>,
+    /// ifHighSpeed in Mbps
+    pub high_speed: Option<i64>,
+    /// ifPhysAddress
+    pub mac: Option<String>,
+    pub admin_status: Option<i64>,
+    pub oper_status: Option<i64>,
+    /// ifLastChange, in sysUpTime ticks
+    pub last_change: Option<i64>,
+}
+
+impl InterfaceInfo {
+    /// The port name: ifName where the agent has one, else ifDescr.
+    pub fn port_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.descr)
+    }
+
+    /// Speed in bits per second, from ifHighSpeed once ifSpeed is too
+    /// small to hold it. None when the agent reports no speed.
+    pub fn speed_bps(&self) -> Option<u64> {
+        let speed = self.speed.map(|bps| bps as u64).filter(|&bps| bps > 0);
+        let high = self.high_speed.map(|mbps| mbps as u64 * 1_000_000).filter(|&bps| bps > 0);
+        match (speed, high) {
+            (Some(bps), Some(high)) if bps >= u32::MAX as u64 => Some(high),
+            (speed, high) => speed.or(high),
+        }
+    }
+}
+
+/// A physical component from entPhysicalTable.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntityInfo {
+    pub index: i32,
+    pub descr: Option<String>,
+    /// entPhysicalVendorType as a dotted OID
+    pub vendor_type: Option<String>,
+    /// Index of the containing entity; 0 at the top of the tree
+    pub contained_in: Option<i32>,
+    /// PhysicalClass name, e.g. "chassis", "module", "powerSupply"
+    pub class: Option<String>,
+    pub pos: Option<i32>,
+    pub name: Option<String>,
+    pub hw_ver: Option<String>,
+    pub fw_ver: Option<String>,
+    pub sw_ver: Option<String>,
+    pub serial: Option<String>,
+    pub model: Option<String>,
+    pub fru: Option<bool>,
+}
+
+/// The chassis of a device: the outermost chassis entity, or failing
+/// that the first one.
+pub fn find_chassis(entities: &[EntityInfo]) -> Option<&EntityInfo> {
+    let mut chassis = entities.iter().filter(|e| e.class.as_deref() == Some("chassis"));
+    let first = chassis.clone().next();
+    chassis.find(|e| e.contained_in.unwrap_or(0) == 0).or(first)
+}
+
+/// IANAifType names for types 1 to 144, in order.
+const IANA_IF_TYPES: [&str; 144] = [
+    "other", "regular1822", "hdh1822", "ddnX25", "rfc877x25", "ethernetCsmacd",
+    "iso88023Csmacd", "iso88024TokenBus", "iso88025TokenRing", "iso88026Man", "starLan",
+    "proteon10Mbit", "proteon80Mbit", "hyperchannel", "fddi", "lapb", "sdlc", "ds1", "e1",
+    "basicISDN", "primaryISDN", "propPointToPointSerial", "ppp", "softwareLoopback", "eon",
+    "ethernet3Mbit", "nsip", "slip", "ultra", "ds3", "sip", "frameRelay", "rs232", "para",
+    "arcnet", "arcnetPlus", "atm", "miox25", "sonet", "x25ple", "iso88022llc", "localTalk",
+    "smdsDxi", "frameRelayService", "v35", "hssi", "hippi", "modem", "aal5", "sonetPath",
+    "sonetVT", "smdsIcip", "propVirtual", "propMultiplexor", "ieee80212", "fibreChannel",
+    "hippiInterface", "frameRelayInterconnect", "aflane8023", "aflane8025", "cctEmul",
+    "fastEther", "isdn", "v11", "v36", "g703at64k", "g703at2mb", "qllc", "fastEtherFX",
+    "channel", "ieee80211", "ibm370parChan", "escon", "dlsw", "isdns", "isdnu", "lapd",
+    "ipSwitch", "rsrb", "atmLogical", "ds0", "ds0Bundle", "bsc", "async", "cnr", "iso88025Dtr",
+    "eplrs", "arap", "propCnls", "hostPad", "termPad", "frameRelayMPI", "x213", "adsl", "radsl",
+    "sdsl", "vdsl", "iso88025CRFPInt", "myrinet", "voiceEM", "voiceFXO", "voiceFXS",
+    "voiceEncap", "voiceOverIp", "atmDxi", "atmFuni", "atmIma", "pppMultilinkBundle",
+    "ipOverCdlc", "ipOverClaw", "stackToStack", "virtualIpAddress", "mpc", "ipOverAtm",
+    "iso88025Fiber", "tdlc", "gigabitEthernet", "hdlc", "lapf", "v37", "x25mlp", "x25huntGroup",
+    "transpHdlc", "interleave", "fast", "ip", "docsCableMaclayer", "docsCableDownstream",
+    "docsCableUpstream", "a12MppSwitch", "tunnel", "coffee", "ces", "atmSubInterface", "l2vlan",
+    "l3ipvlan", "l3ipxvlan", "digitalPowerline", "mediaMailOverIp", "dtm", "dcn", "ipForward",
+    "msdsl", "ieee1394",
+];
+
+/// IANAifType enumeration name, or the number for types not listed.
+pub fn if_type_name(if_type: i64) -> String {
+    let name = match if_type {
+        1..=144 => IANA_IF_TYPES[if_type as usize - 1],
+        150 => "mplsTunnel",
+        161 => "ieee8023adLag",
+        166 => "mpls",
+        171 => "pos",
+        188 => "radioMAC",
+        209 => "bridge",
+        215 => "sixToFour",
+        222 => "ciscoISLvlan",
+        250 => "gpon",
+        251 => "vdsl2",
+        _ => return if_type.to_string(),
+    };
+    name.to_string()
+}
+
+/// PhysicalClass enumeration names (ENTITY-MIB).
+fn entity_class_name(class: i64) -> String {
+    match class {
+        1 => "other",
+        2 => "unknown",
+        3 => "chassis",
+        4 => "backplane",
+        5 => "container",
+        6 => "powerSupply",
+        7 => "fan",
+        8 => "sensor",
+        9 => "module",
+        10 => "port",
+        11 => "stack",
+        12 => "cpu",
+        13 => "energyObject",
+        14 => "battery",
+        15 => "storageDrive",
+        _ => return class.to_string(),
+    }.to_string()
+}
+
+/// An entity text column, trimmed; agents pad serials with spaces and
+/// report unknown values as empty strings.
+fn entity_string(value: Option<SnmpValue>) -> Option<String> {
+    value.and_then(|v| v.as_string())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// A device's VLANs and which ports carry them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VlanTable {
+    /// VLANs in VLAN order
+    pub vlans: Vec<VlanInfo>,
+    pub vtp_domain: Option<String>,
+    /// Port VLAN ID (native or access VLAN) by ifIndex
+    pub pvids: BTreeMap<i32, i32>,
+    /// Membership, by ifIndex then VLAN
+    pub members: Vec<VlanMember>,
+}
+
+/// A VLAN configured on a device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VlanInfo {
+    pub vlan: i32,
+    pub name: Option<String>,
+}
+
+/// A VLAN carried on a port.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VlanMember {
+    pub ifindex: i32,
+    pub vlan: i32,
+    /// Sent untagged, rather than tagged
+    pub untagged: bool,
+}
+
+/// The members of a PortList bitmap, numbered from 1 starting at the most
+/// significant bit of the first octet.
+pub fn port_list(octets: &[u8]) -> Vec<u32> {
+    octets.iter().enumerate()
+        .flat_map(|(i, byte)| (0..8).filter(move |bit| byte & (0x80 >> bit) != 0).map(move |bit| (i * 8 + bit) as u32 + 1))
+        .collect()
+}
+
+/// PoE power sourcing equipment on a device.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoeInfo {
+    pub modules: Vec<PseModule>,
+    pub ports: Vec<PsePort>,
+}
+
+/// A pethMainPseTable row: one PSE group, usually a stack member or slot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PseModule {
+    pub group: i32,
+    /// Budget in watts
+    pub power: Option<i32>,
+    /// "on", "off" or "faulty"
+    pub status: Option<String>,
+    /// Drawn in watts
+    pub consumption: Option<i32>,
+}
+
+/// A pethPsePortTable row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PsePort {
+    pub group: i32,
+    /// Port index within the group, usually its position (1.1 to 1.48 on a
+    /// 48-port switch) rather than the ifIndex
+    pub port: i32,
+    /// The interface the port powers, where it could be matched
+    pub ifindex: Option<i32>,
+    /// "true" or "false"
+    pub admin: Option<String>,
+    /// Detection status, e.g. "deliveringPower" or "searching"
+    pub status: Option<String>,
+    /// "class0" to "class4"
+    pub class: Option<String>,
+    /// Drawn in milliwatts
+    pub power: Option<i32>,
+}
+
+/// The ifIndex of pethPsePortTable row `group.port`. POWER-ETHERNET-MIB
+/// leaves the numbering to the agent, so in order of preference:
+///
+/// - `aliased`, the ifIndex the agent maps the port's entity to (Cisco's
+///   cpeExtPsePortEntPhyIndex through entAliasMappingTable);
+/// - the Ethernet interface at that position, whose name ends in `port`
+///   and, if several do, starts with `group` (`Gi2/0/5` for 2.5);
+/// - the Ethernet interface whose ifIndex is `port`, as on HP ProCurve.
+pub fn pse_port_ifindex(group: u32, port: u32, aliased: Option<i32>, interfaces: &[InterfaceInfo]) -> Option<i32> {
+    let ethernet: Vec<&InterfaceInfo> = interfaces.iter()
+        .filter(|i| matches!(i.if_type.as_deref(), Some("ethernetCsmacd" | "fastEther" | "fastEtherFX" | "gigabitEthernet")))
+        .collect();
+    if let Some(ifindex) = aliased.filter(|&ifindex| interfaces.iter().any(|i| i.ifindex == ifindex)) {
+        return Some(ifindex);
+    }
+
+    let numbers = |iface: &InterfaceInfo| -> Vec<u32> {
+        iface.port_name()
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|n| n.parse().ok())
+            .collect()
+    };
+    let mut by_position: Vec<(&InterfaceInfo, Vec<u32>)> = ethernet.iter()
+        .map(|&i| (i, numbers(i)))
+        .filter(|(_, n)| n.last() == Some(&port) && (n.len() > 1 || group == 1))
+        .collect();
+    // Narrow by stack member or slot, then prefer the base ports
+    // (Gi1/0/1) over uplink modules (Te1/1/1)
+    let narrow = |ports: &mut Vec<(&InterfaceInfo, Vec<u32>)>, keep: &dyn Fn(&[u32]) -> bool| {
+        if ports.len() > 1 && ports.iter().any(|(_, n)| keep(n)) {
+            ports.retain(|(_, n)| keep(n));
+        }
+    };
+    narrow(&mut by_position, &|n| n.len() > 1 && n[0] == group);
+    narrow(&mut by_position, &|n| n.len() > 2 && n[1..n.len() - 1].iter().all(|&m| m == 0));
+    if let [(iface, _)] = by_position[..] {
+        return Some(iface.ifindex);
+    }
+
+    ethernet.iter().find(|i| i.ifindex == port as i32).map(|i| i.ifindex)
+}
+
+/// The name of a 1-based SNMP enumeration value, or the number for values
+/// outside `names`.
+fn enum_name(names: &[&str], value: i64) -> String {
+    usize::try_from(value - 1).ok()
+        .and_then(|i| names.get(i))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Spanning tree on a device.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StpInfo {
+    /// "stp", "rstp", "mstp", "pvst", "rapid-pvst" or "mistp"
+    pub protocol: Option<String>,
+    pub instances: Vec<StpInstance>,
+}
+
+/// One spanning tree instance: the CIST or single tree (0), an MST
+/// instance, or a PVST VLAN.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StpInstance {
+    /// MSTID, or the VLAN for PVST
+    pub instance: i32,
+    /// VLANs mapped to the instance; empty for the CIST or single tree
+    pub vlans: Vec<i32>,
+    /// This bridge's ID, as "priority.mac"
+    pub bridge: Option<String>,
+    /// The root bridge's ID, as "priority.mac"
+    pub root: Option<String>,
+    pub root_cost: Option<i32>,
+    /// ifIndex of the root port; None on the root bridge
+    pub root_port: Option<i32>,
+    /// Port state by ifIndex: "forwarding", "blocking", "disabled", ...
+    pub ports: BTreeMap<i32, String>,
+}
+
+impl StpInstance {
+    /// Whether this bridge is the root of the instance.
+    pub fn is_root(&self) -> bool {
+        let mac = |id: &Option<String>| id.as_deref().and_then(|id| id.split_once('.')).map(|(_, mac)| mac.to_string());
+        mac(&self.bridge).is_some() && mac(&self.bridge) == mac(&self.root)
+    }
+}
+
+/// Format an 8-octet BridgeId as "priority.mac", e.g. "32768.00:1a:2b:3c:4d:5e".
+pub fn bridge_id(octets: &[u8]) -> Option<String> {
+    let [high, low, mac @ ..] = octets else { return None };
+    if mac.len() != 6 {
+        return None;
+    }
+    Some(format!("{}.{}", u16::from_be_bytes([*high, *low]), format_mac_octets(mac)))
+}
+
+/// One of a device's own IP addresses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpAddressInfo {
+    pub ip: IpAddr,
+    /// Interface the address is configured on
+    pub ifindex: Option<i32>,
+    /// Subnet prefix length, when the agent reports it
+    pub prefix_len: Option<u8>,
+}
+
+impl IpAddressInfo {
+    /// The subnet the address is in.
+    pub fn subnet(&self) -> Option<IpNetwork> {
+        let net = IpNetwork::new(self.ip, self.prefix_len?).ok()?;
+        IpNetwork::new(net.network(), net.prefix()).ok()
+    }
+}
+
+/// A CDP or LLDP neighbor seen on a local interface.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NeighborInfo {
+    /// "lldp" or "cdp"
+    pub protocol: &'static str,
+    /// Local ifIndex the neighbor was seen on
+    pub ifindex: i32,
+    /// Management address, IPv4 preferred
+    pub remote_ip: Option<IpAddr>,
+    pub remote_port: Option<String>,
+    /// LLDP sysDescr or CDP platform
+    pub remote_type: Option<String>,
+    /// LLDP sysName (or chassis ID) or CDP device ID
+    pub remote_id: Option<String>,
+    /// Enabled capabilities, named as the protocol names them
+    pub capabilities: Vec<&'static str>,
+}
+
+impl NeighborInfo {
+    fn has_capability(&self, names: &[&str]) -> bool {
+        self.capabilities.iter().any(|c| names.contains(c))
+    }
+
+    /// An IP phone.
+    pub fn is_phone(&self) -> bool {
+        self.has_capability(&["telephone", "Phone"])
+    }
+
+    /// A wireless access point. Aironets only advertise Trans-Bridge
+    /// over CDP.
+    pub fn is_wap(&self) -> bool {
+        self.has_capability(&["wlanAccessPoint"])
+            || self.remote_type.as_deref().is_some_and(|t| t.contains("AIR-"))
+            || (self.has_capability(&["Trans-Bridge"]) && !self.has_capability(&["Switch", "Router"]))
+    }
+
+    /// A router that does not also bridge, reached over a routed link.
+    pub fn is_routed(&self) -> bool {
+        self.has_capability(&["router", "Router"])
+            && !self.has_capability(&["bridge", "Switch", "Trans-Bridge", "Source-Route-Bridge"])
     }
+}
 
-    /// Walk the dot1dTpFdbTable (MAC address table) for macsuck.
-    pub fn get_mac_table(&self) -> Result<Vec<MacEntry>> {
-        info!("Getting MAC address table from {}", self.target);
-        let fdb_port = self.walk(&super::oids::DOT1D_TP_FDB_PORT)?;
-        let _fdb_address = self.walk(&super::oids::DOT1D_TP_FDB_ADDRESS)?;
+/// An lldpLocPortTable row.
+struct LldpLocalPort {
+    id_subtype: Option<i64>,
+    id: Option<String>,
+    desc: Option<String>,
+}
 
-        let mut entries = Vec::new();
-        for (oid, port_bytes) in &fdb_port {
-            let bridge_port = parse_integer(port_bytes).unwrap_or(0);
-            // MAC is encoded in the last 6 octets of the OID
-            if oid.len() >= 6 {
-                let mac_parts: Vec<String> = oid[oid.len()-6..]
-                    .iter()
-                    .map(|b| format!("{:02x}", b))
-                    .collect();
-                let mac = mac_parts.join(":");
-                entries.push(MacEntry {
-                    mac,
-                    bridge_port: bridge_port as i32,
-                    vlan: None,
-                });
+/// The ifIndex for an lldpLocPortNum. Agents number LLDP ports by
+/// ifIndex, by bridge port or arbitrarily, so match the port's
+/// description and ID to ifDescr or ifName first, as SNMP::Info does.
+fn lldp_local_ifindex(
+    port_num: i32,
+    local: Option<&LldpLocalPort>,
+    interfaces: &[InterfaceInfo],
+    bridge_ports: &BTreeMap<i32, i32>,
+) -> Option<i32> {
+    let by_descr = |name: &str| interfaces.iter()
+        .find(|i| i.descr == name || i.name.as_deref() == Some(name))
+        .map(|i| i.ifindex);
+    let is_ifindex = |ifindex: i32| interfaces.iter().any(|i| i.ifindex == ifindex);
+
+    if let Some(local) = local {
+        if let Some(ifindex) = local.desc.as_deref().and_then(by_descr) {
+            return Some(ifindex);
+        }
+        if let Some(id) = local.id.as_deref() {
+            match local.id_subtype {
+                // interfaceName(5)
+                Some(5) => if let Some(ifindex) = by_descr(id) { return Some(ifindex) },
+                // local(7), usually the ifIndex
+                Some(7) => if let Some(ifindex) = id.parse().ok().filter(|&i| is_ifindex(i)) { return Some(ifindex) },
+                _ => {}
             }
         }
+    }
+    bridge_ports.get(&port_num).copied()
+        .or_else(|| is_ifindex(port_num).then_some(port_num))
+}
 
-        Ok(entries)
+/// An LLDP chassis or port ID as text.
+fn lldp_id(value: Option<SnmpValue>, is_mac: bool, is_network: bool) -> Option<String> {
+    let bytes = value.as_ref()?.as_bytes()?;
+    if is_mac && bytes.len() == 6 {
+        return Some(format_mac_octets(bytes));
+    }
+    if is_network {
+        // IANA address family, then the address
+        if let Some(ip) = bytes.split_first().and_then(|(_, addr)| ip_from_octets(addr)) {
+            return Some(ip.to_string());
+        }
     }
+    entity_string(value)
+}
 
-    /// Walk the ipNetToMediaTable (ARP table) for arpnip.
-    pub fn get_arp_table(&self) -> Result<Vec<ArpEntry>> {
-        info!("Getting ARP table from {}", self.target);
-        let arp_phys = self.walk(&super::oids::IP_NET_TO_MEDIA_PHYS)?;
+/// An lldpRemManAddrTable index suffix: subtype, length, octets.
+fn decode_lldp_man_addr(index: &[u32]) -> Option<IpAddr> {
+    let (&subtype, rest) = index.split_first()?;
+    let (&len, octets) = rest.split_first()?;
+    // ipV4(1), ipV6(2)
+    if !(subtype == 1 || subtype == 2) || octets.len() != len as usize {
+        return None;
+    }
+    let octets: Vec<u8> = octets.iter().map(|&o| u8::try_from(o).ok()).collect::<Option<_>>()?;
+    ip_from_octets(&octets)
+}
 
-        let mut entries = Vec::new();
-        for (oid, mac_bytes) in &arp_phys {
-            // IP is encoded in the last 4 octets of the OID
-            if oid.len() >= 4 {
-                let ip = format!("{}.{}.{}.{}", oid[oid.len()-4], oid[oid.len()-3], oid[oid.len()-2], oid[oid.len()-1]);
-                let mac = mac_bytes.iter()
-                    .map(|b| format!("{:02x}", b))
-                    .collect::<Vec<_>>()
-                    .join(":");
-                entries.push(ArpEntry { ip, mac });
-            }
-        }
+/// An address encoded in OID index arcs, one arc per octet.
+fn ip_from_index(index: &[u32]) -> Option<IpAddr> {
+    let octets: Vec<u8> = index.iter().map(|&o| u8::try_from(o).ok()).collect::<Option<_>>()?;
+    ip_from_octets(&octets)
+}
 
-        Ok(entries)
+fn ip_from_octets(octets: &[u8]) -> Option<IpAddr> {
+    match octets.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(octets).ok()?)),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(octets).ok()?)),
+        _ => None,
     }
+}
 
-    /// Send a PDU and receive the response with retry logic.
-    fn send_receive(&self, pdu: &[u8]) -> Result<Vec<u8>> {
-        let socket = UdpSocket::bind("0.0.0.0:0")
-            .context("Failed to bind UDP socket")?;
-        socket.set_read_timeout(Some(self.timeout))
-            .context("Failed to set socket timeout")?;
-        socket.connect(self.target)
-            .context("Failed to connect to SNMP target")?;
+fn format_mac_octets(octets: &[u8]) -> String {
+    octets.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
 
-        for attempt in 0..=self.retries {
-            socket.send(pdu).context("Failed to send SNMP PDU")?;
+/// A MAC address from the last six arcs of a table index.
+fn mac_from_index(index: &[u32]) -> Option<String> {
+    let octets: Vec<u8> = index.iter().map(|&b| u8::try_from(b).ok()).collect::<Option<_>>()?;
+    (octets.len() == 6).then(|| format_mac_octets(&octets))
+}
 
-            let mut buf = vec![0u8; 65535];
-            match socket.recv(&mut buf) {
-                Ok(len) => {
-                    buf.truncate(len);
-                    return Ok(buf);
-                }
-                Err(e) if attempt < self.retries => {
-                    debug!("SNMP retry {} after error: {}", attempt + 1, e);
-                    continue;
-                }
-                Err(e) => {
-                    return Err(e).context("SNMP request timed out");
-                }
-            }
-        }
+/// The bridge port of an FDB row, unless it is on none or is one of the
+/// switch's own addresses (status `self`).
+fn fdb_port(port: &Option<SnmpValue>, status: &Option<SnmpValue>) -> Option<i32> {
+    if status.as_ref().and_then(|v| v.as_i64()) == Some(4) {
+        return None;
+    }
+    port.as_ref().and_then(|v| v.as_i64()).filter(|&p| p > 0).map(|p| p as i32)
+}
 
-        unreachable!()
+/// The VLAN for each FID, from `(vlan, fid)` pairs (dot1qVlanFdbId). A FID
+/// shared by several VLANs (shared VLAN learning) maps to None, as the
+/// VLAN an entry was learned in cannot be told. FIDs missing here are
+/// taken to be the VLAN number.
+pub fn fdb_vlans(fids: impl IntoIterator<Item = (i32, i32)>) -> BTreeMap<i32, Option<i32>> {
+    let mut vlans: BTreeMap<i32, Option<i32>> = BTreeMap::new();
+    for (vlan, fid) in fids {
+        vlans.entry(fid)
+            .and_modify(|v| if *v != Some(vlan) { *v = None })
+            .or_insert(Some(vlan));
     }
+    vlans
 }
 
-/// System information from SNMP system group.
-#[derive(Debug, Clone)]
-pub struct SystemInfo {
-    pub description: Option<String>,
-    pub object_id: Option<String>,
-    pub uptime: Option<i64>,
-    pub contact: Option<String>,
-    pub name: Option<String>,
-    pub location: Option<String>,
-    pub services: Option<i64>,
+/// LldpSystemCapabilitiesMap BITS, most significant bit first.
+fn lldp_capabilities(bits: &[u8]) -> Vec<&'static str> {
+    const NAMES: [&str; 8] = [
+        "other", "repeater", "bridge", "wlanAccessPoint",
+        "router", "telephone", "docsisCableDevice", "stationOnly",
+    ];
+    NAMES.iter().enumerate()
+        .filter(|(bit, _)| bits.get(bit / 8).is_some_and(|b| b & (0x80 >> (bit % 8)) != 0))
+        .map(|(_, name)| *name)
+        .collect()
 }
 
-/// Interface information from ifTable.
-#[derive(Debug, Clone)]
-pub struct InterfaceInfo {
-    pub ifindex: i32,
-    pub descr: String,
-    pub if_type: Option<String>,
-    pub speed: Option<i64>,
-    pub admin_status: Option<i64>,
-    pub oper_status: Option<i64>,
+/// cdpCacheCapabilities, a 32-bit mask in four octets.
+fn cdp_capabilities(octets: &[u8]) -> Vec<&'static str> {
+    const NAMES: [&str; 11] = [
+        "Router", "Trans-Bridge", "Source-Route-Bridge", "Switch", "Host", "IGMP",
+        "Repeater", "Phone", "Remote", "CVTA", "Two-port Mac Relay",
+    ];
+    let mask = octets.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+    NAMES.iter().enumerate()
+        .filter(|(bit, _)| mask & (1 << bit) != 0)
+        .map(|(_, name)| *name)
+        .collect()
 }
 
 /// MAC address table entry.
@@ -341,10 +2010,21 @@ pub struct ArpEntry {
     pub mac: String,
 }
 
+/// The agent address for an IPv4 or IPv6 literal, which may be
+/// bracketed ("[2001:db8::1]").
+pub fn target_addr(host: &str, port: u16) -> Result<SocketAddr> {
+    let literal = host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    let ip: IpAddr = literal.parse()
+        .with_context(|| format!("Invalid SNMP target: {}", host))?;
+    Ok(SocketAddr::new(ip, port))
+}
+
 // ==================== BER Parsing Helpers ====================
 
 /// Read a BER tag byte (returns the tag).
-fn read_tag(data: &[u8], pos: &mut usize) -> Result<u8> {
+pub(crate) fn read_tag(data: &[u8], pos: &mut usize) -> Result<u8> {
     if *pos >= data.len() {
         anyhow::bail!("BER: unexpected end of data reading tag");
     }
@@ -354,7 +2034,7 @@ fn read_tag(data: &[u8], pos: &mut usize) -> Result<u8> {
 }
 
 /// Read a BER length field and return the length value.
-fn read_length(data: &[u8], pos: &mut usize) -> Result<usize> {
+pub(crate) fn read_length(data: &[u8], pos: &mut usize) -> Result<usize> {
     if *pos >= data.len() {
         anyhow::bail!("BER: unexpected end of data reading length");
     }
@@ -378,7 +2058,7 @@ fn read_length(data: &[u8], pos: &mut usize) -> Result<usize> {
 }
 
 /// Skip a BER TLV (tag + length + value), returning the bytes skipped.
-fn skip_tlv(data: &[u8], pos: &mut usize) -> Result<()> {
+pub(crate) fn skip_tlv(data: &[u8], pos: &mut usize) -> Result<()> {
     read_tag(data, pos)?;
     let len = read_length(data, pos)?;
     if *pos + len > data.len() {
@@ -389,7 +2069,7 @@ fn skip_tlv(data: &[u8], pos: &mut usize) -> Result<()> {
 }
 
 /// Read the content bytes of a TLV without interpreting the tag.
-fn read_tlv_value(data: &[u8], pos: &mut usize) -> Result<(u8, Vec<u8>)> {
+pub(crate) fn read_tlv_value(data: &[u8], pos: &mut usize) -> Result<(u8, Vec<u8>)> {
     let tag = read_tag(data, pos)?;
     let len = read_length(data, pos)?;
     if *pos + len > data.len() {
@@ -401,7 +2081,7 @@ fn read_tlv_value(data: &[u8], pos: &mut usize) -> Result<(u8, Vec<u8>)> {
 }
 
 /// Decode an OID from BER-encoded bytes.
-fn decode_oid(data: &[u8]) -> Result<Vec<u32>> {
+pub(crate) fn decode_oid(data: &[u8]) -> Result<Vec<u32>> {
     if data.is_empty() {
         anyhow::bail!("BER: empty OID");
     }
@@ -428,8 +2108,42 @@ fn decode_oid(data: &[u8]) -> Result<Vec<u32>> {
     Ok(oid)
 }
 
-/// Parse an SNMP response and extract the first varbind (OID, value).
-fn parse_snmp_response(data: &[u8]) -> Result<Vec<(Vec<u32>, Vec<u8>)>> {
+/// Decode the content octets of a BER INTEGER (two's complement).
+pub(crate) fn decode_integer(data: &[u8]) -> Result<i64> {
+    if data.is_empty() || data.len() > 8 {
+        anyhow::bail!("BER: invalid INTEGER length {}", data.len());
+    }
+    let mut value: i64 = if data[0] & 0x80 != 0 { -1 } else { 0 };
+    for &byte in data {
+        value = (value << 8) | byte as i64;
+    }
+    Ok(value)
+}
+
+/// Read a TLV and check that it carries the expected tag.
+pub(crate) fn read_expected(data: &[u8], pos: &mut usize, expected: u8, what: &str) -> Result<Vec<u8>> {
+    let (tag, value) = read_tlv_value(data, pos)?;
+    if tag != expected {
+        anyhow::bail!("SNMP: expected {} (0x{:02x}), got 0x{:02x}", what, expected, tag);
+    }
+    Ok(value)
+}
+
+/// Read a SEQUENCE header, returning the end offset of its contents.
+pub(crate) fn read_sequence(data: &[u8], pos: &mut usize, what: &str) -> Result<usize> {
+    let tag = read_tag(data, pos)?;
+    if tag != 0x30 {
+        anyhow::bail!("SNMP: expected {} SEQUENCE (0x30), got 0x{:02x}", what, tag);
+    }
+    let len = read_length(data, pos)?;
+    if *pos + len > data.len() {
+        anyhow::bail!("BER: {} extends past end of data", what);
+    }
+    Ok(*pos + len)
+}
+
+/// Unwrap a v1/v2c message (version, community, PDU) and return the PDU.
+fn decode_community_message(data: &[u8]) -> Result<Vec<u8>> {
     if data.len() < 2 {
         anyhow::bail!("SNMP response too short");
     }
@@ -441,7 +2155,8 @@ fn parse_snmp_response(data: &[u8]) -> Result<Vec<(Vec<u32>, Vec<u8>)>> {
     if tag != 0x30 {
         anyhow::bail!("SNMP: expected SEQUENCE (0x30), got 0x{:02x}", tag);
     }
-    let _msg_len = read_length(data, &mut pos)?;
+    let msg_len = read_length(data, &mut pos)?;
+    let msg_end = (pos + msg_len).min(data.len());
 
     // Version (INTEGER)
     skip_tlv(data, &mut pos)?;
@@ -449,10 +2164,51 @@ fn parse_snmp_response(data: &[u8]) -> Result<Vec<(Vec<u32>, Vec<u8>)>> {
     // Community (OCTET STRING)
     skip_tlv(data, &mut pos)?;
 
-    // PDU (context-specific: 0xa2 = GetResponse)
+    Ok(data[pos..msg_end].to_vec())
+}
+
+/// The id a response is matched on: msgID for SNMPv3, otherwise the
+/// PDU request-id. `None` if the message is malformed.
+pub(crate) fn message_request_id(data: &[u8]) -> Option<i64> {
+    let mut pos: usize = 0;
+    read_sequence(data, &mut pos, "message").ok()?;
+    let version = decode_integer(&read_expected(data, &mut pos, 0x02, "version").ok()?).ok()?;
+    if version == 3 {
+        read_sequence(data, &mut pos, "msgGlobalData").ok()?;
+        return decode_integer(&read_expected(data, &mut pos, 0x02, "msgID").ok()?).ok();
+    }
+    skip_tlv(data, &mut pos).ok()?; // community
+    pdu_request_id(&data[pos..]).ok()
+}
+
+/// The request-id of a PDU.
+pub(crate) fn pdu_request_id(pdu: &[u8]) -> Result<i64> {
+    let mut pos: usize = 0;
+    read_tag(pdu, &mut pos)?;
+    read_length(pdu, &mut pos)?;
+    decode_integer(&read_expected(pdu, &mut pos, 0x02, "request-id")?)
+}
+
+fn check_request_id(pdu: &[u8], request_id: i32) -> Result<()> {
+    let response_id = pdu_request_id(pdu)?;
+    if response_id != request_id as i64 {
+        anyhow::bail!("SNMP: response request-id {} does not match request {}", response_id, request_id);
+    }
+    Ok(())
+}
+
+/// Parse a GetResponse PDU and extract its varbinds (OID, value).
+fn parse_snmp_response(data: &[u8]) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+    parse_pdu_varbinds(data, PDU_RESPONSE, "GetResponse")
+}
+
+/// Parse a PDU of the expected type and extract its varbinds.
+fn parse_pdu_varbinds(data: &[u8], expected_tag: u8, pdu_name: &str) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+    let mut pos: usize = 0;
+
     let pdu_tag = read_tag(data, &mut pos)?;
-    if pdu_tag != 0xa2 {
-        anyhow::bail!("SNMP: expected GetResponse PDU (0xa2), got 0x{:02x}", pdu_tag);
+    if pdu_tag != expected_tag {
+        anyhow::bail!("SNMP: expected {} PDU (0x{:02x}), got 0x{:02x}", pdu_name, expected_tag, pdu_tag);
     }
     let _pdu_len = read_length(data, &mut pos)?;
 
@@ -461,136 +2217,203 @@ fn parse_snmp_response(data: &[u8]) -> Result<Vec<(Vec<u32>, Vec<u8>)>> {
 
     // error-status (INTEGER)
     let (_tag, error_status_bytes) = read_tlv_value(data, &mut pos)?;
-    let error_status = if error_status_bytes.len() == 1 {
-        error_status_bytes[0] as i32
-    } else {
-        0
-    };
+    let error_status = decode_integer(&error_status_bytes).unwrap_or(0) as i32;
+
+    // error-index (INTEGER)
+    let (_tag, error_index_bytes) = read_tlv_value(data, &mut pos)?;
     if error_status != 0 {
-        let error_name = match error_status {
-            1 => "tooBig",
-            2 => "noSuchName",
-            3 => "badValue",
-            4 => "readOnly",
-            5 => "genErr",
-            6 => "noAccess",
-            7 => "wrongType",
-            _ => "unknown",
-        };
-        anyhow::bail!("SNMP error-status: {} ({})", error_status, error_name);
+        let index = decode_integer(&error_index_bytes).unwrap_or(0) as i32;
+        return Err(ErrorStatus { status: error_status, index }.into());
     }
 
-    // error-index (INTEGER)
-    skip_tlv(data, &mut pos)?;
+    decode_varbind_list(data, &mut pos)
+}
 
-    // VarBindList (SEQUENCE)
-    let tag = read_tag(data, &mut pos)?;
+/// Decode a VarBindList SEQUENCE.
+pub(crate) fn decode_varbind_list(data: &[u8], pos: &mut usize) -> Result<Vec<(Vec<u32>, SnmpValue)>> {
+    let tag = read_tag(data, pos)?;
     if tag != 0x30 {
         anyhow::bail!("SNMP: expected VarBindList SEQUENCE, got 0x{:02x}", tag);
     }
-    let varbind_list_len = read_length(data, &mut pos)?;
-    let varbind_list_end = pos + varbind_list_len;
+    let varbind_list_len = read_length(data, pos)?;
+    let varbind_list_end = *pos + varbind_list_len;
 
     let mut results = Vec::new();
 
-    while pos < varbind_list_end && pos < data.len() {
+    while *pos < varbind_list_end && *pos < data.len() {
         // VarBind (SEQUENCE)
-        let tag = read_tag(data, &mut pos)?;
+        let tag = read_tag(data, pos)?;
         if tag != 0x30 {
             break;
         }
-        let _varbind_len = read_length(data, &mut pos)?;
+        let _varbind_len = read_length(data, pos)?;
 
         // OID
-        let (oid_tag, oid_bytes) = read_tlv_value(data, &mut pos)?;
+        let (oid_tag, oid_bytes) = read_tlv_value(data, pos)?;
         if oid_tag != 0x06 {
             anyhow::bail!("SNMP: expected OID tag (0x06), got 0x{:02x}", oid_tag);
         }
         let oid = decode_oid(&oid_bytes)?;
 
         // Value
-        let (value_tag, value_bytes) = read_tlv_value(data, &mut pos)?;
+        let (value_tag, value_bytes) = read_tlv_value(data, pos)?;
+        let value = SnmpValue::decode(value_tag, &value_bytes)
+            .with_context(|| format!("SNMP: bad value for {}", oid_to_string(&oid)))?;
 
-        // Skip endOfMibView, noSuchObject, noSuchInstance
-        if value_tag == 0x82 || value_tag == 0x80 || value_tag == 0x81 {
-            continue;
-        }
-
-        results.push((oid, value_bytes));
+        results.push((oid, value));
     }
 
     Ok(results)
 }
 
-// ==================== Public Parsing Functions ====================
+/// A non-zero error-status in a response PDU.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorStatus {
+    pub status: i32,
+    /// 1-based index of the varbind at fault, or 0.
+    pub index: i32,
+}
+
+impl ErrorStatus {
+    pub const TOO_BIG: i32 = 1;
+    pub const NO_SUCH_NAME: i32 = 2;
 
-fn parse_get_response(data: &[u8]) -> Result<Vec<u8>> {
-    let varbinds = parse_snmp_response(data)?;
-    varbinds.into_iter()
-        .next()
-        .map(|(_, value)| value)
-        .ok_or_else(|| anyhow::anyhow!("SNMP GET: no varbind in response"))
+    pub fn name(&self) -> &'static str {
+        match self.status {
+            1 => "tooBig",
+            2 => "noSuchName",
+            3 => "badValue",
+            4 => "readOnly",
+            5 => "genErr",
+            6 => "noAccess",
+            7 => "wrongType",
+            8 => "wrongLength",
+            9 => "wrongEncoding",
+            10 => "wrongValue",
+            11 => "noCreation",
+            12 => "inconsistentValue",
+            13 => "resourceUnavailable",
+            14 => "commitFailed",
+            15 => "undoFailed",
+            16 => "authorizationError",
+            17 => "notWritable",
+            18 => "inconsistentName",
+            _ => "unknown",
+        }
+    }
 }
 
-fn parse_getnext_response(data: &[u8]) -> Result<(Vec<u32>, Vec<u8>)> {
-    let varbinds = parse_snmp_response(data)?;
-    varbinds.into_iter()
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("SNMP GETNEXT: no varbind in response"))
+impl std::fmt::Display for ErrorStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SNMP error-status: {} ({})", self.status, self.name())
+    }
+}
+
+impl std::error::Error for ErrorStatus {}
+
+/// An SNMPv3 Report that the agent has no such context
+/// (snmpUnknownContexts), e.g. no `vlan-N` context for a VLAN's FDB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownContext {
+    pub context: String,
+}
+
+impl std::fmt::Display for UnknownContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SNMPv3: unknown context '{}'", self.context)
+    }
 }
 
-fn parse_getbulk_response(data: &[u8]) -> Result<Vec<(Vec<u32>, Vec<u8>)>> {
-    parse_snmp_response(data)
+impl std::error::Error for UnknownContext {}
+
+fn is_too_big(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<ErrorStatus>()
+        .is_some_and(|e| e.status == ErrorStatus::TOO_BIG)
 }
 
 // ==================== Helper Functions ====================
 
-fn oid_to_string(oid: &[u32]) -> String {
+pub(crate) fn oid_to_string(oid: &[u32]) -> String {
     oid.iter().map(|o| o.to_string()).collect::<Vec<_>>().join(".")
 }
 
-fn find_value_for_index(table: &[(Vec<u32>, Vec<u8>)], index: i32) -> Option<Vec<u8>> {
-    table.iter()
-        .find(|(oid, _)| oid.last().copied() == Some(index as u32))
-        .map(|(_, v)| v.clone())
+fn oids_to_string(oids: &[&[u32]]) -> String {
+    oids.iter().map(|oid| oid_to_string(oid)).collect::<Vec<_>>().join(", ")
 }
 
-fn parse_integer(data: &[u8]) -> Option<i64> {
-    match data.len() {
-        1 => Some(data[0] as i8 as i64),
-        2 => Some(i16::from_be_bytes([data[0], data[1]]) as i64),
-        4 => Some(i32::from_be_bytes([data[0], data[1], data[2], data[3]]) as i64),
-        8 => Some(i64::from_be_bytes([data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7]])),
-        _ => None,
-    }
+// ==================== PDU Builders ====================
+
+pub(crate) const PDU_GET_REQUEST: u8 = 0xa0;
+pub(crate) const PDU_GET_NEXT_REQUEST: u8 = 0xa1;
+pub(crate) const PDU_RESPONSE: u8 = 0xa2;
+pub(crate) const PDU_SET_REQUEST: u8 = 0xa3;
+pub(crate) const PDU_GET_BULK_REQUEST: u8 = 0xa5;
+pub(crate) const PDU_REPORT: u8 = 0xa8;
+
+/// GETBULK max-repetitions when no `bulkwalk_repeaters` is configured.
+const DEFAULT_MAX_REPETITIONS: u32 = 20;
+
+/// Concurrent requests per device when no `snmp_max_inflight` is configured.
+const DEFAULT_MAX_INFLIGHT: usize = 4;
+
+/// Build a request PDU with a NULL-valued varbind per OID. `bulk` is
+/// (non-repeaters, max-repetitions) for GETBULK.
+fn build_pdu(pdu_type: u8, request_id: i32, oids: &[&[u32]], bulk: Option<(u32, u32)>) -> Vec<u8> {
+    let varbinds: Vec<(&[u32], SnmpValue)> = oids.iter().map(|oid| (*oid, SnmpValue::Null)).collect();
+    encode_pdu(pdu_type, request_id, bulk, &encode_varbinds(&varbinds))
 }
 
-fn parse_timeticks(data: &[u8]) -> Option<i64> {
-    // TimeTicks is an unsigned 32-bit value
-    match data.len() {
-        4 => Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as i64),
-        _ => parse_integer(data),
+/// Encode varbinds (without the enclosing VarBindList SEQUENCE).
+pub(crate) fn encode_varbinds(varbinds: &[(&[u32], SnmpValue)]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for (oid, value) in varbinds {
+        let mut varbind = encode_oid(oid);
+        varbind.extend_from_slice(&value.encode());
+        encoded.extend_from_slice(&encode_tlv(0x30, &varbind));
     }
+    encoded
 }
 
-// ==================== PDU Builders ====================
+/// Encode a PDU around already-encoded varbinds.
+pub(crate) fn encode_pdu(pdu_type: u8, request_id: i32, bulk: Option<(u32, u32)>, varbinds: &[u8]) -> Vec<u8> {
+    let mut pdu_content = Vec::new();
+    // request-id
+    pdu_content.extend_from_slice(&[0x02, 0x04]);
+    pdu_content.extend_from_slice(&request_id.to_be_bytes());
 
-fn build_get_pdu(version: &SnmpVersion, community: &str, oid: &[u32]) -> Vec<u8> {
-    build_pdu(0xa0, version, community, oid, None) // GetRequest-PDU
-}
+    match bulk {
+        Some((non_repeaters, max_repetitions)) => {
+            pdu_content.extend_from_slice(&encode_integer(non_repeaters as i64));
+            pdu_content.extend_from_slice(&encode_integer(max_repetitions as i64));
+        }
+        None => {
+            // error-status = 0
+            pdu_content.extend_from_slice(&[0x02, 0x01, 0x00]);
+            // error-index = 0
+            pdu_content.extend_from_slice(&[0x02, 0x01, 0x00]);
+        }
+    }
+    // VarBindList
+    pdu_content.extend_from_slice(&encode_tlv(0x30, varbinds));
 
-fn build_getnext_pdu(version: &SnmpVersion, community: &str, oid: &[u32]) -> Vec<u8> {
-    build_pdu(0xa1, version, community, oid, None) // GetNextRequest-PDU
+    encode_tlv(pdu_type, &pdu_content)
 }
 
-fn build_getbulk_pdu(version: &SnmpVersion, community: &str, oid: &[u32], max_repetitions: u32) -> Vec<u8> {
-    build_pdu(0xa5, version, community, oid, Some(max_repetitions)) // GetBulkRequest-PDU
-}
+/// Wrap a PDU in a v1/v2c message: version + community + PDU.
+pub(crate) fn encode_community_message(version: &SnmpVersion, community: &str, pdu: &[u8]) -> Vec<u8> {
+    let mut message_content = Vec::new();
+    // Version byte
+    message_content.extend_from_slice(&[0x02, 0x01, version.version_byte()]);
+    // Community string
+    message_content.extend_from_slice(&encode_tlv(0x04, community.as_bytes()));
+    message_content.extend_from_slice(pdu);
 
-fn build_pdu(pdu_type: u8, version: &SnmpVersion, community: &str, oid: &[u32], max_repetitions: Option<u32>) -> Vec<u8> {
-    let mut pdu = Vec::new();
+    // Wrap in SEQUENCE
+    encode_tlv(0x30, &message_content)
+}
 
-    // Encode OID
+/// BER-encode an OBJECT IDENTIFIER as a complete TLV.
+pub(crate) fn encode_oid(oid: &[u32]) -> Vec<u8> {
     let mut oid_bytes = vec![0x06]; // OBJECT IDENTIFIER
     let mut oid_value = Vec::new();
     if oid.len() >= 2 {
@@ -614,68 +2437,19 @@ fn build_pdu(pdu_type: u8, version: &SnmpVersion, community: &str, oid: &[u32],
     }
     encode_length(&mut oid_bytes, oid_value.len());
     oid_bytes.extend_from_slice(&oid_value);
+    oid_bytes
+}
 
-    // VarBind: OID + NULL value
-    let mut varbind = Vec::new();
-    varbind.push(0x30); // SEQUENCE
-    let varbind_content_len = oid_bytes.len() + 2; // OID + NULL (05 00)
-    encode_length(&mut varbind, varbind_content_len);
-    varbind.extend_from_slice(&oid_bytes);
-    varbind.extend_from_slice(&[0x05, 0x00]); // NULL
-
-    // VarBindList
-    let mut varbind_list = vec![0x30]; // SEQUENCE
-    encode_length(&mut varbind_list, varbind.len());
-    varbind_list.extend_from_slice(&varbind);
-
-    // PDU
-    let request_id: u32 = rand::random::<u16>() as u32;
-    let mut pdu_content = Vec::new();
-    // request-id
-    pdu_content.extend_from_slice(&[0x02, 0x04]);
-    pdu_content.extend_from_slice(&request_id.to_be_bytes());
-
-    if let Some(max_rep) = max_repetitions {
-        // GETBULK: non-repeaters = 0, max-repetitions = N
-        pdu_content.extend_from_slice(&[0x02, 0x01, 0x00]); // non-repeaters = 0
-        // max-repetitions
-        pdu_content.push(0x02); // INTEGER
-        let rep_bytes = max_rep.to_be_bytes();
-        // Find first non-zero byte for minimal encoding
-        let start = rep_bytes.iter().position(|&b| b != 0).unwrap_or(3);
-        let significant = &rep_bytes[start..];
-        pdu_content.push(significant.len() as u8);
-        pdu_content.extend_from_slice(significant);
-    } else {
-        // error-status = 0
-        pdu_content.extend_from_slice(&[0x02, 0x01, 0x00]);
-        // error-index = 0
-        pdu_content.extend_from_slice(&[0x02, 0x01, 0x00]);
-    }
-    pdu_content.extend_from_slice(&varbind_list);
-
-    let mut pdu_bytes = vec![pdu_type];
-    encode_length(&mut pdu_bytes, pdu_content.len());
-    pdu_bytes.extend_from_slice(&pdu_content);
-
-    // Community string
-    let mut comm_bytes = vec![0x04]; // OCTET STRING
-    encode_length(&mut comm_bytes, community.len());
-    comm_bytes.extend_from_slice(community.as_bytes());
-
-    // Message: version + community + PDU
-    let mut message_content = Vec::new();
-    // Version byte
-    message_content.extend_from_slice(&[0x02, 0x01, version.version_byte()]);
-    message_content.extend_from_slice(&comm_bytes);
-    message_content.extend_from_slice(&pdu_bytes);
-
-    // Wrap in SEQUENCE
-    pdu.push(0x30); // SEQUENCE
-    encode_length(&mut pdu, message_content.len());
-    pdu.extend_from_slice(&message_content);
+pub(crate) fn encode_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut buf = vec![tag];
+    encode_length(&mut buf, content.len());
+    buf.extend_from_slice(content);
+    buf
+}
 
-    pdu
+/// Encode a BER INTEGER with minimal two's-complement content octets.
+pub(crate) fn encode_integer(value: i64) -> Vec<u8> {
+    SnmpValue::Integer(value).encode()
 }
 
 fn encode_length(buf: &mut Vec<u8>, len: usize) {
@@ -690,3 +2464,329 @@ fn encode_length(buf: &mut Vec<u8>, len: usize) {
         buf.push((len & 0xff) as u8);
     }
 }
+
+// ==================== SNMPv3 Message Processing ====================
+
+/// msgMaxSize advertised in SNMPv3 requests (largest UDP/IPv4 payload).
+const MSG_MAX_SIZE: i64 = 65507;
+
+/// USM security parameters carried in an SNMPv3 message (RFC 3414 2.4).
+#[derive(Debug, Clone, Default)]
+pub struct UsmSecurityParameters {
+    pub engine_id: Vec<u8>,
+    pub engine_boots: u32,
+    pub engine_time: u32,
+    pub user_name: String,
+}
+
+/// A decoded SNMPv3 message, already authenticated and decrypted.
+#[derive(Debug, Clone)]
+pub struct V3Message {
+    pub msg_id: i32,
+    pub flags: u8,
+    pub security: UsmSecurityParameters,
+    pub context_engine_id: Vec<u8>,
+    pub context_name: Vec<u8>,
+    /// The PDU, including its tag and length.
+    pub pdu: Vec<u8>,
+}
+
+/// Encode an SNMPv3 message around a PDU, in the default context.
+///
+/// The security level follows `keys`: the message is encrypted when a
+/// privacy key is present and signed when an authentication key is.
+pub fn encode_v3_message(
+    msg_id: i32,
+    reportable: bool,
+    security: &UsmSecurityParameters,
+    keys: &UsmKeys,
+    salt: u64,
+    context_engine_id: &[u8],
+    pdu: &[u8],
+) -> Result<Vec<u8>> {
+    encode_v3_message_in_context(msg_id, reportable, security, keys, salt, context_engine_id, &[], pdu)
+}
+
+/// Encode an SNMPv3 message around a PDU, addressed to `context_name`.
+#[allow(clippy::too_many_arguments)]
+pub fn encode_v3_message_in_context(
+    msg_id: i32,
+    reportable: bool,
+    security: &UsmSecurityParameters,
+    keys: &UsmKeys,
+    salt: u64,
+    context_engine_id: &[u8],
+    context_name: &[u8],
+    pdu: &[u8],
+) -> Result<Vec<u8>> {
+    let mut flags = keys.flags();
+    if reportable {
+        flags |= usm::FLAG_REPORTABLE;
+    }
+
+    // msgGlobalData
+    let mut global = encode_integer(msg_id as i64);
+    global.extend_from_slice(&encode_integer(MSG_MAX_SIZE));
+    global.extend_from_slice(&encode_tlv(0x04, &[flags]));
+    global.extend_from_slice(&encode_integer(usm::SECURITY_MODEL_USM as i64));
+    let global = encode_tlv(0x30, &global);
+
+    // ScopedPDU: contextEngineID, contextName, PDU
+    let mut scoped = encode_tlv(0x04, context_engine_id);
+    scoped.extend_from_slice(&encode_tlv(0x04, context_name));
+    scoped.extend_from_slice(pdu);
+    let scoped = encode_tlv(0x30, &scoped);
+
+    let (msg_data, priv_params) = match &keys.privacy {
+        Some((proto, key)) => {
+            let (ciphertext, params) = proto.encrypt(
+                key, security.engine_boots, security.engine_time, salt, &scoped,
+            )?;
+            (encode_tlv(0x04, &ciphertext), params)
+        }
+        None => (scoped, Vec::new()),
+    };
+
+    // UsmSecurityParameters, with a zero-filled placeholder for the
+    // authentication parameters that is overwritten once the whole
+    // message has been signed.
+    let auth_len = keys.auth.as_ref().map(|(proto, _)| proto.mac_len()).unwrap_or(0);
+    let mut sec_prefix = encode_tlv(0x04, &security.engine_id);
+    sec_prefix.extend_from_slice(&encode_integer(security.engine_boots as i64));
+    sec_prefix.extend_from_slice(&encode_integer(security.engine_time as i64));
+    sec_prefix.extend_from_slice(&encode_tlv(0x04, security.user_name.as_bytes()));
+    let auth_tlv = encode_tlv(0x04, &vec![0u8; auth_len]);
+
+    let mut sec = sec_prefix.clone();
+    sec.extend_from_slice(&auth_tlv);
+    sec.extend_from_slice(&encode_tlv(0x04, &priv_params));
+    let sec_seq = encode_tlv(0x30, &sec);
+    let sec_octets = encode_tlv(0x04, &sec_seq);
+
+    let version = encode_integer(3);
+    let mut content = version.clone();
+    content.extend_from_slice(&global);
+    content.extend_from_slice(&sec_octets);
+    content.extend_from_slice(&msg_data);
+    let mut message = encode_tlv(0x30, &content);
+
+    if let Some((proto, key)) = &keys.auth {
+        let offset = (message.len() - content.len())
+            + version.len()
+            + global.len()
+            + (sec_octets.len() - sec_seq.len())
+            + (sec_seq.len() - sec.len())
+            + sec_prefix.len()
+            + (auth_tlv.len() - auth_len);
+        let mac = proto.sign(key, &message);
+        message[offset..offset + auth_len].copy_from_slice(&mac);
+    }
+
+    Ok(message)
+}
+
+/// Decode an SNMPv3 message, verifying and decrypting it with `keys`.
+///
+/// Unauthenticated messages (such as discovery Reports) decode without
+/// keys; authenticated or encrypted ones fail unless matching keys are
+/// supplied.
+pub fn decode_v3_message(data: &[u8], keys: Option<&UsmKeys>) -> Result<V3Message> {
+    let V3Header { msg_id, flags, security, auth_offset, auth_len, priv_params, mut pos } = decode_v3_header(data)?;
+    let auth_params = &data[auth_offset..auth_offset + auth_len];
+
+    if flags & usm::FLAG_AUTH != 0 {
+        let (proto, key) = keys
+            .and_then(|k| k.auth.as_ref())
+            .context("SNMPv3: authenticated message but no authentication key")?;
+        if auth_len != proto.mac_len() {
+            anyhow::bail!("SNMPv3: msgAuthenticationParameters is {} bytes, expected {}", auth_len, proto.mac_len());
+        }
+        let mut zeroed = data.to_vec();
+        zeroed[auth_offset..auth_offset + auth_len].fill(0);
+        let expected = proto.sign(key, &zeroed);
+        let diff = expected.iter().zip(auth_params).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if diff != 0 {
+            anyhow::bail!("SNMPv3: message authentication failed (wrong digest)");
+        }
+    } else if flags & usm::FLAG_PRIV != 0 {
+        anyhow::bail!("SNMPv3: invalid msgFlags 0x{:02x} (privacy without authentication)", flags);
+    }
+
+    let scoped = if flags & usm::FLAG_PRIV != 0 {
+        let (proto, key) = keys
+            .and_then(|k| k.privacy.as_ref())
+            .context("SNMPv3: encrypted message but no privacy key")?;
+        let ciphertext = read_expected(data, &mut pos, 0x04, "encryptedPDU")?;
+        proto.decrypt(key, security.engine_boots, security.engine_time, &priv_params, &ciphertext)
+            .context("SNMPv3: decryption failed")?
+    } else {
+        data[pos..].to_vec()
+    };
+
+    // ScopedPDU
+    let mut spos: usize = 0;
+    read_sequence(&scoped, &mut spos, "ScopedPDU")
+        .context("SNMPv3: malformed ScopedPDU (wrong privacy key?)")?;
+    let context_engine_id = read_expected(&scoped, &mut spos, 0x04, "contextEngineID")?;
+    let context_name = read_expected(&scoped, &mut spos, 0x04, "contextName")?;
+    let pdu_start = spos;
+    read_tag(&scoped, &mut spos)?;
+    let pdu_len = read_length(&scoped, &mut spos)?;
+    if spos + pdu_len > scoped.len() {
+        anyhow::bail!("BER: PDU extends past end of ScopedPDU");
+    }
+
+    Ok(V3Message {
+        msg_id,
+        flags,
+        security,
+        context_engine_id,
+        context_name,
+        pdu: scoped[pdu_start..spos + pdu_len].to_vec(),
+    })
+}
+
+/// The msgFlags and security parameters of an SNMPv3 message, read
+/// without authenticating it (to pick the user's keys).
+pub fn peek_v3_security(data: &[u8]) -> Result<(u8, UsmSecurityParameters)> {
+    let header = decode_v3_header(data)?;
+    Ok((header.flags, header.security))
+}
+
+/// The unauthenticated part of an SNMPv3 message, up to msgData.
+struct V3Header {
+    msg_id: i32,
+    flags: u8,
+    security: UsmSecurityParameters,
+    auth_offset: usize,
+    auth_len: usize,
+    priv_params: Vec<u8>,
+    /// Offset of msgData
+    pos: usize,
+}
+
+fn decode_v3_header(data: &[u8]) -> Result<V3Header> {
+    let mut pos: usize = 0;
+
+    read_sequence(data, &mut pos, "message")?;
+    let version = decode_integer(&read_expected(data, &mut pos, 0x02, "version")?)?;
+    if version != 3 {
+        anyhow::bail!("SNMP: expected version 3 message, got {}", version);
+    }
+
+    // msgGlobalData
+    read_sequence(data, &mut pos, "msgGlobalData")?;
+    let msg_id = decode_integer(&read_expected(data, &mut pos, 0x02, "msgID")?)? as i32;
+    skip_tlv(data, &mut pos)?; // msgMaxSize
+    let flags = read_expected(data, &mut pos, 0x04, "msgFlags")?
+        .first()
+        .copied()
+        .context("SNMPv3: empty msgFlags")?;
+    let model = decode_integer(&read_expected(data, &mut pos, 0x02, "msgSecurityModel")?)?;
+    if model != usm::SECURITY_MODEL_USM as i64 {
+        anyhow::bail!("SNMPv3: unsupported security model {}", model);
+    }
+
+    // msgSecurityParameters: an OCTET STRING wrapping the USM SEQUENCE
+    let tag = read_tag(data, &mut pos)?;
+    if tag != 0x04 {
+        anyhow::bail!("SNMP: expected msgSecurityParameters (0x04), got 0x{:02x}", tag);
+    }
+    read_length(data, &mut pos)?;
+    read_sequence(data, &mut pos, "UsmSecurityParameters")?;
+    let engine_id = read_expected(data, &mut pos, 0x04, "msgAuthoritativeEngineID")?;
+    let engine_boots = decode_integer(&read_expected(data, &mut pos, 0x02, "msgAuthoritativeEngineBoots")?)?;
+    let engine_time = decode_integer(&read_expected(data, &mut pos, 0x02, "msgAuthoritativeEngineTime")?)?;
+    let user_name = read_expected(data, &mut pos, 0x04, "msgUserName")?;
+
+    let tag = read_tag(data, &mut pos)?;
+    if tag != 0x04 {
+        anyhow::bail!("SNMP: expected msgAuthenticationParameters (0x04), got 0x{:02x}", tag);
+    }
+    let auth_len = read_length(data, &mut pos)?;
+    let auth_offset = pos;
+    if auth_offset + auth_len > data.len() {
+        anyhow::bail!("BER: value extends past end of data");
+    }
+    pos += auth_len;
+    let priv_params = read_expected(data, &mut pos, 0x04, "msgPrivacyParameters")?;
+
+    let security = UsmSecurityParameters {
+        engine_id,
+        engine_boots: u32::try_from(engine_boots).context("SNMPv3: invalid msgAuthoritativeEngineBoots")?,
+        engine_time: u32::try_from(engine_time).context("SNMPv3: invalid msgAuthoritativeEngineTime")?,
+        user_name: String::from_utf8_lossy(&user_name).to_string(),
+    };
+
+    Ok(V3Header { msg_id, flags, security, auth_offset, auth_len, priv_params, pos })
+}
+
+/// Build the USM user for SNMPv3 credentials.
+///
+/// Protocols default to MD5 and DES, as in Net-SNMP.
+pub(crate) fn usm_user_from_credentials(credentials: &SnmpCredentials) -> Result<UsmUser> {
+    let name = credentials.username.clone()
+        .filter(|u| !u.is_empty())
+        .context("SNMPv3 requires a username")?;
+
+    let auth = match &credentials.auth_password {
+        Some(pass) => {
+            let proto = credentials.auth_protocol.as_deref().unwrap_or("MD5").parse()?;
+            Some((proto, pass.clone()))
+        }
+        None => None,
+    };
+    let privacy = match &credentials.priv_password {
+        Some(pass) => {
+            let proto = credentials.priv_protocol.as_deref().unwrap_or("DES").parse()?;
+            Some((proto, pass.clone()))
+        }
+        None => None,
+    };
+
+    if privacy.is_some() && auth.is_none() {
+        anyhow::bail!("SNMPv3 privacy requires an authentication password");
+    }
+    if let Some((_, pass)) = &auth {
+        if pass.len() < 8 {
+            anyhow::bail!("SNMPv3 authentication password must be at least 8 characters");
+        }
+    }
+    if let Some((_, pass)) = &privacy {
+        if pass.len() < 8 {
+            anyhow::bail!("SNMPv3 privacy password must be at least 8 characters");
+        }
+    }
+
+    Ok(UsmUser { name, auth, privacy })
+}
+
+/// First varbind OID of a Report PDU (the usmStats counter that fired).
+fn usm_report_oid(pdu: &[u8]) -> Result<Vec<u32>> {
+    parse_pdu_varbinds(pdu, PDU_REPORT, "Report")?
+        .into_iter()
+        .next()
+        .map(|(oid, _)| oid)
+        .ok_or_else(|| anyhow::anyhow!("SNMPv3: empty Report PDU"))
+}
+
+fn usm_report_name(oid: &[u32]) -> String {
+    use super::oids::*;
+    let reports: [(&[u32], &str); 7] = [
+        (&SNMP_UNKNOWN_CONTEXTS, "unknownContext"),
+        (&USM_STATS_UNSUPPORTED_SEC_LEVELS, "unsupportedSecurityLevel"),
+        (&USM_STATS_NOT_IN_TIME_WINDOWS, "notInTimeWindow"),
+        (&USM_STATS_UNKNOWN_USER_NAMES, "unknownUserName"),
+        (&USM_STATS_UNKNOWN_ENGINE_IDS, "unknownEngineID"),
+        (&USM_STATS_WRONG_DIGESTS, "wrongDigest"),
+        (&USM_STATS_DECRYPTION_ERRORS, "decryptionError"),
+    ];
+    reports.iter()
+        .find(|(prefix, _)| oid.starts_with(prefix))
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| format!("report {}", oid_to_string(oid)))
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
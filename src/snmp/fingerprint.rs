@@ -0,0 +1,196 @@
+//! Device fingerprinting.
+//!
+//! Works out a device's vendor, operating system, OS version and device
+//! class from sysObjectID, sysDescr and sysServices, in the manner of
+//! SNMP::Info's `device_type`. The vendor comes from the `enterprise`
+//! table, keyed on the sysObjectID enterprise arc; the OS is found by
+//! matching sysDescr against a per-vendor rule set.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::client::SystemInfo;
+use super::oids::ENTERPRISES;
+use crate::models::enterprise::Enterprise;
+
+/// What fingerprinting learned about a device.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fingerprint {
+    pub vendor: Option<String>,
+    pub os: Option<String>,
+    pub os_ver: Option<String>,
+    pub snmp_class: Option<String>,
+}
+
+/// How a rule picks the device class.
+enum Class {
+    Fixed(&'static str),
+    /// Platforms that ship as both switches and routers
+    ByLayer { switch: &'static str, router: &'static str },
+    /// Generic classes from sysServices
+    Layers,
+}
+
+struct Rule {
+    enterprise: u32,
+    /// Must match sysDescr for the rule to apply; None matches anything
+    descr: Option<&'static str>,
+    os: &'static str,
+    /// First capture group is the OS version
+    version: Option<&'static str>,
+    class: Class,
+}
+
+/// Rules are tried in order, so more specific sysDescr patterns for an
+/// enterprise come before the catch-all ones.
+const RULES: &[Rule] = &[
+    // Cisco
+    Rule { enterprise: 9, descr: Some(r"NX-OS"), os: "nx-os",
+        version: Some(r"Version ([^\s,]+)"),
+        class: Class::Fixed("SNMP::Info::Layer3::Nexus") },
+    Rule { enterprise: 9, descr: Some(r"IOS[ -]?XR"), os: "ios-xr",
+        version: Some(r"Version ([^\s,\[]+)"),
+        class: Class::Fixed("SNMP::Info::Layer3::Cisco") },
+    // 16.x and later images only say so in the image name: CAT9K_IOSXE,
+    // CAT3K_CAA (3650/3850) and X86_64_LINUX_IOSD (ISR, ASR and CSR)
+    Rule { enterprise: 9, descr: Some(r"IOS[ -]?XE|IOSXE|CAT3K_CAA|LINUX_IOSD"), os: "ios-xe",
+        version: Some(r"Version ([^\s,]+)"),
+        class: Class::ByLayer { switch: "SNMP::Info::Layer3::CiscoSwitch", router: "SNMP::Info::Layer3::Cisco" } },
+    Rule { enterprise: 9, descr: Some(r"Adaptive Security Appliance"), os: "asa",
+        version: Some(r"Version ([^\s,]+)"),
+        class: Class::Fixed("SNMP::Info::Layer3::CiscoASA") },
+    Rule { enterprise: 9, descr: Some(r"Catalyst Operating System"), os: "catalyst",
+        version: Some(r"Version ([^\s,]+)"),
+        class: Class::Fixed("SNMP::Info::Layer2::Catalyst") },
+    Rule { enterprise: 9, descr: Some(r"Cisco (IOS|Internetwork Operating System)"), os: "ios",
+        version: Some(r"Version ([^\s,]+)"),
+        class: Class::ByLayer { switch: "SNMP::Info::Layer3::CiscoSwitch", router: "SNMP::Info::Layer3::Cisco" } },
+    // Juniper
+    Rule { enterprise: 2636, descr: None, os: "junos",
+        version: Some(r"JUNOS ([^\s,\]]+)"),
+        class: Class::Fixed("SNMP::Info::Layer3::Juniper") },
+    // Arista
+    Rule { enterprise: 30065, descr: None, os: "eos",
+        version: Some(r"EOS version (\S+)"),
+        class: Class::Fixed("SNMP::Info::Layer3::Arista") },
+    // HP, H3C and HPE: Comware, then ProCurve / ArubaOS-Switch
+    Rule { enterprise: 11, descr: Some(r"Comware"), os: "comware",
+        version: Some(r"Version (\d[\d.]*(?:, Release \w+)?)"),
+        class: Class::Fixed("SNMP::Info::Layer3::H3C") },
+    Rule { enterprise: 25506, descr: None, os: "comware",
+        version: Some(r"Version (\d[\d.]*(?:, Release \w+)?)"),
+        class: Class::Fixed("SNMP::Info::Layer3::H3C") },
+    Rule { enterprise: 11, descr: Some(r"revision [A-Z]{1,2}\."), os: "procurve",
+        version: Some(r"revision ([A-Z]{1,2}\.[\d.]+)"),
+        class: Class::Fixed("SNMP::Info::Layer2::HP") },
+    // Aruba: controllers and AOS-CX
+    Rule { enterprise: 14823, descr: None, os: "arubaos",
+        version: Some(r"Version (\d[\d.]*)"),
+        class: Class::Fixed("SNMP::Info::Layer3::Aruba") },
+    Rule { enterprise: 47196, descr: None, os: "aos-cx",
+        version: Some(r"\b([A-Z]{2}\.\d{2}\.\d{2}\.\d{4}\w*)"),
+        class: Class::Fixed("SNMP::Info::Layer3::ArubaCX") },
+    // Huawei
+    Rule { enterprise: 2011, descr: Some(r"VRP"), os: "vrp",
+        version: Some(r"\b(V\d{3}R\d{3}\w*)"),
+        class: Class::Fixed("SNMP::Info::Layer3::Huawei") },
+    // Extreme
+    Rule { enterprise: 1916, descr: Some(r"ExtremeXOS"), os: "xos",
+        version: Some(r"version (\S+)"),
+        class: Class::Fixed("SNMP::Info::Layer3::Extreme") },
+    // Firewalls and routers
+    Rule { enterprise: 12356, descr: None, os: "fortios",
+        version: Some(r"FortiOS v?(\d+\.\d+\.\d+)"),
+        class: Class::Fixed("SNMP::Info::Layer3::Fortinet") },
+    Rule { enterprise: 25461, descr: None, os: "panos",
+        version: None,
+        class: Class::Fixed("SNMP::Info::Layer3::PaloAlto") },
+    Rule { enterprise: 14988, descr: None, os: "routeros",
+        version: Some(r"RouterOS (\d[\d.]*)"),
+        class: Class::Fixed("SNMP::Info::Layer3::Mikrotik") },
+    // Net-SNMP hosts
+    Rule { enterprise: 8072, descr: Some(r"^Linux "), os: "linux",
+        version: Some(r"^Linux \S+ (\S+)"),
+        class: Class::Layers },
+];
+
+struct Compiled {
+    rule: &'static Rule,
+    descr: Option<Regex>,
+    version: Option<Regex>,
+}
+
+/// The rules with their patterns compiled once.
+static COMPILED: LazyLock<Vec<Compiled>> = LazyLock::new(|| {
+    let compile = |pattern: Option<&str>| pattern.map(|p| Regex::new(p).expect("fingerprint rule pattern"));
+    RULES.iter()
+        .map(|rule| Compiled { rule, descr: compile(rule.descr), version: compile(rule.version) })
+        .collect()
+});
+
+/// The enterprise number from a sysObjectID such as `1.3.6.1.4.1.9.1.516`.
+pub fn enterprise_number(sys_object_id: &str) -> Option<u32> {
+    let arcs: Vec<u32> = sys_object_id.trim_start_matches('.')
+        .split('.')
+        .map(|arc| arc.parse().ok())
+        .collect::<Option<_>>()?;
+    if arcs.starts_with(&ENTERPRISES) {
+        arcs.get(ENTERPRISES.len()).copied()
+    } else {
+        None
+    }
+}
+
+/// Fingerprint a device from its system group.
+///
+/// `enterprise` is the `enterprise` table row for the sysObjectID arc, if
+/// there is one, and supplies the vendor name.
+pub fn fingerprint(sys_info: &SystemInfo, enterprise: Option<&Enterprise>) -> Fingerprint {
+    let descr = sys_info.description.as_deref().unwrap_or("");
+    let number = sys_info.object_id.as_deref().and_then(enterprise_number);
+
+    let mut fp = Fingerprint {
+        vendor: enterprise.and_then(|e| e.name.clone()),
+        snmp_class: Some(layer_class(sys_info.services).to_string()),
+        ..Default::default()
+    };
+
+    let Some(number) = number else { return fp };
+    let matched = COMPILED.iter().find(|c| {
+        c.rule.enterprise == number && c.descr.as_ref().is_none_or(|re| re.is_match(descr))
+    });
+    if let Some(Compiled { rule, version, .. }) = matched {
+        fp.os = Some(rule.os.to_string());
+        fp.os_ver = version.as_ref()
+            .and_then(|re| re.captures(descr))
+            .and_then(|caps| caps.get(1))
+            .map(|m| m.as_str().to_string());
+        fp.snmp_class = Some(match rule.class {
+            Class::Fixed(class) => class,
+            Class::ByLayer { switch, router } => {
+                if has_layer(sys_info.services, 2) { switch } else { router }
+            }
+            Class::Layers => layer_class(sys_info.services),
+        }.to_string());
+    }
+
+    fp
+}
+
+fn has_layer(services: Option<i64>, layer: u32) -> bool {
+    services.is_some_and(|svc| svc & (1 << (layer - 1)) != 0)
+}
+
+/// SNMP::Info's generic classes, for devices no rule knows about.
+fn layer_class(services: Option<i64>) -> &'static str {
+    if has_layer(services, 3) {
+        "SNMP::Info::Layer3"
+    } else if has_layer(services, 2) {
+        "SNMP::Info::Layer2"
+    } else if has_layer(services, 1) {
+        "SNMP::Info::Layer1"
+    } else {
+        "SNMP::Info"
+    }
+}
@@ -1,11 +1,19 @@
 //! SNMP client for device polling.
 //!
-//! Supports SNMP v1, v2c, and v3. Wraps the `snmp` crate with
-//! Netdisco-specific functionality for device discovery, MAC table
-//! collection, and ARP table collection.
+//! Supports SNMP v1, v2c, and v3 (USM). Provides Netdisco-specific
+//! functionality for device discovery, MAC table collection, and ARP
+//! table collection over a raw UDP/BER implementation.
 
+pub mod auth;
 pub mod client;
+pub mod fingerprint;
 pub mod oids;
+pub mod simulator;
+pub mod transport;
+pub mod trap;
+pub mod usm;
+pub mod value;
 
 pub use client::*;
 pub use oids::*;
+pub use value::SnmpValue;
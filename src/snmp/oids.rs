@@ -9,6 +9,9 @@ pub const SYS_NAME: [u32; 8] = [1, 3, 6, 1, 2, 1, 1, 5];
 pub const SYS_LOCATION: [u32; 8] = [1, 3, 6, 1, 2, 1, 1, 6];
 pub const SYS_SERVICES: [u32; 8] = [1, 3, 6, 1, 2, 1, 1, 7];
 
+// SNMPv2-SMI - private enterprise arc, the root of most sysObjectIDs
+pub const ENTERPRISES: [u32; 6] = [1, 3, 6, 1, 4, 1];
+
 // Interfaces MIB (IF-MIB)
 pub const IF_NUMBER: [u32; 8] = [1, 3, 6, 1, 2, 1, 2, 1];
 pub const IF_INDEX: [u32; 10] = [1, 3, 6, 1, 2, 1, 2, 2, 1, 1];
@@ -22,50 +25,179 @@ pub const IF_OPER_STATUS: [u32; 10] = [1, 3, 6, 1, 2, 1, 2, 2, 1, 8];
 pub const IF_LAST_CHANGE: [u32; 10] = [1, 3, 6, 1, 2, 1, 2, 2, 1, 9];
 
 // ifXTable (IF-MIB)
-pub const IF_NAME: [u32; 10] = [1, 3, 6, 1, 2, 1, 31, 1, 1, 1];
+pub const IF_NAME: [u32; 11] = [1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 1];
 pub const IF_HIGH_SPEED: [u32; 11] = [1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 15];
 pub const IF_ALIAS: [u32; 11] = [1, 3, 6, 1, 2, 1, 31, 1, 1, 1, 18];
 
+// IF-MIB - ifStackTable, indexed by higher and lower layer ifIndex
+pub const IF_STACK_STATUS: [u32; 11] = [1, 3, 6, 1, 2, 1, 31, 1, 2, 1, 3];
+
+// IEEE8023-LAG-MIB - aggregator a port is attached to, by port ifIndex
+pub const DOT3AD_AGG_PORT_ATTACHED_AGG_ID: [u32; 11] = [1, 2, 840, 10006, 300, 43, 1, 2, 1, 1, 13];
+
+// CISCO-PAGP-MIB - EtherChannel a port belongs to, by port ifIndex
+pub const PAGP_GROUP_IF_INDEX: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 98, 1, 1, 1, 1, 8];
+
+// Bridge MIB (BRIDGE-MIB) - bridge port to ifIndex
+pub const DOT1D_BASE_PORT_IF_INDEX: [u32; 11] = [1, 3, 6, 1, 2, 1, 17, 1, 4, 1, 2];
+
 // Bridge MIB (BRIDGE-MIB) - MAC address table
-pub const DOT1D_TP_FDB_ADDRESS: [u32; 10] = [1, 3, 6, 1, 2, 1, 17, 4, 3, 1];
+pub const DOT1D_TP_FDB_ADDRESS: [u32; 11] = [1, 3, 6, 1, 2, 1, 17, 4, 3, 1, 1];
 pub const DOT1D_TP_FDB_PORT: [u32; 11] = [1, 3, 6, 1, 2, 1, 17, 4, 3, 1, 2];
 pub const DOT1D_TP_FDB_STATUS: [u32; 11] = [1, 3, 6, 1, 2, 1, 17, 4, 3, 1, 3];
 
-// Q-BRIDGE-MIB - VLAN-aware MAC table
-pub const DOT1Q_TP_FDB_PORT: [u32; 11] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 2, 2];
+// Q-BRIDGE-MIB - VLAN-aware MAC table, indexed by FID and MAC
+pub const DOT1Q_TP_FDB_PORT: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 2, 2, 1, 2];
+pub const DOT1Q_TP_FDB_STATUS: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 2, 2, 1, 3];
+pub const DOT1Q_PVID: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 5, 1, 1];
+
+// IP MIB - ipAddrTable (IPv4 addresses, indexed by address)
+pub const IP_AD_ENT_IF_INDEX: [u32; 10] = [1, 3, 6, 1, 2, 1, 4, 20, 1, 2];
+pub const IP_AD_ENT_NET_MASK: [u32; 10] = [1, 3, 6, 1, 2, 1, 4, 20, 1, 3];
+
+// IP MIB - ipAddressTable (IPv4 and IPv6, indexed by type and address)
+pub const IP_ADDRESS_IF_INDEX: [u32; 10] = [1, 3, 6, 1, 2, 1, 4, 34, 1, 3];
+pub const IP_ADDRESS_TYPE: [u32; 10] = [1, 3, 6, 1, 2, 1, 4, 34, 1, 4];
+pub const IP_ADDRESS_PREFIX: [u32; 10] = [1, 3, 6, 1, 2, 1, 4, 34, 1, 5];
+/// ipAddressPrefixTable, which ipAddressPrefix points into
+pub const IP_ADDRESS_PREFIX_TABLE: [u32; 8] = [1, 3, 6, 1, 2, 1, 4, 32];
 
 // IP MIB - ARP table
 pub const IP_NET_TO_MEDIA_PHYS: [u32; 10] = [1, 3, 6, 1, 2, 1, 4, 22, 1, 2];
 pub const IP_NET_TO_MEDIA_TYPE: [u32; 10] = [1, 3, 6, 1, 2, 1, 4, 22, 1, 4];
 
 // Entity MIB (ENTITY-MIB) - modules/inventory
-pub const ENT_PHYSICAL_DESCR: [u32; 10] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1];
-pub const ENT_PHYSICAL_CLASS: [u32; 11] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 5];
-pub const ENT_PHYSICAL_NAME: [u32; 11] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 7];
-pub const ENT_PHYSICAL_HW_REV: [u32; 11] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 8];
-pub const ENT_PHYSICAL_FW_REV: [u32; 11] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 9];
-pub const ENT_PHYSICAL_SW_REV: [u32; 11] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 10];
-pub const ENT_PHYSICAL_SERIAL: [u32; 11] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 11];
-pub const ENT_PHYSICAL_MODEL: [u32; 11] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 13];
-
-// LLDP MIB (LLDP-MIB) - neighbor discovery
-pub const LLDP_REM_SYS_NAME: [u32; 10] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 9];
-pub const LLDP_REM_SYS_DESC: [u32; 10] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 10];
-pub const LLDP_REM_PORT_ID: [u32; 10] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 7];
-pub const LLDP_REM_MAN_ADDR: [u32; 10] = [1, 0, 8802, 1, 1, 2, 1, 4, 2, 1];
-
-// CDP MIB (CISCO-CDP-MIB) - Cisco neighbor discovery
-pub const CDP_CACHE_DEVICE_ID: [u32; 11] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2];
-pub const CDP_CACHE_DEVICE_PORT: [u32; 12] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 1];
-pub const CDP_CACHE_ADDRESS: [u32; 12] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 4];
-pub const CDP_CACHE_PLATFORM: [u32; 12] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 8];
-
-// VLAN MIB
-pub const VTP_VLAN_STATE: [u32; 12] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 3, 1];
-pub const VTP_VLAN_NAME: [u32; 13] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 3, 1, 4];
-pub const DOT1Q_VLAN_STATIC_NAME: [u32; 11] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 3];
-
-// PoE MIB (POWER-ETHERNET-MIB)
+pub const ENT_PHYSICAL_DESCR: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 2];
+pub const ENT_PHYSICAL_VENDOR_TYPE: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 3];
+pub const ENT_PHYSICAL_CONTAINED_IN: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 4];
+pub const ENT_PHYSICAL_CLASS: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 5];
+pub const ENT_PHYSICAL_PARENT_REL_POS: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 6];
+pub const ENT_PHYSICAL_NAME: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 7];
+pub const ENT_PHYSICAL_HW_REV: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 8];
+pub const ENT_PHYSICAL_FW_REV: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 9];
+pub const ENT_PHYSICAL_SW_REV: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 10];
+pub const ENT_PHYSICAL_SERIAL: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 11];
+pub const ENT_PHYSICAL_MODEL: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 13];
+pub const ENT_PHYSICAL_IS_FRU: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 1, 1, 1, 16];
+
+// Entity MIB (ENTITY-MIB) - entAliasMappingIdentifier, indexed by
+// entPhysicalIndex and logical entity; for ports, ifIndex.<n>
+pub const ENT_ALIAS_MAPPING_IDENTIFIER: [u32; 12] = [1, 3, 6, 1, 2, 1, 47, 1, 3, 2, 1, 2];
+
+// LLDP MIB (LLDP-MIB) - local ports, lldpLocPortTable
+pub const LLDP_LOC_PORT_ID_SUBTYPE: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 3, 7, 1, 2];
+pub const LLDP_LOC_PORT_ID: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 3, 7, 1, 3];
+pub const LLDP_LOC_PORT_DESC: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 3, 7, 1, 4];
+
+// LLDP MIB (LLDP-MIB) - neighbors, lldpRemTable indexed by
+// (lldpRemTimeMark, lldpRemLocalPortNum, lldpRemIndex)
+pub const LLDP_REM_CHASSIS_ID_SUBTYPE: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 4];
+pub const LLDP_REM_CHASSIS_ID: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 5];
+pub const LLDP_REM_PORT_ID_SUBTYPE: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 6];
+pub const LLDP_REM_PORT_ID: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 7];
+pub const LLDP_REM_PORT_DESC: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 8];
+pub const LLDP_REM_SYS_NAME: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 9];
+pub const LLDP_REM_SYS_DESC: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 10];
+pub const LLDP_REM_SYS_CAP_ENABLED: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 12];
+// lldpRemManAddrTable; the address is in the index after the lldpRemTable
+// index, as (subtype, length, octets...)
+pub const LLDP_REM_MAN_ADDR_IF_SUBTYPE: [u32; 11] = [1, 0, 8802, 1, 1, 2, 1, 4, 2, 1, 3];
+
+// CDP MIB (CISCO-CDP-MIB) - cdpCacheTable indexed by (ifIndex, device)
+pub const CDP_CACHE_ADDRESS_TYPE: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 1, 1, 3];
+pub const CDP_CACHE_ADDRESS: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 1, 1, 4];
+pub const CDP_CACHE_DEVICE_ID: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 1, 1, 6];
+pub const CDP_CACHE_DEVICE_PORT: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 1, 1, 7];
+pub const CDP_CACHE_PLATFORM: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 1, 1, 8];
+pub const CDP_CACHE_CAPABILITIES: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 23, 1, 2, 1, 1, 9];
+
+// CISCO-VTP-MIB - VTP domains and VLANs, indexed by domain (and VLAN)
+pub const MANAGEMENT_DOMAIN_NAME: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 2, 1, 1, 2];
+pub const VTP_VLAN_STATE: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 3, 1, 1, 2];
+pub const VTP_VLAN_TYPE: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 3, 1, 1, 3];
+pub const VTP_VLAN_NAME: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 3, 1, 1, 4];
+
+// CISCO-VTP-MIB - trunk ports, indexed by ifIndex
+pub const VLAN_TRUNK_PORT_VLANS_ENABLED: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 6, 1, 1, 4];
+pub const VLAN_TRUNK_PORT_NATIVE_VLAN: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 6, 1, 1, 5];
+pub const VLAN_TRUNK_PORT_DYNAMIC_STATUS: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 6, 1, 1, 14];
+pub const VLAN_TRUNK_PORT_VLANS_ENABLED_2K: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 6, 1, 1, 17];
+pub const VLAN_TRUNK_PORT_VLANS_ENABLED_3K: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 6, 1, 1, 18];
+pub const VLAN_TRUNK_PORT_VLANS_ENABLED_4K: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 46, 1, 6, 1, 1, 19];
+
+// CISCO-VLAN-MEMBERSHIP-MIB - access port VLAN, indexed by ifIndex
+pub const VM_VLAN: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 68, 1, 2, 2, 1, 2];
+
+// Q-BRIDGE-MIB - VLANs; port lists are bitmaps of bridge ports
+pub const DOT1Q_VLAN_FDB_ID: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 2, 1, 3];
+pub const DOT1Q_VLAN_CURRENT_EGRESS_PORTS: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 2, 1, 4];
+pub const DOT1Q_VLAN_CURRENT_UNTAGGED_PORTS: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 2, 1, 5];
+pub const DOT1Q_VLAN_STATIC_NAME: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 3, 1, 1];
+pub const DOT1Q_VLAN_STATIC_EGRESS_PORTS: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 3, 1, 2];
+pub const DOT1Q_VLAN_STATIC_UNTAGGED_PORTS: [u32; 13] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 4, 3, 1, 4];
+
+// PoE MIB (POWER-ETHERNET-MIB) - PSE ports, indexed by group and port
 pub const PSE_PORT_ADMIN: [u32; 11] = [1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 3];
 pub const PSE_PORT_STATUS: [u32; 11] = [1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 6];
-pub const PSE_PORT_POWER: [u32; 11] = [1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 7];
+pub const PSE_PORT_PRIORITY: [u32; 11] = [1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 7];
+pub const PSE_PORT_CLASS: [u32; 11] = [1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 10];
+
+// PoE MIB (POWER-ETHERNET-MIB) - main PSE per group, in watts
+pub const PETH_MAIN_PSE_POWER: [u32; 12] = [1, 3, 6, 1, 2, 1, 105, 1, 3, 1, 1, 2];
+pub const PETH_MAIN_PSE_OPER_STATUS: [u32; 12] = [1, 3, 6, 1, 2, 1, 105, 1, 3, 1, 1, 3];
+pub const PETH_MAIN_PSE_CONSUMPTION: [u32; 12] = [1, 3, 6, 1, 2, 1, 105, 1, 3, 1, 1, 4];
+
+// CISCO-POWER-ETHERNET-EXT-MIB - per-port draw in milliwatts and the
+// port's entPhysicalIndex, indexed as pethPsePortTable
+pub const CPE_EXT_PSE_PORT_PWR_CONSUMPTION: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 402, 1, 2, 1, 1, 9];
+pub const CPE_EXT_PSE_PORT_ENT_PHY_INDEX: [u32; 14] = [1, 3, 6, 1, 4, 1, 9, 9, 402, 1, 2, 1, 1, 11];
+
+// BRIDGE-MIB - spanning tree scalars (append .0) and dot1dStpPortState,
+// indexed by bridge port; dot1dStpVersion is from RSTP-MIB
+pub const DOT1D_BASE_BRIDGE_ADDRESS: [u32; 9] = [1, 3, 6, 1, 2, 1, 17, 1, 1];
+pub const DOT1D_STP_PRIORITY: [u32; 9] = [1, 3, 6, 1, 2, 1, 17, 2, 2];
+pub const DOT1D_STP_DESIGNATED_ROOT: [u32; 9] = [1, 3, 6, 1, 2, 1, 17, 2, 5];
+pub const DOT1D_STP_ROOT_COST: [u32; 9] = [1, 3, 6, 1, 2, 1, 17, 2, 6];
+pub const DOT1D_STP_ROOT_PORT: [u32; 9] = [1, 3, 6, 1, 2, 1, 17, 2, 7];
+pub const DOT1D_STP_PORT_STATE: [u32; 11] = [1, 3, 6, 1, 2, 1, 17, 2, 15, 1, 3];
+pub const DOT1D_STP_VERSION: [u32; 9] = [1, 3, 6, 1, 2, 1, 17, 2, 16];
+
+// IEEE8021-MSTP-MIB - MST instances, indexed by component and MSTID; the
+// Vids columns are 1024-VLAN bitmaps
+pub const IEEE8021_MSTP_BRIDGE_ID: [u32; 12] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 2, 1, 3];
+pub const IEEE8021_MSTP_DESIGNATED_ROOT: [u32; 12] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 2, 1, 7];
+pub const IEEE8021_MSTP_ROOT_PATH_COST: [u32; 12] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 2, 1, 8];
+pub const IEEE8021_MSTP_ROOT_PORT: [u32; 12] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 2, 1, 9];
+pub const IEEE8021_MSTP_VIDS0: [u32; 12] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 2, 1, 11];
+pub const IEEE8021_MSTP_VIDS1: [u32; 12] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 2, 1, 12];
+pub const IEEE8021_MSTP_VIDS2: [u32; 12] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 2, 1, 13];
+pub const IEEE8021_MSTP_VIDS3: [u32; 12] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 2, 1, 14];
+
+// IEEE8021-MSTP-MIB - port state per MST instance, indexed by component,
+// MSTID and bridge port
+pub const IEEE8021_MSTP_PORT_STATE: [u32; 12] = [1, 3, 111, 2, 802, 1, 1, 6, 1, 4, 1, 5];
+
+// CISCO-STP-EXTENSIONS-MIB - stpxSpanningTreeType scalar (append .0)
+pub const STPX_SPANNING_TREE_TYPE: [u32; 12] = [1, 3, 6, 1, 4, 1, 9, 9, 82, 1, 6, 1];
+
+// SNMPv2-MIB - notifications
+pub const SNMP_TRAP_OID: [u32; 10] = [1, 3, 6, 1, 6, 3, 1, 1, 4, 1];
+pub const SNMP_TRAPS: [u32; 9] = [1, 3, 6, 1, 6, 3, 1, 1, 5];
+pub const COLD_START: [u32; 10] = [1, 3, 6, 1, 6, 3, 1, 1, 5, 1];
+pub const LINK_DOWN: [u32; 10] = [1, 3, 6, 1, 6, 3, 1, 1, 5, 3];
+pub const LINK_UP: [u32; 10] = [1, 3, 6, 1, 6, 3, 1, 1, 5, 4];
+
+// CISCO-MAC-NOTIFICATION-MIB
+pub const CMN_MAC_CHANGED_NOTIFICATION: [u32; 12] = [1, 3, 6, 1, 4, 1, 9, 9, 215, 2, 0, 1];
+pub const CMN_MAC_MOVE_NOTIFICATION: [u32; 12] = [1, 3, 6, 1, 4, 1, 9, 9, 215, 2, 0, 2];
+
+// SNMP-MPD-MIB / SNMP-TARGET-MIB counters returned in Report PDUs
+pub const SNMP_UNKNOWN_CONTEXTS: [u32; 9] = [1, 3, 6, 1, 6, 3, 12, 1, 5];
+
+// SNMP-USER-BASED-SM-MIB - usmStats counters returned in Report PDUs
+pub const USM_STATS_UNSUPPORTED_SEC_LEVELS: [u32; 10] = [1, 3, 6, 1, 6, 3, 15, 1, 1, 1];
+pub const USM_STATS_NOT_IN_TIME_WINDOWS: [u32; 10] = [1, 3, 6, 1, 6, 3, 15, 1, 1, 2];
+pub const USM_STATS_UNKNOWN_USER_NAMES: [u32; 10] = [1, 3, 6, 1, 6, 3, 15, 1, 1, 3];
+pub const USM_STATS_UNKNOWN_ENGINE_IDS: [u32; 10] = [1, 3, 6, 1, 6, 3, 15, 1, 1, 4];
+pub const USM_STATS_WRONG_DIGESTS: [u32; 10] = [1, 3, 6, 1, 6, 3, 15, 1, 1, 5];
+pub const USM_STATS_DECRYPTION_ERRORS: [u32; 10] = [1, 3, 6, 1, 6, 3, 15, 1, 1, 6];
@@ -0,0 +1,441 @@
+//! Offline SNMP agent that replays a recorded walk.
+//!
+//! A [`Recording`] is loaded from `snmpwalk -On` output or an snmprec
+//! file (as used by snmpsim). A [`Simulator`] answers v1/v2c GET,
+//! GETNEXT and GETBULK from it over UDP, so the pollers can run against
+//! a capture instead of a live device. `netdisco-do --replay` points a
+//! device's address at a simulator through `snmp_replay` in the config.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+use super::client::*;
+use super::value::SnmpValue;
+
+/// Largest response a GETBULK is trimmed to.
+const MAX_RESPONSE: usize = 65000;
+
+type Varbinds = Vec<(Vec<u32>, SnmpValue)>;
+
+/// A recorded MIB: every object instance and its value, in OID order.
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    mib: BTreeMap<Vec<u32>, SnmpValue>,
+}
+
+impl Recording {
+    /// Load a recording, detecting the format from its content.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read SNMP recording {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid SNMP recording {}", path.display()))
+    }
+
+    /// Parse `snmpwalk -On` output or snmprec, whichever `text` looks like.
+    pub fn parse(text: &str) -> Result<Self> {
+        let first = text.lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'));
+        match first {
+            Some(line) if !line.contains(" = ") && line.contains('|') => Self::parse_snmprec(text),
+            _ => Self::parse_snmpwalk(text),
+        }
+    }
+
+    /// Parse `snmpwalk -On` output: `.1.3.6.1.2.1.1.5.0 = STRING: "name"`.
+    ///
+    /// Quoted strings and Hex-STRINGs may run over several lines. Lines
+    /// reporting noSuchObject and friends are skipped.
+    pub fn parse_snmpwalk(text: &str) -> Result<Self> {
+        let mut records: Vec<(usize, String)> = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            if starts_walk_record(line) {
+                records.push((n + 1, line.to_string()));
+            } else if let Some((_, record)) = records.last_mut() {
+                record.push('\n');
+                record.push_str(line);
+            } else if !line.trim().is_empty() {
+                anyhow::bail!("line {}: expected 'OID = TYPE: value'", n + 1);
+            }
+        }
+
+        let mut recording = Self::default();
+        for (line, record) in records {
+            let (oid, value) = record.split_once(" = ")
+                .with_context(|| format!("line {}: expected 'OID = TYPE: value'", line))?;
+            let oid = parse_oid(oid.trim())
+                .with_context(|| format!("line {}: bad OID", line))?;
+            if let Some(value) = parse_walk_value(value.trim_end())
+                .with_context(|| format!("line {}: bad value for {}", line, oid_to_string(&oid)))?
+            {
+                recording.mib.insert(oid, value);
+            }
+        }
+        Ok(recording)
+    }
+
+    /// Parse snmprec: `1.3.6.1.2.1.1.5.0|4|name`, where the middle field
+    /// is the BER tag in decimal, with an `x` suffix for hex values.
+    pub fn parse_snmprec(text: &str) -> Result<Self> {
+        let mut recording = Self::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(3, '|');
+            let (Some(oid), Some(tag), Some(value)) = (fields.next(), fields.next(), fields.next()) else {
+                anyhow::bail!("line {}: expected 'OID|TYPE|VALUE'", n + 1);
+            };
+            let oid = parse_oid(oid).with_context(|| format!("line {}: bad OID", n + 1))?;
+            let value = parse_snmprec_value(tag, value)
+                .with_context(|| format!("line {}: bad value for {}", n + 1, oid_to_string(&oid)))?;
+            recording.mib.insert(oid, value);
+        }
+        Ok(recording)
+    }
+
+    pub fn insert(&mut self, oid: Vec<u32>, value: SnmpValue) {
+        self.mib.insert(oid, value);
+    }
+
+    pub fn get(&self, oid: &[u32]) -> Option<&SnmpValue> {
+        self.mib.get(oid)
+    }
+
+    /// The first object after `oid`.
+    pub fn next(&self, oid: &[u32]) -> Option<(&Vec<u32>, &SnmpValue)> {
+        use std::ops::Bound;
+        self.mib.range::<[u32], _>((Bound::Excluded(oid), Bound::Unbounded)).next()
+    }
+
+    pub fn len(&self) -> usize {
+        self.mib.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mib.is_empty()
+    }
+
+    /// Answer one v1/v2c request message. `None` means the agent stays
+    /// silent: a wrong community, or a request it would not answer.
+    pub fn respond(&self, request: &[u8], community: Option<&str>) -> Result<Option<Vec<u8>>> {
+        let mut pos = 0;
+        read_sequence(request, &mut pos, "message")?;
+        let version = match decode_integer(&read_expected(request, &mut pos, 0x02, "version")?)? {
+            0 => SnmpVersion::V1,
+            1 => SnmpVersion::V2c,
+            v => {
+                debug!("SNMP simulator: ignoring version {} request", v);
+                return Ok(None);
+            }
+        };
+        let request_community = String::from_utf8_lossy(&read_expected(request, &mut pos, 0x04, "community")?).to_string();
+        if community.is_some_and(|c| c != request_community) {
+            return Ok(None);
+        }
+
+        let pdu_type = read_tag(request, &mut pos)?;
+        read_length(request, &mut pos)?;
+        let request_id = decode_integer(&read_expected(request, &mut pos, 0x02, "request-id")?)? as i32;
+        let non_repeaters = decode_integer(&read_expected(request, &mut pos, 0x02, "error-status")?)?.max(0) as usize;
+        let max_repetitions = decode_integer(&read_expected(request, &mut pos, 0x02, "error-index")?)?.max(0) as usize;
+        let varbinds = decode_varbind_list(request, &mut pos)?;
+        let oids: Vec<Vec<u32>> = varbinds.iter().map(|(oid, _)| oid.clone()).collect();
+
+        let v1 = version == SnmpVersion::V1;
+        let (error, response) = match pdu_type {
+            PDU_GET_REQUEST => self.answer(&oids, v1, |oid| match self.get(oid) {
+                Some(value) => Some((oid.to_vec(), value.clone())),
+                None => (!v1).then(|| (oid.to_vec(), self.missing(oid))),
+            }),
+            PDU_GET_NEXT_REQUEST => self.answer(&oids, v1, |oid| match self.next(oid) {
+                Some((next, value)) => Some((next.clone(), value.clone())),
+                None => (!v1).then(|| (oid.to_vec(), SnmpValue::EndOfMibView)),
+            }),
+            PDU_GET_BULK_REQUEST if !v1 => (None, self.bulk(&oids, non_repeaters, max_repetitions)),
+            PDU_SET_REQUEST => {
+                // notWritable, or readOnly for v1
+                let status = if v1 { 4 } else { 17 };
+                (Some((status, 1)), Vec::new())
+            }
+            _ => {
+                debug!("SNMP simulator: ignoring PDU type 0x{:02x}", pdu_type);
+                return Ok(None);
+            }
+        };
+
+        let (error_status, error_index) = error.unwrap_or((0, 0));
+        let response = if error_status == 0 { response } else { varbinds };
+        let response: Vec<(&[u32], SnmpValue)> = response.iter().map(|(oid, v)| (oid.as_slice(), v.clone())).collect();
+        let pdu = response_pdu(request_id, error_status, error_index, &encode_varbinds(&response));
+        Ok(Some(encode_community_message(&version, &request_community, &pdu)))
+    }
+
+    /// Answer each OID in turn; the first one `lookup` has no answer for
+    /// is a v1 noSuchName.
+    fn answer(
+        &self,
+        oids: &[Vec<u32>],
+        v1: bool,
+        lookup: impl Fn(&[u32]) -> Option<(Vec<u32>, SnmpValue)>,
+    ) -> (Option<(i64, i64)>, Varbinds) {
+        let mut response = Vec::new();
+        for (i, oid) in oids.iter().enumerate() {
+            match lookup(oid) {
+                Some(varbind) => response.push(varbind),
+                None if v1 => return (Some((ErrorStatus::NO_SUCH_NAME as i64, i as i64 + 1)), response),
+                None => {}
+            }
+        }
+        (None, response)
+    }
+
+    fn bulk(&self, oids: &[Vec<u32>], non_repeaters: usize, max_repetitions: usize) -> Varbinds {
+        let split = non_repeaters.min(oids.len());
+        let next = |oid: &[u32]| match self.next(oid) {
+            Some((next, value)) => (next.clone(), value.clone()),
+            None => (oid.to_vec(), SnmpValue::EndOfMibView),
+        };
+
+        let mut response: Vec<_> = oids[..split].iter().map(|oid| next(oid)).collect();
+        let mut size: usize = response.iter().map(|(oid, value)| oid.len() * 2 + value.encode().len()).sum();
+        let mut cursors = oids[split..].to_vec();
+        for _ in 0..max_repetitions {
+            if cursors.is_empty() {
+                break;
+            }
+            let row: Vec<_> = cursors.iter().map(|oid| next(oid)).collect();
+            size += row.iter().map(|(oid, value)| oid.len() * 2 + value.encode().len()).sum::<usize>();
+            // Agents may return fewer repetitions than asked (RFC 3416 4.2.3)
+            if size > MAX_RESPONSE && !response.is_empty() {
+                break;
+            }
+            let done = row.iter().all(|(_, value)| *value == SnmpValue::EndOfMibView);
+            for (cursor, (oid, _)) in cursors.iter_mut().zip(&row) {
+                cursor.clone_from(oid);
+            }
+            response.extend(row);
+            if done {
+                break;
+            }
+        }
+        response
+    }
+
+    /// noSuchInstance when the object exists with other instances,
+    /// otherwise noSuchObject.
+    fn missing(&self, oid: &[u32]) -> SnmpValue {
+        let column = &oid[..oid.len().saturating_sub(1)];
+        match self.next(column) {
+            Some((next, _)) if !column.is_empty() && next.starts_with(column) => SnmpValue::NoSuchInstance,
+            _ => SnmpValue::NoSuchObject,
+        }
+    }
+}
+
+/// A running simulated agent. Stops when dropped.
+pub struct Simulator {
+    local_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Simulator {
+    /// Serve `recording` on `bind`. With a `community`, requests
+    /// carrying any other community are ignored, as a real agent would.
+    pub fn start(bind: SocketAddr, recording: Recording, community: Option<String>) -> Result<Self> {
+        let socket = UdpSocket::bind(bind)
+            .with_context(|| format!("Failed to bind SNMP simulator on {}", bind))?;
+        socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+        let local_addr = socket.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let stopping = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("snmp-simulator".into())
+            .spawn(move || {
+                let mut buf = vec![0u8; 65535];
+                while !stopping.load(Ordering::Relaxed) {
+                    let Ok((len, peer)) = socket.recv_from(&mut buf) else { continue };
+                    match recording.respond(&buf[..len], community.as_deref()) {
+                        Ok(Some(response)) => {
+                            if let Err(e) = socket.send_to(&response, peer) {
+                                debug!("SNMP simulator: failed to reply to {}: {}", peer, e);
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => debug!("SNMP simulator: bad request from {}: {:#}", peer, e),
+                    }
+                }
+            })
+            .context("Failed to start SNMP simulator thread")?;
+
+        debug!("SNMP simulator listening on {}", local_addr);
+        Ok(Self { local_addr, stop, thread: Some(thread) })
+    }
+
+    /// Serve `recording` on an ephemeral loopback port of the same
+    /// address family as `device`.
+    pub fn start_for(device: IpAddr, recording: Recording) -> Result<Self> {
+        let loopback = match device {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(_) => IpAddr::V6(std::net::Ipv6Addr::LOCALHOST),
+        };
+        Self::start(SocketAddr::new(loopback, 0), recording, None)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn response_pdu(request_id: i32, error_status: i64, error_index: i64, varbinds: &[u8]) -> Vec<u8> {
+    let mut content = encode_integer(request_id as i64);
+    content.extend_from_slice(&encode_integer(error_status));
+    content.extend_from_slice(&encode_integer(error_index));
+    content.extend_from_slice(&encode_tlv(0x30, varbinds));
+    encode_tlv(PDU_RESPONSE, &content)
+}
+
+fn starts_walk_record(line: &str) -> bool {
+    let Some((oid, _)) = line.split_once(" = ") else { return false };
+    let oid = oid.trim().trim_start_matches('.');
+    !oid.is_empty() && oid.contains('.') && oid.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+fn parse_oid(text: &str) -> Result<Vec<u32>> {
+    text.trim().trim_start_matches('.')
+        .split('.')
+        .map(|part| part.parse::<u32>().with_context(|| format!("invalid OID component '{}'", part)))
+        .collect()
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>> {
+    let digits: String = text.chars().filter(|c| !c.is_whitespace() && *c != ':').collect();
+    if !digits.len().is_multiple_of(2) {
+        anyhow::bail!("odd number of hex digits");
+    }
+    (0..digits.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).with_context(|| format!("invalid hex '{}'", &digits[i..i + 2])))
+        .collect()
+}
+
+/// The first number in `text`: the `1` in `up(1)`, the `1500` in `1500 octets`.
+fn leading_number<T: std::str::FromStr>(text: &str) -> Result<T> {
+    let text = text.trim();
+    let number = match (text.find('('), text.find(')')) {
+        (Some(open), Some(close)) if open < close => &text[open + 1..close],
+        _ => text.split_whitespace().next().unwrap_or(""),
+    };
+    number.parse().map_err(|_| anyhow::anyhow!("expected a number, got '{}'", text))
+}
+
+/// Decode the text after ` = ` in snmpwalk output. `None` for the
+/// "No Such Object" family of lines.
+fn parse_walk_value(text: &str) -> Result<Option<SnmpValue>> {
+    if text.starts_with("No Such") || text.starts_with("No more variables") {
+        return Ok(None);
+    }
+    if text == "\"\"" {
+        return Ok(Some(SnmpValue::OctetString(Vec::new())));
+    }
+    if text == "NULL" {
+        return Ok(Some(SnmpValue::Null));
+    }
+    let (kind, value) = text.split_once(':')
+        .with_context(|| format!("expected 'TYPE: value', got '{}'", text))?;
+    let value = value.strip_prefix(' ').unwrap_or(value);
+
+    Ok(Some(match kind {
+        "STRING" => SnmpValue::OctetString(parse_walk_string(value)?),
+        "Hex-STRING" | "BITS" => {
+            // BITS prints the octets then the set bit names
+            let hex: String = value.split_whitespace()
+                .take_while(|w| w.len() == 2 && w.chars().all(|c| c.is_ascii_hexdigit()))
+                .collect();
+            SnmpValue::OctetString(parse_hex(&hex)?)
+        }
+        "INTEGER" => SnmpValue::Integer(leading_number(value)?),
+        "Counter32" => SnmpValue::Counter32(leading_number(value)?),
+        "Gauge32" | "Unsigned32" => SnmpValue::Gauge32(leading_number(value)?),
+        "Counter64" => SnmpValue::Counter64(leading_number(value)?),
+        "Timeticks" => SnmpValue::TimeTicks(leading_number(value)?),
+        "OID" => SnmpValue::Oid(parse_oid(value)?),
+        "IpAddress" => SnmpValue::IpAddress(value.trim().parse().context("invalid IpAddress")?),
+        "Network Address" => {
+            let octets: [u8; 4] = parse_hex(value)?.try_into()
+                .map_err(|_| anyhow::anyhow!("Network Address must be 4 octets"))?;
+            SnmpValue::IpAddress(Ipv4Addr::from(octets))
+        }
+        "Opaque" => SnmpValue::Opaque(parse_hex(value.trim_start_matches("Hex-STRING:"))?),
+        _ => anyhow::bail!("unsupported type '{}'", kind),
+    }))
+}
+
+/// A STRING value: quoted with `\"` escapes, or bare. A bare
+/// colon-separated MAC (the PhysAddress display hint) becomes its octets.
+fn parse_walk_string(value: &str) -> Result<Vec<u8>> {
+    let Some(quoted) = value.strip_prefix('"') else {
+        let parts: Vec<&str> = value.trim().split(':').collect();
+        if parts.len() == 6 && parts.iter().all(|p| (1..=2).contains(&p.len()) && p.chars().all(|c| c.is_ascii_hexdigit())) {
+            return parts.iter().map(|p| Ok(u8::from_str_radix(p, 16)?)).collect();
+        }
+        return Ok(value.as_bytes().to_vec());
+    };
+    let quoted = quoted.strip_suffix('"').context("unterminated string")?;
+    let mut out = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    Ok(out.into_bytes())
+}
+
+fn parse_snmprec_value(tag: &str, value: &str) -> Result<SnmpValue> {
+    let (tag, hex) = match tag.strip_suffix('x') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+    };
+    let tag: u8 = tag.parse().with_context(|| format!("invalid type '{}'", tag))?;
+    let bytes = || if hex { parse_hex(value) } else { Ok(value.as_bytes().to_vec()) };
+    let number = || -> Result<String> { Ok(String::from_utf8(bytes()?)?.trim().to_string()) };
+
+    Ok(match tag {
+        2 => SnmpValue::Integer(number()?.parse()?),
+        4 => SnmpValue::OctetString(bytes()?),
+        5 => SnmpValue::Null,
+        6 => SnmpValue::Oid(parse_oid(&number()?)?),
+        64 => match hex {
+            true => SnmpValue::IpAddress(Ipv4Addr::from(<[u8; 4]>::try_from(bytes()?.as_slice())?)),
+            false => SnmpValue::IpAddress(value.trim().parse()?),
+        },
+        65 => SnmpValue::Counter32(number()?.parse()?),
+        66 => SnmpValue::Gauge32(number()?.parse()?),
+        67 => SnmpValue::TimeTicks(number()?.parse()?),
+        68 => SnmpValue::Opaque(bytes()?),
+        70 => SnmpValue::Counter64(number()?.parse()?),
+        128 => SnmpValue::NoSuchObject,
+        129 => SnmpValue::NoSuchInstance,
+        130 => SnmpValue::EndOfMibView,
+        _ => anyhow::bail!("unsupported type {}", tag),
+    })
+}
@@ -0,0 +1,207 @@
+//! Shared asynchronous UDP transport for SNMP.
+//!
+//! All clients share one socket, owned by a dedicated I/O thread with
+//! its own single-threaded tokio runtime. Requests are handed to that
+//! thread over a channel and responses are routed back by (agent
+//! address, request-id) — the PDU request-id for v1/v2c, the msgID for
+//! v3. A slow device therefore never blocks a worker thread, and clients
+//! can be used from any runtime.
+//!
+//! There is one shared socket per local address: the IPv4 and IPv6
+//! wildcards by default, or a configured source address on multi-homed
+//! pollers. Each target uses a socket of its own address family.
+//!
+//! The transport also keeps each target's limit on requests in flight, so
+//! that every client polling a device shares it.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tracing::{debug, warn};
+
+use super::client::message_request_id;
+
+type Reply = oneshot::Sender<Result<Vec<u8>>>;
+
+struct Outgoing {
+    target: SocketAddr,
+    request_id: i64,
+    message: Vec<u8>,
+    reply: Reply,
+}
+
+/// A UDP socket shared by SNMP clients.
+#[derive(Debug)]
+pub struct SnmpTransport {
+    requests: mpsc::UnboundedSender<Outgoing>,
+    local_addr: SocketAddr,
+    next_id: AtomicU32,
+    /// Per-target limits on requests in flight
+    inflight: Mutex<HashMap<SocketAddr, Arc<Semaphore>>>,
+}
+
+static SHARED: Mutex<Vec<Arc<SnmpTransport>>> = Mutex::new(Vec::new());
+
+impl SnmpTransport {
+    /// The process-wide transport for reaching `target`, bound to an
+    /// ephemeral port on `source` (or the wildcard address of the
+    /// target's family) on first use.
+    pub fn shared_for(target: &SocketAddr, source: Option<IpAddr>) -> Result<Arc<SnmpTransport>> {
+        let local_ip = match source {
+            Some(source) if source.is_ipv4() != target.is_ipv4() => {
+                anyhow::bail!("SNMP source address {} cannot reach {}", source, target)
+            }
+            Some(source) => source,
+            None if target.is_ipv4() => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            None => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+
+        let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+        shared.retain(|transport| !transport.requests.is_closed());
+        if let Some(transport) = shared.iter().find(|t| t.local_addr.ip() == local_ip) {
+            return Ok(transport.clone());
+        }
+        let transport = Arc::new(Self::bind(SocketAddr::new(local_ip, 0))?);
+        shared.push(transport.clone());
+        Ok(transport)
+    }
+
+    /// Bind a new transport and start its I/O thread.
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let socket = std::net::UdpSocket::bind(addr)
+            .with_context(|| format!("Failed to bind SNMP socket on {}", addr))?;
+        socket.set_nonblocking(true)
+            .context("Failed to set SNMP socket non-blocking")?;
+        let local_addr = socket.local_addr()?;
+
+        let (requests, rx) = mpsc::unbounded_channel();
+        std::thread::Builder::new()
+            .name("snmp-transport".into())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        warn!("SNMP transport failed to start: {}", e);
+                        return;
+                    }
+                };
+                runtime.block_on(async move {
+                    match tokio::net::UdpSocket::from_std(socket) {
+                        Ok(socket) => run(socket, rx).await,
+                        Err(e) => warn!("SNMP transport failed to start: {}", e),
+                    }
+                });
+            })
+            .context("Failed to start SNMP transport thread")?;
+
+        debug!("SNMP transport listening on {}", local_addr);
+        Ok(Self {
+            requests,
+            local_addr,
+            next_id: AtomicU32::new(rand::random::<u32>() & 0x3fff_ffff),
+            inflight: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The limit on requests in flight to `target`, shared by every client
+    /// that reaches it through this transport: discover, macsuck and
+    /// per-VLAN clients of one device all draw on the same permits. It
+    /// allows `max` at once unless a client already holds one for the
+    /// target, which is kept.
+    pub fn inflight_for(&self, target: SocketAddr, max: usize) -> Arc<Semaphore> {
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        // Forget the limits of devices no client is talking to
+        inflight.retain(|_, limit| Arc::strong_count(limit) > 1);
+        inflight.entry(target)
+            .or_insert_with(|| Arc::new(Semaphore::new(max.max(1))))
+            .clone()
+    }
+
+    /// A request-id (or msgID) unique among this transport's requests.
+    pub fn next_request_id(&self) -> i32 {
+        (self.next_id.fetch_add(1, Ordering::Relaxed) & 0x7fff_ffff).max(1) as i32
+    }
+
+    /// Send a message and wait for the response carrying `request_id`,
+    /// resending up to `retries` times.
+    pub async fn request(
+        &self,
+        target: SocketAddr,
+        request_id: i32,
+        message: &[u8],
+        timeout: Duration,
+        retries: u32,
+    ) -> Result<Vec<u8>> {
+        for attempt in 0..=retries {
+            if attempt > 0 {
+                debug!("SNMP retry {} to {}", attempt, target);
+            }
+            let (reply, response) = oneshot::channel();
+            self.requests
+                .send(Outgoing { target, request_id: request_id as i64, message: message.to_vec(), reply })
+                .map_err(|_| anyhow::anyhow!("SNMP transport has stopped"))?;
+
+            match tokio::time::timeout(timeout, response).await {
+                Ok(Ok(result)) => return result,
+                Ok(Err(_)) => anyhow::bail!("SNMP transport has stopped"),
+                Err(_) => continue,
+            }
+        }
+
+        anyhow::bail!("SNMP request to {} timed out", target)
+    }
+}
+
+/// The I/O loop: send queued requests and route each response to the
+/// waiting request with the same agent address and request-id.
+async fn run(socket: tokio::net::UdpSocket, mut requests: mpsc::UnboundedReceiver<Outgoing>) {
+    let mut pending: HashMap<(SocketAddr, i64), Reply> = HashMap::new();
+    let mut buf = vec![0u8; 65535];
+
+    loop {
+        tokio::select! {
+            request = requests.recv() => {
+                let Some(request) = request else { break };
+                // Forget requests whose caller has timed out
+                pending.retain(|_, reply| !reply.is_closed());
+                match socket.send_to(&request.message, request.target).await {
+                    Ok(_) => {
+                        pending.insert((request.target, request.request_id), request.reply);
+                    }
+                    Err(e) => {
+                        let _ = request.reply.send(
+                            Err(anyhow::Error::new(e).context(format!("Failed to send SNMP request to {}", request.target))),
+                        );
+                    }
+                }
+            }
+            received = socket.recv_from(&mut buf) => {
+                let (len, peer) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        debug!("SNMP transport receive error: {}", e);
+                        continue;
+                    }
+                };
+                let Some(request_id) = message_request_id(&buf[..len]) else {
+                    debug!("SNMP: dropping malformed datagram from {}", peer);
+                    continue;
+                };
+                match pending.remove(&(peer, request_id)) {
+                    Some(reply) => {
+                        let _ = reply.send(Ok(buf[..len].to_vec()));
+                    }
+                    None => debug!("SNMP: dropping unexpected response from {} (request-id {})", peer, request_id),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! SNMP notification receiver.
+//!
+//! Decodes v1 Trap-PDUs and v2c/v3 SNMPv2-Trap and InformRequest PDUs,
+//! and builds the Response an inform expects. v1 traps are translated to
+//! their SNMPv2 snmpTrapOID (RFC 3584 section 3.1), so callers see one
+//! shape regardless of version.
+//!
+//! For SNMPv3 traps the sender is the authoritative engine; for informs
+//! the receiver is, so it answers engine discovery with a Report carrying
+//! its own engineID, boots and time (RFC 3414 section 4). Authenticated
+//! traps are checked against the sender's clock as last seen, which is
+//! learned from its first authenticated message (RFC 3414 section 3.2
+//! step 7b), so a captured trap cannot be replayed later.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use super::client::*;
+use super::oids;
+use super::usm::{self, UsmKeys, UsmUser};
+use super::value::SnmpValue;
+
+const PDU_TRAP_V1: u8 = 0xa4;
+const PDU_INFORM_REQUEST: u8 = 0xa6;
+const PDU_TRAP_V2: u8 = 0xa7;
+
+/// Seconds either side of our engine time that an authenticated inform
+/// may be (RFC 3414 section 3.2 step 7).
+const TIME_WINDOW: i64 = 150;
+
+/// snmpEngineBoots at which an engine's clock has run out.
+const MAX_ENGINE_BOOTS: u32 = 2_147_483_647;
+
+/// A trap sender's clock as we last learned it.
+#[derive(Debug, Clone, Copy)]
+struct EngineClock {
+    boots: u32,
+    time: u32,
+    /// latestReceivedEngineTime
+    latest: u32,
+    at: Instant,
+}
+
+/// A decoded trap or inform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub version: SnmpVersion,
+    /// The agent that sent it: the datagram source, which is what the
+    /// backend attributes the notification to.
+    pub agent: IpAddr,
+    /// The v1 agent-addr field, when set. Anyone with the community can
+    /// put any address here, so it is only logged.
+    pub agent_addr: Option<IpAddr>,
+    /// Community string, or the SNMPv3 user name
+    pub security_name: String,
+    /// snmpTrapOID.0 (translated for v1 traps)
+    pub trap_oid: Vec<u32>,
+    /// sysUpTime.0 of the agent, in hundredths of a second
+    pub uptime: Option<u32>,
+    /// Varbinds after sysUpTime.0 and snmpTrapOID.0
+    pub varbinds: Vec<(Vec<u32>, SnmpValue)>,
+    /// True for an InformRequest
+    pub inform: bool,
+}
+
+/// What the backend should react to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrapEvent {
+    ColdStart,
+    LinkChange { ifindex: u32, up: bool },
+    MacNotification,
+    Other,
+}
+
+impl Notification {
+    pub fn event(&self) -> TrapEvent {
+        let oid = self.trap_oid.as_slice();
+        if oid == oids::COLD_START {
+            TrapEvent::ColdStart
+        } else if oid == oids::LINK_UP || oid == oids::LINK_DOWN {
+            // linkUp/linkDown carry ifIndex, ifAdminStatus and
+            // ifOperStatus for the interface
+            let ifindex = self.varbinds.iter().find_map(|(oid, value)| {
+                if oid.starts_with(&oids::IF_INDEX) {
+                    value.as_u64().and_then(|i| u32::try_from(i).ok())
+                } else if oid.starts_with(&oids::IF_ADMIN_STATUS) || oid.starts_with(&oids::IF_OPER_STATUS) {
+                    oid.last().copied()
+                } else {
+                    None
+                }
+            });
+            match ifindex {
+                Some(ifindex) => TrapEvent::LinkChange { ifindex, up: oid == oids::LINK_UP },
+                None => TrapEvent::Other,
+            }
+        } else if oid == oids::CMN_MAC_CHANGED_NOTIFICATION || oid == oids::CMN_MAC_MOVE_NOTIFICATION {
+            TrapEvent::MacNotification
+        } else {
+            TrapEvent::Other
+        }
+    }
+
+    /// Short name of the trap, e.g. "linkDown", or its OID.
+    pub fn name(&self) -> String {
+        let names: [(&[u32], &str); 5] = [
+            (&oids::COLD_START, "coldStart"),
+            (&oids::LINK_DOWN, "linkDown"),
+            (&oids::LINK_UP, "linkUp"),
+            (&oids::CMN_MAC_CHANGED_NOTIFICATION, "cmnMacChangedNotification"),
+            (&oids::CMN_MAC_MOVE_NOTIFICATION, "cmnMacMoveNotification"),
+        ];
+        names.iter()
+            .find(|(oid, _)| *oid == self.trap_oid.as_slice())
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_else(|| oid_to_string(&self.trap_oid))
+    }
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = match self.version {
+            SnmpVersion::V1 => "v1",
+            SnmpVersion::V2c => "v2c",
+            SnmpVersion::V3 => "v3",
+        };
+        write!(f, "{} {} from {} ({})", if self.inform { "inform" } else { "trap" }, self.name(), self.agent, version)?;
+        if let Some(agent_addr) = self.agent_addr.filter(|&addr| addr != self.agent) {
+            write!(f, " agent-addr={}", agent_addr)?;
+        }
+        for (oid, value) in &self.varbinds {
+            write!(f, " {}={}", oid_to_string(oid), value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of one datagram: a notification to act on, and a message
+/// to send back (inform Response or SNMPv3 Report).
+#[derive(Debug, Default)]
+pub struct Received {
+    pub notification: Option<Notification>,
+    pub reply: Option<Vec<u8>>,
+}
+
+/// Decodes and authenticates incoming notifications.
+pub struct TrapReceiver {
+    /// Accepted v1/v2c communities; empty accepts any
+    communities: Vec<String>,
+    users: Vec<UsmUser>,
+    engine_id: Vec<u8>,
+    started: Instant,
+    salt: AtomicU64,
+    unknown_engine_ids: AtomicU32,
+    not_in_time_windows: AtomicU32,
+    keys: Mutex<HashMap<(String, Vec<u8>), UsmKeys>>,
+    /// Clocks of the engines that sent us authenticated traps
+    engines: Mutex<HashMap<Vec<u8>, EngineClock>>,
+}
+
+impl TrapReceiver {
+    pub fn new(communities: Vec<String>, users: Vec<UsmUser>) -> Self {
+        // Net-SNMP enterprise, format 5 (administratively assigned octets)
+        let mut engine_id = vec![0x80, 0x00, 0x1f, 0x88, 0x05];
+        engine_id.extend_from_slice(&rand::random::<[u8; 8]>());
+        Self {
+            communities,
+            users,
+            engine_id,
+            started: Instant::now(),
+            salt: AtomicU64::new(rand::random()),
+            unknown_engine_ids: AtomicU32::new(0),
+            not_in_time_windows: AtomicU32::new(0),
+            keys: Mutex::new(HashMap::new()),
+            engines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Our snmpEngineID, which SNMPv3 informs must be addressed to.
+    pub fn engine_id(&self) -> &[u8] {
+        &self.engine_id
+    }
+
+    fn engine_time(&self) -> u32 {
+        self.started.elapsed().as_secs() as u32
+    }
+
+    /// Decode a datagram received from `source`.
+    pub fn receive(&self, data: &[u8], source: IpAddr) -> Result<Received> {
+        let mut pos = 0;
+        read_sequence(data, &mut pos, "message")?;
+        let version = decode_integer(&read_expected(data, &mut pos, 0x02, "version")?)?;
+        match version {
+            0 | 1 => self.receive_community(data, source),
+            3 => self.receive_v3(data, source),
+            _ => anyhow::bail!("SNMP: unsupported version {}", version),
+        }
+    }
+
+    fn receive_community(&self, data: &[u8], source: IpAddr) -> Result<Received> {
+        let mut pos = 0;
+        read_sequence(data, &mut pos, "message")?;
+        let version = match decode_integer(&read_expected(data, &mut pos, 0x02, "version")?)? {
+            0 => SnmpVersion::V1,
+            _ => SnmpVersion::V2c,
+        };
+        let community = String::from_utf8_lossy(&read_expected(data, &mut pos, 0x04, "community")?).to_string();
+        if !self.communities.is_empty() && !self.communities.contains(&community) {
+            anyhow::bail!("SNMP: unknown community from {}", source);
+        }
+        let pdu = &data[pos..];
+
+        let notification = if version == SnmpVersion::V1 {
+            decode_v1_trap(pdu, source, community.clone())?
+        } else {
+            decode_v2_notification(pdu, version, source, community.clone())?
+        };
+        let reply = if notification.inform {
+            Some(encode_community_message(&version, &community, &inform_response(pdu, &notification)?))
+        } else {
+            None
+        };
+        Ok(Received { notification: Some(notification), reply })
+    }
+
+    fn receive_v3(&self, data: &[u8], source: IpAddr) -> Result<Received> {
+        let (flags, security) = peek_v3_security(data)?;
+
+        // Engine discovery ahead of an inform
+        if security.engine_id.is_empty() {
+            let message = decode_v3_message(data, None)?;
+            let count = self.unknown_engine_ids.fetch_add(1, Ordering::Relaxed) + 1;
+            let reply = self.report(&message, &oids::USM_STATS_UNKNOWN_ENGINE_IDS, count, &UsmKeys::default())?;
+            return Ok(Received { notification: None, reply: Some(reply) });
+        }
+
+        let user = self.users.iter()
+            .find(|u| u.name == security.user_name)
+            .with_context(|| format!("SNMPv3: unknown user '{}' from {}", security.user_name, source))?;
+        let keys = self.keys_for(user, &security.engine_id)?;
+        if flags & usm::FLAG_AUTH == 0 && keys.auth.is_some() {
+            anyhow::bail!("SNMPv3: unauthenticated message for user '{}' from {}", user.name, source);
+        }
+        let message = decode_v3_message(data, Some(&keys))?;
+
+        let mut pos = 0;
+        let tag = read_tag(&message.pdu, &mut pos)?;
+        if tag == PDU_INFORM_REQUEST {
+            if message.security.engine_id != self.engine_id {
+                let count = self.unknown_engine_ids.fetch_add(1, Ordering::Relaxed) + 1;
+                let reply = self.report(&message, &oids::USM_STATS_UNKNOWN_ENGINE_IDS, count, &UsmKeys::default())?;
+                return Ok(Received { notification: None, reply: Some(reply) });
+            }
+            let skew = message.security.engine_time as i64 - self.engine_time() as i64;
+            if message.flags & usm::FLAG_AUTH != 0 && (message.security.engine_boots != 1 || skew.abs() > TIME_WINDOW) {
+                let count = self.not_in_time_windows.fetch_add(1, Ordering::Relaxed) + 1;
+                let auth_only = UsmKeys { auth: keys.auth.clone(), privacy: None };
+                let reply = self.report(&message, &oids::USM_STATS_NOT_IN_TIME_WINDOWS, count, &auth_only)?;
+                return Ok(Received { notification: None, reply: Some(reply) });
+            }
+        } else if message.flags & usm::FLAG_AUTH != 0
+            && !self.in_time_window(&message.security.engine_id, message.security.engine_boots, message.security.engine_time)
+        {
+            // The sender is authoritative, so there is nobody to report to
+            self.not_in_time_windows.fetch_add(1, Ordering::Relaxed);
+            anyhow::bail!("SNMPv3: trap from {} outside the time window", source);
+        }
+
+        let notification = decode_v2_notification(&message.pdu, SnmpVersion::V3, source, user.name.clone())?;
+        let reply = if notification.inform {
+            let security = UsmSecurityParameters {
+                engine_id: self.engine_id.clone(),
+                engine_boots: 1,
+                engine_time: self.engine_time(),
+                user_name: user.name.clone(),
+            };
+            Some(encode_v3_message(
+                message.msg_id,
+                false,
+                &security,
+                &keys,
+                self.salt.fetch_add(1, Ordering::Relaxed),
+                &self.engine_id,
+                &inform_response(&message.pdu, &notification)?,
+            )?)
+        } else {
+            None
+        };
+        Ok(Received { notification: Some(notification), reply })
+    }
+
+    /// Localized keys for a user, cached per sending engine.
+    fn keys_for(&self, user: &UsmUser, engine_id: &[u8]) -> Result<UsmKeys> {
+        let mut cache = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let key = (user.name.clone(), engine_id.to_vec());
+        if let Some(keys) = cache.get(&key) {
+            return Ok(keys.clone());
+        }
+        let keys = user.localize(engine_id)?;
+        cache.insert(key, keys.clone());
+        Ok(keys)
+    }
+
+    /// Whether an authenticated trap's boots and time are within the
+    /// window of the sender's clock, updating the clock as RFC 3414
+    /// section 3.2 step 7b describes. The first trap from an engine is
+    /// accepted and sets its clock.
+    fn in_time_window(&self, engine_id: &[u8], boots: u32, time: u32) -> bool {
+        let mut engines = self.engines.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let clock = engines.entry(engine_id.to_vec())
+            .or_insert(EngineClock { boots, time, latest: time, at: now });
+        if boots > clock.boots || (boots == clock.boots && time > clock.latest) {
+            *clock = EngineClock { boots, time, latest: time, at: now };
+        }
+        let expected = clock.time as i64 + clock.at.elapsed().as_secs() as i64;
+        !(clock.boots == MAX_ENGINE_BOOTS
+            || boots < clock.boots
+            || (boots == clock.boots && (time as i64) < expected - TIME_WINDOW))
+    }
+
+    /// A Report PDU carrying a usmStats counter, from our engine.
+    fn report(&self, request: &V3Message, counter: &[u32], count: u32, keys: &UsmKeys) -> Result<Vec<u8>> {
+        let request_id = pdu_request_id(&request.pdu)? as i32;
+        let oid: Vec<u32> = counter.iter().copied().chain([0]).collect();
+        let pdu = encode_pdu(PDU_REPORT, request_id, None, &encode_varbinds(&[(&oid, SnmpValue::Counter32(count))]));
+        let security = UsmSecurityParameters {
+            engine_id: self.engine_id.clone(),
+            engine_boots: 1,
+            engine_time: self.engine_time(),
+            user_name: request.security.user_name.clone(),
+        };
+        encode_v3_message(request.msg_id, false, &security, keys, 0, &self.engine_id, &pdu)
+    }
+}
+
+/// Decode a v1 Trap-PDU and translate it to SNMPv2 form.
+fn decode_v1_trap(pdu: &[u8], source: IpAddr, community: String) -> Result<Notification> {
+    let mut pos = 0;
+    let tag = read_tag(pdu, &mut pos)?;
+    if tag != PDU_TRAP_V1 {
+        anyhow::bail!("SNMP: expected v1 Trap PDU (0x{:02x}), got 0x{:02x}", PDU_TRAP_V1, tag);
+    }
+    read_length(pdu, &mut pos)?;
+    let enterprise = decode_oid(&read_expected(pdu, &mut pos, 0x06, "enterprise")?)?;
+    let agent_addr = read_expected(pdu, &mut pos, 0x40, "agent-addr")?;
+    let generic = decode_integer(&read_expected(pdu, &mut pos, 0x02, "generic-trap")?)?;
+    let specific = decode_integer(&read_expected(pdu, &mut pos, 0x02, "specific-trap")?)?;
+    let (_, timestamp) = read_tlv_value(pdu, &mut pos)?;
+    let uptime = SnmpValue::decode(0x43, &timestamp)?;
+    let varbinds = decode_varbind_list(pdu, &mut pos)?;
+
+    let trap_oid = if generic == 6 {
+        enterprise.iter().copied().chain([0, specific as u32]).collect()
+    } else {
+        oids::SNMP_TRAPS.iter().copied().chain([generic as u32 + 1]).collect()
+    };
+    let agent_addr = match <[u8; 4]>::try_from(agent_addr.as_slice()) {
+        Ok(octets) if octets != [0, 0, 0, 0] => Some(IpAddr::V4(Ipv4Addr::from(octets))),
+        _ => None,
+    };
+
+    Ok(Notification {
+        version: SnmpVersion::V1,
+        agent: source,
+        agent_addr,
+        security_name: community,
+        trap_oid,
+        uptime: uptime.as_u64().map(|t| t as u32),
+        varbinds,
+        inform: false,
+    })
+}
+
+/// Decode an SNMPv2-Trap or InformRequest PDU.
+fn decode_v2_notification(pdu: &[u8], version: SnmpVersion, source: IpAddr, security_name: String) -> Result<Notification> {
+    let mut pos = 0;
+    let tag = read_tag(pdu, &mut pos)?;
+    let inform = match tag {
+        PDU_TRAP_V2 => false,
+        PDU_INFORM_REQUEST => true,
+        _ => anyhow::bail!("SNMP: expected a trap or inform PDU, got 0x{:02x}", tag),
+    };
+    read_length(pdu, &mut pos)?;
+    skip_tlv(pdu, &mut pos)?; // request-id
+    skip_tlv(pdu, &mut pos)?; // error-status
+    skip_tlv(pdu, &mut pos)?; // error-index
+    let mut varbinds = decode_varbind_list(pdu, &mut pos)?;
+
+    let uptime_oid: Vec<u32> = oids::SYS_UPTIME.iter().copied().chain([0]).collect();
+    let trap_oid_oid: Vec<u32> = oids::SNMP_TRAP_OID.iter().copied().chain([0]).collect();
+    let uptime = varbinds.iter()
+        .find(|(oid, _)| *oid == uptime_oid)
+        .and_then(|(_, v)| v.as_u64())
+        .map(|t| t as u32);
+    let trap_oid = varbinds.iter()
+        .find(|(oid, _)| *oid == trap_oid_oid)
+        .and_then(|(_, v)| v.as_oid().map(|o| o.to_vec()))
+        .context("SNMP: notification without snmpTrapOID.0")?;
+    varbinds.retain(|(oid, _)| *oid != uptime_oid && *oid != trap_oid_oid);
+
+    Ok(Notification { version, agent: source, agent_addr: None, security_name, trap_oid, uptime, varbinds, inform })
+}
+
+/// The Response to an InformRequest: same request-id and varbinds.
+fn inform_response(pdu: &[u8], notification: &Notification) -> Result<Vec<u8>> {
+    let request_id = pdu_request_id(pdu)? as i32;
+    let mut pos = 0;
+    read_tag(pdu, &mut pos)?;
+    read_length(pdu, &mut pos)?;
+    skip_tlv(pdu, &mut pos)?;
+    skip_tlv(pdu, &mut pos)?;
+    skip_tlv(pdu, &mut pos)?;
+    let varbinds = decode_varbind_list(pdu, &mut pos)
+        .with_context(|| format!("SNMP: bad inform from {}", notification.agent))?;
+    let varbinds: Vec<(&[u32], SnmpValue)> = varbinds.iter().map(|(oid, v)| (oid.as_slice(), v.clone())).collect();
+    Ok(encode_pdu(PDU_RESPONSE, request_id, None, &encode_varbinds(&varbinds)))
+}
@@ -0,0 +1,209 @@
+//! Typed SNMP values.
+//!
+//! Decodes the SMIv2 application types (RFC 2578) and the SNMPv2
+//! exception values (RFC 3416) from their BER encoding, so callers get
+//! an integer, string, OID or counter rather than raw content octets.
+
+use anyhow::Result;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use super::client::{decode_integer, decode_oid, encode_oid, encode_tlv};
+
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_IP_ADDRESS: u8 = 0x40;
+pub const TAG_COUNTER32: u8 = 0x41;
+pub const TAG_GAUGE32: u8 = 0x42;
+pub const TAG_TIMETICKS: u8 = 0x43;
+pub const TAG_OPAQUE: u8 = 0x44;
+pub const TAG_COUNTER64: u8 = 0x46;
+pub const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+pub const TAG_NO_SUCH_INSTANCE: u8 = 0x81;
+pub const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+/// A decoded varbind value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnmpValue {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Null,
+    Oid(Vec<u32>),
+    IpAddress(Ipv4Addr),
+    Counter32(u32),
+    Gauge32(u32),
+    TimeTicks(u32),
+    Opaque(Vec<u8>),
+    Counter64(u64),
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+}
+
+impl SnmpValue {
+    /// Decode a value from its BER tag and content octets.
+    pub fn decode(tag: u8, data: &[u8]) -> Result<Self> {
+        Ok(match tag {
+            TAG_INTEGER => SnmpValue::Integer(decode_integer(data)?),
+            TAG_OCTET_STRING => SnmpValue::OctetString(data.to_vec()),
+            TAG_NULL => SnmpValue::Null,
+            TAG_OID => SnmpValue::Oid(decode_oid(data)?),
+            TAG_IP_ADDRESS => {
+                let octets: [u8; 4] = data.try_into()
+                    .map_err(|_| anyhow::anyhow!("BER: IpAddress must be 4 octets, got {}", data.len()))?;
+                SnmpValue::IpAddress(Ipv4Addr::from(octets))
+            }
+            TAG_COUNTER32 => SnmpValue::Counter32(decode_unsigned(data, 4)? as u32),
+            TAG_GAUGE32 => SnmpValue::Gauge32(decode_unsigned(data, 4)? as u32),
+            TAG_TIMETICKS => SnmpValue::TimeTicks(decode_unsigned(data, 4)? as u32),
+            TAG_OPAQUE => SnmpValue::Opaque(data.to_vec()),
+            TAG_COUNTER64 => SnmpValue::Counter64(decode_unsigned(data, 8)?),
+            TAG_NO_SUCH_OBJECT => SnmpValue::NoSuchObject,
+            TAG_NO_SUCH_INSTANCE => SnmpValue::NoSuchInstance,
+            TAG_END_OF_MIB_VIEW => SnmpValue::EndOfMibView,
+            _ => anyhow::bail!("BER: unsupported value type 0x{:02x}", tag),
+        })
+    }
+
+    /// BER-encode this value as a complete TLV.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            SnmpValue::Integer(v) => encode_tlv(TAG_INTEGER, &signed_octets(*v)),
+            SnmpValue::OctetString(v) => encode_tlv(TAG_OCTET_STRING, v),
+            SnmpValue::Null => encode_tlv(TAG_NULL, &[]),
+            SnmpValue::Oid(oid) => encode_oid(oid),
+            SnmpValue::IpAddress(ip) => encode_tlv(TAG_IP_ADDRESS, &ip.octets()),
+            SnmpValue::Counter32(v) => encode_tlv(TAG_COUNTER32, &unsigned_octets(*v as u64)),
+            SnmpValue::Gauge32(v) => encode_tlv(TAG_GAUGE32, &unsigned_octets(*v as u64)),
+            SnmpValue::TimeTicks(v) => encode_tlv(TAG_TIMETICKS, &unsigned_octets(*v as u64)),
+            SnmpValue::Opaque(v) => encode_tlv(TAG_OPAQUE, v),
+            SnmpValue::Counter64(v) => encode_tlv(TAG_COUNTER64, &unsigned_octets(*v)),
+            SnmpValue::NoSuchObject => encode_tlv(TAG_NO_SUCH_OBJECT, &[]),
+            SnmpValue::NoSuchInstance => encode_tlv(TAG_NO_SUCH_INSTANCE, &[]),
+            SnmpValue::EndOfMibView => encode_tlv(TAG_END_OF_MIB_VIEW, &[]),
+        }
+    }
+
+    /// True for noSuchObject, noSuchInstance and endOfMibView.
+    pub fn is_exception(&self) -> bool {
+        matches!(self, SnmpValue::NoSuchObject | SnmpValue::NoSuchInstance | SnmpValue::EndOfMibView)
+    }
+
+    /// Numeric value of any integer-like type.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            SnmpValue::Integer(v) => Some(*v),
+            SnmpValue::Counter32(v) | SnmpValue::Gauge32(v) | SnmpValue::TimeTicks(v) => Some(*v as i64),
+            SnmpValue::Counter64(v) => i64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Numeric value of any non-negative integer-like type.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            SnmpValue::Counter64(v) => Some(*v),
+            other => other.as_i64().and_then(|v| u64::try_from(v).ok()),
+        }
+    }
+
+    /// Raw octets of an OCTET STRING or Opaque.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            SnmpValue::OctetString(v) | SnmpValue::Opaque(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// An OCTET STRING as text (lossy UTF-8).
+    pub fn as_string(&self) -> Option<String> {
+        match self {
+            SnmpValue::OctetString(v) => Some(String::from_utf8_lossy(v).to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn as_oid(&self) -> Option<&[u32]> {
+        match self {
+            SnmpValue::Oid(oid) => Some(oid),
+            _ => None,
+        }
+    }
+
+    pub fn as_ip(&self) -> Option<Ipv4Addr> {
+        match self {
+            SnmpValue::IpAddress(ip) => Some(*ip),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SnmpValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnmpValue::Integer(v) => write!(f, "{}", v),
+            SnmpValue::OctetString(v) => match std::str::from_utf8(v) {
+                Ok(s) if !s.chars().any(|c| c.is_control() && !c.is_whitespace()) => f.write_str(s),
+                _ => write_hex(f, v),
+            },
+            SnmpValue::Null => f.write_str("NULL"),
+            SnmpValue::Oid(oid) => {
+                let parts: Vec<String> = oid.iter().map(|c| c.to_string()).collect();
+                f.write_str(&parts.join("."))
+            }
+            SnmpValue::IpAddress(ip) => write!(f, "{}", ip),
+            SnmpValue::Counter32(v) | SnmpValue::Gauge32(v) | SnmpValue::TimeTicks(v) => write!(f, "{}", v),
+            SnmpValue::Opaque(v) => write_hex(f, v),
+            SnmpValue::Counter64(v) => write!(f, "{}", v),
+            SnmpValue::NoSuchObject => f.write_str("noSuchObject"),
+            SnmpValue::NoSuchInstance => f.write_str("noSuchInstance"),
+            SnmpValue::EndOfMibView => f.write_str("endOfMibView"),
+        }
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    f.write_str("0x")?;
+    for b in bytes {
+        write!(f, "{:02x}", b)?;
+    }
+    Ok(())
+}
+
+/// Decode an unsigned application type of at most `width` octets, plus
+/// the leading zero octet BER needs when the top bit is set.
+fn decode_unsigned(data: &[u8], width: usize) -> Result<u64> {
+    let significant = match data.iter().position(|&b| b != 0) {
+        Some(start) => &data[start..],
+        None => &[][..],
+    };
+    if data.is_empty() || significant.len() > width {
+        anyhow::bail!("BER: invalid unsigned length {}", data.len());
+    }
+    Ok(significant.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+}
+
+fn signed_octets(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
+fn unsigned_octets(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(7);
+    let mut out = Vec::with_capacity(9);
+    if bytes[start] & 0x80 != 0 {
+        out.push(0);
+    }
+    out.extend_from_slice(&bytes[start..]);
+    out
+}
@@ -18,6 +18,14 @@ pub fn format_mac_ieee(mac: &str) -> String {
         .to_lowercase()
 }
 
+/// Parse a MAC address written with `:`, `-` or `.` separators, or none,
+/// into IEEE format. None if it is not twelve hex digits.
+pub fn parse_mac(mac: &str) -> Option<String> {
+    let valid = mac.chars().all(|c| c.is_ascii_hexdigit() || matches!(c, ':' | '-' | '.'))
+        && mac.chars().filter(|c| c.is_ascii_hexdigit()).count() == 12;
+    valid.then(|| format_mac_ieee(mac))
+}
+
 /// Format uptime ticks (hundredths of a second) into human-readable string.
 pub fn format_uptime(ticks: i64) -> String {
     let seconds = ticks / 100;
@@ -41,3 +49,24 @@ pub fn format_uptime(ticks: i64) -> String {
         format!("{:02}:{:02}:{:02}", hours, minutes, secs)
     }
 }
+
+/// Format an interface speed in bits per second the way SNMP::Info
+/// does: one decimal place below ten units ("1.0 Gbps", "2.5 Gbps"),
+/// whole numbers above ("10 Gbps", "100 Mbps").
+pub fn format_speed(bps: u64) -> String {
+    const UNITS: [(u64, &str); 4] = [
+        (1_000_000_000_000, "Tbps"),
+        (1_000_000_000, "Gbps"),
+        (1_000_000, "Mbps"),
+        (1_000, "kbps"),
+    ];
+    for (scale, unit) in UNITS {
+        if bps >= scale * 10 {
+            return format!("{} {}", bps / scale, unit);
+        }
+        if bps >= scale {
+            return format!("{:.1} {}", bps as f64 / scale as f64, unit);
+        }
+    }
+    format!("{} bps", bps)
+}
@@ -10,6 +10,8 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
             .route("/object/device", web::get().to(api_list_devices))
             .route("/object/device/{ip}", web::get().to(api_get_device))
             .route("/object/device/{ip}/ports", web::get().to(api_device_ports))
+            .route("/object/device/{ip}/stp", web::get().to(api_device_stp))
+            .route("/object/node/{mac}/history", web::get().to(api_node_history))
             .route("/search/node", web::get().to(api_search_node))
             .route("/search/device", web::get().to(api_search_device))
             .route("/queue", web::get().to(api_list_jobs))
@@ -70,6 +72,39 @@ async fn api_device_ports(
     HttpResponse::Ok().json(ports)
 }
 
+async fn api_device_stp(
+    state: web::Data<super::AppState>,
+    session: Session,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Some(resp) = auth::require_auth(&session, &state.config) {
+        return resp;
+    }
+    let ip: ipnetwork::IpNetwork = match path.into_inner().parse() {
+        Ok(ip) => ip,
+        Err(_) => return HttpResponse::BadRequest().json(serde_json::json!({"error": "Invalid IP"})),
+    };
+    let instances = db::get_device_stp(&state.pool, &ip).await.unwrap_or_default();
+    let ports = db::get_device_port_stp(&state.pool, &ip).await.unwrap_or_default();
+    HttpResponse::Ok().json(serde_json::json!({"instances": instances, "ports": ports}))
+}
+
+async fn api_node_history(
+    state: web::Data<super::AppState>,
+    session: Session,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Some(resp) = auth::require_auth(&session, &state.config) {
+        return resp;
+    }
+    let Some(mac) = crate::util::parse_mac(&path.into_inner()) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({"error": "Invalid MAC"}));
+    };
+    let history = db::node_history(&state.pool, &mac).await.unwrap_or_default();
+    let moves = db::node_moves(&state.pool, &mac).await.unwrap_or_default();
+    HttpResponse::Ok().json(serde_json::json!({"mac": mac, "history": history, "moves": moves}))
+}
+
 async fn api_search_node(
     state: web::Data<super::AppState>,
     session: Session,
@@ -210,11 +210,20 @@ pub async fn search_device(
     }
 }
 
-pub async fn search_vlan(session: Session, state: web::Data<AppState>) -> HttpResponse {
+pub async fn search_vlan(
+    session: Session,
+    state: web::Data<AppState>,
+    query: web::Query<DeviceQuery>,
+) -> HttpResponse {
     if let Some(resp) = auth::require_auth(&session, &state.config) {
         return resp;
     }
-    HttpResponse::Ok().json(serde_json::json!({"status": "not_implemented"}))
+    if let Some(q) = &query.q {
+        let vlans = db::search_vlans(&state.pool, q).await.unwrap_or_default();
+        HttpResponse::Ok().json(vlans)
+    } else {
+        HttpResponse::Ok().json(serde_json::json!([]))
+    }
 }
 
 pub async fn search_port(session: Session, state: web::Data<AppState>) -> HttpResponse {
@@ -230,7 +239,24 @@ pub async fn report(session: Session, state: web::Data<AppState>, path: web::Pat
     if let Some(resp) = auth::require_auth(&session, &state.config) {
         return resp;
     }
-    HttpResponse::Ok().json(serde_json::json!({"report": path.into_inner(), "status": "not_implemented"}))
+    let name = path.into_inner();
+    match name.as_str() {
+        "devicepoestatus" => match db::poe_headroom(&state.pool).await {
+            Ok(rows) => HttpResponse::Ok().json(rows),
+            Err(e) => {
+                error!("Database error building report {}: {}", name, e);
+                HttpResponse::InternalServerError().json(serde_json::json!({"error": "Internal server error"}))
+            }
+        },
+        "spanningtree" => match tokio::try_join!(db::stp_roots(&state.pool), db::stp_blocking(&state.pool)) {
+            Ok((roots, blocking)) => HttpResponse::Ok().json(serde_json::json!({"roots": roots, "blocking": blocking})),
+            Err(e) => {
+                error!("Database error building report {}: {}", name, e);
+                HttpResponse::InternalServerError().json(serde_json::json!({"error": "Internal server error"}))
+            }
+        },
+        _ => HttpResponse::Ok().json(serde_json::json!({"report": name, "status": "not_implemented"})),
+    }
 }
 
 pub async fn admin_job_queue(state: web::Data<AppState>, session: Session) -> HttpResponse {
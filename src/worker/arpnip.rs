@@ -3,11 +3,12 @@
 use anyhow::{Context, Result};
 use ipnetwork::IpNetwork;
 use sqlx::PgPool;
-use tracing::{info, warn, error, debug};
+use tracing::{info, warn, debug};
 
 use crate::config::NetdiscoConfig;
 use crate::db;
-use crate::snmp::SnmpClient;
+use crate::snmp::auth::Access;
+use crate::snmp::ArpEntry;
 use crate::util::permission;
 
 /// Collect ARP table from a single device.
@@ -19,48 +20,76 @@ pub async fn arpnip_device(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetwor
 
     info!("Arpnipping device {}", ip);
 
-    let host = ip.ip().to_string();
-    let client = SnmpClient::from_config(config, &host)?;
+    let client = super::snmp_connect(config, pool, ip, Access::Read).await?;
 
-    let arp_entries = client.get_arp_table()
+    let arp_entries = client.get_arp_table().await
         .context("Failed to get ARP table")?;
 
     info!("  Found {} ARP entries", arp_entries.len());
 
-    let mut stored = 0;
-    for entry in &arp_entries {
-        if let Ok(entry_ip) = entry.ip.parse::<std::net::IpAddr>() {
-            let ip_network = IpNetwork::from(entry_ip);
-            if let Err(e) = db::upsert_node_ip(pool, &entry.mac, &ip_network).await {
-                debug!("Failed to store ARP {}->{}: {}", entry.mac, entry.ip, e);
-            } else {
-                stored += 1;
+    let (stored, deactivated) = store_arp_entries(config, pool, ip, &arp_entries).await?;
+
+    let msg = format!(
+        "Arpnip {}: stored {} of {} entries, deactivated {}",
+        ip, stored, arp_entries.len(), deactivated,
+    );
+    info!("{}", msg);
+    Ok(msg)
+}
+
+/// Store an arpnip run's entries from router `ip` in one transaction, as
+/// macsuck's `store_nodes` does, retrying if it deadlocks with another
+/// run. Returns how many entries were stored and how many deactivated.
+pub async fn store_arp_entries(
+    config: &NetdiscoConfig,
+    pool: &PgPool,
+    ip: &IpNetwork,
+    entries: &[ArpEntry],
+) -> Result<(usize, u64)> {
+    let mut sorted: Vec<(&str, IpNetwork)> = entries.iter()
+        .filter_map(|entry| match entry.ip.parse::<std::net::IpAddr>() {
+            Ok(addr) => Some((entry.mac.as_str(), IpNetwork::from(addr))),
+            Err(_) => {
+                debug!("Skipping ARP entry with bad IP {}", entry.ip);
+                None
+            }
+        })
+        .collect();
+    sorted.sort();
+
+    let mut attempt = 1;
+    loop {
+        match store_arp_entries_once(config, pool, ip, &sorted).await {
+            Err(e) if attempt < db::RUN_ATTEMPTS && db::is_retryable(&e) => {
+                warn!("  Storing ARP entries for {} failed, retrying: {:#}", ip, e);
+                attempt += 1;
             }
+            result => return result,
         }
     }
+}
 
-    // Mark old entries as inactive
-    if let Err(e) = sqlx::query(
-        "UPDATE node_ip SET active = false WHERE time_last < NOW() - interval '5 minutes' AND active = true"
-    )
-        .execute(pool)
-        .await
-    {
-        error!("Failed to deactivate old ARP entries: {}", e);
+async fn store_arp_entries_once(
+    config: &NetdiscoConfig,
+    pool: &PgPool,
+    ip: &IpNetwork,
+    entries: &[(&str, IpNetwork)],
+) -> Result<(usize, u64)> {
+    let mut tx = pool.begin().await?;
+    for (mac, addr) in entries {
+        db::upsert_node_ip(&mut tx, mac, addr, ip).await
+            .with_context(|| format!("Failed to store ARP {}->{}", mac, addr.ip()))?;
     }
 
-    // Update last_arpnip timestamp
-    if let Err(e) = sqlx::query("UPDATE device SET last_arpnip = NOW() WHERE ip = $1")
+    let deactivated = db::deactivate_stale_node_ips(&mut tx, ip, config.node_freshness).await
+        .context("Failed to deactivate stale ARP entries")?;
+    sqlx::query("UPDATE device SET last_arpnip = NOW() WHERE ip = $1")
         .bind(ip)
-        .execute(pool)
+        .execute(&mut *tx)
         .await
-    {
-        error!("Failed to update last_arpnip for {}: {}", ip, e);
-    }
-
-    let msg = format!("Arpnip {}: stored {} of {} entries", ip, stored, arp_entries.len());
-    info!("{}", msg);
-    Ok(msg)
+        .context("Failed to update last_arpnip")?;
+    tx.commit().await?;
+    Ok((entries.len(), deactivated))
 }
 
 /// Walk all devices for ARP table collection (scheduled).
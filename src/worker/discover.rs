@@ -4,15 +4,26 @@
 //! VLANs, neighbors, and modules.
 
 use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use futures::stream::{FuturesUnordered, StreamExt};
 use ipnetwork::IpNetwork;
 use sqlx::PgPool;
 use tracing::{info, warn, error, debug};
 
 use crate::config::NetdiscoConfig;
 use crate::db;
-use crate::snmp::SnmpClient;
+use crate::snmp::auth::Access;
+use crate::snmp::fingerprint::{enterprise_number, fingerprint};
+use crate::snmp::{
+    find_chassis, EntityInfo, InterfaceInfo, IpAddressInfo, NeighborInfo, PoeInfo, SnmpClient, SnmpVersion,
+    StpInfo, VlanTable,
+};
 use crate::models::device::Device;
-use crate::util::permission;
+use crate::models::device_ip::DeviceIp;
+use crate::models::device_module::DeviceModule;
+use crate::models::{DevicePortPower, DevicePortStp, DevicePortVlan, DevicePower, DeviceStp, DeviceVlan};
+use crate::util::{dns, format_speed, permission};
 
 /// Discover a single device by IP address.
 pub async fn discover_device(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetwork) -> Result<String> {
@@ -23,12 +34,11 @@ pub async fn discover_device(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetw
 
     info!("Discovering device {}", ip);
 
-    let host = ip.ip().to_string();
-    let client = SnmpClient::from_config(config, &host)
-        .context("Failed to create SNMP client")?;
+    let client = super::snmp_connect(config, pool, ip, Access::Read).await
+        .context("Failed to connect over SNMP")?;
 
     // 1. Get system information
-    let sys_info = client.get_system_info()
+    let sys_info = client.get_system_info().await
         .context("Failed to get system info")?;
 
     info!("  sysName: {:?}", sys_info.name);
@@ -40,6 +50,127 @@ pub async fn discover_device(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetw
             .collect::<String>()
     });
 
+    // Vendor from the sysObjectID enterprise arc, then OS and class
+    let enterprise = match sys_info.object_id.as_deref().and_then(enterprise_number) {
+        Some(number) => db::find_enterprise(pool, number).await.unwrap_or_else(|e| {
+            warn!("Failed to look up enterprise {} for {}: {}", number, ip, e);
+            None
+        }),
+        None => None,
+    };
+    let fp = fingerprint(&sys_info, enterprise.as_ref());
+    info!("  vendor: {:?} os: {:?} {:?}", fp.vendor, fp.os, fp.os_ver);
+
+    // The device's own addresses and interfaces, which decide the address
+    // the device is stored under
+    let addresses = match client.get_ip_addresses().await {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            warn!("Failed to get IP addresses for {}: {}", ip, e);
+            Vec::new()
+        }
+    };
+    let interfaces = match client.get_interfaces().await {
+        Ok(ifaces) => ifaces,
+        Err(e) => {
+            warn!("Failed to enumerate interfaces for {}: {}", ip, e);
+            Vec::new()
+        }
+    };
+    info!("  Found {} interfaces", interfaces.len());
+    let mut aliases = device_ips(ip, &addresses, &interfaces);
+
+    // Hardware inventory; the chassis gives the device serial and model.
+    // Read early, as the serial tells whether rows sharing an address are
+    // this device
+    let entities = match client.get_entities().await {
+        Ok(entities) => entities,
+        Err(e) => {
+            warn!("Failed to get entity inventory for {}: {}", ip, e);
+            Vec::new()
+        }
+    };
+    let chassis = find_chassis(&entities);
+    info!("  Found {} entities", entities.len());
+
+    let sys_name_ip = match &sys_info.name {
+        Some(name) if config.device_identity.iter().any(|m| m == "sysname") => {
+            dns::ip_from_hostname(name).await.and_then(|addr| addr.parse().ok())
+        }
+        _ => None,
+    };
+    let mut candidates = vec![*ip];
+    candidates.extend(aliases.iter().map(|a| a.alias));
+    let found = db::find_devices_by_addresses(pool, &candidates).await.unwrap_or_else(|e| {
+        warn!("Failed to look up existing devices for {}: {}", ip, e);
+        Vec::new()
+    });
+    // Only the row at the address polled and rows with this box's serial
+    // or sysName are this device; others just share an address with it
+    let serial = chassis.and_then(|c| c.serial.as_deref());
+    let (existing, conflicts): (Vec<_>, Vec<_>) = found.into_iter()
+        .partition(|row| row.ip == *ip || row.corroborated_by(sys_info.name.as_deref(), serial));
+    let (canonical, reason) = match canonical_ip(config, &aliases, &interfaces, sys_name_ip) {
+        Some((canonical, method)) if !conflicts.iter().any(|c| c.ip == canonical) => {
+            (canonical, format!("{} address", method))
+        }
+        _ => match existing.first() {
+            Some(first) => (first.ip, "address first discovered at".to_string()),
+            None => (*ip, "address discovered at".to_string()),
+        },
+    };
+
+    // Fold any other rows for this device into the canonical one
+    let mut decisions = Vec::new();
+    if canonical != *ip {
+        info!("  {} is stored under {} ({})", ip, canonical, reason);
+        decisions.push(format!("discovered via {}, stored under {} ({})", ip.ip(), canonical.ip(), reason));
+    }
+    for conflict in &conflicts {
+        warn!("  Not merging device {} into {}: it shares an address but not the serial or sysName", conflict.ip, canonical);
+        decisions.push(format!("not merged with device {}, which shares an address but not the serial or sysName",
+            conflict.ip.ip()));
+    }
+    for duplicate in existing.iter().map(|e| &e.ip).filter(|&&e| e != canonical) {
+        db::merge_device(pool, duplicate, &canonical).await
+            .with_context(|| format!("Failed to merge device {} into {}", duplicate, canonical))?;
+        info!("  Merged duplicate device {} into {}", duplicate, canonical);
+        decisions.push(format!("merged duplicate device {} into {}", duplicate.ip(), canonical.ip()));
+    }
+    let reached = *ip;
+    let ip = &canonical;
+    for alias in &mut aliases {
+        alias.ip = canonical;
+    }
+
+    let vlans = match client.get_vlan_table().await {
+        Ok(vlans) => vlans,
+        Err(e) => {
+            warn!("Failed to get VLANs for {}: {}", ip, e);
+            VlanTable::default()
+        }
+    };
+    info!("  Found {} VLANs", vlans.vlans.len());
+
+    let lag = match client.get_lag_members(&interfaces).await {
+        Ok(lag) => lag,
+        Err(e) => {
+            warn!("Failed to get link aggregation for {}: {}", ip, e);
+            BTreeMap::new()
+        }
+    };
+
+    // Spanning tree; PVST is read per VLAN
+    let vlan_ids: Vec<i32> = vlans.vlans.iter().map(|v| v.vlan).collect();
+    let stp = match client.get_stp(&vlan_ids).await {
+        Ok(stp) => Some(stp),
+        Err(e) => {
+            warn!("Failed to get spanning tree for {}: {}", ip, e);
+            None
+        }
+    };
+    let stp_states = stp.as_ref().map(port_stp_states).unwrap_or_default();
+
     // 2. Build device record
     let device = Device {
         ip: *ip,
@@ -53,17 +184,20 @@ pub async fn discover_device(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetw
         layers,
         ports: None,
         mac: None,
-        serial: None,
-        model: None,
+        serial: chassis.and_then(|c| c.serial.clone()),
+        model: chassis.and_then(|c| c.model.clone()),
         ps1_type: None, ps2_type: None, ps1_status: None, ps2_status: None,
         fan: None, slots: None,
-        vendor: None,
-        os: None, os_ver: None,
+        vendor: fp.vendor,
+        os: fp.os, os_ver: fp.os_ver,
         log: None,
-        snmp_ver: Some(config.snmpver as i32),
-        snmp_comm: config.community.first().cloned(),
-        snmp_class: None,
-        vtp_domain: None,
+        snmp_ver: Some(u8::from(client.version()) as i32),
+        snmp_comm: match client.version() {
+            SnmpVersion::V3 => None,
+            _ => Some(client.credentials().community.clone()),
+        },
+        snmp_class: fp.snmp_class,
+        vtp_domain: vlans.vtp_domain.clone(),
         last_discover: Some(chrono::Local::now().naive_local()),
         last_macsuck: None,
         last_arpnip: None,
@@ -76,20 +210,31 @@ pub async fn discover_device(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetw
     db::upsert_device(pool, &device).await
         .context("Failed to store device")?;
 
-    // 3. Discover interfaces
-    let interfaces = match client.get_interfaces() {
-        Ok(ifaces) => ifaces,
-        Err(e) => {
-            warn!("Failed to enumerate interfaces for {}: {}", ip, e);
-            Vec::new()
+    // Keep the previous inventory if this device no longer answers
+    // ENTITY-MIB, e.g. after a timeout
+    if !entities.is_empty() {
+        let now = chrono::Local::now().naive_local();
+        let modules: Vec<DeviceModule> = entities.iter()
+            .map(|e| entity_to_module(ip, e, now))
+            .collect();
+        if let Err(e) = db::replace_device_modules(pool, ip, &modules).await {
+            error!("Failed to store modules for {}: {}", ip, e);
         }
-    };
-    info!("  Found {} interfaces", interfaces.len());
+    }
 
+    if !decisions.is_empty() {
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+        let entries: Vec<String> = decisions.iter().map(|d| format!("{} {}", now, d)).collect();
+        if let Err(e) = db::append_device_log(pool, ip, &entries).await {
+            error!("Failed to log identity of {}: {}", ip, e);
+        }
+    }
+
+    // 3. Store interfaces
     for iface in &interfaces {
         let port = crate::models::device_port::DevicePort {
             ip: *ip,
-            port: iface.descr.clone(),
+            port: iface.port_name().to_string(),
             creation: None,
             descr: Some(iface.descr.clone()),
             up: iface.oper_status.map(|s| if s == 1 { "up".to_string() } else { "down".to_string() }),
@@ -97,95 +242,466 @@ pub async fn discover_device(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetw
             port_type: iface.if_type.clone(),
             duplex: None,
             duplex_admin: None,
-            speed: iface.speed.map(|s| s.to_string()),
-            name: None,
-            mac: None,
-            mtu: None,
-            stp: None,
+            speed: iface.speed_bps().map(format_speed),
+            name: iface.alias.clone(),
+            mac: iface.mac.clone(),
+            mtu: iface.mtu.map(|m| m as i32),
+            stp: stp_states.get(&iface.ifindex).cloned(),
             remote_ip: None, remote_port: None, remote_type: None, remote_id: None,
-            vlan: None,
-            pvid: None,
-            lastchange: None,
+            vlan: vlans.pvids.get(&iface.ifindex).map(|v| v.to_string()),
+            pvid: vlans.pvids.get(&iface.ifindex).copied(),
+            lastchange: iface.last_change,
             ifindex: Some(iface.ifindex),
             is_uplink: None,
             speed_admin: None,
-            is_master: None,
-            slave_of: None,
+            is_master: Some(iface.if_type.as_deref() == Some("ieee8023adLag")
+                || lag.values().any(|&agg| agg == iface.ifindex)),
+            slave_of: lag.get(&iface.ifindex)
+                .and_then(|agg| interfaces.iter().find(|i| i.ifindex == *agg))
+                .map(|agg| agg.port_name().to_string()),
             custom_fields: None,
             tags: None,
         };
 
-        if let Err(e) = sqlx::query(
-            r#"INSERT INTO device_port (ip, port, descr, up, up_admin, type, speed, ifindex)
-               VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-               ON CONFLICT (port, ip) DO UPDATE SET
-                descr = EXCLUDED.descr,
-                up = EXCLUDED.up,
-                up_admin = EXCLUDED.up_admin,
-                type = EXCLUDED.type,
-                speed = EXCLUDED.speed,
-                ifindex = EXCLUDED.ifindex"#
-        )
-            .bind(port.ip)
-            .bind(&port.port)
-            .bind(&port.descr)
-            .bind(&port.up)
-            .bind(&port.up_admin)
-            .bind(&port.port_type)
-            .bind(&port.speed)
-            .bind(port.ifindex)
-            .execute(pool)
-            .await
-        {
+        if let Err(e) = db::upsert_device_port(pool, &port).await {
             error!("Failed to store port {} on {}: {}", port.port, ip, e);
         }
     }
 
-    // 4. Try to discover neighbors (LLDP/CDP)
-    if let Err(e) = discover_neighbors(config, pool, ip, &client).await {
+    // Drop ports the device no longer has, including any stored under
+    // ifDescr before ifName was used as the port name
+    if !interfaces.is_empty() {
+        let names: Vec<String> = interfaces.iter().map(|i| i.port_name().to_string()).collect();
+        if let Err(e) = db::delete_stale_ports(pool, ip, &names).await {
+            error!("Failed to remove old ports on {}: {}", ip, e);
+        }
+    }
+
+    // VLANs, unless the device reported none at all
+    if !vlans.vlans.is_empty() {
+        let now = chrono::Local::now().naive_local();
+        let (device_vlans, port_vlans) = vlan_rows(ip, &vlans, &interfaces, now);
+        if let Err(e) = db::replace_device_vlans(pool, ip, &device_vlans, &port_vlans).await {
+            error!("Failed to store VLANs for {}: {}", ip, e);
+        }
+    }
+
+    // PoE budget and port power
+    match client.get_poe(&interfaces).await {
+        Ok(poe) if !poe.modules.is_empty() || !poe.ports.is_empty() => {
+            let (modules, ports) = power_rows(ip, &poe, &interfaces);
+            info!("  Found {} PoE modules, {} PoE ports", modules.len(), ports.len());
+            if let Err(e) = db::replace_device_power(pool, ip, &modules, &ports).await {
+                error!("Failed to store PoE status for {}: {}", ip, e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to get PoE status for {}: {}", ip, e),
+    }
+
+    // Spanning tree instances, kept as they were if the walk failed
+    if let Some(stp) = &stp {
+        let now = chrono::Local::now().naive_local();
+        let (instances, ports) = stp_rows(ip, stp, &interfaces, now);
+        info!("  Found {} spanning tree instances ({})", instances.len(), stp.protocol.as_deref().unwrap_or("none"));
+        if let Err(e) = db::replace_device_stp(pool, ip, &instances, &ports).await {
+            error!("Failed to store spanning tree for {}: {}", ip, e);
+        }
+    }
+
+    // Keep the previous aliases if the address tables failed
+    if !addresses.is_empty() {
+        info!("  Found {} IP aliases", aliases.len());
+        if let Err(e) = db::replace_device_ips(pool, ip, &aliases).await {
+            error!("Failed to store IP aliases for {}: {}", ip, e);
+        }
+        let subnets = device_subnets(&aliases);
+        if let Err(e) = db::upsert_subnets(pool, &subnets).await {
+            error!("Failed to store subnets for {}: {}", ip, e);
+        }
+    }
+
+    // 4. Neighbors (LLDP/CDP)
+    let neighbors = discover_neighbors(config, pool, ip, &client, &interfaces).await.unwrap_or_else(|e| {
         warn!("Failed to discover neighbors for {}: {}", ip, e);
+        Vec::new()
+    });
+
+    // Uplinks: ports facing a known device, and trunks
+    if !interfaces.is_empty() {
+        let mut known = Vec::new();
+        for neighbor in &neighbors {
+            let Some(remote) = neighbor.remote_ip else { continue };
+            if db::device_known(pool, &IpNetwork::from(remote)).await.unwrap_or(false) {
+                known.push(neighbor.ifindex);
+            }
+        }
+        let uplinks: Vec<String> = uplink_ports(&lag, &known, &vlans).iter()
+            .filter_map(|ifindex| interfaces.iter().find(|i| i.ifindex == *ifindex))
+            .map(|i| i.port_name().to_string())
+            .collect();
+        debug!("  Uplinks: {:?}", uplinks);
+        if let Err(e) = db::set_port_uplinks(pool, ip, &uplinks).await {
+            error!("Failed to mark uplinks on {}: {}", ip, e);
+        }
     }
 
-    let msg = format!("Discovered {} with {} interfaces", ip, interfaces.len());
+    let msg = if reached == *ip {
+        format!("Discovered {} with {} interfaces", ip, interfaces.len())
+    } else {
+        format!("Discovered {} (via {}) with {} interfaces", ip, reached, interfaces.len())
+    };
     info!("{}", msg);
     Ok(msg)
 }
 
-/// Discover neighbors via LLDP and CDP.
+/// A device_module row for an entPhysicalTable entry.
+pub fn entity_to_module(ip: &IpNetwork, entity: &EntityInfo, now: chrono::NaiveDateTime) -> DeviceModule {
+    DeviceModule {
+        ip: *ip,
+        index: entity.index,
+        description: entity.descr.clone(),
+        module_type: entity.vendor_type.clone(),
+        parent: entity.contained_in,
+        name: entity.name.clone(),
+        class: entity.class.clone(),
+        pos: entity.pos,
+        hw_ver: entity.hw_ver.clone(),
+        fw_ver: entity.fw_ver.clone(),
+        sw_ver: entity.sw_ver.clone(),
+        serial: entity.serial.clone(),
+        model: entity.model.clone(),
+        fru: entity.fru,
+        creation: None,
+        last_discover: Some(now),
+    }
+}
+
+/// device_vlan and device_port_vlan rows for a device's VLAN table.
+/// Untagged membership is stored as `native`, with `vlantype` "untagged"
+/// or "tagged".
+pub fn vlan_rows(
+    ip: &IpNetwork,
+    vlans: &VlanTable,
+    interfaces: &[InterfaceInfo],
+    now: chrono::NaiveDateTime,
+) -> (Vec<DeviceVlan>, Vec<DevicePortVlan>) {
+    let device_vlans = vlans.vlans.iter()
+        .map(|v| DeviceVlan {
+            ip: *ip,
+            vlan: v.vlan,
+            description: v.name.clone(),
+            creation: None,
+            last_discover: Some(now),
+        })
+        .collect();
+    let port_vlans = vlans.members.iter()
+        .filter_map(|m| {
+            let iface = interfaces.iter().find(|i| i.ifindex == m.ifindex)?;
+            Some(DevicePortVlan {
+                ip: *ip,
+                port: iface.port_name().to_string(),
+                vlan: m.vlan,
+                native: m.untagged,
+                creation: None,
+                last_discover: Some(now),
+                vlantype: Some(if m.untagged { "untagged" } else { "tagged" }.to_string()),
+            })
+        })
+        .collect();
+    (device_vlans, port_vlans)
+}
+
+/// device_power and device_port_power rows for a device's PSE tables.
+/// PSE ports [`get_poe`](crate::snmp::SnmpClient::get_poe) could not match
+/// to an interface are skipped.
+pub fn power_rows(ip: &IpNetwork, poe: &PoeInfo, interfaces: &[InterfaceInfo]) -> (Vec<DevicePower>, Vec<DevicePortPower>) {
+    let modules = poe.modules.iter()
+        .map(|m| DevicePower {
+            ip: *ip,
+            module: m.group,
+            power: m.power,
+            status: m.status.clone(),
+            consumption: m.consumption,
+        })
+        .collect();
+    let ports = poe.ports.iter()
+        .filter_map(|p| {
+            let Some(iface) = interfaces.iter().find(|i| Some(i.ifindex) == p.ifindex) else {
+                debug!("PSE port {}.{} on {} matches no interface", p.group, p.port, ip);
+                return None;
+            };
+            Some(DevicePortPower {
+                ip: *ip,
+                port: iface.port_name().to_string(),
+                module: Some(p.group),
+                admin: p.admin.clone(),
+                status: p.status.clone(),
+                class: p.class.clone(),
+                power: p.power,
+            })
+        })
+        .collect();
+    (modules, ports)
+}
+
+/// Each port's `device_port.stp`: "blocking" if it blocks in any instance,
+/// else its state in the lowest instance it is in.
+pub fn port_stp_states(stp: &StpInfo) -> BTreeMap<i32, String> {
+    let mut states: BTreeMap<i32, String> = BTreeMap::new();
+    for instance in &stp.instances {
+        for (ifindex, state) in &instance.ports {
+            let current = states.entry(*ifindex).or_insert_with(|| state.clone());
+            if state == "blocking" {
+                *current = state.clone();
+            }
+        }
+    }
+    states
+}
+
+/// device_stp and device_port_stp rows for a device's spanning tree.
+/// Ports are matched to interfaces by ifIndex; others are skipped.
+pub fn stp_rows(
+    ip: &IpNetwork,
+    stp: &StpInfo,
+    interfaces: &[InterfaceInfo],
+    now: chrono::NaiveDateTime,
+) -> (Vec<DeviceStp>, Vec<DevicePortStp>) {
+    let port_name = |ifindex: i32| interfaces.iter()
+        .find(|i| i.ifindex == ifindex)
+        .map(|i| i.port_name().to_string());
+    let instances = stp.instances.iter()
+        .map(|inst| DeviceStp {
+            ip: *ip,
+            instance: inst.instance,
+            protocol: stp.protocol.clone(),
+            vlans: inst.vlans.clone(),
+            bridge: inst.bridge.clone(),
+            root: inst.root.clone(),
+            root_cost: inst.root_cost,
+            root_port: inst.root_port.and_then(port_name),
+            last_discover: Some(now),
+        })
+        .collect();
+    let ports = stp.instances.iter()
+        .flat_map(|inst| inst.ports.iter().map(move |(ifindex, state)| (inst.instance, *ifindex, state)))
+        .filter_map(|(instance, ifindex, state)| Some(DevicePortStp {
+            ip: *ip,
+            port: port_name(ifindex)?,
+            instance,
+            state: Some(state.clone()),
+        }))
+        .collect();
+    (instances, ports)
+}
+
+/// device_ip rows for a device's addresses, with the port each is on.
+///
+/// Loopback (127/8, ::1), link-local and unspecified addresses are
+/// skipped; they are the same on every device and identify none of them.
+pub fn device_ips(ip: &IpNetwork, addresses: &[IpAddressInfo], interfaces: &[InterfaceInfo]) -> Vec<DeviceIp> {
+    let mut aliases: Vec<DeviceIp> = Vec::new();
+    for address in addresses {
+        let addr = address.ip;
+        let link_local = match addr {
+            IpAddr::V4(v4) => v4.is_link_local(),
+            IpAddr::V6(v6) => v6.is_unicast_link_local(),
+        };
+        if addr.is_loopback() || addr.is_unspecified() || link_local {
+            continue;
+        }
+        let alias = IpNetwork::from(addr);
+        if aliases.iter().any(|a| a.alias == alias) {
+            continue;
+        }
+        aliases.push(DeviceIp {
+            ip: *ip,
+            alias,
+            subnet: address.subnet(),
+            port: address.ifindex
+                .and_then(|ifindex| interfaces.iter().find(|i| i.ifindex == ifindex))
+                .map(|i| i.port_name().to_string()),
+            dns: None,
+            creation: None,
+        });
+    }
+    aliases
+}
+
+/// The distinct subnets of a device's aliases, leaving out host routes
+/// such as loopback /32s.
+pub fn device_subnets(aliases: &[DeviceIp]) -> Vec<IpNetwork> {
+    let mut subnets: Vec<IpNetwork> = Vec::new();
+    for subnet in aliases.iter().filter_map(|a| a.subnet) {
+        let host_len = if subnet.is_ipv4() { 32 } else { 128 };
+        if subnet.prefix() < host_len && !subnets.contains(&subnet) {
+            subnets.push(subnet);
+        }
+    }
+    subnets
+}
+
+/// The address a device should be stored under, by the first
+/// `device_identity` method that picks one of its aliases, with the name of
+/// that method.
+///
+/// - `loopback`: the lowest address on a softwareLoopback interface
+/// - `sysname`: the address sysName resolves to in DNS (`sysname_ip`)
+/// - `lowest`: the lowest address
+///
+/// Only aliases the discover ACL permits are considered, so the device can
+/// be polled at the address it is stored under.
+pub fn canonical_ip(
+    config: &NetdiscoConfig,
+    aliases: &[DeviceIp],
+    interfaces: &[InterfaceInfo],
+    sysname_ip: Option<IpAddr>,
+) -> Option<(IpNetwork, &'static str)> {
+    let mut permitted: Vec<IpNetwork> = aliases.iter()
+        .map(|a| a.alias)
+        .filter(|alias| permission::is_permitted(alias, &config.discover_only, &config.discover_no))
+        .collect();
+    permitted.sort_by_key(|alias| alias.ip());
+
+    let on_loopback = |alias: &IpNetwork| aliases.iter()
+        .filter(|a| a.alias == *alias)
+        .filter_map(|a| a.port.as_deref())
+        .any(|port| interfaces.iter()
+            .any(|i| i.port_name() == port && i.if_type.as_deref() == Some("softwareLoopback")));
+
+    for method in &config.device_identity {
+        let (found, name) = match method.as_str() {
+            "loopback" => (permitted.iter().find(|a| on_loopback(a)), "loopback"),
+            "sysname" => (permitted.iter().find(|a| Some(a.ip()) == sysname_ip), "sysName"),
+            "lowest" => (permitted.first(), "lowest"),
+            other => {
+                warn!("Unknown device_identity method '{}'", other);
+                continue;
+            }
+        };
+        if let Some(&alias) = found {
+            return Some((alias, name));
+        }
+    }
+    None
+}
+
+/// The ifIndexes of a device's uplinks: ports with a neighbor that is a
+/// known device (`neighbor_ports`) and ports carrying tagged VLANs. An
+/// aggregate is an uplink if any member is, and then so are all its
+/// members.
+pub fn uplink_ports(lag: &BTreeMap<i32, i32>, neighbor_ports: &[i32], vlans: &VlanTable) -> BTreeSet<i32> {
+    let mut uplinks: BTreeSet<i32> = neighbor_ports.iter().copied()
+        .chain(vlans.members.iter().filter(|m| !m.untagged).map(|m| m.ifindex))
+        .collect();
+    for (member, agg) in lag {
+        if uplinks.contains(member) {
+            uplinks.insert(*agg);
+        }
+    }
+    for (member, agg) in lag {
+        if uplinks.contains(agg) {
+            uplinks.insert(*member);
+        }
+    }
+    uplinks
+}
+
+/// Discover neighbors via LLDP and CDP: record them on the local ports and
+/// queue new devices for discovery. Returns the neighbor on each port.
 async fn discover_neighbors(
     config: &NetdiscoConfig,
-    _pool: &PgPool,
+    pool: &PgPool,
     device_ip: &IpNetwork,
     client: &SnmpClient,
-) -> Result<()> {
-    if !config.discover_neighbors {
-        return Ok(());
+    interfaces: &[InterfaceInfo],
+) -> Result<Vec<NeighborInfo>> {
+    debug!("Discovering neighbors for {}", device_ip);
+
+    let lldp = client.get_lldp_neighbors(interfaces).await;
+    let cdp = client.get_cdp_neighbors().await;
+    if let (Err(lldp), Err(cdp)) = (&lldp, &cdp) {
+        return Err(anyhow::anyhow!("LLDP: {}; CDP: {}", lldp, cdp));
     }
+    let lldp = lldp.unwrap_or_else(|e| { debug!("  LLDP: {}", e); Vec::new() });
+    let cdp = cdp.unwrap_or_else(|e| { debug!("  CDP: {}", e); Vec::new() });
 
-    debug!("Discovering neighbors for {}", device_ip);
+    let neighbors = merge_neighbors(&lldp, &cdp);
+    let mut ports = Vec::new();
+    for neighbor in &neighbors {
+        let Some(iface) = interfaces.iter().find(|i| i.ifindex == neighbor.ifindex) else { continue };
+        info!("  {} neighbor on {}: {:?} {:?} port {:?}", neighbor.protocol.to_uppercase(), iface.port_name(),
+            neighbor.remote_id, neighbor.remote_ip, neighbor.remote_port);
+        ports.push((iface.port_name().to_string(), neighbor.clone()));
+    }
+    db::set_port_neighbors(pool, device_ip, &ports).await
+        .context("Failed to store neighbors")?;
 
-    // Try LLDP first
-    let lldp_names = client.walk(&crate::snmp::oids::LLDP_REM_SYS_NAME).unwrap_or_default();
-    let _lldp_ports = client.walk(&crate::snmp::oids::LLDP_REM_PORT_ID).unwrap_or_default();
-    let _lldp_addrs = client.walk(&crate::snmp::oids::LLDP_REM_MAN_ADDR).unwrap_or_default();
+    if !config.discover_neighbors {
+        return Ok(neighbors);
+    }
 
-    for (_oid, name_bytes) in &lldp_names {
-        let remote_name = String::from_utf8_lossy(name_bytes).to_string();
-        info!("  LLDP neighbor: {}", remote_name);
-        // TODO: resolve name to IP/auto-discover
+    let mut queued = 0;
+    let mut seen = Vec::new();
+    for neighbor in lldp.iter().chain(&cdp) {
+        let Some(ip) = discoverable_neighbor(config, neighbor) else { continue };
+        if ip.ip() == device_ip.ip() || seen.contains(&ip) {
+            continue;
+        }
+        seen.push(ip);
+        if db::device_known(pool, &ip).await? || db::job_is_queued(pool, "discover", &ip).await? {
+            continue;
+        }
+        db::enqueue_job(pool, "discover", Some(&ip), None, None).await?;
+        info!("  Queued discover of neighbor {}", ip);
+        queued += 1;
     }
+    debug!("Queued {} new neighbors of {}", queued, device_ip);
+
+    Ok(neighbors)
+}
 
-    // Try CDP
-    let cdp_devices = client.walk(&crate::snmp::oids::CDP_CACHE_DEVICE_ID).unwrap_or_default();
-    for (_oid, device_bytes) in &cdp_devices {
-        let remote_device = String::from_utf8_lossy(device_bytes).to_string();
-        info!("  CDP neighbor: {}", remote_device);
+/// One neighbor per local port: LLDP where a port has it, else CDP.
+pub fn merge_neighbors(lldp: &[NeighborInfo], cdp: &[NeighborInfo]) -> Vec<NeighborInfo> {
+    let mut by_port: BTreeMap<i32, NeighborInfo> = BTreeMap::new();
+    for neighbor in lldp.iter().chain(cdp) {
+        by_port.entry(neighbor.ifindex).or_insert_with(|| neighbor.clone());
     }
+    by_port.into_values().collect()
+}
 
-    Ok(())
+/// The address to discover a neighbor at, unless the discover ACL or the
+/// discover_waps, discover_phones or discover_routed_neighbors settings
+/// rule it out.
+pub fn discoverable_neighbor(config: &NetdiscoConfig, neighbor: &NeighborInfo) -> Option<IpNetwork> {
+    let ip = neighbor.remote_ip
+        .filter(|ip| !ip.is_unspecified() && !ip.is_loopback() && !ip.is_multicast())?;
+    let name = neighbor.remote_id.as_deref().unwrap_or("?");
+    if !config.discover_phones && neighbor.is_phone() {
+        debug!("Skipping phone {} ({})", name, ip);
+        return None;
+    }
+    if !config.discover_waps && neighbor.is_wap() {
+        debug!("Skipping wireless AP {} ({})", name, ip);
+        return None;
+    }
+    if !config.discover_routed_neighbors && neighbor.is_routed() {
+        debug!("Skipping routed neighbor {} ({})", name, ip);
+        return None;
+    }
+    let ip = IpNetwork::from(ip);
+    if !permission::is_permitted(&ip, &config.discover_only, &config.discover_no) {
+        debug!("Neighbor {} ({}) is not permitted by discover ACL", name, ip);
+        return None;
+    }
+    Some(ip)
 }
 
 /// Discover all known devices (scheduled task).
+///
+/// Devices are polled concurrently, as many at once as there are
+/// backend workers.
 pub async fn discover_all(config: &NetdiscoConfig, pool: &PgPool) -> Result<String> {
     info!("Starting discovery of all devices");
     let devices = db::list_devices(pool, None).await?;
@@ -193,11 +709,22 @@ pub async fn discover_all(config: &NetdiscoConfig, pool: &PgPool) -> Result<Stri
     let mut success = 0;
     let mut failed = 0;
 
-    for device in &devices {
-        match discover_device(config, pool, &device.ip).await {
+    let concurrency = crate::backend::calculate_workers(&config.workers.tasks);
+    let mut queue = devices.iter();
+    let mut running = FuturesUnordered::new();
+
+    loop {
+        while running.len() < concurrency {
+            match queue.next() {
+                Some(device) => running.push(discover_queued(config, pool, &device.ip)),
+                None => break,
+            }
+        }
+        let Some((ip, result)) = running.next().await else { break };
+        match result {
             Ok(_) => success += 1,
             Err(e) => {
-                warn!("Failed to discover {}: {}", device.ip, e);
+                warn!("Failed to discover {}: {}", ip, e);
                 failed += 1;
             }
         }
@@ -207,3 +734,7 @@ pub async fn discover_all(config: &NetdiscoConfig, pool: &PgPool) -> Result<Stri
     info!("{}", msg);
     Ok(msg)
 }
+
+async fn discover_queued(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetwork) -> (IpNetwork, Result<String>) {
+    (*ip, discover_device(config, pool, ip).await)
+}
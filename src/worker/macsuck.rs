@@ -1,14 +1,18 @@
 //! MAC address table collection (macsuck) worker.
 
 use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
 use ipnetwork::IpNetwork;
 use sqlx::PgPool;
-use tracing::{info, warn, error, debug};
+use tracing::{info, warn, debug};
 
 use crate::config::NetdiscoConfig;
 use crate::db;
-use crate::snmp::SnmpClient;
+use crate::snmp::auth::Access;
+use crate::snmp::{MacEntry, UnknownContext};
 use crate::models::node::Node;
+use crate::models::DeviceVlan;
 use crate::util::permission;
 
 /// Collect MAC address table from a single device.
@@ -20,60 +24,245 @@ pub async fn macsuck_device(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetwo
 
     info!("Macsucking device {}", ip);
 
-    let host = ip.ip().to_string();
-    let client = SnmpClient::from_config(config, &host)?;
+    let client = super::snmp_connect(config, pool, ip, Access::Read).await?;
 
-    // Get MAC address table via SNMP
-    let mac_entries = client.get_mac_table()
+    let device_ports = db::get_device_ports(pool, ip).await?;
+    if device_ports.is_empty() {
+        warn!("  No ports stored for {}; discover it before macsuck", ip);
+    }
+    let port_names: BTreeMap<i32, String> = device_ports.iter()
+        .filter_map(|p| Some((p.ifindex?, p.port.clone())))
+        .collect();
+    // Uplinks and ports facing other known devices see the MACs of every
+    // host behind them, so their entries are dropped unless bleed is on
+    let mut inter_switch: BTreeSet<String> = BTreeSet::new();
+    if !config.macsuck_bleed {
+        inter_switch.extend(device_ports.iter().filter(|p| p.is_uplink == Some(true)).map(|p| p.port.clone()));
+        inter_switch.extend(db::get_device_neighbor_ports(pool, ip).await?);
+    }
+    let vlans = db::get_device_vlans(pool, ip).await?;
+
+    // Q-BRIDGE (or VLAN-unaware) FDB from the default context
+    let mac_entries = client.get_mac_table().await
         .context("Failed to get MAC table")?;
+    let bridge_ports = client.get_bridge_ports().await
+        .context("Failed to get bridge port table")?;
+    let mut sightings: Vec<(MacEntry, String)> = mac_entries.into_iter()
+        .filter_map(|entry| {
+            let port = entry_port(&entry, &bridge_ports, &port_names)?.to_string();
+            Some((entry, port))
+        })
+        .collect();
 
-    info!("  Found {} MAC entries", mac_entries.len());
+    // Without Q-BRIDGE, agents such as Cisco's only give VLAN 1's FDB in
+    // the default context; the others need community or context indexing
+    let vlan_aware = sightings.iter().any(|(entry, _)| entry.vlan.is_some());
+    let mut deactivate = true;
+    if config.macsuck_all_vlans && !vlan_aware {
+        let vlan_ids: Vec<i32> = vlans.iter()
+            .map(|v| v.vlan)
+            .filter(|&vlan| !config.macsuck_no_unnamed || vlan_is_named(vlan, &vlans))
+            .collect();
+        let pass = per_vlan_sightings(&vlan_ids, |vlan| {
+            let vlan_client = client.for_vlan(vlan);
+            let port_names = &port_names;
+            async move {
+                let table = vlan_client.get_mac_table().await?;
+                // Bridge port numbering can differ between VLANs. Without
+                // it none of the VLAN's entries has a port, so the VLAN
+                // counts as failed rather than as empty
+                let bridge_ports = vlan_client.get_bridge_ports().await
+                    .context("Failed to get bridge port table")?;
+                Ok(table.into_iter()
+                    .filter_map(|entry| {
+                        let port = entry_port(&entry, &bridge_ports, port_names)?.to_string();
+                        Some((MacEntry { vlan: Some(vlan), ..entry }, port))
+                    })
+                    .collect())
+            }
+        }).await;
+        match pass {
+            Err(e) => {
+                // A timeout looks the same as an agent without community or
+                // context indexing, and the default context may then hold
+                // only part of the FDB (VLAN 1 on Cisco)
+                if !no_vlan_indexing(&e) {
+                    deactivate = false;
+                }
+                warn!("  No per-VLAN FDB from {}, keeping the default context's: {:#}", ip, e);
+            }
+            Ok(pass) => {
+                if !pass.failed.is_empty() {
+                    warn!("  No FDB for VLANs {:?} on {}; not deactivating nodes this run", pass.failed, ip);
+                    deactivate = false;
+                }
+                if pass.sightings.is_empty() {
+                    warn!("  No per-VLAN FDB from {}; keeping the default context's", ip);
+                } else {
+                    sightings = pass.sightings;
+                }
+            }
+        }
+    }
+
+    info!("  Found {} MAC entries", sightings.len());
+
+    if config.macsuck_no_unnamed {
+        sightings.retain(|(entry, _)| entry.vlan.is_none_or(|vlan| vlan_is_named(vlan, &vlans)));
+    }
+    let dropped = drop_inter_switch(&mut sightings, &inter_switch);
+    if dropped > 0 {
+        info!("  Dropped {} MACs on uplink or inter-switch ports of {}", dropped, ip);
+    }
+
+    let (moved, deactivated) = store_nodes(config, pool, ip, &sightings, deactivate).await?;
+
+    let msg = format!(
+        "Macsuck {}: stored {} MACs ({} moved), deactivated {}, dropped {} on uplinks",
+        ip, sightings.len(), moved, deactivated, dropped,
+    );
+    info!("{}", msg);
+    Ok(msg)
+}
+
+/// Store a macsuck run's sightings on `ip` in one transaction: upsert the
+/// nodes, deactivate the switch's nodes the run did not see (unless
+/// `deactivate` is false, for a run that missed part of the FDB) and stamp
+/// `last_macsuck`. The transaction is retried if it deadlocks with another
+/// run. Returns how many MACs moved and how many nodes were deactivated.
+pub async fn store_nodes(
+    config: &NetdiscoConfig,
+    pool: &PgPool,
+    ip: &IpNetwork,
+    sightings: &[(MacEntry, String)],
+    deactivate: bool,
+) -> Result<(usize, u64)> {
+    // Lock rows in MAC order, the same order as any concurrent run
+    let mut sorted: Vec<&(MacEntry, String)> = sightings.iter().collect();
+    sorted.sort_by(|(a, a_port), (b, b_port)| (&a.mac, a_port).cmp(&(&b.mac, b_port)));
+
+    let mut attempt = 1;
+    loop {
+        match store_nodes_once(config, pool, ip, &sorted, deactivate).await {
+            Err(e) if attempt < db::RUN_ATTEMPTS && db::is_retryable(&e) => {
+                warn!("  Storing nodes for {} failed, retrying: {:#}", ip, e);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
 
-    let mut stored = 0;
-    for entry in &mac_entries {
-        // Store each MAC entry
+async fn store_nodes_once(
+    config: &NetdiscoConfig,
+    pool: &PgPool,
+    ip: &IpNetwork,
+    sightings: &[&(MacEntry, String)],
+    deactivate: bool,
+) -> Result<(usize, u64)> {
+    let mut tx = pool.begin().await?;
+    let mut moved = 0;
+    for (entry, port) in sightings.iter().copied() {
         let node = Node {
             mac: entry.mac.clone(),
             switch: *ip,
-            port: format!("bridge-port-{}", entry.bridge_port),
-            vlan: entry.vlan.map(|v| v.to_string()),
+            port: port.clone(),
+            vlan: Some(entry.vlan.unwrap_or(0).to_string()),
             active: Some(true),
             oui: Some(Node::extract_oui(&entry.mac)),
             time_first: None,
             time_recent: None,
             time_last: None,
         };
-
-        if let Err(e) = db::upsert_node(pool, &node).await {
-            debug!("Failed to store MAC {}: {}", entry.mac, e);
-        } else {
-            stored += 1;
+        let from = db::upsert_node(&mut tx, &node).await
+            .with_context(|| format!("Failed to store MAC {} on {}", entry.mac, port))?;
+        for (switch, port) in &from {
+            debug!("  {}", node.move_entry(switch, port));
         }
+        moved += usize::from(!from.is_empty());
     }
 
-    // Mark old entries as inactive
-    if let Err(e) = sqlx::query(
-        "UPDATE node SET active = false WHERE switch = $1 AND time_last < NOW() - interval '5 minutes' AND active = true"
-    )
+    // NOW() is fixed for the transaction, so rows this run did not refresh
+    // are the ones with an older time_last
+    let deactivated = if deactivate {
+        db::deactivate_stale_nodes(&mut tx, ip, config.node_freshness).await
+            .context("Failed to deactivate stale nodes")?
+    } else {
+        0
+    };
+    sqlx::query("UPDATE device SET last_macsuck = NOW() WHERE ip = $1")
         .bind(ip)
-        .execute(pool)
+        .execute(&mut *tx)
         .await
-    {
-        error!("Failed to deactivate old nodes for {}: {}", ip, e);
-    }
+        .context("Failed to update last_macsuck")?;
+    tx.commit().await?;
+    Ok((moved, deactivated))
+}
 
-    // Update last_macsuck timestamp
-    if let Err(e) = sqlx::query("UPDATE device SET last_macsuck = NOW() WHERE ip = $1")
-        .bind(ip)
-        .execute(pool)
-        .await
-    {
-        error!("Failed to update last_macsuck for {}: {}", ip, e);
+/// A per-VLAN FDB pass from [`per_vlan_sightings`].
+#[derive(Debug, Default)]
+pub struct VlanSightings {
+    /// Sightings from every VLAN that was read, tagged with its VLAN.
+    pub sightings: Vec<(MacEntry, String)>,
+    /// VLANs that could not be read; their nodes were not seen this run.
+    pub failed: Vec<i32>,
+}
+
+/// Read each of `vlans` with `read`, one at a time. If the first VLAN
+/// fails the agent most likely has no community or context indexing, which
+/// would fail every VLAN the same way, so its error is returned without
+/// waiting out the rest (see [`no_vlan_indexing`]). Later failures are
+/// collected in [`VlanSightings::failed`].
+pub async fn per_vlan_sightings<F, Fut>(vlans: &[i32], mut read: F) -> Result<VlanSightings>
+where
+    F: FnMut(i32) -> Fut,
+    Fut: Future<Output = Result<Vec<(MacEntry, String)>>>,
+{
+    let mut pass = VlanSightings::default();
+    for (n, &vlan) in vlans.iter().enumerate() {
+        match read(vlan).await {
+            Ok(sightings) => pass.sightings.extend(sightings),
+            Err(e) if n == 0 => return Err(e.context(format!("No FDB for VLAN {}", vlan))),
+            Err(e) => {
+                debug!("  No FDB for VLAN {}: {:#}", vlan, e);
+                pass.failed.push(vlan);
+            }
+        }
     }
+    Ok(pass)
+}
 
-    let msg = format!("Macsuck {}: stored {} of {} MACs", ip, stored, mac_entries.len());
-    info!("{}", msg);
-    Ok(msg)
+/// Whether a per-VLAN pass failed because the agent definitely has no
+/// per-VLAN contexts (an SNMPv3 unknownContext report), rather than for a
+/// reason such as a timeout, which leaves the default context's FDB
+/// possibly incomplete.
+pub fn no_vlan_indexing(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<UnknownContext>().is_some()
+}
+
+/// The port an FDB entry was learned on, by way of the bridge port's
+/// ifIndex (`bridge_ports`, from dot1dBasePortIfIndex) and the port name
+/// discover stored for that ifIndex (`port_names`).
+pub fn entry_port<'a>(
+    entry: &MacEntry,
+    bridge_ports: &BTreeMap<i32, i32>,
+    port_names: &'a BTreeMap<i32, String>,
+) -> Option<&'a str> {
+    let ifindex = bridge_ports.get(&entry.bridge_port)?;
+    port_names.get(ifindex).map(String::as_str)
+}
+
+/// Remove sightings learned on any of `ports`, returning how many went.
+pub fn drop_inter_switch(sightings: &mut Vec<(MacEntry, String)>, ports: &BTreeSet<String>) -> usize {
+    let before = sightings.len();
+    sightings.retain(|(_, port)| !ports.contains(port));
+    before - sightings.len()
+}
+
+/// Whether `vlan` has a name in the device's VLAN table, for
+/// `macsuck_no_unnamed`.
+pub fn vlan_is_named(vlan: i32, vlans: &[DeviceVlan]) -> bool {
+    vlans.iter().any(|v| v.vlan == vlan && v.description.as_deref().is_some_and(|d| !d.is_empty()))
 }
 
 /// Walk all devices for MAC address collection (scheduled).
@@ -6,3 +6,42 @@ pub mod arpnip;
 pub mod nbtstat;
 pub mod expire;
 pub mod portcontrol;
+
+use anyhow::Result;
+use ipnetwork::IpNetwork;
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::config::NetdiscoConfig;
+use crate::db;
+use crate::snmp::auth::{self, Access, RememberedAuth};
+use crate::snmp::SnmpClient;
+
+/// Connect to a device with the first credential that works, trying the
+/// one remembered from the last run first, and remember the winner.
+pub async fn snmp_connect(config: &NetdiscoConfig, pool: &PgPool, ip: &IpNetwork, access: Access) -> Result<SnmpClient> {
+    let host = ip.ip().to_string();
+    let mut stanzas = auth::stanzas_for(config, &host, access);
+
+    let remembered = match db::get_snmp_auth(pool, ip, access).await {
+        Ok(remembered) => remembered,
+        Err(e) => {
+            warn!("Failed to load remembered SNMP credential for {}: {}", ip, e);
+            RememberedAuth::default()
+        }
+    };
+    auth::prefer_remembered(&mut stanzas, &remembered);
+
+    let (client, stanza) = auth::connect(config, &host, &stanzas).await?;
+    let mut winner = RememberedAuth::from_stanza(&stanza);
+    if access == Access::Write {
+        // The write credential has no version column of its own
+        winner.snmp_ver = None;
+    }
+    if winner != remembered {
+        if let Err(e) = db::set_snmp_auth(pool, ip, access, &winner).await {
+            warn!("Failed to remember SNMP credential for {}: {}", ip, e);
+        }
+    }
+    Ok(client)
+}
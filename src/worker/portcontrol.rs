@@ -1,7 +1,9 @@
-//! Port control worker - change port admin status, VLAN, PoE.
+//! Port control worker - change port admin status, name, VLAN, PoE.
 //!
-//! NOTE: SNMP SET operations are not yet implemented. These actions currently
-//! only update the local database. The physical device is NOT modified.
+//! Changes are written to the device with SNMP SET (using the first
+//! `device_auth` write stanza or `community_rw` that works), read back to
+//! confirm they took effect, and recorded in device_port_log whether or
+//! not they succeeded.
 
 use anyhow::{Context, Result};
 use sqlx::PgPool;
@@ -9,69 +11,198 @@ use tracing::{info, warn};
 
 use crate::config::NetdiscoConfig;
 use crate::models::admin::Admin;
+use crate::snmp::auth::Access;
+use crate::snmp::{oids, SnmpClient, SnmpValue};
 
-pub async fn port_action(_config: &NetdiscoConfig, pool: &PgPool, job: &Admin) -> Result<String> {
+/// A change requested on a single port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortChange {
+    /// ifAdminStatus up (true) or down (false)
+    AdminStatus(bool),
+    /// ifAlias
+    Name(String),
+    /// Access VLAN (vmVlan or dot1qPvid)
+    Vlan(u32),
+    /// pethPsePortAdminEnable
+    Power(bool),
+}
+
+impl PortChange {
+    /// Parse a job's action and subaction.
+    pub fn parse(action: &str, subaction: &str) -> Result<Self> {
+        match action {
+            "portcontrol" => match subaction {
+                "up" | "enable" => Ok(PortChange::AdminStatus(true)),
+                "down" | "disable" => Ok(PortChange::AdminStatus(false)),
+                _ => Err(anyhow::anyhow!("Invalid port control subaction: {}", subaction)),
+            },
+            "portname" => Ok(PortChange::Name(subaction.to_string())),
+            "portvlan" => {
+                let vlan: u32 = subaction.parse()
+                    .context("Invalid VLAN number")?;
+                if !(1..=4094).contains(&vlan) {
+                    anyhow::bail!("VLAN {} is out of range", vlan);
+                }
+                Ok(PortChange::Vlan(vlan))
+            }
+            "power" => match subaction {
+                "on" | "enable" | "true" => Ok(PortChange::Power(true)),
+                "off" | "disable" | "false" => Ok(PortChange::Power(false)),
+                _ => Err(anyhow::anyhow!("Invalid power subaction: {}", subaction)),
+            },
+            _ => Err(anyhow::anyhow!("Unknown port action: {}", action)),
+        }
+    }
+
+    /// Short description for logs and job results.
+    pub fn describe(&self) -> String {
+        match self {
+            PortChange::AdminStatus(up) => format!("admin status {}", if *up { "up" } else { "down" }),
+            PortChange::Name(name) => format!("name '{}'", name),
+            PortChange::Vlan(vlan) => format!("VLAN {}", vlan),
+            PortChange::Power(on) => format!("PoE {}", if *on { "on" } else { "off" }),
+        }
+    }
+}
+
+pub async fn port_action(config: &NetdiscoConfig, pool: &PgPool, job: &Admin) -> Result<String> {
     let action = job.action.as_deref().unwrap_or("unknown");
     let device_ip = job.device.ok_or_else(|| anyhow::anyhow!("No device IP"))?;
     let port = job.port.as_deref().ok_or_else(|| anyhow::anyhow!("No port specified"))?;
     let subaction = job.subaction.as_deref().unwrap_or("");
+    let change = PortChange::parse(action, subaction)?;
 
-    info!("Port {} on {} {}: action={}, subaction={}",
-        port, device_ip, action, action, subaction);
+    info!("Port {} on {}: action={}, subaction={}", port, device_ip, action, subaction);
 
-    warn!("SNMP SET not yet implemented - only updating local database, device {} is NOT modified", device_ip);
+    let ifindex: Option<i32> = sqlx::query_scalar(
+        "SELECT ifindex FROM device_port WHERE ip = $1 AND port = $2"
+    )
+        .bind(device_ip)
+        .bind(port)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
 
-    match action {
-        "portcontrol" => {
-            let target_status = match subaction {
-                "up" | "enable" => "up",
-                "down" | "disable" => "down",
-                _ => return Err(anyhow::anyhow!("Invalid port control subaction: {}", subaction)),
-            };
+    let result = match ifindex.and_then(|i| u32::try_from(i).ok()) {
+        Some(ifindex) => {
+            match super::snmp_connect(config, pool, &device_ip, Access::Write).await {
+                Ok(client) => apply_port_change(&client, ifindex, &change).await,
+                Err(e) => Err(e),
+            }
+        }
+        None => Err(anyhow::anyhow!("No ifIndex known for port {} on {}", port, device_ip)),
+    };
+
+    let log = match &result {
+        Ok(()) => format!("Set {} on port {}", change.describe(), port),
+        Err(e) => format!("Failed to set {} on port {}: {:#}", change.describe(), port, e),
+    };
+    sqlx::query(
+        r#"INSERT INTO device_port_log (ip, port, reason, log, username, userip, action)
+           VALUES ($1, $2, $3, $4, $5, $6, $7)"#
+    )
+        .bind(device_ip)
+        .bind(port)
+        .bind(format!("Port {} {}", action, subaction))
+        .bind(&log)
+        .bind(job.username.as_deref())
+        .bind(job.userip)
+        .bind(action)
+        .execute(pool)
+        .await?;
+
+    if let Err(e) = result {
+        warn!("{} on {}", log, device_ip);
+        return Err(e);
+    }
 
-            // Log the port change
-            sqlx::query(
-                r#"INSERT INTO device_port_log (ip, port, reason, log, username, action)
-                   VALUES ($1, $2, $3, $4, $5, $6)"#
-            )
+    // Keep the database in step until the next discover
+    match &change {
+        PortChange::AdminStatus(up) => {
+            sqlx::query("UPDATE device_port SET up_admin = $3 WHERE ip = $1 AND port = $2")
                 .bind(device_ip)
                 .bind(port)
-                .bind(format!("Port {} {}", action, subaction))
-                .bind(format!("Set port {} to {} (DB only, SNMP SET not implemented)", port, target_status))
-                .bind(job.username.as_deref())
-                .bind(action)
+                .bind(if *up { "up" } else { "down" })
                 .execute(pool)
                 .await?;
-
-            Ok(format!("Port {} on {} set to {} (DB only)", port, device_ip, target_status))
         }
-        "portname" => {
+        PortChange::Name(name) => {
             sqlx::query("UPDATE device_port SET name = $3 WHERE ip = $1 AND port = $2")
                 .bind(device_ip)
                 .bind(port)
-                .bind(subaction)
+                .bind(name)
                 .execute(pool)
                 .await?;
-
-            Ok(format!("Port {} on {} name set to '{}' (DB only)", port, device_ip, subaction))
         }
-        "portvlan" => {
-            let vlan: i32 = subaction.parse()
-                .context("Invalid VLAN number")?;
-
+        PortChange::Vlan(vlan) => {
             sqlx::query("UPDATE device_port SET pvid = $3 WHERE ip = $1 AND port = $2")
                 .bind(device_ip)
                 .bind(port)
-                .bind(vlan)
+                .bind(*vlan as i32)
                 .execute(pool)
                 .await?;
+        }
+        PortChange::Power(on) => {
+            sqlx::query("UPDATE device_port_power SET admin = $3 WHERE ip = $1 AND port = $2")
+                .bind(device_ip)
+                .bind(port)
+                .bind(if *on { "true" } else { "false" })
+                .execute(pool)
+                .await?;
+        }
+    }
+
+    Ok(format!("Port {} on {} {}", port, device_ip, change.describe()))
+}
 
-            Ok(format!("Port {} on {} VLAN set to {} (DB only)", port, device_ip, vlan))
+/// Write a change to the port with `ifindex` and confirm it took effect.
+pub async fn apply_port_change(client: &SnmpClient, ifindex: u32, change: &PortChange) -> Result<()> {
+    match change {
+        PortChange::AdminStatus(up) => {
+            let status = if *up { 1 } else { 2 };
+            client.set_verified(&instance(&oids::IF_ADMIN_STATUS, &[ifindex]), SnmpValue::Integer(status)).await
         }
-        "power" => {
-            warn!("PoE control not implemented for port {} on {}", port, device_ip);
-            Err(anyhow::anyhow!("PoE control is not yet implemented"))
+        PortChange::Name(name) => {
+            client.set_verified(&instance(&oids::IF_ALIAS, &[ifindex]), SnmpValue::OctetString(name.as_bytes().to_vec())).await
+        }
+        PortChange::Vlan(vlan) => {
+            // Cisco access ports take vmVlan; everything else dot1qPvid,
+            // which is indexed by bridge port rather than ifIndex
+            let vm_vlan = instance(&oids::VM_VLAN, &[ifindex]);
+            if client.get(&vm_vlan).await.is_ok_and(|v| !v.is_exception()) {
+                return client.set_verified(&vm_vlan, SnmpValue::Integer(*vlan as i64)).await;
+            }
+            let bridge_port = bridge_port_for_ifindex(client, ifindex).await?;
+            client.set_verified(&instance(&oids::DOT1Q_PVID, &[bridge_port]), SnmpValue::Gauge32(*vlan)).await
+        }
+        PortChange::Power(on) => {
+            let pse_port = pse_port_for_ifindex(client, ifindex).await?;
+            let enable = if *on { 1 } else { 2 };
+            client.set_verified(&instance(&oids::PSE_PORT_ADMIN, &pse_port), SnmpValue::Integer(enable)).await
         }
-        _ => Err(anyhow::anyhow!("Unknown port action: {}", action)),
     }
 }
+
+fn instance(column: &[u32], index: &[u32]) -> Vec<u32> {
+    column.iter().chain(index).copied().collect()
+}
+
+/// Find the dot1dBasePort for an ifIndex.
+async fn bridge_port_for_ifindex(client: &SnmpClient, ifindex: u32) -> Result<u32> {
+    let ports = client.walk(&oids::DOT1D_BASE_PORT_IF_INDEX).await?;
+    ports.iter()
+        .find(|(_, value)| value.as_i64() == Some(ifindex as i64))
+        .and_then(|(oid, _)| oid.get(oids::DOT1D_BASE_PORT_IF_INDEX.len()).copied())
+        .ok_or_else(|| anyhow::anyhow!("No bridge port for ifIndex {}", ifindex))
+}
+
+/// Find the pethPsePortTable index (group, port) for an ifIndex, matched
+/// the way discover matches them (see [`crate::snmp::pse_port_ifindex`]).
+async fn pse_port_for_ifindex(client: &SnmpClient, ifindex: u32) -> Result<Vec<u32>> {
+    let interfaces = client.get_interfaces().await?;
+    let poe = client.get_poe(&interfaces).await?;
+    poe.ports.iter()
+        .find(|p| p.ifindex == Some(ifindex as i32))
+        .map(|p| vec![p.group as u32, p.port as u32])
+        .ok_or_else(|| anyhow::anyhow!("No PSE port for ifIndex {}", ifindex))
+}
@@ -1 +1,3 @@
 mod workflows;
+mod replay;
+mod workers;
@@ -0,0 +1,386 @@
+//! Pollers run against recorded device walks.
+//!
+//! Each capture under `tests/fixtures/snmp` is served by the built-in
+//! simulator and the device's address is pointed at it, so the same
+//! calls the discover, macsuck and arpnip workers make run end to end.
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use netdisco::config::NetdiscoConfig;
+use netdisco::snmp::auth::{self, Access};
+use netdisco::snmp::simulator::{Recording, Simulator};
+use netdisco::snmp::fingerprint::fingerprint;
+use netdisco::snmp::{find_chassis, MacEntry, SnmpClient};
+use netdisco::util::format_speed;
+use netdisco::worker::discover::{
+    device_ips, device_subnets, discoverable_neighbor, entity_to_module, merge_neighbors, port_stp_states,
+    power_rows, stp_rows, uplink_ports, vlan_rows,
+};
+use netdisco::worker::macsuck::entry_port;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/snmp").join(name)
+}
+
+/// Serve `name` as `device` and connect the way a worker would.
+async fn replay(name: &str, device: &str) -> (Simulator, SnmpClient) {
+    let ip: IpAddr = device.parse().unwrap();
+    let recording = Recording::load(&fixture(name)).unwrap();
+    let simulator = Simulator::start_for(ip, recording).unwrap();
+
+    let mut config = NetdiscoConfig { snmpver: 2, ..Default::default() };
+    config.snmp_replay.insert(ip, simulator.local_addr());
+    let stanzas = auth::stanzas_for(&config, device, Access::Read);
+    let (client, _) = auth::connect(&config, device, &stanzas).await.unwrap();
+    (simulator, client)
+}
+
+#[tokio::test]
+async fn test_replay_cisco_c3750() {
+    let (_simulator, client) = replay("cisco_c3750.walk", "192.0.2.10").await;
+
+    let system = client.get_system_info().await.unwrap();
+    assert_eq!(system.name.as_deref(), Some("access-sw1.example.com"));
+    assert_eq!(system.object_id.as_deref(), Some("1.3.6.1.4.1.9.1.516"));
+    assert_eq!(system.uptime, Some(1234567890));
+    assert!(system.description.as_deref().unwrap().contains("\nTechnical Support"));
+    let fp = fingerprint(&system, None);
+    assert_eq!((fp.os.as_deref(), fp.os_ver.as_deref()), (Some("ios"), Some("12.2(55)SE12")));
+    assert_eq!(fp.snmp_class.as_deref(), Some("SNMP::Info::Layer3::CiscoSwitch"));
+
+    let interfaces = client.get_interfaces().await.unwrap();
+    assert_eq!(interfaces.len(), 5);
+    let gi3 = interfaces.iter().find(|i| i.ifindex == 10103).unwrap();
+    assert_eq!(gi3.descr, "GigabitEthernet1/0/3");
+    assert_eq!(gi3.admin_status, Some(2));
+    assert_eq!(gi3.speed, Some(10_000_000));
+    assert_eq!(gi3.port_name(), "Gi1/0/3");
+    assert_eq!(gi3.if_type.as_deref(), Some("ethernetCsmacd"));
+    assert_eq!((gi3.mtu, gi3.mac.as_deref()), (Some(1500), Some("00:1b:2b:0a:0b:03")));
+    let gi1 = interfaces.iter().find(|i| i.ifindex == 10101).unwrap();
+    assert_eq!(gi1.alias.as_deref(), Some("desk 2.14"));
+    assert_eq!(gi1.speed_bps().map(format_speed).as_deref(), Some("1.0 Gbps"));
+    let vlan1 = interfaces.iter().find(|i| i.ifindex == 1).unwrap();
+    assert_eq!((vlan1.if_type.as_deref(), vlan1.alias.as_deref()), (Some("propVirtual"), None));
+
+    // The switch's own address (status self) is left out
+    let macs = client.get_mac_table().await.unwrap();
+    assert_eq!(macs.len(), 3);
+    assert!(macs.iter().any(|m| m.mac == "00:50:56:9a:10:21" && m.bridge_port == 2));
+    assert!(!macs.iter().any(|m| m.mac == "00:1a:2b:3c:4d:01"));
+
+    // No Q-BRIDGE FDB, so no VLAN; other VLANs are read with community@vlan
+    assert!(macs.iter().all(|m| m.vlan.is_none()));
+    assert!(client.for_vlan(10).credentials().community.ends_with("@10"));
+
+    // Bridge port 49 is ifIndex 10149, which discover stores as Gi1/0/49
+    let bridge_ports = client.get_bridge_ports().await.unwrap();
+    let port_names = interfaces.iter().map(|i| (i.ifindex, i.port_name().to_string())).collect();
+    let gateway = macs.iter().find(|m| m.mac == "00:00:0c:07:ac:01").unwrap();
+    assert_eq!(entry_port(gateway, &bridge_ports, &port_names), Some("Gi1/0/49"));
+    assert_eq!(entry_port(&MacEntry { bridge_port: 7, ..gateway.clone() }, &bridge_ports, &port_names), None);
+
+    let arp = client.get_arp_table().await.unwrap();
+    assert_eq!(arp.len(), 2);
+    assert!(arp.iter().any(|a| a.ip == "10.10.1.1" && a.mac == "00:00:0c:07:ac:01"));
+
+    let entities = client.get_entities().await.unwrap();
+    assert_eq!(entities.len(), 7);
+    let chassis = find_chassis(&entities).unwrap();
+    assert_eq!(chassis.serial.as_deref(), Some("FOC1234X0AB"));
+    assert_eq!(chassis.model.as_deref(), Some("WS-C3750G-48PS-S"));
+
+    let ip = "192.0.2.10/32".parse().unwrap();
+    let now = chrono::Local::now().naive_local();
+    let modules: Vec<_> = entities.iter().map(|e| entity_to_module(&ip, e, now)).collect();
+    let sfp = modules.iter().find(|m| m.index == 1049).unwrap();
+    assert_eq!(sfp.parent, Some(1001));
+    assert_eq!(sfp.class.as_deref(), Some("port"));
+    assert_eq!(sfp.serial.as_deref(), Some("FNS11111AAA"));
+    assert_eq!(sfp.hw_ver.as_deref(), Some("V01"));
+    assert_eq!(sfp.fru, Some(true));
+    assert_eq!(sfp.module_type.as_deref(), Some("1.3.6.1.4.1.9.12.3.1.9.5.42"));
+    let fan = modules.iter().find(|m| m.class.as_deref() == Some("fan")).unwrap();
+    assert_eq!((fan.module_type.as_deref(), fan.serial.as_deref()), (None, None));
+    assert!(modules.iter().filter(|m| m.index != 1001).all(|m| m.parent == Some(1001)));
+
+    // LLDP's local port 49 is matched to Gi1/0/49 by its description
+    let lldp = client.get_lldp_neighbors(&interfaces).await.unwrap();
+    assert_eq!(lldp.len(), 1);
+    assert_eq!(lldp[0].ifindex, 10149);
+    assert_eq!(lldp[0].remote_id.as_deref(), Some("core-sw1.example.com"));
+    assert_eq!(lldp[0].remote_port.as_deref(), Some("Te1/1/1"));
+    assert_eq!(lldp[0].remote_ip, Some("10.10.0.1".parse().unwrap()));
+    assert_eq!(lldp[0].capabilities, vec!["bridge", "router"]);
+
+    let cdp = client.get_cdp_neighbors().await.unwrap();
+    assert_eq!(cdp.len(), 3);
+    let phone = cdp.iter().find(|n| n.ifindex == 10101).unwrap();
+    assert_eq!(phone.remote_id.as_deref(), Some("SEP001122334455"));
+    assert_eq!(phone.remote_type.as_deref(), Some("Cisco IP Phone 8841"));
+    assert_eq!(phone.capabilities, vec!["Host", "Phone", "Two-port Mac Relay"]);
+    let ap = cdp.iter().find(|n| n.ifindex == 10102).unwrap();
+    assert_eq!(ap.remote_ip, Some("10.10.1.60".parse().unwrap()));
+    assert!(ap.is_wap() && !ap.is_phone());
+
+    // LLDP wins on the uplink, where both protocols see core-sw1
+    let merged = merge_neighbors(&lldp, &cdp);
+    assert_eq!(merged.iter().map(|n| (n.ifindex, n.protocol)).collect::<Vec<_>>(),
+        vec![(10101, "cdp"), (10102, "cdp"), (10149, "lldp")]);
+
+    // Phones are skipped by default, APs are not
+    let mut config = NetdiscoConfig::default();
+    let queued = |config: &NetdiscoConfig| merged.iter()
+        .filter_map(|n| discoverable_neighbor(config, n))
+        .map(|ip| ip.ip().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(queued(&config), vec!["10.10.1.60", "10.10.0.1"]);
+    config.discover_waps = false;
+    assert_eq!(queued(&config), vec!["10.10.0.1"]);
+    config.discover_phones = true;
+    config.discover_no = vec!["10.10.0.0/24".into()];
+    assert_eq!(queued(&config), vec!["10.10.1.50"]);
+
+    let addresses = client.get_ip_addresses().await.unwrap();
+    let device = "192.0.2.10/32".parse().unwrap();
+    let aliases = device_ips(&device, &addresses, &interfaces);
+    let rows: Vec<(String, Option<String>, Option<&str>)> = aliases.iter()
+        .map(|a| (a.alias.ip().to_string(), a.subnet.map(|s| s.to_string()), a.port.as_deref()))
+        .collect();
+    assert_eq!(rows, vec![
+        ("10.10.1.2".into(), Some("10.10.1.0/24".into()), Some("Vl1")),
+        ("172.16.5.1".into(), Some("172.16.5.0/30".into()), Some("Gi1/0/49")),
+    ]);
+    assert!(aliases.iter().all(|a| a.ip == device));
+
+    // No Q-BRIDGE: names from VTP (FDDI VLANs left out), access ports from
+    // vmVlan and the trunk's VLANs from its enabled bitmap
+    let vlans = client.get_vlan_table().await.unwrap();
+    assert_eq!(vlans.vtp_domain.as_deref(), Some("CAMPUS"));
+    let names: Vec<(i32, Option<&str>)> = vlans.vlans.iter().map(|v| (v.vlan, v.name.as_deref())).collect();
+    assert_eq!(names, vec![(1, Some("default")), (10, Some("users")), (20, Some("voice"))]);
+    let members: Vec<(i32, i32, bool)> = vlans.members.iter().map(|m| (m.ifindex, m.vlan, m.untagged)).collect();
+    assert_eq!(members, vec![
+        (10101, 10, true), (10102, 20, true), (10103, 10, true),
+        (10149, 1, true), (10149, 10, false), (10149, 20, false),
+    ]);
+    assert_eq!(vlans.pvids.get(&10149), Some(&1));
+    assert_eq!(vlans.pvids.get(&10102), Some(&20));
+
+    // No aggregation; the trunk carries tagged VLANs so is an uplink
+    let lag = client.get_lag_members(&interfaces).await.unwrap();
+    assert!(lag.is_empty());
+    assert_eq!(uplink_ports(&lag, &[], &vlans), [10149].into_iter().collect());
+
+    // Rapid PVST+: one instance per VLAN, each read with community indexing
+    let vlan_ids: Vec<i32> = vlans.vlans.iter().map(|v| v.vlan).collect();
+    let stp = client.get_stp(&vlan_ids).await.unwrap();
+    assert_eq!(stp.protocol.as_deref(), Some("rapid-pvst"));
+    let instances: Vec<(i32, &[i32])> = stp.instances.iter().map(|i| (i.instance, i.vlans.as_slice())).collect();
+    assert_eq!(instances, vec![(1, &[1][..]), (10, &[10][..]), (20, &[20][..])]);
+    let vlan10 = &stp.instances[1];
+    assert_eq!(vlan10.root.as_deref(), Some("4097.00:19:e7:11:22:00"));
+    assert_eq!(vlan10.bridge.as_deref(), Some("32769.00:1a:2b:3c:4d:00"));
+    assert_eq!((vlan10.root_cost, vlan10.root_port), (Some(4), Some(10149)));
+    assert!(!vlan10.is_root());
+    let states = port_stp_states(&stp);
+    assert_eq!(states.get(&10103).map(String::as_str), Some("blocking"));
+    assert_eq!(states.get(&10149).map(String::as_str), Some("forwarding"));
+    let (rows, ports) = stp_rows(&device, &stp, &interfaces, Default::default());
+    assert_eq!(rows[0].root_port.as_deref(), Some("Gi1/0/49"));
+    assert_eq!(ports.len(), 12);
+
+    // One PSE group; per-port draw comes from the Cisco extension in mW.
+    // PSE ports are numbered 1.1 to 1.48 and reach their ifIndex through
+    // cpeExtPsePortEntPhyIndex and entAliasMappingTable
+    let poe = client.get_poe(&interfaces).await.unwrap();
+    assert_eq!(poe.modules.len(), 1);
+    let module = &poe.modules[0];
+    assert_eq!((module.group, module.power, module.consumption), (1, Some(370), Some(22)));
+    assert_eq!(module.status.as_deref(), Some("on"));
+    let pse: Vec<_> = poe.ports.iter().map(|p| (p.group, p.port, p.ifindex)).collect();
+    assert_eq!(pse, vec![(1, 1, Some(10101)), (1, 2, Some(10102)), (1, 3, Some(10103))]);
+    let (modules, ports) = power_rows(&device, &poe, &interfaces);
+    assert_eq!(modules.len(), 1);
+    let ports: Vec<_> = ports.iter()
+        .map(|p| (p.port.as_str(), p.admin.as_deref(), p.status.as_deref(), p.class.as_deref(), p.power))
+        .collect();
+    assert_eq!(ports, vec![
+        ("Gi1/0/1", Some("true"), Some("deliveringPower"), Some("class3"), Some(6300)),
+        ("Gi1/0/2", Some("true"), Some("deliveringPower"), Some("class4"), Some(15400)),
+        ("Gi1/0/3", Some("false"), Some("disabled"), Some("class0"), Some(0)),
+    ]);
+}
+
+/// IOS-XE 16.x only names itself in the image (CAT9K_IOSXE)
+#[tokio::test]
+async fn test_replay_cisco_c9300() {
+    let (_simulator, client) = replay("cisco_c9300.walk", "192.0.2.13").await;
+
+    let system = client.get_system_info().await.unwrap();
+    assert_eq!(system.name.as_deref(), Some("dist-sw2.example.com"));
+    assert!(!system.description.as_deref().unwrap().contains("IOS-XE"));
+    let fp = fingerprint(&system, None);
+    assert_eq!((fp.os.as_deref(), fp.os_ver.as_deref()), (Some("ios-xe"), Some("16.9.5")));
+    assert_eq!(fp.snmp_class.as_deref(), Some("SNMP::Info::Layer3::CiscoSwitch"));
+
+    let interfaces = client.get_interfaces().await.unwrap();
+    let names: Vec<&str> = interfaces.iter().map(|i| i.port_name()).collect();
+    assert_eq!(names, vec!["Gi1/0/1", "Te1/1/1"]);
+}
+
+#[tokio::test]
+async fn test_replay_juniper_ex2300_snmprec() {
+    let (_simulator, client) = replay("juniper_ex2300.snmprec", "192.0.2.11").await;
+
+    let system = client.get_system_info().await.unwrap();
+    assert_eq!(system.name.as_deref(), Some("ex2300-lab"));
+    assert_eq!(system.contact.as_deref(), Some(""));
+    assert_eq!(system.services, Some(6));
+    let fp = fingerprint(&system, None);
+    assert_eq!((fp.os.as_deref(), fp.os_ver.as_deref()), (Some("junos"), Some("20.4R3-S2.6")));
+
+    let interfaces = client.get_interfaces().await.unwrap();
+    let names: Vec<&str> = interfaces.iter().map(|i| i.descr.as_str()).collect();
+    assert_eq!(names, vec!["fxp0", "ge-0/0/0", "ge-0/0/1"]);
+
+    // Q-BRIDGE FDB: FIDs 3 and 5 are VLANs 50 and 100 via dot1qVlanFdbId
+    let macs = client.get_mac_table().await.unwrap();
+    assert_eq!(macs.len(), 2);
+    assert!(macs.iter().any(|m| m.mac == "50:6b:8d:15:0a:0b" && m.bridge_port == 515 && m.vlan == Some(100)));
+    assert!(macs.iter().any(|m| m.mac == "0c:86:10:9e:1f:01" && m.bridge_port == 513 && m.vlan == Some(50)));
+
+    let arp = client.get_arp_table().await.unwrap();
+    assert_eq!(arp.len(), 1);
+    assert_eq!(arp[0].ip, "192.168.50.1");
+
+    // The chassis serial repeats on the FPC; the chassis is the top entity
+    let entities = client.get_entities().await.unwrap();
+    let chassis = find_chassis(&entities).unwrap();
+    assert_eq!(chassis.index, 1);
+    assert_eq!(chassis.model.as_deref(), Some("EX2300-24P"));
+    let pic = entities.iter().find(|e| e.index == 3).unwrap();
+    assert_eq!((pic.class.as_deref(), pic.contained_in), (Some("module"), Some(2)));
+
+    // ge-0/0/0 is found from lldpLocPortDesc; port 515 has no local port
+    // row and falls back to the bridge port table
+    let lldp = client.get_lldp_neighbors(&interfaces).await.unwrap();
+    assert_eq!(lldp.len(), 2);
+    let router = lldp.iter().find(|n| n.ifindex == 513).unwrap();
+    assert_eq!(router.remote_id.as_deref(), Some("mx-edge1"));
+    assert_eq!(router.remote_port.as_deref(), Some("ge-0/0/10"));
+    assert_eq!(router.remote_ip, Some("192.168.50.1".parse().unwrap()));
+    assert!(router.is_routed());
+    let server = lldp.iter().find(|n| n.ifindex == 515).unwrap();
+    assert_eq!(server.remote_id.as_deref(), Some("srv01"));
+    assert_eq!(server.remote_port.as_deref(), Some("50:6b:8d:15:0a:0b"));
+    assert_eq!(server.remote_ip, Some("2001:db8::10".parse().unwrap()));
+    assert_eq!(server.capabilities, vec!["stationOnly"]);
+    assert!(client.get_cdp_neighbors().await.unwrap().is_empty());
+
+    let config = NetdiscoConfig { discover_routed_neighbors: false, ..Default::default() };
+    assert_eq!(discoverable_neighbor(&config, router), None);
+    assert_eq!(discoverable_neighbor(&config, server), Some("2001:db8::10/128".parse().unwrap()));
+
+    // 192.168.50.10 is in both address tables; loopback and link-local
+    // addresses are left out, as are /32 and /128 host routes from subnets
+    let addresses = client.get_ip_addresses().await.unwrap();
+    assert_eq!(addresses.len(), 4);
+    let v6 = addresses.iter().find(|a| a.ip.is_ipv6() && a.prefix_len.is_some()).unwrap();
+    assert_eq!((v6.ifindex, v6.prefix_len), (Some(501), Some(64)));
+    let aliases = device_ips(&"192.0.2.11".parse().unwrap(), &addresses, &interfaces);
+    let found: Vec<String> = aliases.iter().map(|a| a.alias.ip().to_string()).collect();
+    assert_eq!(found, vec!["192.168.50.10", "2001:db8:50::10"]);
+    assert!(aliases.iter().all(|a| a.port.as_deref() == Some("fxp0")));
+    let subnets: Vec<String> = device_subnets(&aliases).iter().map(|s| s.to_string()).collect();
+    assert_eq!(subnets, vec!["192.168.50.0/24", "2001:db8:50::/64"]);
+
+    // Q-BRIDGE static table: bridge ports 513 and 515 are bits in the 65th
+    // octet of each port list
+    let vlans = client.get_vlan_table().await.unwrap();
+    assert_eq!(vlans.vtp_domain, None);
+    let names: Vec<(i32, Option<&str>)> = vlans.vlans.iter().map(|v| (v.vlan, v.name.as_deref())).collect();
+    assert_eq!(names, vec![(1, Some("default")), (50, Some("mgmt")), (100, Some("servers"))]);
+    assert_eq!(vlans.pvids, [(513, 1), (515, 100)].into_iter().collect());
+    let (device_vlans, port_vlans) = vlan_rows(&"192.0.2.11".parse().unwrap(), &vlans, &interfaces, Default::default());
+    assert_eq!(device_vlans.len(), 3);
+    let rows: Vec<(&str, i32, bool)> = port_vlans.iter().map(|pv| (pv.port.as_str(), pv.vlan, pv.native)).collect();
+    assert_eq!(rows, vec![
+        ("ge-0/0/0", 1, true), ("ge-0/0/0", 50, false), ("ge-0/0/0", 100, false), ("ge-0/0/1", 100, true),
+    ]);
+
+    // MSTP: the CIST from BRIDGE-MIB, where this switch is root, and MSTI 1
+    // from IEEE8021-MSTP-MIB carrying VLANs 50 and 100
+    let stp = client.get_stp(&[1, 50, 100]).await.unwrap();
+    assert_eq!(stp.protocol.as_deref(), Some("mstp"));
+    assert_eq!(stp.instances.len(), 2);
+    let cist = &stp.instances[0];
+    assert!(cist.is_root());
+    assert_eq!((cist.instance, cist.vlans.is_empty(), cist.root_port), (0, true, None));
+    let msti = &stp.instances[1];
+    assert_eq!((msti.instance, msti.vlans.as_slice()), (1, &[50, 100][..]));
+    assert_eq!(msti.root.as_deref(), Some("4097.0c:86:10:9e:1f:00"));
+    assert_eq!((msti.root_cost, msti.root_port), (Some(20000), Some(513)));
+    assert_eq!(msti.ports.get(&515).map(String::as_str), Some("blocking"));
+    let states = port_stp_states(&stp);
+    assert_eq!(states, [(513, "forwarding".to_string()), (515, "blocking".to_string())].into_iter().collect());
+    assert_eq!(port_vlans[1].vlantype.as_deref(), Some("tagged"));
+}
+
+#[tokio::test]
+async fn test_replay_aruba_2930f() {
+    let (_simulator, client) = replay("aruba_2930f.walk", "192.0.2.12").await;
+
+    let system = client.get_system_info().await.unwrap();
+    assert_eq!(system.name.as_deref(), Some("edge-2930f"));
+    let fp = fingerprint(&system, None);
+    assert_eq!((fp.os.as_deref(), fp.os_ver.as_deref()), (Some("procurve"), Some("WC.16.10.0012")));
+    assert_eq!(fp.snmp_class.as_deref(), Some("SNMP::Info::Layer2::HP"));
+
+    let interfaces = client.get_interfaces().await.unwrap();
+    assert_eq!(interfaces.len(), 4);
+    // ifSpeed saturates on the 10G uplink; ifHighSpeed has the real speed
+    let uplink = interfaces.iter().find(|i| i.ifindex == 28).unwrap();
+    assert_eq!(uplink.speed, Some(4294967295));
+    assert_eq!(uplink.speed_bps().map(format_speed).as_deref(), Some("10 Gbps"));
+    assert_eq!(uplink.mac.as_deref(), Some("94:f1:28:c3:4d:e4"));
+    assert_eq!(interfaces.iter().find(|i| i.ifindex == 2).unwrap().speed_bps(), None);
+
+    // No dot1dTpFdbAddress column: MACs come from the table index
+    let macs = client.get_mac_table().await.unwrap();
+    assert_eq!(macs.len(), 2);
+    assert!(macs.iter().any(|m| m.mac == "00:0c:29:aa:bb:01" && m.bridge_port == 28));
+
+    // PhysAddress display hint ("0:c:29:aa:bb:1") is read back as octets
+    let arp = client.get_arp_table().await.unwrap();
+    assert!(arp.iter().any(|a| a.ip == "172.16.8.1" && a.mac == "00:0c:29:aa:bb:01"));
+
+    // No ENTITY-MIB, LLDP or CDP in this capture
+    assert!(client.get_entities().await.unwrap().is_empty());
+    assert!(client.get_lldp_neighbors(&interfaces).await.unwrap().is_empty());
+    assert!(client.get_cdp_neighbors().await.unwrap().is_empty());
+    assert!(client.get_ip_addresses().await.unwrap().is_empty());
+    assert_eq!(client.get_vlan_table().await.unwrap(), Default::default());
+    assert_eq!(client.get_poe(&interfaces).await.unwrap(), Default::default());
+
+    // Ports 1 and 2 are members of Trk1; port 28 reports no aggregator.
+    // A neighbor on one member makes the trunk and both members uplinks
+    let lag = client.get_lag_members(&interfaces).await.unwrap();
+    assert_eq!(lag, [(1, 289), (2, 289)].into_iter().collect());
+    let trunk = interfaces.iter().find(|i| i.ifindex == 289).unwrap();
+    assert_eq!((trunk.port_name(), trunk.if_type.as_deref()), ("Trk1", Some("ieee8023adLag")));
+    let vlans = Default::default();
+    assert_eq!(uplink_ports(&lag, &[2], &vlans), [1, 2, 289].into_iter().collect());
+    assert!(uplink_ports(&lag, &[28], &vlans).into_iter().eq([28]));
+
+    // RSTP: a single tree, rooted elsewhere through port 28
+    let stp = client.get_stp(&[]).await.unwrap();
+    assert_eq!(stp.protocol.as_deref(), Some("rstp"));
+    assert_eq!(stp.instances.len(), 1);
+    let tree = &stp.instances[0];
+    assert_eq!((tree.instance, tree.root_port, tree.root_cost), (0, Some(28), Some(20000)));
+    assert_eq!(tree.bridge.as_deref(), Some("32768.94:f1:28:c3:4d:00"));
+    assert!(!tree.is_root());
+    assert_eq!(tree.ports.get(&2).map(String::as_str), Some("disabled"));
+}
@@ -0,0 +1,243 @@
+//! Discover, macsuck and arpnip run against recorded device walks and a
+//! PostgreSQL database.
+//!
+//! Each capture under `tests/fixtures/snmp` is served by the built-in
+//! simulator, as in `replay`, and the workers store what they find. Like
+//! the database integration tests, these do nothing unless
+//! `NETDISCO_TEST_DATABASE_URL` is set.
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use ipnetwork::IpNetwork;
+use sqlx::PgPool;
+
+use netdisco::config::NetdiscoConfig;
+use netdisco::db;
+use netdisco::models::device_port::DevicePort;
+use netdisco::snmp::simulator::{Recording, Simulator};
+use netdisco::worker::{arpnip, discover, macsuck};
+
+use crate::integration::database::TestDb;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/snmp").join(name)
+}
+
+/// Serve `name` as `device`, then discover, macsuck and arpnip it in the
+/// order the backend would. Returns each worker's message.
+async fn run_workers(pool: &PgPool, name: &str, device: &str) -> [String; 3] {
+    let addr: IpAddr = device.parse().unwrap();
+    let recording = Recording::load(&fixture(name)).unwrap();
+    let simulator = Simulator::start_for(addr, recording).unwrap();
+    let mut config = NetdiscoConfig { snmpver: 2, ..Default::default() };
+    config.snmp_replay.insert(addr, simulator.local_addr());
+
+    let ip = IpNetwork::from(addr);
+    let discovered = discover::discover_device(&config, pool, &ip).await.unwrap();
+    let macsucked = macsuck::macsuck_device(&config, pool, &ip).await.unwrap();
+    let arpnipped = arpnip::arpnip_device(&config, pool, &ip).await.unwrap();
+    [discovered, macsucked, arpnipped]
+}
+
+async fn ports(pool: &PgPool, device: &str) -> Vec<DevicePort> {
+    db::get_device_ports(pool, &device.parse().unwrap()).await.unwrap()
+}
+
+fn port<'a>(ports: &'a [DevicePort], name: &str) -> &'a DevicePort {
+    ports.iter().find(|p| p.port == name).unwrap()
+}
+
+/// The switch's nodes as (mac, port, vlan, active).
+async fn nodes(pool: &PgPool, device: &str) -> Vec<(String, String, String, bool)> {
+    sqlx::query_as("SELECT mac::text, port, vlan, active FROM node WHERE switch = $1 ORDER BY mac")
+        .bind(device.parse::<IpNetwork>().unwrap())
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+/// The router's ARP entries as (address, mac).
+async fn node_ips(pool: &PgPool, device: &str) -> Vec<(String, String)> {
+    sqlx::query_as("SELECT host(ip), mac::text FROM node_ip WHERE router = $1 AND active ORDER BY ip")
+        .bind(device.parse::<IpNetwork>().unwrap())
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+async fn row_count(pool: &PgPool, table: &str, device: &str) -> i64 {
+    sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table} WHERE ip = $1"))
+        .bind(device.parse::<IpNetwork>().unwrap())
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_workers_cisco_c3750() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let device = "192.0.2.10";
+    let messages = run_workers(&test.pool, "cisco_c3750.walk", device).await;
+    assert_eq!(messages, [
+        "Discovered 192.0.2.10/32 with 5 interfaces",
+        "Macsuck 192.0.2.10/32: stored 2 MACs (0 moved), deactivated 0, dropped 1 on uplinks",
+        "Arpnip 192.0.2.10/32: stored 2 of 2 entries, deactivated 0",
+    ]);
+
+    let stored = db::find_device(&test.pool, &device.parse().unwrap()).await.unwrap().unwrap();
+    assert_eq!(stored.name.as_deref(), Some("access-sw1.example.com"));
+    assert_eq!((stored.os.as_deref(), stored.os_ver.as_deref()), (Some("ios"), Some("12.2(55)SE12")));
+    assert_eq!(stored.serial.as_deref(), Some("FOC1234X0AB"));
+    assert!(stored.last_macsuck.is_some() && stored.last_arpnip.is_some());
+
+    let ports = ports(&test.pool, device).await;
+    assert_eq!(ports.iter().map(|p| p.port.as_str()).collect::<Vec<_>>(),
+        vec!["Gi1/0/1", "Gi1/0/2", "Gi1/0/3", "Gi1/0/49", "Vl1"]);
+    let gi3 = port(&ports, "Gi1/0/3");
+    assert_eq!((gi3.mac.as_deref(), gi3.stp.as_deref()), (Some("00:1b:2b:0a:0b:03"), Some("blocking")));
+    assert_eq!(port(&ports, "Gi1/0/2").pvid, Some(20));
+    let uplink = port(&ports, "Gi1/0/49");
+    assert_eq!((uplink.is_uplink, uplink.remote_id.as_deref()), (Some(true), Some("core-sw1.example.com")));
+    assert_eq!(port(&ports, "Gi1/0/1").remote_id.as_deref(), Some("SEP001122334455"));
+
+    for (table, rows) in [
+        ("device_module", 7), ("device_ip", 2), ("device_vlan", 3), ("device_port_vlan", 6),
+        ("device_power", 1), ("device_port_power", 3), ("device_stp", 3), ("device_port_stp", 12),
+    ] {
+        assert_eq!(row_count(&test.pool, table, device).await, rows, "{table}");
+    }
+
+    // The gateway behind the uplink is left out
+    assert_eq!(nodes(&test.pool, device).await, vec![
+        ("00:50:56:9a:10:20".into(), "Gi1/0/1".into(), "0".into(), true),
+        ("00:50:56:9a:10:21".into(), "Gi1/0/2".into(), "0".into(), true),
+    ]);
+    assert_eq!(node_ips(&test.pool, device).await, vec![
+        ("10.10.1.1".into(), "00:00:0c:07:ac:01".into()),
+        ("10.10.1.20".into(), "00:50:56:9a:10:20".into()),
+    ]);
+
+    // A second round changes nothing
+    let [_, macsucked, arpnipped] = run_workers(&test.pool, "cisco_c3750.walk", device).await;
+    assert!(macsucked.contains("deactivated 0") && arpnipped.contains("deactivated 0"));
+    assert_eq!(row_count(&test.pool, "device_port", device).await, 5);
+    test.destroy().await;
+}
+
+/// Rows sharing one of the switch's addresses are only merged into it if
+/// they are the same box
+#[tokio::test]
+async fn test_discover_merges_only_corroborated_devices() {
+    let Some(test) = TestDb::create(None).await else { return };
+    for (device, name, serial, alias) in [
+        ("192.0.2.98", "access-sw1.example.com", None, "10.10.1.2"),
+        ("192.0.2.99", "edge-rtr1", Some("FXS1111A0AA"), "172.16.5.1"),
+    ] {
+        sqlx::query("INSERT INTO device (ip, name, serial) VALUES ($1, $2, $3)")
+            .bind(device.parse::<IpNetwork>().unwrap())
+            .bind(name)
+            .bind(serial)
+            .execute(&test.pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO device_ip (ip, alias) VALUES ($1, $2)")
+            .bind(device.parse::<IpNetwork>().unwrap())
+            .bind(alias.parse::<IpNetwork>().unwrap())
+            .execute(&test.pool)
+            .await
+            .unwrap();
+    }
+
+    run_workers(&test.pool, "cisco_c3750.walk", "192.0.2.10").await;
+
+    // The switch is kept under the address its old row (same sysName) was
+    // first discovered at; the router that only shares 172.16.5.1 is left
+    // alone
+    let addr = |device: &str| device.parse::<IpNetwork>().unwrap();
+    assert!(db::find_device(&test.pool, &addr("192.0.2.10")).await.unwrap().is_none());
+    let switch = db::find_device(&test.pool, &addr("192.0.2.98")).await.unwrap().unwrap();
+    assert_eq!(switch.serial.as_deref(), Some("FOC1234X0AB"));
+    assert_eq!(row_count(&test.pool, "device_port", "192.0.2.98").await, 5);
+    let router = db::find_device(&test.pool, &addr("192.0.2.99")).await.unwrap().unwrap();
+    assert_eq!((router.name.as_deref(), router.serial.as_deref()), (Some("edge-rtr1"), Some("FXS1111A0AA")));
+    assert_eq!(row_count(&test.pool, "device_ip", "192.0.2.99").await, 1);
+    let log = switch.log.unwrap_or_default();
+    assert!(log.contains("not merged with device 192.0.2.99"), "{log}");
+    test.destroy().await;
+}
+
+#[tokio::test]
+async fn test_workers_cisco_c9300() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let device = "192.0.2.13";
+    let [discovered, ..] = run_workers(&test.pool, "cisco_c9300.walk", device).await;
+    assert_eq!(discovered, "Discovered 192.0.2.13/32 with 2 interfaces");
+
+    let stored = db::find_device(&test.pool, &device.parse().unwrap()).await.unwrap().unwrap();
+    assert_eq!((stored.os.as_deref(), stored.os_ver.as_deref()), (Some("ios-xe"), Some("16.9.5")));
+    let ports = ports(&test.pool, device).await;
+    assert_eq!(ports.iter().map(|p| p.port.as_str()).collect::<Vec<_>>(), vec!["Gi1/0/1", "Te1/1/1"]);
+    assert!(nodes(&test.pool, device).await.is_empty());
+    assert!(node_ips(&test.pool, device).await.is_empty());
+    test.destroy().await;
+}
+
+#[tokio::test]
+async fn test_workers_juniper_ex2300() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let device = "192.0.2.11";
+    let messages = run_workers(&test.pool, "juniper_ex2300.snmprec", device).await;
+    assert_eq!(messages, [
+        "Discovered 192.0.2.11/32 with 3 interfaces",
+        "Macsuck 192.0.2.11/32: stored 1 MACs (0 moved), deactivated 0, dropped 1 on uplinks",
+        "Arpnip 192.0.2.11/32: stored 1 of 1 entries, deactivated 0",
+    ]);
+
+    let stored = db::find_device(&test.pool, &device.parse().unwrap()).await.unwrap().unwrap();
+    assert_eq!((stored.vendor.as_deref(), stored.os.as_deref()), (Some("juniper"), Some("junos")));
+    let ports = ports(&test.pool, device).await;
+    let uplink = port(&ports, "ge-0/0/0");
+    assert_eq!((uplink.is_uplink, uplink.remote_id.as_deref()), (Some(true), Some("mx-edge1")));
+    assert_eq!(uplink.mac.as_deref(), Some("f4:b5:2f:1a:2b:03"));
+    assert_eq!(port(&ports, "ge-0/0/1").pvid, Some(100));
+
+    // Q-BRIDGE gives the VLAN without a per-VLAN pass
+    assert_eq!(nodes(&test.pool, device).await, vec![
+        ("50:6b:8d:15:0a:0b".into(), "ge-0/0/1".into(), "100".into(), true),
+    ]);
+    assert_eq!(node_ips(&test.pool, device).await, vec![
+        ("192.168.50.1".into(), "0c:86:10:9e:1f:01".into()),
+    ]);
+    test.destroy().await;
+}
+
+#[tokio::test]
+async fn test_workers_aruba_2930f() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let device = "192.0.2.12";
+    let messages = run_workers(&test.pool, "aruba_2930f.walk", device).await;
+    assert_eq!(messages, [
+        "Discovered 192.0.2.12/32 with 4 interfaces",
+        "Macsuck 192.0.2.12/32: stored 2 MACs (0 moved), deactivated 0, dropped 0 on uplinks",
+        "Arpnip 192.0.2.12/32: stored 2 of 2 entries, deactivated 0",
+    ]);
+
+    let stored = db::find_device(&test.pool, &device.parse().unwrap()).await.unwrap().unwrap();
+    assert_eq!(stored.os.as_deref(), Some("procurve"));
+    let ports = ports(&test.pool, device).await;
+    assert_eq!(ports.iter().map(|p| p.port.as_str()).collect::<Vec<_>>(), vec!["1", "2", "28", "Trk1"]);
+    assert_eq!(port(&ports, "Trk1").is_master, Some(true));
+    assert_eq!(port(&ports, "1").slave_of.as_deref(), Some("Trk1"));
+    assert_eq!(port(&ports, "2").slave_of.as_deref(), Some("Trk1"));
+
+    assert_eq!(nodes(&test.pool, device).await, vec![
+        ("00:0c:29:aa:bb:01".into(), "28".into(), "0".into(), true),
+        ("28:80:23:11:22:33".into(), "1".into(), "0".into(), true),
+    ]);
+    assert_eq!(node_ips(&test.pool, device).await, vec![
+        ("172.16.8.1".into(), "00:0c:29:aa:bb:01".into()),
+        ("172.16.8.77".into(), "28:80:23:11:22:33".into()),
+    ]);
+    test.destroy().await;
+}
@@ -9,6 +9,9 @@ use netdisco::models::node::Node;
 use netdisco::models::admin::{self, Admin};
 use netdisco::snmp::client::*;
 use netdisco::util;
+use netdisco::models::DeviceVlan;
+use netdisco::worker::macsuck::{drop_inter_switch, entry_port, no_vlan_indexing, per_vlan_sightings, vlan_is_named};
+use std::collections::{BTreeMap, BTreeSet};
 use ipnetwork::IpNetwork;
 
 // ==================== Discovery Workflow Tests ====================
@@ -103,6 +106,7 @@ fn test_discovery_interface_processing() {
             speed: Some(1000000000),
             admin_status: Some(1),
             oper_status: Some(1),
+            ..Default::default()
         },
         InterfaceInfo {
             ifindex: 2,
@@ -111,6 +115,7 @@ fn test_discovery_interface_processing() {
             speed: Some(1000000000),
             admin_status: Some(1),
             oper_status: Some(2), // down
+            ..Default::default()
         },
         InterfaceInfo {
             ifindex: 3,
@@ -119,6 +124,7 @@ fn test_discovery_interface_processing() {
             speed: Some(1000000000),
             admin_status: Some(1),
             oper_status: Some(1),
+            ..Default::default()
         },
     ];
 
@@ -154,18 +160,24 @@ fn test_macsuck_workflow() {
     let mac_entries = vec![
         MacEntry { mac: "00:11:22:33:44:55".to_string(), bridge_port: 1, vlan: Some(100) },
         MacEntry { mac: "00:aa:bb:cc:dd:ee".to_string(), bridge_port: 2, vlan: Some(200) },
-        MacEntry { mac: "ff:ff:ff:ff:ff:ff".to_string(), bridge_port: 0, vlan: None },
+        MacEntry { mac: "ff:ff:ff:ff:ff:ff".to_string(), bridge_port: 3, vlan: None },
     ];
+    // Bridge ports 1 and 2 are ifIndex 10101 and 10102; 3 has no interface
+    let bridge_ports: BTreeMap<i32, i32> = [(1, 10101), (2, 10102)].into_iter().collect();
+    let port_names: BTreeMap<i32, String> = [(10101, "Gi1/0/1".to_string()), (10102, "Gi1/0/2".to_string())]
+        .into_iter()
+        .collect();
 
     let device_ip: IpNetwork = "10.0.0.1/32".parse().unwrap();
 
     let mut nodes = Vec::new();
     for entry in &mac_entries {
+        let Some(port) = entry_port(entry, &bridge_ports, &port_names) else { continue };
         let node = Node {
             mac: entry.mac.clone(),
             switch: device_ip,
-            port: format!("bridge-port-{}", entry.bridge_port),
-            vlan: entry.vlan.map(|v| v.to_string()),
+            port: port.to_string(),
+            vlan: Some(entry.vlan.unwrap_or(0).to_string()),
             active: Some(true),
             oui: Some(Node::extract_oui(&entry.mac)),
             time_first: None,
@@ -175,14 +187,96 @@ fn test_macsuck_workflow() {
         nodes.push(node);
     }
 
-    assert_eq!(nodes.len(), 3);
+    assert_eq!(nodes.len(), 2);
     assert_eq!(nodes[0].oui, Some("001122".to_string()));
     assert_eq!(nodes[1].oui, Some("00AABB".to_string()));
-    assert_eq!(nodes[2].oui, Some("FFFFFF".to_string()));
-    assert_eq!(nodes[0].port, "bridge-port-1");
+    assert_eq!(nodes[0].port, "Gi1/0/1");
+    assert_eq!(nodes[1].port, "Gi1/0/2");
     assert_eq!(nodes[0].vlan, Some("100".to_string()));
 }
 
+/// Entries learned on uplink or inter-switch ports are dropped and counted
+#[test]
+fn test_macsuck_drops_inter_switch_ports() {
+    let sighting = |mac: &str, port: &str| {
+        (MacEntry { mac: mac.to_string(), bridge_port: 0, vlan: Some(10) }, port.to_string())
+    };
+    let mut sightings = vec![
+        sighting("00:11:22:33:44:55", "Gi1/0/1"),
+        sighting("00:11:22:33:44:66", "Gi1/0/49"),
+        sighting("00:11:22:33:44:77", "Gi1/0/49"),
+        sighting("00:11:22:33:44:88", "Po1"),
+    ];
+    let inter_switch: BTreeSet<String> = ["Gi1/0/49".to_string(), "Po1".to_string()].into_iter().collect();
+
+    assert_eq!(drop_inter_switch(&mut sightings, &inter_switch), 3);
+    assert_eq!(sightings.len(), 1);
+    assert_eq!(sightings[0].1, "Gi1/0/1");
+
+    // With macsuck_bleed the set is empty and nothing goes
+    assert_eq!(drop_inter_switch(&mut sightings, &BTreeSet::new()), 0);
+}
+
+/// VLANs without a name are skipped when macsuck_no_unnamed is set
+#[test]
+fn test_macsuck_unnamed_vlans() {
+    let ip: IpNetwork = "10.0.0.1/32".parse().unwrap();
+    let vlan = |vlan: i32, description: Option<&str>| DeviceVlan {
+        ip,
+        vlan,
+        description: description.map(String::from),
+        creation: None,
+        last_discover: None,
+    };
+    let vlans = vec![vlan(1, Some("default")), vlan(10, Some("")), vlan(20, None)];
+
+    assert!(vlan_is_named(1, &vlans));
+    assert!(!vlan_is_named(10, &vlans));
+    assert!(!vlan_is_named(20, &vlans));
+    assert!(!vlan_is_named(30, &vlans));
+}
+
+/// A VLAN that fails mid-pass is skipped and reported; the first failing
+/// means the agent has no indexing and ends the pass
+#[tokio::test]
+async fn test_macsuck_per_vlan_pass_failures() {
+    let read = |vlans: &'static [i32]| {
+        move |vlan: i32| async move {
+            if vlans.contains(&vlan) {
+                anyhow::bail!("timeout");
+            }
+            let mac = format!("00:11:22:33:44:{:02x}", vlan);
+            Ok(vec![(MacEntry { mac, bridge_port: 1, vlan: Some(vlan) }, "Gi1/0/1".to_string())])
+        }
+    };
+
+    let pass = per_vlan_sightings(&[1, 10, 20, 30], read(&[20])).await.unwrap();
+    assert_eq!(pass.failed, vec![20]);
+    let seen: Vec<_> = pass.sightings.iter().filter_map(|(entry, _)| entry.vlan).collect();
+    assert_eq!(seen, vec![1, 10, 30]);
+
+    let complete = per_vlan_sightings(&[1, 10], read(&[])).await.unwrap();
+    assert!(complete.failed.is_empty());
+    assert_eq!(complete.sightings.len(), 2);
+
+    let mut reads = 0;
+    let first = per_vlan_sightings(&[1, 10, 20], |vlan| {
+        reads += 1;
+        read(&[1, 10, 20])(vlan)
+    }).await;
+    let first = first.unwrap_err();
+    assert!(first.to_string().contains("VLAN 1"));
+    assert_eq!(reads, 1);
+
+    // Only an unknownContext report says the agent has no per-VLAN FDB;
+    // a timeout may hide VLANs the default context lacks
+    assert!(!no_vlan_indexing(&first));
+    let unknown = per_vlan_sightings(&[1, 10], |_| async {
+        Err::<Vec<(MacEntry, String)>, _>(UnknownContext { context: "vlan-1".into() }.into())
+    }).await;
+    assert!(no_vlan_indexing(&unknown.unwrap_err()));
+}
+
 // ==================== Arpnip Workflow Tests ====================
 
 /// Simulate ARP table collection
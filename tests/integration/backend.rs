@@ -221,3 +221,79 @@ fn test_expire_interval_format() {
     let job_interval = format!("{} days", config.expire_jobs);
     assert_eq!(job_interval, "14 days");
 }
+
+// ==================== Device Identity Tests ====================
+
+fn alias(address: &str, port: &str) -> netdisco::models::device_ip::DeviceIp {
+    netdisco::models::device_ip::DeviceIp {
+        ip: "10.10.1.2".parse().unwrap(),
+        alias: address.parse().unwrap(),
+        subnet: None,
+        port: Some(port.to_string()),
+        dns: None,
+        creation: None,
+    }
+}
+
+fn identity_interfaces() -> Vec<netdisco::snmp::InterfaceInfo> {
+    let interface = |ifindex: i32, name: &str, if_type: &str| netdisco::snmp::InterfaceInfo {
+        ifindex,
+        descr: name.to_string(),
+        if_type: Some(if_type.to_string()),
+        ..Default::default()
+    };
+    vec![
+        interface(1, "Vlan1", "propVirtual"),
+        interface(2, "Loopback0", "softwareLoopback"),
+        interface(3, "Vlan20", "propVirtual"),
+    ]
+}
+
+#[test]
+fn test_canonical_ip_methods_in_order() {
+    use netdisco::worker::discover::canonical_ip;
+
+    let aliases = vec![
+        alias("10.10.1.2", "Vlan1"),
+        alias("10.255.0.1", "Loopback0"),
+        alias("10.10.20.1", "Vlan20"),
+    ];
+    let interfaces = identity_interfaces();
+    let mut config = NetdiscoConfig::default();
+    let pick = |config: &NetdiscoConfig, sysname: Option<&str>| {
+        canonical_ip(config, &aliases, &interfaces, sysname.map(|s| s.parse().unwrap()))
+            .map(|(ip, method)| (ip.ip().to_string(), method))
+    };
+
+    // No policy: the caller keeps the address the device is known by
+    assert_eq!(pick(&config, None), None);
+
+    config.device_identity = vec!["loopback".into(), "lowest".into()];
+    assert_eq!(pick(&config, None), Some(("10.255.0.1".into(), "loopback")));
+
+    config.device_identity = vec!["sysname".into(), "lowest".into()];
+    assert_eq!(pick(&config, Some("10.10.20.1")), Some(("10.10.20.1".into(), "sysName")));
+    assert_eq!(pick(&config, Some("192.0.2.1")), Some(("10.10.1.2".into(), "lowest")));
+
+    // The loopback is outside discover_only, so the next method decides
+    config.device_identity = vec!["loopback".into(), "lowest".into()];
+    config.discover_only = vec!["10.10.0.0/16".into()];
+    assert_eq!(pick(&config, None), Some(("10.10.1.2".into(), "lowest")));
+}
+
+// ==================== Trap Receiver Tests ====================
+
+/// A listen address that can't be bound fails startup instead of leaving
+/// the receiver dead in the background
+#[tokio::test]
+async fn test_bind_trap_receiver_fails_on_port_in_use() {
+    let taken = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut config = NetdiscoConfig::default();
+    config.trap_receiver.listen = taken.local_addr().unwrap().to_string();
+
+    let err = netdisco::backend::trapd::bind_trap_receiver(&config).await.unwrap_err();
+    assert!(err.to_string().contains("Failed to bind trap receiver"), "{}", err);
+
+    config.trap_receiver.listen = "127.0.0.1:0".to_string();
+    assert!(netdisco::backend::trapd::bind_trap_receiver(&config).await.is_ok());
+}
@@ -0,0 +1,315 @@
+//! Integration tests for queries and worker storage against PostgreSQL.
+//!
+//! Set `NETDISCO_TEST_DATABASE_URL` to a server the tests may create
+//! databases on (e.g. `postgres://postgres@localhost/postgres`). Each test
+//! gets a database of its own; without the variable they do nothing.
+
+use std::str::FromStr;
+
+use ipnetwork::IpNetwork;
+use sqlx::postgres::{PgConnectOptions, PgPool};
+
+use netdisco::config::NetdiscoConfig;
+use netdisco::db;
+use netdisco::models::device_port::DevicePort;
+use netdisco::snmp::{ArpEntry, MacEntry};
+use netdisco::worker::arpnip::store_arp_entries;
+use netdisco::worker::macsuck::store_nodes;
+
+pub(crate) struct TestDb {
+    admin: PgPool,
+    name: String,
+    pub(crate) pool: PgPool,
+}
+
+impl TestDb {
+    /// A fresh database, migrated up to and including `version` (all
+    /// migrations if None). None if no test server is configured.
+    pub(crate) async fn create(version: Option<i64>) -> Option<TestDb> {
+        let Ok(url) = std::env::var("NETDISCO_TEST_DATABASE_URL") else {
+            eprintln!("NETDISCO_TEST_DATABASE_URL is not set; skipping");
+            return None;
+        };
+        let options = PgConnectOptions::from_str(&url).unwrap();
+        let admin = PgPool::connect_with(options.clone()).await.unwrap();
+        let name = format!("netdisco_test_{}", rand::random::<u32>());
+        sqlx::query(&format!("CREATE DATABASE {name}")).execute(&admin).await.unwrap();
+        let pool = PgPool::connect_with(options.database(&name)).await.unwrap();
+
+        let mut migrator = sqlx::migrate!("./migrations");
+        if let Some(version) = version {
+            migrator.migrations = migrator.migrations.iter()
+                .filter(|m| m.version <= version)
+                .cloned()
+                .collect::<Vec<_>>()
+                .into();
+        }
+        migrator.run(&pool).await.unwrap();
+        Some(TestDb { admin, name, pool })
+    }
+
+    pub(crate) async fn destroy(self) {
+        self.pool.close().await;
+        sqlx::query(&format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", self.name)).execute(&self.admin).await.unwrap();
+    }
+}
+
+fn ip(s: &str) -> IpNetwork {
+    s.parse().unwrap()
+}
+
+async fn node_ip_state(pool: &PgPool, mac: &str, addr: &str) -> (bool, Option<IpNetwork>) {
+    sqlx::query_as("SELECT active, router FROM node_ip WHERE mac = $1::macaddr AND ip = $2")
+        .bind(mac)
+        .bind(ip(addr))
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+// ==================== node_ip Deactivation Tests ====================
+
+/// Rows from before node_ip.router can't be deactivated by any arpnip run
+#[tokio::test]
+async fn test_node_ip_router_migration_archives_unowned_rows() {
+    let Some(test) = TestDb::create(Some(6)).await else { return };
+    sqlx::query("INSERT INTO node_ip (mac, ip, active) VALUES ('00:11:22:33:44:55', '10.1.1.10', true)")
+        .execute(&test.pool)
+        .await
+        .unwrap();
+
+    db::run_migrations(&test.pool).await.unwrap();
+    assert_eq!(node_ip_state(&test.pool, "00:11:22:33:44:55", "10.1.1.10").await, (false, None));
+    test.destroy().await;
+}
+
+#[tokio::test]
+async fn test_delete_device_archives_its_node_ips() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let router = ip("10.0.0.1");
+    let mut tx = test.pool.begin().await.unwrap();
+    db::upsert_node_ip(&mut tx, "00:11:22:33:44:55", &ip("10.1.1.10"), &router).await.unwrap();
+    tx.commit().await.unwrap();
+
+    db::delete_device(&test.pool, &router).await.unwrap();
+    assert_eq!(node_ip_state(&test.pool, "00:11:22:33:44:55", "10.1.1.10").await, (false, None));
+    test.destroy().await;
+}
+
+/// A run only deactivates the entries its own router no longer has
+#[tokio::test]
+async fn test_deactivate_stale_node_ips_is_scoped_to_router() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let (r1, r2) = (ip("10.0.0.1"), ip("10.0.0.2"));
+    let mut tx = test.pool.begin().await.unwrap();
+    db::upsert_node_ip(&mut tx, "00:11:22:33:44:01", &ip("10.1.1.1"), &r1).await.unwrap();
+    db::upsert_node_ip(&mut tx, "00:11:22:33:44:02", &ip("10.1.1.2"), &r1).await.unwrap();
+    db::upsert_node_ip(&mut tx, "00:11:22:33:44:03", &ip("10.2.2.3"), &r2).await.unwrap();
+    tx.commit().await.unwrap();
+
+    // r1's next run only sees the first entry
+    let mut tx = test.pool.begin().await.unwrap();
+    db::upsert_node_ip(&mut tx, "00:11:22:33:44:01", &ip("10.1.1.1"), &r1).await.unwrap();
+    assert_eq!(db::deactivate_stale_node_ips(&mut tx, &r1, 0).await.unwrap(), 1);
+    tx.commit().await.unwrap();
+
+    assert_eq!(node_ip_state(&test.pool, "00:11:22:33:44:01", "10.1.1.1").await, (true, Some(r1)));
+    assert_eq!(node_ip_state(&test.pool, "00:11:22:33:44:02", "10.1.1.2").await, (false, Some(r1)));
+    assert_eq!(node_ip_state(&test.pool, "00:11:22:33:44:03", "10.2.2.3").await, (true, Some(r2)));
+
+    // Within node_freshness nothing is deactivated
+    let mut tx = test.pool.begin().await.unwrap();
+    assert_eq!(db::deactivate_stale_node_ips(&mut tx, &r2, 60).await.unwrap(), 0);
+    tx.commit().await.unwrap();
+    test.destroy().await;
+}
+
+// ==================== Concurrent Run Tests ====================
+
+/// Two switches reporting the same MACs (bleed, or hosts behind an
+/// unmanaged switch) store their runs at the same time without losing one
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_store_nodes_overlapping_runs_concurrently() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let config = NetdiscoConfig::default();
+    let (a, b) = (ip("10.0.0.1"), ip("10.0.0.2"));
+    let sightings = |uplink: &str| -> Vec<(MacEntry, String)> {
+        (0..200)
+            .map(|i| {
+                let mac = format!("00:50:56:00:{:02x}:{:02x}", i / 256, i % 256);
+                let port = if i % 2 == 0 { format!("Gi1/0/{}", i % 48 + 1) } else { uplink.to_string() };
+                (MacEntry { mac, bridge_port: 0, vlan: Some(10) }, port)
+            })
+            .collect()
+    };
+    // Opposite orders, as two agents might return them
+    let on_a = sightings("Gi1/0/49");
+    let mut on_b = sightings("Te1/1/1");
+    on_b.reverse();
+
+    for _ in 0..5 {
+        let (from_a, from_b) = tokio::join!(
+            store_nodes(&config, &test.pool, &a, &on_a, true),
+            store_nodes(&config, &test.pool, &b, &on_b, true),
+        );
+        from_a.unwrap();
+        from_b.unwrap();
+    }
+
+    // Every MAC is active on exactly one switch port, and both runs stored
+    let active: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT mac::text, count(*) FROM node WHERE active GROUP BY mac"
+    )
+        .fetch_all(&test.pool)
+        .await
+        .unwrap();
+    assert_eq!(active.len(), 200);
+    assert!(active.iter().all(|(_, rows)| *rows == 1));
+    let switches: i64 = sqlx::query_scalar("SELECT count(DISTINCT switch) FROM node")
+        .fetch_one(&test.pool)
+        .await
+        .unwrap();
+    assert_eq!(switches, 2);
+    test.destroy().await;
+}
+
+// ==================== Device Port Tests ====================
+
+fn device_port(switch: IpNetwork, name: &str, mac: Option<&str>) -> DevicePort {
+    DevicePort {
+        ip: switch, port: name.to_string(), creation: None,
+        descr: Some(name.to_string()), up: Some("up".to_string()), up_admin: Some("up".to_string()),
+        port_type: Some("ethernetCsmacd".to_string()), duplex: None, duplex_admin: None,
+        speed: Some("1 Gbps".to_string()), name: None, mac: mac.map(str::to_string), mtu: Some(1500),
+        stp: None, remote_ip: None, remote_port: None, remote_type: None, remote_id: None,
+        vlan: Some("1".to_string()), pvid: Some(1), lastchange: None, ifindex: Some(10101),
+        is_uplink: None, speed_admin: None, is_master: Some(false), slave_of: None,
+        custom_fields: None, tags: None,
+    }
+}
+
+/// Ports are stored with their MAC and read back as text
+#[tokio::test]
+async fn test_upsert_device_port_with_mac() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let switch = ip("10.0.0.1");
+    db::upsert_device_port(&test.pool, &device_port(switch, "Gi1/0/1", Some("00:1A:2B:3C:4D:01"))).await.unwrap();
+    db::upsert_device_port(&test.pool, &device_port(switch, "Vl1", None)).await.unwrap();
+
+    let ports = db::get_device_ports(&test.pool, &switch).await.unwrap();
+    assert_eq!(ports.len(), 2);
+    assert_eq!(ports[0].port, "Gi1/0/1");
+    assert_eq!(ports[0].mac.as_deref(), Some("00:1a:2b:3c:4d:01"));
+    assert_eq!(ports[1].mac, None);
+
+    // Rediscovery updates the MAC in place
+    db::upsert_device_port(&test.pool, &device_port(switch, "Gi1/0/1", Some("00:1a:2b:3c:4d:02"))).await.unwrap();
+    let ports = db::get_device_ports(&test.pool, &switch).await.unwrap();
+    assert_eq!(ports[0].mac.as_deref(), Some("00:1a:2b:3c:4d:02"));
+    test.destroy().await;
+}
+
+// ==================== Node Deactivation Tests ====================
+
+async fn node_active(pool: &PgPool, mac: &str) -> bool {
+    sqlx::query_scalar("SELECT active FROM node WHERE mac = $1::macaddr")
+        .bind(mac)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+/// A run whose per-VLAN pass missed a VLAN leaves that VLAN's nodes active
+#[tokio::test]
+async fn test_store_nodes_partial_run_keeps_missed_vlans_active() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let config = NetdiscoConfig::default();
+    let switch = ip("10.0.0.1");
+    let sighting = |mac: &str, vlan: i32, port: &str| {
+        (MacEntry { mac: mac.to_string(), bridge_port: 0, vlan: Some(vlan) }, port.to_string())
+    };
+    let vlan_1 = sighting("00:11:22:33:44:01", 1, "Gi1/0/1");
+    let vlan_10 = sighting("00:11:22:33:44:10", 10, "Gi1/0/2");
+    let vlan_20 = sighting("00:11:22:33:44:20", 20, "Gi1/0/3");
+    store_nodes(&config, &test.pool, &switch, &[vlan_1.clone(), vlan_10.clone(), vlan_20], true).await.unwrap();
+
+    // VLAN 20 timed out on the next run
+    let (_, deactivated) = store_nodes(&config, &test.pool, &switch, &[vlan_1.clone(), vlan_10.clone()], false)
+        .await
+        .unwrap();
+    assert_eq!(deactivated, 0);
+    assert!(node_active(&test.pool, "00:11:22:33:44:20").await);
+
+    // A complete run that no longer sees it does deactivate it
+    let (_, deactivated) = store_nodes(&config, &test.pool, &switch, &[vlan_1, vlan_10], true).await.unwrap();
+    assert_eq!(deactivated, 1);
+    assert!(!node_active(&test.pool, "00:11:22:33:44:20").await);
+    assert!(node_active(&test.pool, "00:11:22:33:44:01").await);
+    test.destroy().await;
+}
+
+/// A MAC seen on one switch and then another has both rows and the move
+#[tokio::test]
+async fn test_node_history_and_moves() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let config = NetdiscoConfig::default();
+    let mac = "00:11:22:33:44:55";
+    let seen = |port: &str| vec![(MacEntry { mac: mac.to_string(), bridge_port: 0, vlan: Some(10) }, port.to_string())];
+    store_nodes(&config, &test.pool, &ip("10.0.0.1"), &seen("Gi1/0/1"), true).await.unwrap();
+    store_nodes(&config, &test.pool, &ip("10.0.0.2"), &seen("Gi1/0/7"), true).await.unwrap();
+
+    // Looked up in any notation the API accepts
+    let history = db::node_history(&test.pool, "00-11-22-33-44-55").await.unwrap();
+    let rows: Vec<(&str, String, &str, Option<bool>)> = history.iter()
+        .map(|n| (n.mac.as_str(), n.switch.ip().to_string(), n.port.as_str(), n.active))
+        .collect();
+    assert_eq!(rows, vec![
+        (mac, "10.0.0.1".to_string(), "Gi1/0/1", Some(false)),
+        (mac, "10.0.0.2".to_string(), "Gi1/0/7", Some(true)),
+    ]);
+
+    let moves = db::node_moves(&test.pool, "00:11:22:33:44:55").await.unwrap();
+    assert_eq!(moves.len(), 1);
+    assert_eq!(moves[0].entry.as_deref(), Some("00:11:22:33:44:55 moved from 10.0.0.1 Gi1/0/1 to 10.0.0.2 Gi1/0/7"));
+    assert!(db::node_history(&test.pool, "00:11:22:33:44:56").await.unwrap().is_empty());
+    test.destroy().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_store_arp_entries_overlapping_runs_concurrently() {
+    let Some(test) = TestDb::create(None).await else { return };
+    let config = NetdiscoConfig::default();
+    let (r1, r2) = (ip("10.0.0.1"), ip("10.0.0.2"));
+    let entries: Vec<ArpEntry> = (0..200)
+        .map(|i| ArpEntry {
+            ip: format!("10.1.{}.{}", i / 256, i % 256),
+            mac: format!("00:50:56:00:{:02x}:{:02x}", i / 256, i % 256),
+        })
+        .collect();
+    let mut reversed = entries.clone();
+    reversed.reverse();
+
+    for _ in 0..5 {
+        let (from_r1, from_r2) = tokio::join!(
+            store_arp_entries(&config, &test.pool, &r1, &entries),
+            store_arp_entries(&config, &test.pool, &r2, &reversed),
+        );
+        assert_eq!(from_r1.unwrap().0, 200);
+        assert_eq!(from_r2.unwrap().0, 200);
+    }
+
+    let active: i64 = sqlx::query_scalar("SELECT count(*) FROM node_ip WHERE active")
+        .fetch_one(&test.pool)
+        .await
+        .unwrap();
+    assert_eq!(active, 200);
+    test.destroy().await;
+}
+
+#[test]
+fn test_retryable_sqlstates() {
+    assert!(db::is_retryable_sqlstate("40P01"));
+    assert!(db::is_retryable_sqlstate("40001"));
+    assert!(!db::is_retryable_sqlstate("23505"));
+    assert!(!db::is_retryable(&anyhow::anyhow!("SNMP timeout")));
+}
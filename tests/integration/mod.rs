@@ -1,3 +1,4 @@
 mod web_api;
 mod web_handlers;
 mod backend;
+pub(crate) mod database;
@@ -65,6 +65,7 @@ fn test_default_config_expiration() {
     assert_eq!(config.expire_nodes_archive, 60);
     assert_eq!(config.expire_jobs, 14);
     assert_eq!(config.expire_userlog, 365);
+    assert_eq!(config.node_freshness, 0);
 }
 
 #[test]
@@ -98,6 +99,17 @@ fn test_default_config_portcontrol() {
     assert!(!config.portctl_uplinks);
 }
 
+#[test]
+fn test_default_config_trap_receiver() {
+    let config = NetdiscoConfig::default();
+    assert!(!config.trap_receiver.enabled);
+    assert_eq!(config.trap_receiver.listen, "0.0.0.0:162");
+    assert!(config.trap_receiver.log);
+    assert!(config.trap_receiver.link_updown);
+    assert!(config.trap_receiver.mac_notification);
+    assert!(config.trap_receiver.coldstart);
+}
+
 #[test]
 fn test_default_config_nbtstat() {
     let config = NetdiscoConfig::default();
@@ -359,6 +371,80 @@ fn test_config_from_yaml_full() {
     assert_eq!(config.database.port, 5433);
 }
 
+#[test]
+fn test_config_device_auth_from_yaml() {
+    let yaml = r#"
+        snmpver: 3
+        device_auth:
+          - user: netdisco
+            auth:
+              pass: authpass123
+              proto: SHA
+            priv:
+              pass: privpass123
+              proto: AES
+          - user: monitor
+    "#;
+    let config: NetdiscoConfig = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(config.device_auth.len(), 2);
+    let first = &config.device_auth[0];
+    assert_eq!(first.user.as_deref(), Some("netdisco"));
+    assert_eq!(first.auth.as_ref().unwrap().proto.as_deref(), Some("SHA"));
+    assert_eq!(first.privacy.as_ref().unwrap().pass, "privpass123");
+    assert!(config.device_auth[1].auth.is_none());
+}
+
+#[test]
+fn test_config_device_auth_community_stanza_from_yaml() {
+    let yaml = r#"
+        device_auth:
+          - tag: core_rw
+            community: s3cret
+            snmpver: 1
+            only: ["10.0.0.0/8"]
+            no: ["10.9.0.0/16"]
+            read: false
+            write: true
+            port: 1161
+            timeout: 500000
+            retries: 1
+    "#;
+    let config: NetdiscoConfig = serde_yaml::from_str(yaml).unwrap();
+    let stanza = &config.device_auth[0];
+    assert_eq!(stanza.tag.as_deref(), Some("core_rw"));
+    assert_eq!(stanza.community.as_deref(), Some("s3cret"));
+    assert_eq!(stanza.snmpver, Some(1));
+    assert_eq!(stanza.only, vec!["10.0.0.0/8"]);
+    assert_eq!(stanza.no, vec!["10.9.0.0/16"]);
+    assert!(!stanza.read);
+    assert!(stanza.write);
+    assert_eq!(stanza.port, Some(1161));
+    assert_eq!(stanza.timeout, Some(500_000));
+    assert_eq!(stanza.retries, Some(1));
+    assert!(stanza.user.is_none());
+}
+
+#[test]
+fn test_config_device_auth_defaults_to_read_only() {
+    let config: NetdiscoConfig = serde_yaml::from_str("device_auth:\n  - community: public\n").unwrap();
+    assert!(config.device_auth[0].read);
+    assert!(!config.device_auth[0].write);
+}
+
+#[test]
+fn test_config_apply_overrides_device_auth() {
+    let mut config = NetdiscoConfig::default();
+    let overrides: serde_yaml::Value = serde_yaml::from_str(r#"
+        device_auth:
+          - user: netdisco
+            auth:
+              pass: authpass123
+    "#).unwrap();
+    config.apply_overrides(&overrides).unwrap();
+    assert_eq!(config.device_auth.len(), 1);
+    assert_eq!(config.device_auth[0].auth.as_ref().unwrap().pass, "authpass123");
+}
+
 #[test]
 fn test_workers_config_from_yaml() {
     let yaml = r#"
@@ -1,6 +1,6 @@
 //! Unit tests for all model types.
 
-use netdisco::models::device::Device;
+use netdisco::models::device::{Device, DeviceIdentity};
 use netdisco::models::device_port::{DevicePort, NewDevicePort};
 use netdisco::models::node::Node;
 use netdisco::models::user::User;
@@ -174,6 +174,24 @@ fn test_device_display_name_empty_name() {
     assert_eq!(device.display_name(), "192.168.1.1/32");
 }
 
+#[test]
+fn test_device_identity_corroborated_by() {
+    let row = |name: Option<&str>, serial: Option<&str>| DeviceIdentity {
+        ip: "192.168.1.1/32".parse().unwrap(),
+        name: name.map(str::to_string),
+        serial: serial.map(str::to_string),
+    };
+    // The serial decides when both sides have one
+    assert!(row(Some("sw1"), Some("FOC123")).corroborated_by(Some("sw2"), Some("foc123")));
+    assert!(!row(Some("Switch"), Some("FOC123")).corroborated_by(Some("Switch"), Some("FOC456")));
+    // Otherwise the sysName does
+    assert!(row(Some("SW1.example.com"), None).corroborated_by(Some("sw1.example.com"), Some("FOC123")));
+    assert!(!row(Some("sw1"), None).corroborated_by(Some("sw2"), None));
+    // A shared address alone is not enough
+    assert!(!row(None, None).corroborated_by(None, None));
+    assert!(!row(Some(""), None).corroborated_by(Some(""), None));
+}
+
 // ==================== Node Model Tests ====================
 
 #[test]
@@ -206,6 +224,38 @@ fn test_extract_oui_mixed_case() {
     assert_eq!(Node::extract_oui("Aa:Bb:Cc:Dd:Ee:Ff"), "AABBCC");
 }
 
+#[test]
+fn test_node_move_entry() {
+    let node = Node {
+        mac: "00:11:22:33:44:55".to_string(),
+        switch: "10.0.0.2/32".parse().unwrap(),
+        port: "Gi1/0/3".to_string(),
+        vlan: Some("10".to_string()),
+        active: Some(true),
+        oui: None,
+        time_first: None,
+        time_recent: None,
+        time_last: None,
+    };
+    let from: IpNetwork = "10.0.0.1/32".parse().unwrap();
+    assert_eq!(
+        node.move_entry(&from, "Gi1/0/1"),
+        "00:11:22:33:44:55 moved from 10.0.0.1 Gi1/0/1 to 10.0.0.2 Gi1/0/3",
+    );
+}
+
+#[test]
+fn test_node_is_move() {
+    let at = |s: &str| Some(NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap());
+    // First sighting on this port
+    assert!(Node::is_move(None, at("2024-01-01 10:00")));
+    // Gone from the other port since this one last saw it
+    assert!(Node::is_move(at("2024-01-01 10:00"), at("2024-01-01 09:00")));
+    // Still seen on the other port since: reported by both, not a move
+    assert!(!Node::is_move(at("2024-01-01 10:00"), at("2024-01-01 10:30")));
+    assert!(!Node::is_move(at("2024-01-01 10:00"), at("2024-01-01 10:00")));
+}
+
 // ==================== User Model Tests ====================
 
 fn make_test_user(is_admin: Option<bool>, port_control: Option<bool>) -> User {
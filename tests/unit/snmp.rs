@@ -1,8 +1,16 @@
 //! Unit tests for SNMP client and OID definitions.
 
+use netdisco::snmp::auth::*;
 use netdisco::snmp::client::*;
+use netdisco::snmp::fingerprint::*;
 use netdisco::snmp::oids;
-use netdisco::config::NetdiscoConfig;
+use netdisco::snmp::simulator::*;
+use netdisco::snmp::trap::*;
+use netdisco::snmp::usm::*;
+use netdisco::snmp::value::*;
+use netdisco::config::{DeviceAuth, DeviceAuthSecret, NetdiscoConfig};
+use netdisco::models::enterprise::Enterprise;
+use netdisco::worker::portcontrol::{apply_port_change, PortChange};
 
 // ==================== SnmpVersion Tests ====================
 
@@ -93,6 +101,60 @@ fn test_snmp_client_new_custom_port() {
     assert!(client.is_ok());
 }
 
+#[test]
+fn test_snmp_client_new_ipv6() {
+    for host in ["2001:db8::1", "[2001:db8::1]"] {
+        let client = SnmpClient::new(host, 161, v2c_credentials(), 1_000_000, 1).unwrap();
+        assert_eq!(client.target().to_string(), "[2001:db8::1]:161");
+        assert!(client.local_addr().is_ipv6());
+    }
+    let client = SnmpClient::new("10.0.0.1", 161, v2c_credentials(), 1_000_000, 1).unwrap();
+    assert!(client.local_addr().is_ipv4());
+}
+
+#[test]
+fn test_snmp_client_for_vlan_keeps_max_inflight() {
+    let mut client = SnmpClient::new("127.0.0.1", 161, v2c_credentials(), 1_000_000, 1).unwrap();
+    client.set_max_inflight(9);
+    let vlan_client = client.for_vlan(10);
+    assert_eq!(vlan_client.max_inflight(), 9);
+    assert_eq!(vlan_client.credentials().community, format!("{}@10", client.credentials().community));
+}
+
+#[test]
+fn test_snmp_client_source_address() {
+    let mut client = SnmpClient::new("127.0.0.1", 161, v2c_credentials(), 1_000_000, 1).unwrap();
+    client.set_source_address("127.0.0.1".parse().unwrap()).unwrap();
+    assert_eq!(client.local_addr().ip().to_string(), "127.0.0.1");
+    assert!(client.set_source_address("::1".parse().unwrap()).is_err());
+}
+
+#[test]
+fn test_snmp_client_from_config_source_address() {
+    let config = NetdiscoConfig {
+        snmp_source_address: vec!["::1".to_string(), "127.0.0.1".to_string()],
+        ..Default::default()
+    };
+    let client = SnmpClient::from_config(&config, "127.0.0.1").unwrap();
+    assert_eq!(client.local_addr().ip().to_string(), "127.0.0.1");
+    let client = SnmpClient::from_config(&config, "::1").unwrap();
+    assert_eq!(client.local_addr().ip().to_string(), "::1");
+
+    let config = NetdiscoConfig { snmp_source_address: vec!["bogus".to_string()], ..Default::default() };
+    assert!(SnmpClient::from_config(&config, "127.0.0.1").is_err());
+}
+
+#[tokio::test]
+async fn test_snmp_client_get_over_ipv6() {
+    let oid = sys_name_oid();
+    let (port, agent) = spawn_mib_agent_on("[::1]:0", vec![(oid.clone(), ber_tlv(0x04, b"v6-switch"))]);
+    let client = SnmpClient::new("::1", port, v2c_credentials(), 1_000_000, 0).unwrap();
+    let value = client.get(&oid).await.unwrap();
+    assert_eq!(value.as_string().as_deref(), Some("v6-switch"));
+    drop(client);
+    assert_eq!(agent.join().unwrap().len(), 1);
+}
+
 #[test]
 fn test_snmp_client_new_invalid_host() {
     let creds = SnmpCredentials {
@@ -123,6 +185,72 @@ fn test_snmp_client_from_config_custom_community() {
     assert!(client.is_ok());
 }
 
+#[test]
+fn test_snmp_client_from_config_v3_device_auth() {
+    let config = NetdiscoConfig {
+        snmpver: 3,
+        device_auth: vec![DeviceAuth {
+            user: Some("netdisco".to_string()),
+            auth: Some(DeviceAuthSecret { pass: "authpass123".to_string(), proto: Some("SHA".to_string()) }),
+            privacy: Some(DeviceAuthSecret { pass: "privpass123".to_string(), proto: Some("AES".to_string()) }),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let client = SnmpClient::from_config(&config, "10.0.0.1").unwrap();
+    assert_eq!(client.version(), SnmpVersion::V3);
+    assert_eq!(client.credentials().username.as_deref(), Some("netdisco"));
+}
+
+#[test]
+fn test_snmp_client_from_config_v3_without_user_falls_back() {
+    let config = NetdiscoConfig { snmpver: 3, ..Default::default() };
+    let client = SnmpClient::from_config(&config, "10.0.0.1").unwrap();
+    assert_eq!(client.version(), SnmpVersion::V2c);
+}
+
+#[test]
+fn test_snmp_client_v3_requires_username() {
+    let creds = SnmpCredentials {
+        version: SnmpVersion::V3,
+        community: "".to_string(),
+        username: None,
+        auth_protocol: None,
+        auth_password: None,
+        priv_protocol: None,
+        priv_password: None,
+    };
+    assert!(SnmpClient::new("127.0.0.1", 161, creds, 1_000_000, 0).is_err());
+}
+
+#[test]
+fn test_snmp_client_v3_rejects_priv_without_auth() {
+    let creds = SnmpCredentials {
+        version: SnmpVersion::V3,
+        community: "".to_string(),
+        username: Some("netdisco".to_string()),
+        auth_protocol: None,
+        auth_password: None,
+        priv_protocol: Some("AES".to_string()),
+        priv_password: Some("privpass123".to_string()),
+    };
+    assert!(SnmpClient::new("127.0.0.1", 161, creds, 1_000_000, 0).is_err());
+}
+
+#[test]
+fn test_snmp_client_v3_rejects_short_password() {
+    let creds = SnmpCredentials {
+        version: SnmpVersion::V3,
+        community: "".to_string(),
+        username: Some("netdisco".to_string()),
+        auth_protocol: Some("SHA".to_string()),
+        auth_password: Some("short".to_string()),
+        priv_protocol: None,
+        priv_password: None,
+    };
+    assert!(SnmpClient::new("127.0.0.1", 161, creds, 1_000_000, 0).is_err());
+}
+
 // ==================== SystemInfo Tests ====================
 
 #[test]
@@ -166,6 +294,7 @@ fn test_interface_info_struct() {
         speed: Some(1000000000),
         admin_status: Some(1),
         oper_status: Some(1),
+        ..Default::default()
     };
     assert_eq!(iface.ifindex, 1);
     assert_eq!(iface.descr, "GigabitEthernet0/1");
@@ -235,12 +364,12 @@ fn test_oid_ip_net_to_media_phys() {
 
 #[test]
 fn test_oid_lldp_rem_sys_name() {
-    assert_eq!(oids::LLDP_REM_SYS_NAME, [1, 0, 8802, 1, 1, 2, 1, 4, 1, 9]);
+    assert_eq!(oids::LLDP_REM_SYS_NAME, [1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 9]);
 }
 
 #[test]
-fn test_oid_pse_port_power() {
-    assert_eq!(oids::PSE_PORT_POWER, [1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 7]);
+fn test_oid_pse_port_priority() {
+    assert_eq!(oids::PSE_PORT_PRIORITY, [1, 3, 6, 1, 2, 1, 105, 1, 1, 1, 7]);
 }
 
 #[test]
@@ -281,3 +410,1935 @@ fn test_oid_if_table_contiguous() {
         assert_eq!(oid[9], *expected_idx);
     }
 }
+
+// ==================== SNMPv3 USM Tests ====================
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+const RFC3414_ENGINE_ID: [u8; 12] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+
+#[test]
+fn test_usm_password_to_key_md5_rfc3414() {
+    // RFC 3414 appendix A.3.1
+    let key = password_to_key(AuthProtocol::Md5, b"maplesyrup", &RFC3414_ENGINE_ID);
+    assert_eq!(hex(&key), "526f5eed9fcce26f8964c2930787d82b");
+}
+
+#[test]
+fn test_usm_password_to_key_sha_rfc3414() {
+    // RFC 3414 appendix A.3.2
+    let key = password_to_key(AuthProtocol::Sha, b"maplesyrup", &RFC3414_ENGINE_ID);
+    assert_eq!(hex(&key), "6695febc9288e36282235fc7151f128497b38f3f");
+}
+
+#[test]
+fn test_usm_password_to_key_sha2() {
+    let cases = [
+        (AuthProtocol::Sha224, "0bd8827c6e29f8065e08e09237f177e410f69b90e1782be682075674"),
+        (AuthProtocol::Sha256, "8982e0e549e866db361a6b625d84cccc11162d453ee8ce3a6445c2d6776f0f8b"),
+        (AuthProtocol::Sha384, "3b298f16164a11184279d5432bf169e2d2a48307de02b3d3f7e2b4f36eb6f0455a53689a3937eea07319a633d2ccba78"),
+        (AuthProtocol::Sha512, "22a5a36cedfcc085807a128d7bc6c2382167ad6c0dbc5fdff856740f3d84c099ad1ea87a8db096714d9788bd544047c9021e4229ce27e4c0a69250adfcffbb0b"),
+    ];
+    for (proto, expected) in cases {
+        let key = password_to_key(proto, b"maplesyrup", &RFC3414_ENGINE_ID);
+        assert_eq!(hex(&key), expected, "{:?}", proto);
+    }
+}
+
+#[test]
+fn test_usm_auth_mac_lengths() {
+    assert_eq!(AuthProtocol::Md5.mac_len(), 12);
+    assert_eq!(AuthProtocol::Sha.mac_len(), 12);
+    assert_eq!(AuthProtocol::Sha224.mac_len(), 16);
+    assert_eq!(AuthProtocol::Sha256.mac_len(), 24);
+    assert_eq!(AuthProtocol::Sha384.mac_len(), 32);
+    assert_eq!(AuthProtocol::Sha512.mac_len(), 48);
+    let mac = AuthProtocol::Sha256.sign(&[0x0b; 32], b"message");
+    assert_eq!(mac.len(), 24);
+}
+
+#[test]
+fn test_usm_protocol_parsing() {
+    assert_eq!("md5".parse::<AuthProtocol>().unwrap(), AuthProtocol::Md5);
+    assert_eq!("SHA".parse::<AuthProtocol>().unwrap(), AuthProtocol::Sha);
+    assert_eq!("SHA-256".parse::<AuthProtocol>().unwrap(), AuthProtocol::Sha256);
+    assert_eq!("sha512".parse::<AuthProtocol>().unwrap(), AuthProtocol::Sha512);
+    assert!("SHA3".parse::<AuthProtocol>().is_err());
+
+    assert_eq!("des".parse::<PrivProtocol>().unwrap(), PrivProtocol::Des);
+    assert_eq!("AES".parse::<PrivProtocol>().unwrap(), PrivProtocol::Aes128);
+    assert_eq!("AES-256".parse::<PrivProtocol>().unwrap(), PrivProtocol::Aes256);
+    assert!("3DES".parse::<PrivProtocol>().is_err());
+}
+
+#[test]
+fn test_usm_extend_key() {
+    let key = password_to_key(AuthProtocol::Sha, b"maplesyrup", &RFC3414_ENGINE_ID);
+    let extended = extend_key(AuthProtocol::Sha, key.clone(), 32);
+    assert_eq!(extended.len(), 32);
+    assert_eq!(&extended[..20], &key[..]);
+    assert_eq!(&extended[20..], &AuthProtocol::Sha.hash(&key)[..12]);
+}
+
+#[test]
+fn test_usm_privacy_round_trip() {
+    let plaintext = b"\x30\x0e\x04\x00\x04\x00\xa0\x08scopedpdu";
+    for proto in [PrivProtocol::Des, PrivProtocol::Aes128, PrivProtocol::Aes256] {
+        let key = vec![0x5a; proto.key_len()];
+        let (ciphertext, params) = proto.encrypt(&key, 7, 1234, 42, plaintext).unwrap();
+        assert_eq!(params.len(), 8);
+        assert_ne!(&ciphertext[..plaintext.len().min(ciphertext.len())], &plaintext[..]);
+        let decrypted = proto.decrypt(&key, 7, 1234, &params, &ciphertext).unwrap();
+        // DES pads to the block size; the BER framing gives the real length
+        assert_eq!(&decrypted[..plaintext.len()], &plaintext[..], "{:?}", proto);
+    }
+}
+
+#[test]
+fn test_usm_user_localize_flags() {
+    let user = UsmUser {
+        name: "netdisco".to_string(),
+        auth: Some((AuthProtocol::Sha256, "authpass123".to_string())),
+        privacy: Some((PrivProtocol::Aes256, "privpass123".to_string())),
+    };
+    let keys = user.localize(&RFC3414_ENGINE_ID).unwrap();
+    assert_eq!(keys.flags(), FLAG_AUTH | FLAG_PRIV);
+    assert_eq!(keys.privacy.as_ref().unwrap().1.len(), 32);
+    // Debug output never includes secrets
+    assert!(!format!("{:?} {:?}", user, keys).contains("authpass123"));
+}
+
+fn test_keys() -> UsmKeys {
+    UsmUser {
+        name: "netdisco".to_string(),
+        auth: Some((AuthProtocol::Sha, "authpass123".to_string())),
+        privacy: Some((PrivProtocol::Aes128, "privpass123".to_string())),
+    }
+    .localize(&RFC3414_ENGINE_ID)
+    .unwrap()
+}
+
+fn test_security() -> UsmSecurityParameters {
+    UsmSecurityParameters {
+        engine_id: RFC3414_ENGINE_ID.to_vec(),
+        engine_boots: 3,
+        engine_time: 4567,
+        user_name: "netdisco".to_string(),
+    }
+}
+
+// GetRequest for sysName.0 with request-id 0x01020304
+const SYS_NAME_GET_PDU: [u8; 28] = [
+    0xa0, 0x1a, 0x02, 0x04, 0x01, 0x02, 0x03, 0x04, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00,
+    0x30, 0x0c, 0x30, 0x0a, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x05, 0x00,
+];
+
+#[test]
+fn test_v3_message_round_trip_auth_priv() {
+    let keys = test_keys();
+    let message = encode_v3_message(
+        1234, true, &test_security(), &keys, 99, &RFC3414_ENGINE_ID, &SYS_NAME_GET_PDU,
+    ).unwrap();
+
+    let decoded = decode_v3_message(&message, Some(&keys)).unwrap();
+    assert_eq!(decoded.msg_id, 1234);
+    assert_eq!(decoded.flags, FLAG_AUTH | FLAG_PRIV | FLAG_REPORTABLE);
+    assert_eq!(decoded.security.user_name, "netdisco");
+    assert_eq!(decoded.security.engine_boots, 3);
+    assert_eq!(decoded.security.engine_time, 4567);
+    assert_eq!(decoded.context_engine_id, RFC3414_ENGINE_ID.to_vec());
+    assert_eq!(decoded.pdu, SYS_NAME_GET_PDU.to_vec());
+}
+
+#[test]
+fn test_v3_message_context_name() {
+    let message = encode_v3_message_in_context(
+        7, false, &test_security(), &UsmKeys::default(), 0, &RFC3414_ENGINE_ID, b"vlan-10", &SYS_NAME_GET_PDU,
+    ).unwrap();
+    let decoded = decode_v3_message(&message, None).unwrap();
+    assert_eq!(decoded.context_name, b"vlan-10".to_vec());
+    assert_eq!(decoded.pdu, SYS_NAME_GET_PDU.to_vec());
+
+    let message = encode_v3_message(
+        7, false, &test_security(), &UsmKeys::default(), 0, &RFC3414_ENGINE_ID, &SYS_NAME_GET_PDU,
+    ).unwrap();
+    assert!(decode_v3_message(&message, None).unwrap().context_name.is_empty());
+}
+
+#[test]
+fn test_v3_message_no_auth_no_priv() {
+    let message = encode_v3_message(
+        1, false, &test_security(), &UsmKeys::default(), 0, &[], &SYS_NAME_GET_PDU,
+    ).unwrap();
+    let decoded = decode_v3_message(&message, None).unwrap();
+    assert_eq!(decoded.flags, 0);
+    assert_eq!(decoded.pdu, SYS_NAME_GET_PDU.to_vec());
+}
+
+#[test]
+fn test_v3_message_wrong_key_rejected() {
+    let keys = test_keys();
+    let message = encode_v3_message(
+        1, true, &test_security(), &keys, 0, &RFC3414_ENGINE_ID, &SYS_NAME_GET_PDU,
+    ).unwrap();
+
+    let other = UsmUser {
+        name: "netdisco".to_string(),
+        auth: Some((AuthProtocol::Sha, "otherpass123".to_string())),
+        privacy: Some((PrivProtocol::Aes128, "privpass123".to_string())),
+    }
+    .localize(&RFC3414_ENGINE_ID)
+    .unwrap();
+    let err = decode_v3_message(&message, Some(&other)).unwrap_err();
+    assert!(err.to_string().contains("wrong digest"));
+    assert!(decode_v3_message(&message, None).is_err());
+}
+
+#[test]
+fn test_v3_message_tampered_rejected() {
+    let keys = UsmKeys { privacy: None, ..test_keys() };
+    let mut message = encode_v3_message(
+        1, true, &test_security(), &keys, 0, &RFC3414_ENGINE_ID, &SYS_NAME_GET_PDU,
+    ).unwrap();
+    let last = message.len() - 1;
+    message[last] ^= 0x01;
+    assert!(decode_v3_message(&message, Some(&keys)).is_err());
+}
+
+/// Answer engine discovery and one authPriv GET for sysName.0, using the
+/// library's own v3 codec as the agent side.
+fn spawn_v3_agent(user: UsmUser) -> (std::net::SocketAddr, std::thread::JoinHandle<()>) {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let addr = socket.local_addr().unwrap();
+    let engine_id = b"\x80\x00\x1f\x88\x04netdisco".to_vec();
+
+    let handle = std::thread::spawn(move || {
+        let mut buf = [0u8; 65535];
+        let security = UsmSecurityParameters {
+            engine_id: engine_id.clone(),
+            engine_boots: 5,
+            engine_time: 1000,
+            user_name: String::new(),
+        };
+
+        // Discovery: Report carrying usmStatsUnknownEngineIDs.0 = 1
+        let (len, peer) = socket.recv_from(&mut buf).unwrap();
+        let request = decode_v3_message(&buf[..len], None).unwrap();
+        assert!(request.security.engine_id.is_empty());
+        let report: [u8; 30] = [
+            0xa8, 0x1c, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00,
+            0x30, 0x11, 0x30, 0x0f, 0x06, 0x0a, 0x2b, 0x06, 0x01, 0x06, 0x03, 0x0f, 0x01, 0x01, 0x04, 0x00,
+            0x41, 0x01, 0x01,
+        ];
+        let reply = encode_v3_message(
+            request.msg_id, false, &security, &UsmKeys::default(), 0, &engine_id, &report,
+        ).unwrap();
+        socket.send_to(&reply, peer).unwrap();
+
+        // Authenticated, encrypted GET
+        let keys = user.localize(&engine_id).unwrap();
+        let (len, peer) = socket.recv_from(&mut buf).unwrap();
+        let request = decode_v3_message(&buf[..len], Some(&keys)).unwrap();
+        assert_eq!(request.flags & (FLAG_AUTH | FLAG_PRIV), FLAG_AUTH | FLAG_PRIV);
+        assert_eq!(request.security.user_name, user.name);
+        assert_eq!(request.security.engine_id, engine_id);
+        assert_eq!(request.pdu[0], 0xa0);
+
+        let mut response = vec![0xa2, 0x23, 0x02, 0x04];
+        response.extend_from_slice(&request.pdu[4..8]);
+        response.extend_from_slice(&[
+            0x02, 0x01, 0x00, 0x02, 0x01, 0x00,
+            0x30, 0x15, 0x30, 0x13, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x05, 0x00,
+        ]);
+        response.extend_from_slice(b"\x04\x07core-01");
+        let reply = encode_v3_message(
+            request.msg_id,
+            false,
+            &UsmSecurityParameters { user_name: user.name.clone(), ..security },
+            &keys,
+            7,
+            &engine_id,
+            &response,
+        ).unwrap();
+        socket.send_to(&reply, peer).unwrap();
+    });
+
+    (addr, handle)
+}
+
+#[tokio::test]
+async fn test_snmp_client_v3_get_auth_priv() {
+    for (auth, privacy) in [("SHA", "AES"), ("MD5", "DES"), ("SHA-256", "AES256")] {
+        let user = UsmUser {
+            name: "netdisco".to_string(),
+            auth: Some((auth.parse().unwrap(), "authpass123".to_string())),
+            privacy: Some((privacy.parse().unwrap(), "privpass123".to_string())),
+        };
+        let (addr, agent) = spawn_v3_agent(user);
+
+        let creds = SnmpCredentials {
+            version: SnmpVersion::V3,
+            community: "".to_string(),
+            username: Some("netdisco".to_string()),
+            auth_protocol: Some(auth.to_string()),
+            auth_password: Some("authpass123".to_string()),
+            priv_protocol: Some(privacy.to_string()),
+            priv_password: Some("privpass123".to_string()),
+        };
+        let client = SnmpClient::new("127.0.0.1", addr.port(), creds, 2_000_000, 0).unwrap();
+        let mut oid = oids::SYS_NAME.to_vec();
+        oid.push(0);
+        let value = client.get(&oid).await.unwrap();
+        assert_eq!(value, SnmpValue::OctetString(b"core-01".to_vec()), "{}/{}", auth, privacy);
+        agent.join().unwrap();
+    }
+}
+
+/// How the scripted v3 agent answers one request after discovery.
+enum V3Answer {
+    /// A Report for usmStats `counter`, authenticated or not, from
+    /// `engine_id` with `boots`
+    Report { counter: u32, auth: bool, engine_id: Vec<u8>, boots: u32 },
+    Respond,
+}
+
+/// Answer engine discovery whenever asked, and other requests from
+/// `script` in order. Returns the boots of each non-discovery request
+/// and the number of discoveries.
+fn spawn_v3_report_agent(user: UsmUser, script: Vec<V3Answer>) -> (std::net::SocketAddr, std::thread::JoinHandle<(Vec<u32>, usize)>) {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let addr = socket.local_addr().unwrap();
+    let engine_id = b"\x80\x00\x1f\x88\x04netdisco".to_vec();
+    let keys = user.localize(&engine_id).unwrap();
+
+    let handle = std::thread::spawn(move || {
+        let mut buf = [0u8; 65535];
+        let (mut boots, mut discoveries) = (Vec::new(), 0);
+        let mut script = script.into_iter();
+        let security = |engine_id: &[u8], engine_boots: u32| UsmSecurityParameters {
+            engine_id: engine_id.to_vec(),
+            engine_boots,
+            engine_time: 1000,
+            user_name: user.name.clone(),
+        };
+        let report = |counter: u32| ber_tlv(0xa8, &[
+            vec![0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00],
+            ber_tlv(0x30, &ber_tlv(0x30, &[ber_oid(&[1, 3, 6, 1, 6, 3, 15, 1, 1, counter, 0]), vec![0x41, 0x01, 0x01]].concat())),
+        ].concat());
+
+        while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+            let (_, requested) = peek_v3_security(&buf[..len]).unwrap();
+            let reply = if requested.engine_id.is_empty() {
+                let request = decode_v3_message(&buf[..len], None).unwrap();
+                discoveries += 1;
+                encode_v3_message(request.msg_id, false, &security(&engine_id, 5), &UsmKeys::default(), 0, &engine_id, &report(4)).unwrap()
+            } else {
+                let request = decode_v3_message(&buf[..len], Some(&keys)).unwrap();
+                boots.push(request.security.engine_boots);
+                match script.next() {
+                    Some(V3Answer::Report { counter, auth, engine_id: from, boots }) => {
+                        let keys = if auth { UsmKeys { auth: keys.auth.clone(), privacy: None } } else { UsmKeys::default() };
+                        encode_v3_message(request.msg_id, false, &security(&from, boots), &keys, 0, &from, &report(counter)).unwrap()
+                    }
+                    Some(V3Answer::Respond) => {
+                        let mut response = vec![0xa2, 0x19, 0x02, 0x04];
+                        response.extend_from_slice(&request.pdu[4..8]);
+                        response.extend_from_slice(&[
+                            0x02, 0x01, 0x00, 0x02, 0x01, 0x00,
+                            0x30, 0x0b, 0x30, 0x09, 0x06, 0x03, 0x2b, 0x06, 0x01, 0x04, 0x02, 0x6f, 0x6b,
+                        ]);
+                        encode_v3_message(request.msg_id, false, &security(&engine_id, 5), &keys, 7, &engine_id, &response).unwrap()
+                    }
+                    None => break,
+                }
+            };
+            socket.send_to(&reply, peer).unwrap();
+        }
+        (boots, discoveries)
+    });
+
+    (addr, handle)
+}
+
+#[tokio::test]
+async fn test_snmp_client_v3_checks_usm_reports() {
+    let user = UsmUser {
+        name: "netdisco".to_string(),
+        auth: Some((AuthProtocol::Sha, "authpass123".to_string())),
+        privacy: None,
+    };
+    let creds = SnmpCredentials {
+        version: SnmpVersion::V3,
+        community: "".to_string(),
+        username: Some("netdisco".to_string()),
+        auth_protocol: Some("SHA".to_string()),
+        auth_password: Some("authpass123".to_string()),
+        priv_protocol: None,
+        priv_password: None,
+    };
+    let engine_id = b"\x80\x00\x1f\x88\x04netdisco".to_vec();
+    let run = |script: Vec<V3Answer>| {
+        let (addr, agent) = spawn_v3_report_agent(user.clone(), script);
+        let client = SnmpClient::new("127.0.0.1", addr.port(), creds.clone(), 500_000, 0).unwrap();
+        async move {
+            let result = client.get(&[1, 3, 6, 1, 0]).await;
+            // A second request shows which clock the client kept
+            let _ = client.get(&[1, 3, 6, 1, 0]).await;
+            (result, agent.join().unwrap())
+        }
+    };
+
+    // An authenticated notInTimeWindow from the engine resynchronises
+    let (result, (boots, discoveries)) = run(vec![
+        V3Answer::Report { counter: 2, auth: true, engine_id: engine_id.clone(), boots: 6 },
+        V3Answer::Respond,
+        V3Answer::Respond,
+    ]).await;
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!((boots, discoveries), (vec![5, 6, 6], 1));
+
+    // An unauthenticated one, or one from another engine, is not trusted
+    for (auth, from) in [(false, engine_id.clone()), (true, b"\x80\x00\x1f\x88\x04spoofed".to_vec())] {
+        let (result, (boots, _)) = run(vec![
+            V3Answer::Report { counter: 2, auth, engine_id: from, boots: 900 },
+            V3Answer::Respond,
+        ]).await;
+        assert!(result.unwrap_err().to_string().contains("notInTimeWindow"));
+        assert_eq!(boots, vec![5, 5]);
+    }
+
+    // unknownEngineID naming the engine we know does not rediscover it
+    let (result, (_, discoveries)) = run(vec![
+        V3Answer::Report { counter: 4, auth: false, engine_id: engine_id.clone(), boots: 5 },
+        V3Answer::Respond,
+    ]).await;
+    assert!(result.unwrap_err().to_string().contains("unknownEngineID"));
+    assert_eq!(discoveries, 1);
+
+    // ...but one naming a different engine does
+    let (result, (_, discoveries)) = run(vec![
+        V3Answer::Report { counter: 4, auth: false, engine_id: b"\x80\x00\x1f\x88\x04replaced".to_vec(), boots: 1 },
+        V3Answer::Respond,
+        V3Answer::Respond,
+    ]).await;
+    assert!(result.is_ok(), "{:?}", result);
+    assert_eq!(discoveries, 2);
+}
+
+// ==================== Walk Tests ====================
+
+fn ber_read(data: &[u8], pos: &mut usize) -> (u8, Vec<u8>) {
+    let tag = data[*pos];
+    let mut len = data[*pos + 1] as usize;
+    *pos += 2;
+    if len & 0x80 != 0 {
+        let n = len & 0x7f;
+        len = data[*pos..*pos + n].iter().fold(0, |acc, &b| (acc << 8) | b as usize);
+        *pos += n;
+    }
+    let value = data[*pos..*pos + len].to_vec();
+    *pos += len;
+    (tag, value)
+}
+
+fn ber_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    if content.len() < 128 {
+        out.push(content.len() as u8);
+    } else {
+        out.extend_from_slice(&[0x82, (content.len() >> 8) as u8, content.len() as u8]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn ber_oid(oid: &[u32]) -> Vec<u8> {
+    let mut out = vec![(oid[0] * 40 + oid[1]) as u8];
+    for &c in &oid[2..] {
+        let mut parts = vec![(c & 0x7f) as u8];
+        let mut v = c >> 7;
+        while v > 0 {
+            parts.push((v & 0x7f) as u8 | 0x80);
+            v >>= 7;
+        }
+        parts.reverse();
+        out.extend_from_slice(&parts);
+    }
+    ber_tlv(0x06, &out)
+}
+
+fn ber_decode_oid(data: &[u8]) -> Vec<u32> {
+    let mut oid = vec![data[0] as u32 / 40, data[0] as u32 % 40];
+    let mut value = 0u32;
+    for &b in &data[1..] {
+        value = (value << 7) | (b & 0x7f) as u32;
+        if b & 0x80 == 0 {
+            oid.push(value);
+            value = 0;
+        }
+    }
+    oid
+}
+
+/// A parsed v1/v2c request.
+struct CommunityRequest {
+    tag: u8,
+    request_id: Vec<u8>,
+    non_repeaters: u32,
+    max_repetitions: u32,
+    oids: Vec<Vec<u32>>,
+    /// Encoded value TLVs, parallel to `oids`
+    values: Vec<Vec<u8>>,
+}
+
+fn decode_community_request(data: &[u8]) -> CommunityRequest {
+    let mut pos = 0;
+    let (_, message) = ber_read(data, &mut pos);
+    let mut pos = 0;
+    ber_read(&message, &mut pos); // version
+    ber_read(&message, &mut pos); // community
+    let tag = message[pos];
+    let (_, pdu) = ber_read(&message, &mut pos);
+    let mut pos = 0;
+    let (_, request_id) = ber_read(&pdu, &mut pos);
+    let unsigned = |bytes: Vec<u8>| bytes.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+    let non_repeaters = unsigned(ber_read(&pdu, &mut pos).1);
+    let max_repetitions = unsigned(ber_read(&pdu, &mut pos).1);
+    let (_, varbinds) = ber_read(&pdu, &mut pos);
+    let mut oids = Vec::new();
+    let mut values = Vec::new();
+    let mut pos = 0;
+    while pos < varbinds.len() {
+        let (_, varbind) = ber_read(&varbinds, &mut pos);
+        let mut vb_pos = 0;
+        let (_, oid) = ber_read(&varbind, &mut vb_pos);
+        oids.push(ber_decode_oid(&oid));
+        values.push(varbind[vb_pos..].to_vec());
+    }
+    CommunityRequest { tag, request_id, non_repeaters, max_repetitions, oids, values }
+}
+
+/// A parsed v1/v2c request: (pdu tag, request-id, max-repetitions, first OID).
+fn parse_community_request(data: &[u8]) -> (u8, Vec<u8>, u32, Vec<u32>) {
+    let request = decode_community_request(data);
+    (request.tag, request.request_id, request.max_repetitions, request.oids[0].clone())
+}
+
+fn community_response(request_id: &[u8], error_status: u8, varbinds: &[(Vec<u32>, Vec<u8>)]) -> Vec<u8> {
+    let mut list = Vec::new();
+    for (oid, value) in varbinds {
+        let mut vb = ber_oid(oid);
+        vb.extend_from_slice(value);
+        list.extend_from_slice(&ber_tlv(0x30, &vb));
+    }
+    let mut pdu = ber_tlv(0x02, request_id);
+    pdu.extend_from_slice(&[0x02, 0x01, error_status, 0x02, 0x01, 0x00]);
+    pdu.extend_from_slice(&ber_tlv(0x30, &list));
+    let mut message = vec![0x02, 0x01, 0x01];
+    message.extend_from_slice(&ber_tlv(0x04, b"public"));
+    message.extend_from_slice(&ber_tlv(0xa2, &pdu));
+    ber_tlv(0x30, &message)
+}
+
+/// A v2c agent serving `rows` ifDescr entries plus one OID after the
+/// table. GETBULK requests for more than `too_big_above` repetitions are
+/// answered with tooBig. Returns the PDU tags and max-repetitions seen.
+fn spawn_walk_agent(rows: u32, too_big_above: u32) -> (u16, std::thread::JoinHandle<Vec<(u8, u32)>>) {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let port = socket.local_addr().unwrap().port();
+
+    let mut table: Vec<(Vec<u32>, Vec<u8>)> = (1..=rows)
+        .map(|i| {
+            let mut oid = oids::IF_DESCR.to_vec();
+            oid.push(i);
+            (oid, ber_tlv(0x04, format!("port{}", i).as_bytes()))
+        })
+        .collect();
+    table.push((oids::IF_TYPE.iter().copied().chain([1]).collect(), vec![0x02, 0x01, 0x06]));
+
+    let handle = std::thread::spawn(move || {
+        let mut seen = Vec::new();
+        let mut buf = [0u8; 65535];
+        while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+            let (tag, request_id, max_rep, oid) = parse_community_request(&buf[..len]);
+            seen.push((tag, max_rep));
+            let next = table.iter().filter(|(o, _)| *o > oid);
+            let reply = if tag == 0xa5 && max_rep > too_big_above {
+                community_response(&request_id, 1, &[])
+            } else if tag == 0xa5 {
+                let mut varbinds: Vec<_> = next.take(max_rep as usize).cloned().collect();
+                while varbinds.len() < max_rep as usize {
+                    let last = varbinds.last().map(|(o, _)| o.clone()).unwrap_or(oid.clone());
+                    varbinds.push((last, vec![0x82, 0x00])); // endOfMibView
+                }
+                community_response(&request_id, 0, &varbinds)
+            } else {
+                let varbinds: Vec<_> = next.take(1).cloned().collect();
+                community_response(&request_id, 0, &varbinds)
+            };
+            socket.send_to(&reply, peer).unwrap();
+        }
+        seen
+    });
+
+    (port, handle)
+}
+
+fn v2c_credentials() -> SnmpCredentials {
+    SnmpCredentials {
+        version: SnmpVersion::V2c,
+        community: "public".to_string(),
+        username: None,
+        auth_protocol: None,
+        auth_password: None,
+        priv_protocol: None,
+        priv_password: None,
+    }
+}
+
+fn v2c_client(port: u16) -> SnmpClient {
+    SnmpClient::new("127.0.0.1", port, v2c_credentials(), 1_000_000, 0).unwrap()
+}
+
+fn assert_if_descr_walk(results: &[(Vec<u32>, SnmpValue)], rows: u32) {
+    assert_eq!(results.len(), rows as usize);
+    for (i, (oid, value)) in results.iter().enumerate() {
+        assert_eq!(*oid.last().unwrap(), i as u32 + 1);
+        assert_eq!(value.as_string(), Some(format!("port{}", i + 1)));
+    }
+}
+
+#[tokio::test]
+async fn test_walk_uses_getbulk() {
+    let (port, agent) = spawn_walk_agent(48, u32::MAX);
+    let mut client = v2c_client(port);
+    client.set_bulkwalk(true, 20);
+    assert!(client.uses_bulkwalk());
+
+    let results = client.walk(&oids::IF_DESCR).await.unwrap();
+    assert_if_descr_walk(&results, 48);
+
+    let seen = agent.join().unwrap();
+    assert_eq!(seen, vec![(0xa5, 20), (0xa5, 20), (0xa5, 20)]);
+}
+
+#[tokio::test]
+async fn test_walk_bulkwalk_off_uses_getnext() {
+    let (port, agent) = spawn_walk_agent(5, u32::MAX);
+    let mut client = v2c_client(port);
+    client.set_bulkwalk(false, 20);
+    assert!(!client.uses_bulkwalk());
+
+    let results = client.walk(&oids::IF_DESCR).await.unwrap();
+    assert_if_descr_walk(&results, 5);
+
+    let seen = agent.join().unwrap();
+    assert_eq!(seen.len(), 6);
+    assert!(seen.iter().all(|(tag, _)| *tag == 0xa1));
+}
+
+#[test]
+fn test_walk_v1_never_uses_getbulk() {
+    let creds = SnmpCredentials {
+        version: SnmpVersion::V1,
+        community: "public".to_string(),
+        username: None,
+        auth_protocol: None,
+        auth_password: None,
+        priv_protocol: None,
+        priv_password: None,
+    };
+    let mut client = SnmpClient::new("127.0.0.1", 161, creds, 1_000_000, 0).unwrap();
+    client.set_bulkwalk(true, 20);
+    assert!(!client.uses_bulkwalk());
+}
+
+#[tokio::test]
+async fn test_walk_shrinks_on_too_big() {
+    let (port, agent) = spawn_walk_agent(12, 5);
+    let mut client = v2c_client(port);
+    client.set_bulkwalk(true, 20);
+
+    let results = client.walk(&oids::IF_DESCR).await.unwrap();
+    assert_if_descr_walk(&results, 12);
+
+    let seen = agent.join().unwrap();
+    assert_eq!(&seen[..3], &[(0xa5, 20), (0xa5, 10), (0xa5, 5)]);
+    assert!(seen[3..].iter().all(|&(tag, rep)| tag == 0xa5 && rep == 5));
+}
+
+#[test]
+fn test_snmp_client_from_config_bulkwalk() {
+    let config = NetdiscoConfig { snmpver: 2, bulkwalk_off: true, ..Default::default() };
+    let client = SnmpClient::from_config(&config, "10.0.0.1").unwrap();
+    assert!(!client.uses_bulkwalk());
+
+    let config = NetdiscoConfig { snmpver: 2, ..Default::default() };
+    let client = SnmpClient::from_config(&config, "10.0.0.1").unwrap();
+    assert!(client.uses_bulkwalk());
+}
+
+// ==================== Multi-varbind Tests ====================
+
+fn mib_next(mib: &[(Vec<u32>, Vec<u8>)], oid: &[u32]) -> (Vec<u32>, Vec<u8>) {
+    mib.iter()
+        .find(|(o, _)| o.as_slice() > oid)
+        .cloned()
+        .unwrap_or((oid.to_vec(), vec![0x82, 0x00]))
+}
+
+/// A v2c agent serving `mib` with full multi-varbind GET, GETNEXT,
+/// GETBULK and SET semantics. Only existing objects can be SET. Returns
+/// every request it answered.
+fn spawn_mib_agent(mib: Vec<(Vec<u32>, Vec<u8>)>) -> (u16, std::thread::JoinHandle<Vec<CommunityRequest>>) {
+    spawn_mib_agent_on("127.0.0.1:0", mib)
+}
+
+fn spawn_mib_agent_on(addr: &str, mut mib: Vec<(Vec<u32>, Vec<u8>)>) -> (u16, std::thread::JoinHandle<Vec<CommunityRequest>>) {
+    let socket = std::net::UdpSocket::bind(addr).unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+    let port = socket.local_addr().unwrap().port();
+    mib.sort();
+
+    let handle = std::thread::spawn(move || {
+        let mut seen = Vec::new();
+        let mut buf = [0u8; 65535];
+        while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+            let request = decode_community_request(&buf[..len]);
+            let next = |oid: &[u32]| mib_next(&mib, oid);
+            let mut error_status = 0;
+            let varbinds: Vec<(Vec<u32>, Vec<u8>)> = match request.tag {
+                0xa0 => request.oids.iter()
+                    .map(|oid| match mib.iter().find(|(o, _)| o == oid) {
+                        Some(found) => found.clone(),
+                        None => (oid.clone(), vec![0x81, 0x00]),
+                    })
+                    .collect(),
+                0xa1 => request.oids.iter().map(|oid| next(oid)).collect(),
+                0xa3 => {
+                    let varbinds: Vec<_> = request.oids.iter().cloned().zip(request.values.iter().cloned()).collect();
+                    if varbinds.iter().all(|(oid, _)| mib.iter().any(|(o, _)| o == oid)) {
+                        for (oid, value) in &varbinds {
+                            if let Some(entry) = mib.iter_mut().find(|(o, _)| o == oid) {
+                                entry.1 = value.clone();
+                            }
+                        }
+                    } else {
+                        error_status = 17; // notWritable
+                    }
+                    varbinds
+                }
+                _ => {
+                    let split = (request.non_repeaters as usize).min(request.oids.len());
+                    let mut varbinds: Vec<_> = request.oids[..split].iter().map(|oid| next(oid)).collect();
+                    let mut cursors = request.oids[split..].to_vec();
+                    for _ in 0..request.max_repetitions {
+                        for cursor in cursors.iter_mut() {
+                            let varbind = next(cursor);
+                            *cursor = varbind.0.clone();
+                            varbinds.push(varbind);
+                        }
+                    }
+                    varbinds
+                }
+            };
+            socket.send_to(&community_response(&request.request_id, error_status, &varbinds), peer).unwrap();
+            seen.push(request);
+        }
+        seen
+    });
+
+    (port, handle)
+}
+
+fn column_entry(column: &[u32], index: u32, value: Vec<u8>) -> (Vec<u32>, Vec<u8>) {
+    (column.iter().copied().chain([index]).collect(), value)
+}
+
+/// ifDescr for ifIndex 1-3, ifSpeed only for 1 and 3, then sysName.
+fn sparse_if_mib() -> Vec<(Vec<u32>, Vec<u8>)> {
+    vec![
+        column_entry(&oids::IF_DESCR, 1, ber_tlv(0x04, b"ge-0/0/1")),
+        column_entry(&oids::IF_DESCR, 2, ber_tlv(0x04, b"ge-0/0/2")),
+        column_entry(&oids::IF_DESCR, 3, ber_tlv(0x04, b"ge-0/0/3")),
+        column_entry(&oids::IF_TYPE, 1, vec![0x02, 0x01, 0x06]),
+        column_entry(&oids::IF_TYPE, 2, vec![0x02, 0x01, 0x06]),
+        column_entry(&oids::IF_TYPE, 3, vec![0x02, 0x01, 0x18]),
+        column_entry(&oids::IF_SPEED, 1, vec![0x42, 0x04, 0x3b, 0x9a, 0xca, 0x00]),
+        column_entry(&oids::IF_SPEED, 3, vec![0x42, 0x04, 0x3b, 0x9a, 0xca, 0x00]),
+    ]
+}
+
+#[tokio::test]
+async fn test_get_many_single_round_trip() {
+    let mib = vec![
+        column_entry(&oids::SYS_NAME, 0, ber_tlv(0x04, b"core-01")),
+        column_entry(&oids::SYS_LOCATION, 0, ber_tlv(0x04, b"DC1")),
+    ];
+    let (port, agent) = spawn_mib_agent(mib);
+    let client = v2c_client(port);
+
+    let name: Vec<u32> = oids::SYS_NAME.iter().copied().chain([0]).collect();
+    let contact: Vec<u32> = oids::SYS_CONTACT.iter().copied().chain([0]).collect();
+    let location: Vec<u32> = oids::SYS_LOCATION.iter().copied().chain([0]).collect();
+    let varbinds = client.get_many(&[&name, &contact, &location]).await.unwrap();
+
+    assert_eq!(varbinds, vec![
+        (name.clone(), SnmpValue::OctetString(b"core-01".to_vec())),
+        (contact.clone(), SnmpValue::NoSuchInstance),
+        (location.clone(), SnmpValue::OctetString(b"DC1".to_vec())),
+    ]);
+
+    let seen = agent.join().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0].oids, vec![name, contact, location]);
+}
+
+#[tokio::test]
+async fn test_walk_without_any_answer_is_an_error() {
+    // An agent with nothing in the subtree answers; a silent one does not
+    let (port, agent) = spawn_mib_agent(vec![column_entry(&oids::SYS_NAME, 0, ber_tlv(0x04, b"core-01"))]);
+    let client = v2c_client(port);
+    assert!(client.get_bridge_ports().await.unwrap().is_empty());
+    assert!(client.get_mac_table().await.unwrap().is_empty());
+    drop(client);
+    agent.join().unwrap();
+
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let client = SnmpClient::new("127.0.0.1", silent.local_addr().unwrap().port(), v2c_credentials(), 50_000, 0).unwrap();
+    let err = client.get_bridge_ports().await.unwrap_err();
+    assert!(err.to_string().contains("timed out"), "{}", err);
+    assert!(client.get_mac_table().await.is_err());
+}
+
+#[tokio::test]
+async fn test_get_bulk_many_non_repeaters() {
+    let mut mib = sparse_if_mib();
+    mib.push(column_entry(&oids::SYS_UPTIME, 0, vec![0x43, 0x01, 0x64]));
+    let (port, agent) = spawn_mib_agent(mib);
+    let client = v2c_client(port);
+
+    let varbinds = client.get_bulk_many(1, 2, &[&oids::SYS_UPTIME, &oids::IF_DESCR, &oids::IF_SPEED]).await.unwrap();
+    let got: Vec<String> = varbinds.iter().map(|(_, v)| v.to_string()).collect();
+    assert_eq!(got, vec!["100", "ge-0/0/1", "1000000000", "ge-0/0/2", "1000000000"]);
+
+    let seen = agent.join().unwrap();
+    assert_eq!((seen[0].tag, seen[0].non_repeaters, seen[0].max_repetitions), (0xa5, 1, 2));
+}
+
+#[tokio::test]
+async fn test_get_table_rows_by_index() {
+    for bulk in [true, false] {
+        let (port, agent) = spawn_mib_agent(sparse_if_mib());
+        let mut client = v2c_client(port);
+        client.set_bulkwalk(bulk, 2);
+
+        let table = client.get_table(&[&oids::IF_DESCR, &oids::IF_SPEED]).await.unwrap();
+        let rows: Vec<(Vec<u32>, Vec<Option<String>>)> = table.into_iter()
+            .map(|(index, row)| (index, row.iter().map(|v| v.as_ref().map(|v| v.to_string())).collect()))
+            .collect();
+        assert_eq!(rows, vec![
+            (vec![1], vec![Some("ge-0/0/1".to_string()), Some("1000000000".to_string())]),
+            (vec![2], vec![Some("ge-0/0/2".to_string()), None]),
+            (vec![3], vec![Some("ge-0/0/3".to_string()), Some("1000000000".to_string())]),
+        ], "bulk={}", bulk);
+
+        let seen = agent.join().unwrap();
+        let expected_tag = if bulk { 0xa5 } else { 0xa1 };
+        assert!(seen.iter().all(|r| r.tag == expected_tag));
+        assert!(seen.iter().all(|r| r.oids.len() <= 2));
+        if !bulk {
+            // One GETNEXT per row, both columns per request
+            assert_eq!(seen[0].oids.len(), 2);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_get_interfaces_from_table() {
+    let (port, _agent) = spawn_mib_agent(sparse_if_mib());
+    let client = v2c_client(port);
+
+    let interfaces = client.get_interfaces().await.unwrap();
+    assert_eq!(interfaces.len(), 3);
+    assert_eq!(interfaces[1].ifindex, 2);
+    assert_eq!(interfaces[1].descr, "ge-0/0/2");
+    assert_eq!(interfaces[1].speed, None);
+    assert_eq!(interfaces[2].if_type.as_deref(), Some("softwareLoopback"));
+    assert_eq!(interfaces[2].speed, Some(1_000_000_000));
+}
+
+#[tokio::test]
+async fn test_get_system_info_one_request() {
+    let mib = vec![
+        column_entry(&oids::SYS_DESCR, 0, ber_tlv(0x04, b"Cisco IOS")),
+        column_entry(&oids::SYS_UPTIME, 0, vec![0x43, 0x01, 0x64]),
+        column_entry(&oids::SYS_NAME, 0, ber_tlv(0x04, b"core-01")),
+    ];
+    let (port, agent) = spawn_mib_agent(mib);
+    let client = v2c_client(port);
+
+    let info = client.get_system_info().await.unwrap();
+    assert_eq!(info.description.as_deref(), Some("Cisco IOS"));
+    assert_eq!(info.uptime, Some(100));
+    assert_eq!(info.name.as_deref(), Some("core-01"));
+    assert_eq!(info.location, None);
+
+    let seen = agent.join().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0].oids.len(), 7);
+}
+
+// ==================== Port Control Tests ====================
+
+#[test]
+fn test_port_change_parse() {
+    assert_eq!(PortChange::parse("portcontrol", "up").unwrap(), PortChange::AdminStatus(true));
+    assert_eq!(PortChange::parse("portcontrol", "disable").unwrap(), PortChange::AdminStatus(false));
+    assert_eq!(PortChange::parse("portname", "uplink to core").unwrap(), PortChange::Name("uplink to core".to_string()));
+    assert_eq!(PortChange::parse("portvlan", "120").unwrap(), PortChange::Vlan(120));
+    assert_eq!(PortChange::parse("power", "off").unwrap(), PortChange::Power(false));
+    assert!(PortChange::parse("portcontrol", "bounce").is_err());
+    assert!(PortChange::parse("portvlan", "abc").is_err());
+    assert!(PortChange::parse("portvlan", "4095").is_err());
+    assert!(PortChange::parse("reboot", "").is_err());
+}
+
+#[test]
+fn test_snmp_client_from_config_rw() {
+    let config = NetdiscoConfig { snmpver: 2, community_rw: vec!["private".to_string()], ..Default::default() };
+    let client = SnmpClient::from_config_rw(&config, "10.0.0.1").unwrap();
+    assert_eq!(client.credentials().community, "private");
+
+    let config = NetdiscoConfig { snmpver: 2, ..Default::default() };
+    assert!(SnmpClient::from_config_rw(&config, "10.0.0.1").is_err());
+}
+
+// ==================== Credential Selection Tests ====================
+
+fn community_stanza(tag: &str, community: &str) -> DeviceAuth {
+    DeviceAuth { tag: Some(tag.to_string()), community: Some(community.to_string()), ..Default::default() }
+}
+
+#[test]
+fn test_stanzas_for_acl_and_legacy_lists() {
+    let config = NetdiscoConfig {
+        community: vec!["legacy".to_string()],
+        community_rw: vec!["legacy_rw".to_string()],
+        device_auth: vec![
+            DeviceAuth { only: vec!["10.1.0.0/16".to_string()], ..community_stanza("site1", "one") },
+            DeviceAuth { no: vec!["10.1.2.0/24".to_string()], ..community_stanza("most", "two") },
+            DeviceAuth { read: false, write: true, ..community_stanza("rw", "three") },
+        ],
+        ..Default::default()
+    };
+
+    let tags = |host: &str, access| -> Vec<Option<String>> {
+        stanzas_for(&config, host, access).into_iter().map(|a| a.tag).collect()
+    };
+    assert_eq!(tags("10.1.1.1", Access::Read), vec![Some("site1".to_string()), Some("most".to_string()), None]);
+    assert_eq!(tags("10.1.2.1", Access::Read), vec![Some("site1".to_string()), None]);
+    assert_eq!(tags("10.2.0.1", Access::Read), vec![Some("most".to_string()), None]);
+    assert_eq!(tags("10.2.0.1", Access::Write), vec![Some("rw".to_string()), None]);
+
+    let legacy = stanzas_for(&config, "10.2.0.1", Access::Write).pop().unwrap();
+    assert_eq!(legacy.community.as_deref(), Some("legacy_rw"));
+    assert!(legacy.write);
+}
+
+#[test]
+fn test_stanzas_for_defaults() {
+    let config = NetdiscoConfig { snmpver: 1, community: vec![], ..Default::default() };
+    let stanzas = stanzas_for(&config, "10.0.0.1", Access::Read);
+    assert_eq!(stanzas.len(), 1);
+    assert_eq!(stanzas[0].community.as_deref(), Some("public"));
+    assert_eq!(stanzas[0].snmpver, Some(1));
+    assert!(stanzas_for(&config, "10.0.0.1", Access::Write).is_empty());
+
+    // v3 is only used with a user, so communities fall back to v2c
+    let config = NetdiscoConfig { snmpver: 3, ..Default::default() };
+    assert_eq!(stanzas_for(&config, "10.0.0.1", Access::Read)[0].snmpver, Some(2));
+}
+
+#[test]
+fn test_prefer_remembered() {
+    let mut stanzas = vec![
+        community_stanza("a", "one"),
+        DeviceAuth { community: Some("two".to_string()), snmpver: Some(2), ..Default::default() },
+        DeviceAuth { user: Some("netdisco".to_string()), ..Default::default() },
+        community_stanza("d", "four"),
+        DeviceAuth { user: Some("backup".to_string()), ..Default::default() },
+    ];
+    let order = |stanzas: &[DeviceAuth]| -> Vec<String> {
+        stanzas.iter().map(describe).collect()
+    };
+
+    prefer_remembered(&mut stanzas, &RememberedAuth { tag: Some("d".to_string()), ..Default::default() });
+    assert_eq!(order(&stanzas), vec!["'d'", "'a'", "v2 community", "v3 user 'netdisco'", "v3 user 'backup'"]);
+
+    // Untagged v3 users are told apart by name
+    let backup = RememberedAuth::from_stanza(&stanzas[4]);
+    prefer_remembered(&mut stanzas, &backup);
+    assert_eq!(order(&stanzas)[0], "v3 user 'backup'");
+    let netdisco = RememberedAuth { snmp_ver: Some(3), snmp_comm: Some("netdisco".to_string()), tag: None };
+    prefer_remembered(&mut stanzas, &netdisco);
+    assert_eq!(order(&stanzas)[0], "v3 user 'netdisco'");
+    // A v3 row remembered without its user name matches no stanza
+    let before = order(&stanzas);
+    prefer_remembered(&mut stanzas, &RememberedAuth { snmp_ver: Some(3), ..Default::default() });
+    assert_eq!(order(&stanzas), before);
+
+    let untagged = RememberedAuth::from_stanza(&stanzas[4]);
+    prefer_remembered(&mut stanzas, &untagged);
+    assert_eq!(stanzas[0].community.as_deref(), Some("two"));
+
+    // A credential no longer configured leaves the order alone
+    let before = order(&stanzas);
+    prefer_remembered(&mut stanzas, &RememberedAuth { tag: Some("gone".to_string()), ..Default::default() });
+    assert_eq!(order(&stanzas), before);
+}
+
+#[test]
+fn test_remembered_auth_from_stanza() {
+    let remembered = RememberedAuth::from_stanza(&DeviceAuth { snmpver: Some(1), ..community_stanza("old", "c") });
+    assert_eq!(remembered, RememberedAuth { snmp_ver: Some(1), snmp_comm: Some("c".to_string()), tag: Some("old".to_string()) });
+
+    let remembered = RememberedAuth::from_stanza(&DeviceAuth { user: Some("u".to_string()), ..Default::default() });
+    assert_eq!(remembered, RememberedAuth { snmp_ver: Some(3), snmp_comm: Some("u".to_string()), tag: None });
+}
+
+#[test]
+fn test_from_device_auth_community_stanza() {
+    let config = NetdiscoConfig::default();
+    let stanza = DeviceAuth { port: Some(1161), timeout: Some(100_000), ..community_stanza("t", "c") };
+    let client = SnmpClient::from_device_auth(&config, "127.0.0.1", &stanza).unwrap();
+    assert_eq!(client.credentials().community, "c");
+    assert_eq!(client.version(), SnmpVersion::V2c);
+}
+
+#[tokio::test]
+async fn test_connect_rotates_to_working_credential() {
+    // Nothing answers on the first stanza's port
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let silent_port = silent.local_addr().unwrap().port();
+    let mib = vec![(
+        oids::SYS_OBJECT_ID.iter().copied().chain([0]).collect(),
+        vec![0x06, 0x06, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x09],
+    )];
+    let (port, agent) = spawn_mib_agent(mib);
+
+    let config = NetdiscoConfig::default();
+    let stanzas = vec![
+        DeviceAuth { port: Some(silent_port), timeout: Some(50_000), retries: Some(0), ..community_stanza("dead", "wrong") },
+        DeviceAuth { port: Some(port), snmpver: Some(2), ..community_stanza("live", "right") },
+    ];
+    let (client, stanza) = connect(&config, "127.0.0.1", &stanzas).await.unwrap();
+    assert_eq!(stanza.tag.as_deref(), Some("live"));
+    assert_eq!(client.credentials().community, "right");
+    drop(client);
+    assert_eq!(agent.join().unwrap().len(), 1);
+
+    let err = connect(&config, "127.0.0.1", &stanzas[..1]).await.unwrap_err();
+    assert!(format!("{:#}", err).contains("No SNMP credentials worked for 127.0.0.1 (1 tried)"));
+}
+
+fn port_control_mib() -> Vec<(Vec<u32>, Vec<u8>)> {
+    vec![
+        column_entry(&oids::IF_ADMIN_STATUS, 5, vec![0x02, 0x01, 0x01]),
+        column_entry(&oids::IF_ALIAS, 5, ber_tlv(0x04, b"old")),
+        column_entry(&oids::DOT1D_BASE_PORT_IF_INDEX, 3, vec![0x02, 0x01, 0x04]),
+        column_entry(&oids::DOT1D_BASE_PORT_IF_INDEX, 4, vec![0x02, 0x01, 0x05]),
+        column_entry(&oids::DOT1Q_PVID, 4, vec![0x42, 0x01, 0x01]),
+    ]
+}
+
+/// A Catalyst stack member's ports and its PSE ports, numbered 1.1 to 1.5
+/// by position rather than by ifIndex.
+fn pse_port_mib() -> Vec<(Vec<u32>, Vec<u8>)> {
+    let mut mib = Vec::new();
+    for (ifindex, name, if_type) in [(1, "Vl1", 53), (10101, "Gi1/0/1", 6), (10105, "Gi1/0/5", 6)] {
+        mib.push(column_entry(&oids::IF_DESCR, ifindex, ber_tlv(0x04, name.as_bytes())));
+        mib.push(column_entry(&oids::IF_TYPE, ifindex, vec![0x02, 0x01, if_type]));
+        mib.push(column_entry(&oids::IF_NAME, ifindex, ber_tlv(0x04, name.as_bytes())));
+    }
+    for port in [1, 5] {
+        mib.push((oids::PSE_PORT_ADMIN.iter().copied().chain([1, port]).collect(), vec![0x02, 0x01, 0x01]));
+    }
+    mib
+}
+
+#[tokio::test]
+async fn test_port_change_sets_and_reads_back() {
+    let (port, agent) = spawn_mib_agent(port_control_mib());
+    let client = v2c_client(port);
+
+    apply_port_change(&client, 5, &PortChange::AdminStatus(false)).await.unwrap();
+    apply_port_change(&client, 5, &PortChange::Name("printer".to_string())).await.unwrap();
+    apply_port_change(&client, 5, &PortChange::Vlan(120)).await.unwrap();
+
+    let seen = agent.join().unwrap();
+    let sets: Vec<(Vec<u32>, Vec<u8>)> = seen.iter()
+        .filter(|r| r.tag == 0xa3)
+        .map(|r| (r.oids[0].clone(), r.values[0].clone()))
+        .collect();
+    assert_eq!(sets, vec![
+        column_entry(&oids::IF_ADMIN_STATUS, 5, vec![0x02, 0x01, 0x02]),
+        column_entry(&oids::IF_ALIAS, 5, ber_tlv(0x04, b"printer")),
+        // ifIndex 5 is bridge port 4
+        column_entry(&oids::DOT1Q_PVID, 4, vec![0x42, 0x01, 0x78]),
+    ]);
+    // Every SET is followed by a GET of the same object
+    for (i, request) in seen.iter().enumerate().filter(|(_, r)| r.tag == 0xa3) {
+        assert_eq!(seen[i + 1].tag, 0xa0);
+        assert_eq!(seen[i + 1].oids, request.oids);
+    }
+}
+
+#[tokio::test]
+async fn test_port_change_unwritable_fails() {
+    let (port, _agent) = spawn_mib_agent(port_control_mib());
+    let client = v2c_client(port);
+
+    // No ifAlias instance for ifIndex 6
+    let err = apply_port_change(&client, 6, &PortChange::Name("x".to_string())).await.unwrap_err();
+    assert!(format!("{:#}", err).contains("notWritable"), "{:#}", err);
+    // No bridge port for ifIndex 6
+    let err = apply_port_change(&client, 6, &PortChange::Vlan(10)).await.unwrap_err();
+    assert!(err.to_string().contains("No bridge port"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_port_power_sets_pse_port_by_position() {
+    let (port, agent) = spawn_mib_agent(pse_port_mib());
+    let client = v2c_client(port);
+
+    // Gi1/0/5 is PSE port 1.5
+    apply_port_change(&client, 10105, &PortChange::Power(false)).await.unwrap();
+    // ifIndex 1 is Vl1, not PSE port 1.1
+    let err = apply_port_change(&client, 1, &PortChange::Power(true)).await.unwrap_err();
+    assert!(err.to_string().contains("No PSE port for ifIndex 1"), "{}", err);
+    drop(client);
+
+    let sets: Vec<(Vec<u32>, Vec<u8>)> = agent.join().unwrap().iter()
+        .filter(|r| r.tag == 0xa3)
+        .map(|r| (r.oids[0].clone(), r.values[0].clone()))
+        .collect();
+    assert_eq!(sets, vec![
+        (oids::PSE_PORT_ADMIN.iter().copied().chain([1, 5]).collect(), vec![0x02, 0x01, 0x02]),
+    ]);
+}
+
+// ==================== Trap Receiver Tests ====================
+
+fn trap_source() -> std::net::IpAddr {
+    "192.0.2.10".parse().unwrap()
+}
+
+fn oid_varbind(oid: &[u32], value: Vec<u8>) -> Vec<u8> {
+    let mut vb = ber_oid(oid);
+    vb.extend_from_slice(&value);
+    ber_tlv(0x30, &vb)
+}
+
+/// An SNMPv2-Trap (0xa7) or InformRequest (0xa6) PDU.
+fn v2_notification_pdu(tag: u8, request_id: u8, trap_oid: &[u32], extra: &[Vec<u8>]) -> Vec<u8> {
+    let mut list = oid_varbind(&[1, 3, 6, 1, 2, 1, 1, 3, 0], vec![0x43, 0x02, 0x30, 0x39]);
+    list.extend_from_slice(&oid_varbind(&[1, 3, 6, 1, 6, 3, 1, 1, 4, 1, 0], ber_oid(trap_oid)));
+    for vb in extra {
+        list.extend_from_slice(vb);
+    }
+    let mut pdu = vec![0x02, 0x01, request_id, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00];
+    pdu.extend_from_slice(&ber_tlv(0x30, &list));
+    ber_tlv(tag, &pdu)
+}
+
+fn community_message(version: u8, community: &str, pdu: &[u8]) -> Vec<u8> {
+    let mut message = vec![0x02, 0x01, version];
+    message.extend_from_slice(&ber_tlv(0x04, community.as_bytes()));
+    message.extend_from_slice(pdu);
+    ber_tlv(0x30, &message)
+}
+
+fn link_down_varbinds() -> Vec<Vec<u8>> {
+    vec![
+        oid_varbind(&[1, 3, 6, 1, 2, 1, 2, 2, 1, 1, 7], vec![0x02, 0x01, 0x07]),
+        oid_varbind(&[1, 3, 6, 1, 2, 1, 2, 2, 1, 7, 7], vec![0x02, 0x01, 0x01]),
+        oid_varbind(&[1, 3, 6, 1, 2, 1, 2, 2, 1, 8, 7], vec![0x02, 0x01, 0x02]),
+    ]
+}
+
+#[test]
+fn test_trap_v2c_link_down() {
+    let receiver = TrapReceiver::new(vec!["traps".to_string()], vec![]);
+    let pdu = v2_notification_pdu(0xa7, 9, &oids::LINK_DOWN, &link_down_varbinds());
+
+    let received = receiver.receive(&community_message(1, "traps", &pdu), trap_source()).unwrap();
+    assert!(received.reply.is_none());
+    let trap = received.notification.unwrap();
+    assert_eq!(trap.version, SnmpVersion::V2c);
+    assert_eq!(trap.agent, trap_source());
+    assert_eq!(trap.uptime, Some(12345));
+    assert_eq!(trap.varbinds.len(), 3);
+    assert_eq!(trap.event(), TrapEvent::LinkChange { ifindex: 7, up: false });
+    assert_eq!(trap.name(), "linkDown");
+    assert!(trap.to_string().starts_with("trap linkDown from 192.0.2.10 (v2c)"), "{}", trap);
+
+    // Wrong community is dropped
+    assert!(receiver.receive(&community_message(1, "public", &pdu), trap_source()).is_err());
+}
+
+#[test]
+fn test_trap_events() {
+    let receiver = TrapReceiver::new(vec![], vec![]);
+    let decode = |trap_oid: &[u32]| {
+        let pdu = v2_notification_pdu(0xa7, 1, trap_oid, &[]);
+        receiver.receive(&community_message(1, "any", &pdu), trap_source()).unwrap().notification.unwrap()
+    };
+    assert_eq!(decode(&oids::COLD_START).event(), TrapEvent::ColdStart);
+    assert_eq!(decode(&oids::CMN_MAC_CHANGED_NOTIFICATION).event(), TrapEvent::MacNotification);
+    assert_eq!(decode(&oids::CMN_MAC_MOVE_NOTIFICATION).event(), TrapEvent::MacNotification);
+    // linkUp without any interface varbind cannot be acted on
+    assert_eq!(decode(&oids::LINK_UP).event(), TrapEvent::Other);
+    assert_eq!(decode(&[1, 3, 6, 1, 4, 1, 9, 0, 1]).name(), "1.3.6.1.4.1.9.0.1");
+}
+
+#[test]
+fn test_trap_v1_translated() {
+    let receiver = TrapReceiver::new(vec![], vec![]);
+    let v1_trap = |generic: u8, specific: u8, agent_addr: [u8; 4], varbinds: &[Vec<u8>]| {
+        let mut pdu = ber_oid(&[1, 3, 6, 1, 4, 1, 9]);
+        pdu.extend_from_slice(&ber_tlv(0x40, &agent_addr));
+        pdu.extend_from_slice(&[0x02, 0x01, generic, 0x02, 0x01, specific, 0x43, 0x01, 0x64]);
+        pdu.extend_from_slice(&ber_tlv(0x30, &varbinds.concat()));
+        community_message(0, "public", &ber_tlv(0xa4, &pdu))
+    };
+
+    let trap = receiver.receive(&v1_trap(2, 0, [10, 1, 1, 1], &link_down_varbinds()), trap_source())
+        .unwrap().notification.unwrap();
+    assert_eq!(trap.version, SnmpVersion::V1);
+    assert_eq!(trap.trap_oid, oids::LINK_DOWN.to_vec());
+    assert_eq!(trap.uptime, Some(100));
+    assert_eq!(trap.event(), TrapEvent::LinkChange { ifindex: 7, up: false });
+    // agent-addr is only logged; the trap is from the datagram source
+    assert_eq!(trap.agent, trap_source());
+    assert_eq!(trap.agent_addr, Some("10.1.1.1".parse().unwrap()));
+    assert!(trap.to_string().contains("from 192.0.2.10 (v1) agent-addr=10.1.1.1"), "{}", trap);
+
+    // Enterprise-specific: enterprise.0.specific; agent-addr 0.0.0.0 is unset
+    let trap = receiver.receive(&v1_trap(6, 42, [0, 0, 0, 0], &[]), trap_source())
+        .unwrap().notification.unwrap();
+    assert_eq!(trap.trap_oid, vec![1, 3, 6, 1, 4, 1, 9, 0, 42]);
+    assert_eq!((trap.agent, trap.agent_addr), (trap_source(), None));
+}
+
+#[test]
+fn test_inform_v2c_acknowledged() {
+    let receiver = TrapReceiver::new(vec![], vec![]);
+    let pdu = v2_notification_pdu(0xa6, 77, &oids::COLD_START, &[]);
+
+    let received = receiver.receive(&community_message(1, "public", &pdu), trap_source()).unwrap();
+    assert!(received.notification.unwrap().inform);
+    let reply = decode_community_request(&received.reply.unwrap());
+    assert_eq!(reply.tag, 0xa2);
+    assert_eq!(reply.request_id.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32), 77);
+    assert_eq!(reply.oids[1], vec![1, 3, 6, 1, 6, 3, 1, 1, 4, 1, 0]);
+}
+
+fn trap_user() -> UsmUser {
+    UsmUser {
+        name: "traps".to_string(),
+        auth: Some((AuthProtocol::Sha256, "authpass123".to_string())),
+        privacy: Some((PrivProtocol::Aes128, "privpass123".to_string())),
+    }
+}
+
+#[test]
+fn test_trap_v3_auth_priv() {
+    let receiver = TrapReceiver::new(vec![], vec![trap_user()]);
+    let sender_engine = b"\x80\x00\x1f\x88\x04switch".to_vec();
+    let keys = trap_user().localize(&sender_engine).unwrap();
+    let security = UsmSecurityParameters {
+        engine_id: sender_engine.clone(),
+        engine_boots: 4,
+        engine_time: 99_000,
+        user_name: "traps".to_string(),
+    };
+    let pdu = v2_notification_pdu(0xa7, 5, &oids::CMN_MAC_CHANGED_NOTIFICATION, &[]);
+    let message = encode_v3_message(11, false, &security, &keys, 3, &sender_engine, &pdu).unwrap();
+
+    let received = receiver.receive(&message, trap_source()).unwrap();
+    let trap = received.notification.unwrap();
+    assert_eq!(trap.version, SnmpVersion::V3);
+    assert_eq!(trap.security_name, "traps");
+    assert_eq!(trap.event(), TrapEvent::MacNotification);
+
+    // Wrong password fails authentication
+    let bad_keys = UsmUser { auth: Some((AuthProtocol::Sha256, "wrongpass1".to_string())), ..trap_user() }
+        .localize(&sender_engine).unwrap();
+    let message = encode_v3_message(12, false, &security, &bad_keys, 3, &sender_engine, &pdu).unwrap();
+    assert!(receiver.receive(&message, trap_source()).is_err());
+}
+
+#[test]
+fn test_trap_v3_outside_time_window_rejected() {
+    let receiver = TrapReceiver::new(vec![], vec![trap_user()]);
+    let sender_engine = b"\x80\x00\x1f\x88\x04switch".to_vec();
+    let keys = trap_user().localize(&sender_engine).unwrap();
+    let pdu = v2_notification_pdu(0xa7, 5, &oids::COLD_START, &[]);
+    let trap = |msg_id: i32, engine_boots: u32, engine_time: u32| {
+        let security = UsmSecurityParameters {
+            engine_id: sender_engine.clone(),
+            engine_boots,
+            engine_time,
+            user_name: "traps".to_string(),
+        };
+        receiver.receive(&encode_v3_message(msg_id, false, &security, &keys, 3, &sender_engine, &pdu).unwrap(), trap_source())
+    };
+
+    // The first trap sets the sender's clock
+    assert!(trap(1, 4, 99_000).is_ok());
+    // Within the window, and a clock that moved forward
+    assert!(trap(2, 4, 98_900).is_ok());
+    assert!(trap(3, 4, 100_000).is_ok());
+    // A replay from before the window, or from an earlier boot
+    assert!(trap(4, 4, 99_000).is_err());
+    assert!(trap(5, 3, 200_000).is_err());
+    // A reboot moves the clock on
+    assert!(trap(6, 5, 10).is_ok());
+    assert!(trap(7, 4, 100_000).is_err());
+
+    // Another engine has its own clock
+    let other_engine = b"\x80\x00\x1f\x88\x04router".to_vec();
+    let other_keys = trap_user().localize(&other_engine).unwrap();
+    let security = UsmSecurityParameters {
+        engine_id: other_engine.clone(),
+        engine_boots: 1,
+        engine_time: 50,
+        user_name: "traps".to_string(),
+    };
+    let message = encode_v3_message(8, false, &security, &other_keys, 3, &other_engine, &pdu).unwrap();
+    assert!(receiver.receive(&message, trap_source()).is_ok());
+}
+
+#[test]
+fn test_inform_v3_discovery_and_response() {
+    let receiver = TrapReceiver::new(vec![], vec![trap_user()]);
+
+    // Discovery: empty engine ID gets a Report with ours
+    let probe = encode_v3_message(
+        21, true, &UsmSecurityParameters::default(), &UsmKeys::default(), 0, &[], &SYS_NAME_GET_PDU,
+    ).unwrap();
+    let received = receiver.receive(&probe, trap_source()).unwrap();
+    assert!(received.notification.is_none());
+    let report = decode_v3_message(&received.reply.unwrap(), None).unwrap();
+    assert_eq!(report.msg_id, 21);
+    assert_eq!(report.pdu[0], 0xa8);
+    assert_eq!(report.security.engine_id, receiver.engine_id());
+
+    // Inform to our engine
+    let engine_id = report.security.engine_id.clone();
+    let keys = trap_user().localize(&engine_id).unwrap();
+    let security = UsmSecurityParameters { user_name: "traps".to_string(), ..report.security.clone() };
+    let pdu = v2_notification_pdu(0xa6, 6, &oids::LINK_UP, &link_down_varbinds());
+    let inform = encode_v3_message(22, true, &security, &keys, 1, &engine_id, &pdu).unwrap();
+
+    let received = receiver.receive(&inform, trap_source()).unwrap();
+    let notification = received.notification.unwrap();
+    assert!(notification.inform);
+    assert_eq!(notification.event(), TrapEvent::LinkChange { ifindex: 7, up: true });
+    let response = decode_v3_message(&received.reply.unwrap(), Some(&keys)).unwrap();
+    assert_eq!(response.msg_id, 22);
+    assert_eq!(response.pdu[0], 0xa2);
+
+    // An inform outside the time window gets a notInTimeWindow Report
+    let stale = UsmSecurityParameters { engine_time: security.engine_time + 1000, ..security };
+    let inform = encode_v3_message(23, true, &stale, &keys, 2, &engine_id, &pdu).unwrap();
+    let received = receiver.receive(&inform, trap_source()).unwrap();
+    assert!(received.notification.is_none());
+    let auth_only = UsmKeys { auth: keys.auth.clone(), privacy: None };
+    let report = decode_v3_message(&received.reply.unwrap(), Some(&auth_only)).unwrap();
+    assert_eq!(report.pdu[0], 0xa8);
+}
+
+// ==================== SnmpValue Tests ====================
+
+#[test]
+fn test_snmp_value_decode_integer_widths() {
+    assert_eq!(SnmpValue::decode(TAG_INTEGER, &[0x05]).unwrap(), SnmpValue::Integer(5));
+    assert_eq!(SnmpValue::decode(TAG_INTEGER, &[0xff]).unwrap(), SnmpValue::Integer(-1));
+    // 3-byte and 5-byte encodings
+    assert_eq!(SnmpValue::decode(TAG_INTEGER, &[0x01, 0x86, 0xa0]).unwrap(), SnmpValue::Integer(100_000));
+    assert_eq!(SnmpValue::decode(TAG_INTEGER, &[0x00, 0xee, 0x6b, 0x28, 0x00]).unwrap(), SnmpValue::Integer(4_000_000_000));
+    assert!(SnmpValue::decode(TAG_INTEGER, &[]).is_err());
+}
+
+#[test]
+fn test_snmp_value_decode_unsigned() {
+    // Counter32 with the leading zero BER needs for values >= 2^31
+    assert_eq!(SnmpValue::decode(TAG_COUNTER32, &[0x00, 0xff, 0xff, 0xff, 0xff]).unwrap(), SnmpValue::Counter32(u32::MAX));
+    assert_eq!(SnmpValue::decode(TAG_GAUGE32, &[0x3b, 0x9a, 0xca, 0x00]).unwrap(), SnmpValue::Gauge32(1_000_000_000));
+    assert_eq!(SnmpValue::decode(TAG_TIMETICKS, &[0x07, 0x5b, 0xcd, 0x15]).unwrap(), SnmpValue::TimeTicks(123_456_789));
+    assert_eq!(
+        SnmpValue::decode(TAG_COUNTER64, &[0x00, 0x80, 0, 0, 0, 0, 0, 0, 0x01]).unwrap(),
+        SnmpValue::Counter64(0x8000_0000_0000_0001)
+    );
+    assert!(SnmpValue::decode(TAG_COUNTER32, &[0x01, 0, 0, 0, 0]).is_err());
+}
+
+#[test]
+fn test_snmp_value_decode_other_types() {
+    assert_eq!(
+        SnmpValue::decode(TAG_OID, &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x09, 0x01, 0x90, 0x12]).unwrap(),
+        SnmpValue::Oid(vec![1, 3, 6, 1, 4, 1, 9, 1, 2066])
+    );
+    assert_eq!(
+        SnmpValue::decode(TAG_IP_ADDRESS, &[192, 168, 1, 1]).unwrap(),
+        SnmpValue::IpAddress("192.168.1.1".parse().unwrap())
+    );
+    assert!(SnmpValue::decode(TAG_IP_ADDRESS, &[192, 168, 1]).is_err());
+    assert_eq!(SnmpValue::decode(TAG_NULL, &[]).unwrap(), SnmpValue::Null);
+    assert_eq!(SnmpValue::decode(TAG_OPAQUE, &[0x9f, 0x78]).unwrap(), SnmpValue::Opaque(vec![0x9f, 0x78]));
+    assert_eq!(SnmpValue::decode(TAG_END_OF_MIB_VIEW, &[]).unwrap(), SnmpValue::EndOfMibView);
+    assert!(SnmpValue::decode(TAG_NO_SUCH_OBJECT, &[]).unwrap().is_exception());
+    assert!(SnmpValue::decode(TAG_NO_SUCH_INSTANCE, &[]).unwrap().is_exception());
+    assert!(SnmpValue::decode(0x47, &[]).is_err());
+}
+
+#[test]
+fn test_snmp_value_encode_round_trip() {
+    let values = [
+        SnmpValue::Integer(0),
+        SnmpValue::Integer(-129),
+        SnmpValue::Integer(4_000_000_000),
+        SnmpValue::OctetString(b"GigabitEthernet0/1".to_vec()),
+        SnmpValue::Null,
+        SnmpValue::Oid(vec![1, 3, 6, 1, 4, 1, 9, 1, 2066]),
+        SnmpValue::IpAddress("10.0.0.1".parse().unwrap()),
+        SnmpValue::Counter32(u32::MAX),
+        SnmpValue::Gauge32(0),
+        SnmpValue::TimeTicks(123_456_789),
+        SnmpValue::Counter64(u64::MAX),
+        SnmpValue::EndOfMibView,
+    ];
+    for value in values {
+        let encoded = value.encode();
+        let decoded = SnmpValue::decode(encoded[0], &encoded[2..]).unwrap();
+        assert_eq!(decoded, value);
+    }
+}
+
+#[test]
+fn test_snmp_value_accessors() {
+    assert_eq!(SnmpValue::Counter64(10).as_i64(), Some(10));
+    assert_eq!(SnmpValue::Integer(-1).as_u64(), None);
+    assert_eq!(SnmpValue::Gauge32(7).as_u64(), Some(7));
+    assert_eq!(SnmpValue::OctetString(b"abc".to_vec()).as_string(), Some("abc".to_string()));
+    assert_eq!(SnmpValue::Integer(1).as_string(), None);
+    assert_eq!(SnmpValue::OctetString(vec![0, 0x11]).as_bytes(), Some(&[0u8, 0x11][..]));
+    assert_eq!(SnmpValue::Oid(vec![1, 3, 6]).as_oid(), Some(&[1u32, 3, 6][..]));
+}
+
+#[test]
+fn test_snmp_value_display() {
+    assert_eq!(SnmpValue::OctetString(b"core-01".to_vec()).to_string(), "core-01");
+    assert_eq!(SnmpValue::OctetString(vec![0x00, 0x11, 0x22]).to_string(), "0x001122");
+    assert_eq!(SnmpValue::Oid(vec![1, 3, 6, 1]).to_string(), "1.3.6.1");
+    assert_eq!(SnmpValue::IpAddress("10.1.2.3".parse().unwrap()).to_string(), "10.1.2.3");
+    assert_eq!(SnmpValue::NoSuchInstance.to_string(), "noSuchInstance");
+}
+
+// ==================== Transport Tests ====================
+
+fn sys_name_oid() -> Vec<u32> {
+    oids::SYS_NAME.iter().copied().chain([0]).collect()
+}
+
+#[tokio::test]
+async fn test_transport_demuxes_out_of_order_responses() {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let port = socket.local_addr().unwrap().port();
+
+    let agent = std::thread::spawn(move || {
+        let mut buf = [0u8; 65535];
+        let mut requests = Vec::new();
+        for _ in 0..2 {
+            let (len, peer) = socket.recv_from(&mut buf).unwrap();
+            let (_, request_id, _, oid) = parse_community_request(&buf[..len]);
+            requests.push((peer, request_id, oid));
+        }
+        // A stray response nobody asked for, then the real ones in reverse
+        let (peer, _, _) = requests[0].clone();
+        let stray = community_response(&[0x7f, 0xff, 0xff, 0xfe], 0, &[(sys_name_oid(), ber_tlv(0x04, b"stray"))]);
+        socket.send_to(&stray, peer).unwrap();
+        for (peer, request_id, oid) in requests.into_iter().rev() {
+            let value = ber_tlv(0x04, format!("value-{}", oid[oid.len() - 2]).as_bytes());
+            socket.send_to(&community_response(&request_id, 0, &[(oid, value)]), peer).unwrap();
+        }
+    });
+
+    let client = v2c_client(port);
+    let name_oid = sys_name_oid();
+    let descr_oid: Vec<u32> = oids::SYS_DESCR.iter().copied().chain([0]).collect();
+    let (name, descr) = tokio::join!(client.get(&name_oid), client.get(&descr_oid));
+    assert_eq!(name.unwrap().as_string().as_deref(), Some("value-5"));
+    assert_eq!(descr.unwrap().as_string().as_deref(), Some("value-1"));
+    agent.join().unwrap();
+}
+
+#[tokio::test]
+async fn test_transport_rejects_mismatched_request_id() {
+    // A response whose request-id matches no outstanding request is
+    // dropped, so the request times out rather than taking the answer.
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+    let port = socket.local_addr().unwrap().port();
+
+    let agent = std::thread::spawn(move || {
+        let mut buf = [0u8; 65535];
+        let (len, peer) = socket.recv_from(&mut buf).unwrap();
+        let (_, mut request_id, _, oid) = parse_community_request(&buf[..len]);
+        let last = request_id.len() - 1;
+        request_id[last] = request_id[last].wrapping_add(1);
+        socket.send_to(&community_response(&request_id, 0, &[(oid, ber_tlv(0x04, b"wrong"))]), peer).unwrap();
+    });
+
+    let creds = SnmpCredentials {
+        version: SnmpVersion::V2c,
+        community: "public".to_string(),
+        username: None,
+        auth_protocol: None,
+        auth_password: None,
+        priv_protocol: None,
+        priv_password: None,
+    };
+    let client = SnmpClient::new("127.0.0.1", port, creds, 300_000, 0).unwrap();
+    let err = client.get(&sys_name_oid()).await.unwrap_err();
+    assert!(err.to_string().contains("timed out"), "{}", err);
+    agent.join().unwrap();
+}
+
+/// Count how many requests arrive before the agent answers any of them,
+/// when three are sent at once by one client, or with `separate_clients`
+/// by a client, a second client for the same device and a per-VLAN one.
+async fn requests_before_first_reply(max_inflight: usize, separate_clients: bool) -> usize {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+
+    let agent = std::thread::spawn(move || {
+        let mut buf = [0u8; 65535];
+        let mut held = Vec::new();
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(300))).unwrap();
+        while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+            held.push((peer, parse_community_request(&buf[..len])));
+        }
+        let seen = held.len();
+        // Release everything, then serve the rest as it arrives
+        socket.set_read_timeout(Some(std::time::Duration::from_millis(500))).unwrap();
+        loop {
+            for (peer, (_, request_id, _, oid)) in held.drain(..) {
+                socket.send_to(&community_response(&request_id, 0, &[(oid, vec![0x02, 0x01, 0x01])]), peer).unwrap();
+            }
+            match socket.recv_from(&mut buf) {
+                Ok((len, peer)) => held.push((peer, parse_community_request(&buf[..len]))),
+                Err(_) => break,
+            }
+        }
+        seen
+    });
+
+    let mut client = v2c_client(port);
+    client.set_max_inflight(max_inflight);
+    let mut other = v2c_client(port);
+    other.set_max_inflight(max_inflight);
+    let vlan = client.for_vlan(10);
+    let (second, third) = if separate_clients { (&other, &vlan) } else { (&client, &client) };
+    let oid = sys_name_oid();
+    let results = tokio::join!(client.get(&oid), second.get(&oid), third.get(&oid));
+    assert!(results.0.is_ok() && results.1.is_ok() && results.2.is_ok());
+    agent.join().unwrap()
+}
+
+#[tokio::test]
+async fn test_client_limits_inflight_requests() {
+    assert_eq!(requests_before_first_reply(1, false).await, 1);
+    assert_eq!(requests_before_first_reply(3, false).await, 3);
+}
+
+#[tokio::test]
+async fn test_clients_share_inflight_limit_per_device() {
+    assert_eq!(requests_before_first_reply(1, true).await, 1);
+    assert_eq!(requests_before_first_reply(2, true).await, 2);
+}
+
+// ==================== Simulator Tests ====================
+
+fn oid(text: &str) -> Vec<u32> {
+    text.trim_start_matches('.').split('.').map(|c| c.parse().unwrap()).collect()
+}
+
+#[test]
+fn test_recording_parse_snmpwalk() {
+    let walk = r#".1.3.6.1.2.1.1.1.0 = STRING: "Line one
+line two with \"quotes\""
+.1.3.6.1.2.1.1.2.0 = OID: .1.3.6.1.4.1.9.1.516
+.1.3.6.1.2.1.1.3.0 = Timeticks: (4500) 0:00:45.00
+.1.3.6.1.2.1.1.4.0 = ""
+.1.3.6.1.2.1.2.2.1.3.1 = INTEGER: ethernetCsmacd(6)
+.1.3.6.1.2.1.2.2.1.4.1 = INTEGER: -3
+.1.3.6.1.2.1.2.2.1.6.1 = Hex-STRING: 00 1B 2B 0A
+0B 01
+.1.3.6.1.2.1.2.2.1.6.2 = STRING: 0:1b:2b:a:b:2
+.1.3.6.1.2.1.2.2.1.10.1 = Counter32: 42
+.1.3.6.1.2.1.4.20.1.1.10.0.0.1 = IpAddress: 10.0.0.1
+.1.3.6.1.2.1.31.1.1.1.6.1 = Counter64: 18446744073709551615
+.1.3.6.1.2.1.31.1.1.1.15.1 = Gauge32: 1000
+.1.3.6.1.2.1.105.1.1.1.3.1.1 = No Such Instance currently exists at this OID
+"#;
+    let recording = Recording::parse(walk).unwrap();
+    assert_eq!(recording.len(), 12);
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.1.1.0")), Some(&SnmpValue::OctetString(b"Line one\nline two with \"quotes\"".to_vec())));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.1.2.0")), Some(&SnmpValue::Oid(oid("1.3.6.1.4.1.9.1.516"))));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.1.3.0")), Some(&SnmpValue::TimeTicks(4500)));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.1.4.0")), Some(&SnmpValue::OctetString(vec![])));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.2.2.1.3.1")), Some(&SnmpValue::Integer(6)));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.2.2.1.4.1")), Some(&SnmpValue::Integer(-3)));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.2.2.1.6.1")), Some(&SnmpValue::OctetString(vec![0x00, 0x1b, 0x2b, 0x0a, 0x0b, 0x01])));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.2.2.1.6.2")), Some(&SnmpValue::OctetString(vec![0x00, 0x1b, 0x2b, 0x0a, 0x0b, 0x02])));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.2.2.1.10.1")), Some(&SnmpValue::Counter32(42)));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.4.20.1.1.10.0.0.1")), Some(&SnmpValue::IpAddress("10.0.0.1".parse().unwrap())));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.31.1.1.1.6.1")), Some(&SnmpValue::Counter64(u64::MAX)));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.105.1.1.1.3.1.1")), None);
+}
+
+#[test]
+fn test_recording_parse_snmprec() {
+    let snmprec = "# comment\n\
+        1.3.6.1.2.1.1.5.0|4|core-1\n\
+        1.3.6.1.2.1.1.4.0|4|\n\
+        1.3.6.1.2.1.1.3.0|67|100\n\
+        1.3.6.1.2.1.2.2.1.6.1|4x|001b2b0a0b01\n\
+        1.3.6.1.2.1.4.20.1.1.10.0.0.1|64|10.0.0.1\n\
+        1.3.6.1.2.1.31.1.1.1.6.1|70|12345678901\n";
+    let recording = Recording::parse(snmprec).unwrap();
+    assert_eq!(recording.len(), 6);
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.1.5.0")), Some(&SnmpValue::OctetString(b"core-1".to_vec())));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.1.4.0")), Some(&SnmpValue::OctetString(vec![])));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.2.2.1.6.1")), Some(&SnmpValue::OctetString(vec![0x00, 0x1b, 0x2b, 0x0a, 0x0b, 0x01])));
+    assert_eq!(recording.get(&oid("1.3.6.1.2.1.31.1.1.1.6.1")), Some(&SnmpValue::Counter64(12345678901)));
+    assert_eq!(recording.next(&oid("1.3.6.1.2.1.1")).unwrap().0, &oid("1.3.6.1.2.1.1.3.0"));
+}
+
+#[test]
+fn test_recording_parse_errors_name_the_line() {
+    let err = Recording::parse(".1.3.6.1.2.1.1.5.0 = STRING: ok\n.1.3.6.1.2.1.1.6.0 = Float: 1.5\n").unwrap_err();
+    assert!(format!("{:#}", err).contains("line 2"), "{:#}", err);
+    let err = Recording::parse("1.3.6.1.2.1.1.5.0|4|ok\n1.3.6.x|4|bad\n").unwrap_err();
+    assert!(format!("{:#}", err).contains("line 2"), "{:#}", err);
+}
+
+fn simulated_system() -> Recording {
+    let mut recording = Recording::default();
+    recording.insert(sys_name_oid(), SnmpValue::OctetString(b"sim".to_vec()));
+    recording.insert(oid("1.3.6.1.2.1.1.6.0"), SnmpValue::OctetString(b"lab".to_vec()));
+    recording
+}
+
+#[tokio::test]
+async fn test_simulator_get_and_exceptions() {
+    let simulator = Simulator::start("127.0.0.1:0".parse().unwrap(), simulated_system(), None).unwrap();
+    let client = v2c_client(simulator.local_addr().port());
+
+    let values = client.get_many(&[&sys_name_oid(), &oid("1.3.6.1.2.1.1.5.1"), &oid("1.3.6.1.2.1.1.9.1")]).await.unwrap();
+    assert_eq!(values[0].1, SnmpValue::OctetString(b"sim".to_vec()));
+    assert_eq!(values[1].1, SnmpValue::NoSuchInstance);
+    assert_eq!(values[2].1, SnmpValue::NoSuchObject);
+
+    let (next, _) = client.get_next(&oid("1.3.6.1.2.1.1.6.0")).await.unwrap();
+    assert_eq!(next, oid("1.3.6.1.2.1.1.6.0"));
+    let (_, end) = client.get_next(&oid("1.3.6.1.2.1.1.6.0")).await.unwrap();
+    assert_eq!(end, SnmpValue::EndOfMibView);
+
+    let walked = client.walk(&oids::SYS_NAME[..7]).await.unwrap();
+    assert_eq!(walked.len(), 2);
+    assert!(client.set(&[(&sys_name_oid(), SnmpValue::OctetString(b"x".to_vec()))]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_simulator_v1_no_such_name() {
+    let simulator = Simulator::start("127.0.0.1:0".parse().unwrap(), simulated_system(), None).unwrap();
+    let creds = SnmpCredentials { version: SnmpVersion::V1, ..v2c_credentials() };
+    let client = SnmpClient::new("127.0.0.1", simulator.local_addr().port(), creds, 1_000_000, 0).unwrap();
+
+    let err = client.get(&oid("1.3.6.1.2.1.1.9.0")).await.unwrap_err();
+    assert_eq!(err.downcast_ref::<ErrorStatus>().map(|e| (e.status, e.index)), Some((2, 1)));
+    assert_eq!(client.walk(&oids::SYS_NAME[..7]).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_simulator_checks_community() {
+    let simulator = Simulator::start("127.0.0.1:0".parse().unwrap(), simulated_system(), Some("secret".to_string())).unwrap();
+    let port = simulator.local_addr().port();
+
+    let client = SnmpClient::new("127.0.0.1", port, v2c_credentials(), 200_000, 0).unwrap();
+    assert!(client.get(&sys_name_oid()).await.is_err());
+    let creds = SnmpCredentials { community: "secret".to_string(), ..v2c_credentials() };
+    let client = SnmpClient::new("127.0.0.1", port, creds, 1_000_000, 0).unwrap();
+    assert!(client.get(&sys_name_oid()).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_simulator_bulk_and_replayed_device() {
+    let mut recording = Recording::default();
+    for i in 1..=50 {
+        recording.insert(column_entry(&oids::IF_DESCR, i, vec![]).0, SnmpValue::OctetString(format!("port{}", i).into_bytes()));
+    }
+    let simulator = Simulator::start_for("198.51.100.7".parse().unwrap(), recording).unwrap();
+
+    // Only clients built from a config that names the device are redirected
+    let client = SnmpClient::new("198.51.100.7", 161, v2c_credentials(), 1_000_000, 0).unwrap();
+    assert_eq!(client.target(), "198.51.100.7:161".parse().unwrap());
+    let mut config = NetdiscoConfig { snmpver: 2, ..Default::default() };
+    config.snmp_replay.insert("198.51.100.7".parse().unwrap(), simulator.local_addr());
+    let client = SnmpClient::from_config(&config, "198.51.100.7").unwrap();
+    assert_eq!(client.target(), simulator.local_addr());
+    assert_eq!(SnmpClient::from_config(&config, "198.51.100.8").unwrap().target(), "198.51.100.8:161".parse().unwrap());
+    let rows = client.get_bulk_many(0, 20, &[&oids::IF_DESCR]).await.unwrap();
+    assert_eq!(rows.len(), 20);
+    assert_eq!(client.walk(&oids::IF_DESCR).await.unwrap().len(), 50);
+}
+
+#[test]
+fn test_find_chassis_prefers_top_level() {
+    let entity = |index, class: &str, contained_in| EntityInfo {
+        index,
+        class: Some(class.to_string()),
+        contained_in: Some(contained_in),
+        ..Default::default()
+    };
+    // A stack: member chassis inside a stack entity, and a virtual chassis
+    let entities = vec![entity(1, "stack", 0), entity(2, "chassis", 1), entity(3, "chassis", 1)];
+    assert_eq!(find_chassis(&entities).map(|e| e.index), Some(2));
+    let entities = vec![entity(5, "module", 4), entity(4, "chassis", 7), entity(9, "chassis", 0)];
+    assert_eq!(find_chassis(&entities).map(|e| e.index), Some(9));
+    assert!(find_chassis(&[entity(1, "module", 0)]).is_none());
+}
+
+fn fingerprint_of(object_id: &str, descr: &str, services: i64) -> Fingerprint {
+    let sys_info = SystemInfo {
+        description: Some(descr.to_string()),
+        object_id: Some(object_id.to_string()),
+        uptime: None,
+        contact: None,
+        name: None,
+        location: None,
+        services: Some(services),
+    };
+    fingerprint(&sys_info, None)
+}
+
+#[test]
+fn test_enterprise_number() {
+    assert_eq!(enterprise_number("1.3.6.1.4.1.9.1.516"), Some(9));
+    assert_eq!(enterprise_number(".1.3.6.1.4.1.2636.1.1.1.2.132"), Some(2636));
+    assert_eq!(enterprise_number("1.3.6.1.4.1"), None);
+    assert_eq!(enterprise_number("1.3.6.1.2.1.1"), None);
+    assert_eq!(enterprise_number("not.an.oid"), None);
+}
+
+#[test]
+fn test_fingerprint_os_versions() {
+    let cases = [
+        ("1.3.6.1.4.1.9.1.516",
+         "Cisco IOS Software, C3750 Software (C3750-IPSERVICESK9-M), Version 12.2(55)SE12, RELEASE SOFTWARE (fc2)",
+         "ios", "12.2(55)SE12"),
+        ("1.3.6.1.4.1.9.1.2494",
+         "Cisco IOS Software [Amsterdam], Catalyst L3 Switch Software (CAT9K_IOSXE), Version 17.3.4, RELEASE SOFTWARE (fc3)",
+         "ios-xe", "17.3.4"),
+        ("1.3.6.1.4.1.9.1.2494",
+         "Cisco IOS Software [Fuji], Catalyst L3 Switch Software (CAT9K_IOSXE), Version 16.9.5, RELEASE SOFTWARE (fc1)",
+         "ios-xe", "16.9.5"),
+        ("1.3.6.1.4.1.9.1.1745",
+         "Cisco IOS Software [Denali], Catalyst L3 Switch Software (CAT3K_CAA-UNIVERSALK9-M), Version 16.3.9, RELEASE SOFTWARE (fc2)",
+         "ios-xe", "16.3.9"),
+        ("1.3.6.1.4.1.9.1.1537",
+         "Cisco IOS Software [Gibraltar], ISR Software (X86_64_LINUX_IOSD-UNIVERSALK9-M), Version 16.12.4, RELEASE SOFTWARE (fc5)",
+         "ios-xe", "16.12.4"),
+        ("1.3.6.1.4.1.9.12.3.1.3.1812",
+         "Cisco NX-OS(tm) n9000, Software (n9000-dk9), Version 9.3(8), RELEASE SOFTWARE Copyright (c) 2002-2021 by Cisco Systems, Inc.",
+         "nx-os", "9.3(8)"),
+        ("1.3.6.1.4.1.9.1.2315",
+         "Cisco Adaptive Security Appliance Version 9.8(4)",
+         "asa", "9.8(4)"),
+        ("1.3.6.1.4.1.2636.1.1.1.2.132",
+         "Juniper Networks, Inc. ex2300-24p Ethernet Switch, kernel JUNOS 20.4R3-S2.6, Build date: 2022-03-05 05:14:09 UTC",
+         "junos", "20.4R3-S2.6"),
+        ("1.3.6.1.4.1.30065.1.3011.7050.3741.48",
+         "Arista Networks EOS version 4.28.3M running on an Arista Networks DCS-7050SX-64",
+         "eos", "4.28.3M"),
+        ("1.3.6.1.4.1.11.2.3.7.11.181.9",
+         "Aruba JL259A 2930F-24G-4SFP Switch, revision WC.16.10.0012, ROM WC.16.01.0008 (/ws/swbuildm/rel_venice_qaoff/code/build/anm(swbuildm_rel_venice_qaoff_rel_venice))",
+         "procurve", "WC.16.10.0012"),
+        ("1.3.6.1.4.1.25506.11.1.239",
+         "H3C Comware Platform Software, Software Version 7.1.070, Release 3208P10\r\nH3C S5130-52S-EI",
+         "comware", "7.1.070, Release 3208P10"),
+        ("1.3.6.1.4.1.11.2.3.7.11.182.23",
+         "HPE Comware Platform Software, Software Version 7.1.045, Release 2432P06",
+         "comware", "7.1.045, Release 2432P06"),
+        ("1.3.6.1.4.1.14823.1.1.32",
+         "ArubaOS (MODEL: Aruba7030), Version 8.10.0.2 LSR",
+         "arubaos", "8.10.0.2"),
+        ("1.3.6.1.4.1.47196.4.1.1.1.300",
+         "Aruba JL658A 6300M 24SFP+ 4SFP56 Swch FL.10.08.1010",
+         "aos-cx", "FL.10.08.1010"),
+        ("1.3.6.1.4.1.2011.2.239.13",
+         "Huawei Versatile Routing Platform Software\r\nVRP (R) software, Version 8.180 (CE6850EI V200R005C10SPC800)",
+         "vrp", "V200R005C10SPC800"),
+        ("1.3.6.1.4.1.1916.2.290",
+         "ExtremeXOS (X440G2-48p-10G4) version 30.7.1.1 30.7.1.1-patch1-5 by release-manager",
+         "xos", "30.7.1.1"),
+        ("1.3.6.1.4.1.8072.3.2.10",
+         "Linux monitor01 5.10.0-23-amd64 #1 SMP Debian 5.10.179-1 (2023-05-12) x86_64",
+         "linux", "5.10.0-23-amd64"),
+    ];
+    for (object_id, descr, os, os_ver) in cases {
+        let fp = fingerprint_of(object_id, descr, 6);
+        assert_eq!(fp.os.as_deref(), Some(os), "{}", descr);
+        assert_eq!(fp.os_ver.as_deref(), Some(os_ver), "{}", descr);
+    }
+}
+
+#[test]
+fn test_fingerprint_device_class() {
+    let ios = "Cisco IOS Software, C2900 Software (C2900-UNIVERSALK9-M), Version 15.7(3)M5, RELEASE SOFTWARE (fc1)";
+    // A switch that also routes, and a router
+    assert_eq!(fingerprint_of("1.3.6.1.4.1.9.1.516", ios, 6).snmp_class.as_deref(),
+        Some("SNMP::Info::Layer3::CiscoSwitch"));
+    assert_eq!(fingerprint_of("1.3.6.1.4.1.9.1.1041", ios, 4 | 72).snmp_class.as_deref(),
+        Some("SNMP::Info::Layer3::Cisco"));
+    assert_eq!(fingerprint_of("1.3.6.1.4.1.2636.1.1.1.2.132", "", 6).snmp_class.as_deref(),
+        Some("SNMP::Info::Layer3::Juniper"));
+
+    // Unknown enterprises and unmatched sysDescr fall back to sysServices
+    let fp = fingerprint_of("1.3.6.1.4.1.99999.1", "Some appliance", 2);
+    assert_eq!(fp, Fingerprint { snmp_class: Some("SNMP::Info::Layer2".into()), ..Default::default() });
+    let fp = fingerprint_of("1.3.6.1.4.1.9.1.1", "Cisco Wireless LAN Controller", 72);
+    assert_eq!((fp.os, fp.snmp_class.as_deref()), (None, Some("SNMP::Info")));
+}
+
+#[test]
+fn test_fingerprint_vendor_from_enterprise() {
+    let sys_info = SystemInfo {
+        description: Some("Juniper Networks, Inc. ex2300-24p Ethernet Switch, kernel JUNOS 20.4R3-S2.6".into()),
+        object_id: Some("1.3.6.1.4.1.2636.1.1.1.2.132".into()),
+        uptime: None,
+        contact: None,
+        name: None,
+        location: None,
+        services: Some(6),
+    };
+    let juniper = Enterprise { id: 2636, name: Some("juniper".into()) };
+    assert_eq!(fingerprint(&sys_info, Some(&juniper)).vendor.as_deref(), Some("juniper"));
+    assert_eq!(fingerprint(&sys_info, None).vendor, None);
+}
+
+#[test]
+fn test_if_type_name() {
+    assert_eq!(if_type_name(6), "ethernetCsmacd");
+    assert_eq!(if_type_name(24), "softwareLoopback");
+    assert_eq!(if_type_name(53), "propVirtual");
+    assert_eq!(if_type_name(117), "gigabitEthernet");
+    assert_eq!(if_type_name(131), "tunnel");
+    assert_eq!(if_type_name(135), "l2vlan");
+    assert_eq!(if_type_name(161), "ieee8023adLag");
+    assert_eq!(if_type_name(9999), "9999");
+}
+
+#[test]
+fn test_interface_speed_and_name() {
+    let iface = |speed, high_speed| InterfaceInfo { speed, high_speed, ..Default::default() };
+    assert_eq!(iface(Some(100_000_000), Some(100)).speed_bps(), Some(100_000_000));
+    // ifHighSpeed is rounded to whole Mbps; ifSpeed is exact below 4.29G
+    assert_eq!(iface(Some(1_544_000), Some(2)).speed_bps(), Some(1_544_000));
+    assert_eq!(iface(Some(4294967295), Some(40000)).speed_bps(), Some(40_000_000_000));
+    assert_eq!(iface(None, Some(25000)).speed_bps(), Some(25_000_000_000));
+    assert_eq!(iface(Some(4294967295), None).speed_bps(), Some(4294967295));
+    assert_eq!(iface(Some(0), Some(0)).speed_bps(), None);
+
+    let mut port = InterfaceInfo { descr: "GigabitEthernet1/0/1".into(), ..Default::default() };
+    assert_eq!(port.port_name(), "GigabitEthernet1/0/1");
+    port.name = Some("Gi1/0/1".into());
+    assert_eq!(port.port_name(), "Gi1/0/1");
+}
+
+#[test]
+fn test_ip_address_subnet() {
+    let address = IpAddressInfo { ip: "10.10.1.2".parse().unwrap(), ifindex: Some(1), prefix_len: Some(24) };
+    assert_eq!(address.subnet(), Some("10.10.1.0/24".parse().unwrap()));
+    let address = IpAddressInfo { prefix_len: None, ..address };
+    assert_eq!(address.subnet(), None);
+}
+
+#[test]
+fn test_port_list() {
+    assert_eq!(port_list(&[0x80, 0x01]), vec![1, 16]);
+    assert_eq!(port_list(&[0x00, 0x00, 0x20]), vec![19]);
+    assert!(port_list(&[]).is_empty());
+}
+
+#[test]
+fn test_pse_port_ifindex() {
+    let port = |ifindex: i32, name: &str, if_type: &str| InterfaceInfo {
+        ifindex,
+        descr: name.to_string(),
+        name: Some(name.to_string()),
+        if_type: Some(if_type.to_string()),
+        ..Default::default()
+    };
+    // A two-member Catalyst stack with uplink modules
+    let stack = vec![
+        port(1, "Vl1", "propVirtual"),
+        port(10101, "Gi1/0/1", "ethernetCsmacd"),
+        port(10105, "Gi1/0/5", "ethernetCsmacd"),
+        port(10201, "Te1/1/1", "ethernetCsmacd"),
+        port(10601, "Gi2/0/1", "ethernetCsmacd"),
+    ];
+    assert_eq!(pse_port_ifindex(1, 5, None, &stack), Some(10105));
+    assert_eq!(pse_port_ifindex(1, 1, None, &stack), Some(10101));
+    assert_eq!(pse_port_ifindex(2, 1, None, &stack), Some(10601));
+    // The agent's own mapping wins, if the interface exists
+    assert_eq!(pse_port_ifindex(1, 1, Some(10201), &stack), Some(10201));
+    assert_eq!(pse_port_ifindex(1, 5, Some(99), &stack), Some(10105));
+    // 1.1 is not ifIndex 1, which is a VLAN interface
+    assert_eq!(pse_port_ifindex(1, 7, None, &stack), None);
+
+    // Standalone switches number from 0, and the position alone decides
+    let standalone = vec![port(10001, "Fa0/1", "ethernetCsmacd"), port(10002, "Fa0/2", "ethernetCsmacd")];
+    assert_eq!(pse_port_ifindex(1, 2, None, &standalone), Some(10002));
+
+    // ProCurve ports are named and indexed by number
+    let procurve = vec![port(5, "5", "ethernetCsmacd"), port(289, "Trk1", "ieee8023adLag")];
+    assert_eq!(pse_port_ifindex(1, 5, None, &procurve), Some(5));
+    assert_eq!(pse_port_ifindex(1, 289, None, &procurve), None);
+}
+
+#[test]
+fn test_bridge_id() {
+    assert_eq!(bridge_id(&[0x80, 0x00, 0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]).as_deref(), Some("32768.00:1a:2b:3c:4d:5e"));
+    assert_eq!(bridge_id(&[0x10, 0x01, 0, 0, 0, 0, 0, 1]).as_deref(), Some("4097.00:00:00:00:00:01"));
+    assert_eq!(bridge_id(&[0x80, 0x00, 0x00]), None);
+}
+
+#[test]
+fn test_fdb_vlans() {
+    // VLANs 10 and 20 share FID 2 (shared VLAN learning); VLAN 30 has its own
+    let vlans = fdb_vlans([(10, 2), (20, 2), (30, 3)]);
+    assert_eq!(vlans.get(&2), Some(&None));
+    assert_eq!(vlans.get(&3), Some(&Some(30)));
+    assert_eq!(vlans.get(&4), None);
+}
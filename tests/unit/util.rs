@@ -25,6 +25,15 @@ fn test_format_mac_ieee_dot_separated() {
     assert_eq!(util::format_mac_ieee("0011.2233.4455"), "00:11:22:33:44:55");
 }
 
+#[test]
+fn test_parse_mac() {
+    assert_eq!(util::parse_mac("0011.2233.4455").as_deref(), Some("00:11:22:33:44:55"));
+    assert_eq!(util::parse_mac("00-1A-2B-3C-4D-5E").as_deref(), Some("00:1a:2b:3c:4d:5e"));
+    assert_eq!(util::parse_mac("00:11:22:33:44"), None);
+    assert_eq!(util::parse_mac("00:11:22:33:44:zz"), None);
+    assert_eq!(util::parse_mac("00:11:22:33:44:55 OR 1=1"), None);
+}
+
 #[test]
 fn test_format_mac_ieee_uppercase() {
     assert_eq!(util::format_mac_ieee("AABBCCDDEEFF"), "aa:bb:cc:dd:ee:ff");
@@ -113,6 +122,20 @@ fn test_format_uptime_multiple_years() {
     assert!(result.contains("years"), "Expected 'years' in: {}", result);
 }
 
+#[test]
+fn test_format_speed() {
+    assert_eq!(util::format_speed(64_000), "64 kbps");
+    assert_eq!(util::format_speed(1_544_000), "1.5 Mbps");
+    assert_eq!(util::format_speed(10_000_000), "10 Mbps");
+    assert_eq!(util::format_speed(100_000_000), "100 Mbps");
+    assert_eq!(util::format_speed(1_000_000_000), "1.0 Gbps");
+    assert_eq!(util::format_speed(2_500_000_000), "2.5 Gbps");
+    assert_eq!(util::format_speed(10_000_000_000), "10 Gbps");
+    assert_eq!(util::format_speed(400_000_000_000), "400 Gbps");
+    assert_eq!(util::format_speed(1_600_000_000_000), "1.6 Tbps");
+    assert_eq!(util::format_speed(300), "300 bps");
+}
+
 // ==================== Permission / ACL Tests ====================
 
 use netdisco::util::permission;